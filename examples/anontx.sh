@@ -102,7 +102,7 @@ deposit() {
     OUTPUT_COMMITS=$($DF output commits $OUTPUT_SECRET)
     echoerr deposit: Created proof commits
 
-    CHALLENGE=$($DF hash-challenge -o $OUTPUT_COMMITS)
+    CHALLENGE=$($DF hash-challenge $TX -o $OUTPUT_COMMITS)
     echoerr deposit: Hashed proof challenge
 
     OUTPUT_PROOF=$($DF output proof $OUTPUT_SECRET $CHALLENGE)
@@ -202,7 +202,7 @@ split() {
     OUTPUT2_COMMITS=$($DF output commits $OUTPUT2_SECRET)
     echoerr split: Created proof commits
 
-    CHALLENGE=$($DF hash-challenge -i $INPUT_COMMITS -o $OUTPUT1_COMMITS -o $OUTPUT2_COMMITS)
+    CHALLENGE=$($DF hash-challenge $TX -i $INPUT_COMMITS -o $OUTPUT1_COMMITS -o $OUTPUT2_COMMITS)
     echoerr split: Hashed proof challenge
 
     INPUT_PROOF=$($DF input proof $VERIFY_KEY $INPUT_SECRET $CHALLENGE)
@@ -47,12 +47,18 @@ fn obj_from_hex_with_params<'a, O: DecodableWithParams<'a, df::OsRngInstance>>(
 }
 
 fn initialize(config_dir: &Path, threshold: u32, total: u32) -> Result<()> {
-    let number_attributes = 2;
+    let number_attributes = 3;
 
-    let (secret_keys, verify_key) = df::generate_keys(number_attributes, threshold, total);
+    let (secret_keys, verify_key) = df::generate_keys(number_attributes, threshold, total)?;
 
+    let coconut = CoconutContext::new(number_attributes, threshold, total)?;
     for (i, secret_key) in secret_keys.iter().enumerate() {
         println!("secret_key-{}: {}", i + 1, hex_repr(secret_key)?);
+        println!(
+            "service_verify_key-{}: {}",
+            i + 1,
+            hex_repr(&secret_key.verify_key(&coconut.params))?
+        );
     }
 
     println!("verify_key: {}", hex_repr(&verify_key)?);
@@ -84,8 +90,8 @@ fn tx_new() -> Result<()> {
 
 fn tx_show(tx_data_str: &str) -> Result<()> {
     let tx = obj_from_hex::<df::Transaction>(tx_data_str)?;
-    println!("withdraws: {}", tx.withdraws);
-    println!("deposits: {}", tx.deposits);
+    println!("withdraws: {}", tx.total_withdraws());
+    println!("deposits: {}", tx.total_deposits());
     println!("inputs: {}", tx.inputs.len());
     println!("outputs: {}", tx.outputs.len());
     Ok(())
@@ -131,7 +137,17 @@ fn tx_compute_pedersens(
 ) -> Result<()> {
     let coconut = get_context(config_dir)?;
     let mut tx = obj_from_hex::<df::Transaction>(tx_data_str)?;
-    let (input_blinds, output_blinds) = tx.compute_pedersens(&coconut, input_values, output_values);
+    let to_asset_values = |values: &Vec<u64>| -> Vec<_> {
+        values
+            .iter()
+            .map(|value| df::AssetValue::new(df::DEFAULT_ASSET, *value))
+            .collect()
+    };
+    let (input_blinds, output_blinds) = tx.compute_pedersens(
+        &coconut,
+        &to_asset_values(input_values),
+        &to_asset_values(output_values),
+    )?;
     for blind in &input_blinds {
         println!("input-blind: {}", hex_repr(blind)?);
     }
@@ -170,6 +186,7 @@ fn tx_set_challenge(tx: &str, challenge: &str) -> Result<()> {
 fn tx_unblind(
     config_dir: &Path,
     tx: &str,
+    verify_keys: &Vec<df::VerifyKey>,
     token_secrets: &Vec<&df::TokenSecret>,
     signatures: Vec<Vec<df::OutputSignature>>,
 ) -> Result<()> {
@@ -179,7 +196,10 @@ fn tx_unblind(
         eprintln!("error: Not enough signatures");
         std::process::exit(-1);
     }
-    let tokens = tx.unblind(&coconut, token_secrets, signatures);
+    let (tokens, rejected) = tx.unblind(&coconut, verify_keys, token_secrets, signatures)?;
+    for index in rejected {
+        eprintln!("warning: Bad signature share from service {}", index);
+    }
     for token in &tokens {
         println!("{}", hex_repr(token)?);
     }
@@ -191,7 +211,7 @@ fn input_new(config_dir: &Path, verify_key: &str, token_secret: &str, token: &st
     let verify_key = obj_from_hex::<df::VerifyKey>(verify_key)?;
     let token_secret = obj_from_hex::<df::TokenSecret>(token_secret)?;
     let token = obj_from_hex::<df::Token>(token)?;
-    let (input, input_secret) = df::Input::new(&coconut, &verify_key, &token, &token_secret);
+    let (input, input_secret) = df::Input::new(&coconut, &verify_key, &token, &token_secret)?;
     println!("input: {}", hex_repr(&input)?);
     println!("input-secret: {}", hex_repr(&input_secret)?);
     Ok(())
@@ -213,7 +233,7 @@ fn input_setup_secret(
         object
     };
     let input_blind = obj_from_hex::<df::bls::Scalar>(input_blind)?;
-    input_secret.setup(input_blind);
+    input_secret.setup(input_blind)?;
     println!("{}", hex_repr(&input_secret)?);
     Ok(())
 }
@@ -255,7 +275,7 @@ fn input_proof(
 fn output_new(config_dir: &Path, token_secret_str: &str) -> Result<()> {
     let coconut = get_context(config_dir)?;
     let token_secret = obj_from_hex::<df::TokenSecret>(token_secret_str)?;
-    let (output, output_secret) = df::Output::new(&coconut, &token_secret);
+    let (output, output_secret) = df::Output::new(&coconut, &token_secret)?;
     println!("output: {}", hex_repr(&output)?);
     println!("output-secret: {}", hex_repr(&output_secret)?);
     Ok(())
@@ -266,7 +286,7 @@ fn output_setup_secret(config_dir: &Path, output_secret: &str, output_blind: &st
     let mut output_secret =
         obj_from_hex_with_params::<df::OutputSecret<df::OsRngInstance>>(&coconut, output_secret)?;
     let output_blind = obj_from_hex::<df::bls::Scalar>(output_blind)?;
-    output_secret.setup(output_blind);
+    output_secret.setup(output_blind)?;
     println!("{}", hex_repr(&output_secret)?);
     Ok(())
 }
@@ -371,7 +391,7 @@ fn get_context(config_dir: &Path) -> Result<CoconutContext> {
         config.coconut.number_attributes,
         config.coconut.threshold,
         config.coconut.total_services,
-    );
+    )?;
     Ok(coconut)
 }
 
@@ -448,6 +468,7 @@ fn main() -> Result<()> {
             (@subcommand unblind =>
                 (about: "Unblind threshold number of partial signatures into a single final signature")
                 (@arg TX: +required "Tx data")
+                (@arg SERVICE_VERIFY_KEY: -k --key ... "Verification keys of each service in index order")
                 (@arg TOKEN_SECRET: -t --token ... "Token secrets")
                 (@arg OUTPUT_SIGNATURE: -s --signature ... "Partial blind signature")
             )
@@ -500,7 +521,8 @@ fn main() -> Result<()> {
             )
         )
         (@subcommand ("hash-challenge") =>
-            (about: "Hash proof commits together and produce proof challenge")
+            (about: "Hash the tx and proof commits together and produce proof challenge")
+            (@arg TX: +required "Tx data")
             (@arg INPUT_PROOF_COMMIT: -i --input ... "Input proof commit hash")
             (@arg OUTPUT_PROOF_COMMIT: -o --output ... "Output proof commit hash")
         )
@@ -637,7 +659,13 @@ fn main() -> Result<()> {
                         signatures.push(outsigs);
                     }
                 }
-                tx_unblind(config_dir, &tx, &token_secrets, signatures)?;
+                let mut verify_keys: Vec<df::VerifyKey> = vec![];
+                if let Some(values) = matches.values_of("SERVICE_VERIFY_KEY") {
+                    for value in values {
+                        verify_keys.push(obj_from_hex(value)?);
+                    }
+                }
+                tx_unblind(config_dir, &tx, &verify_keys, &token_secrets, signatures)?;
             }
             _ => {
                 eprintln!("error: Invalid tx subcommand invoked");
@@ -698,20 +726,21 @@ fn main() -> Result<()> {
             }
         },
         ("hash-challenge", Some(matches)) => {
-            let mut hasher = df::HasherToScalar::new();
+            let tx = obj_from_hex::<df::Transaction>(matches.value_of("TX").unwrap())?;
+            let mut transcript = tx.transcript();
             if let Some(commits) = matches.values_of("INPUT_PROOF_COMMIT") {
                 for commit in commits {
                     let commit = obj_from_hex::<df::bls::Scalar>(commit)?;
-                    hasher.add(commit);
+                    transcript.append_scalar(b"input", &commit);
                 }
             }
             if let Some(commits) = matches.values_of("OUTPUT_PROOF_COMMIT") {
                 for commit in commits {
                     let commit = obj_from_hex::<df::bls::Scalar>(commit)?;
-                    hasher.add(commit);
+                    transcript.append_scalar(b"output", &commit);
                 }
             }
-            println!("{}", hex_repr(&hasher.finish())?);
+            println!("{}", hex_repr(&transcript.challenge_scalar(b"challenge"))?);
         }
         ("run-service", Some(matches)) => {
             let secret_key: String = matches.value_of("SECRET_KEY").unwrap().parse()?;
@@ -72,7 +72,7 @@ fn get_context(config_dir: &Path) -> Result<CoconutContext> {
         config.coconut.number_attributes,
         config.coconut.threshold,
         config.coconut.total_services,
-    );
+    )?;
     Ok(coconut)
 }
 
@@ -110,8 +110,8 @@ fn token_new_secret(config_dir: &Path, value: u64) -> Result<String> {
 
 fn tx_show(tx_data_str: &str) -> Result<()> {
     let tx = obj_from_hex::<df::Transaction>(tx_data_str)?;
-    println!("withdraws: {}", tx.withdraws);
-    println!("deposits: {}", tx.deposits);
+    println!("withdraws: {}", tx.total_withdraws());
+    println!("deposits: {}", tx.total_deposits());
     println!("inputs: {}", tx.inputs.len());
     println!("outputs: {}", tx.outputs.len());
     Ok(())
@@ -155,7 +155,17 @@ fn tx_compute_pedersens(
 ) -> Result<()> {
     let coconut = get_context(config_dir)?;
     let mut tx = obj_from_hex::<df::Transaction>(tx_data_str)?;
-    let (input_blinds, output_blinds) = tx.compute_pedersens(&coconut, input_values, output_values);
+    let to_asset_values = |values: &Vec<u64>| -> Vec<_> {
+        values
+            .iter()
+            .map(|value| df::AssetValue::new(df::DEFAULT_ASSET, *value))
+            .collect()
+    };
+    let (input_blinds, output_blinds) = tx.compute_pedersens(
+        &coconut,
+        &to_asset_values(input_values),
+        &to_asset_values(output_values),
+    )?;
     for blind in &input_blinds {
         println!("input-blind: {}", hex_repr(blind)?);
     }
@@ -194,6 +204,7 @@ fn tx_set_challenge(tx: &str, challenge: &str) -> Result<()> {
 fn tx_unblind(
     config_dir: &Path,
     tx: &str,
+    verify_keys: &Vec<df::VerifyKey>,
     token_secrets: &Vec<&df::TokenSecret>,
     signatures: Vec<Vec<df::OutputSignature>>,
 ) -> Result<()> {
@@ -203,7 +214,10 @@ fn tx_unblind(
         eprintln!("error: Not enough signatures");
         std::process::exit(-1);
     }
-    let tokens = tx.unblind(&coconut, token_secrets, signatures);
+    let (tokens, rejected) = tx.unblind(&coconut, verify_keys, token_secrets, signatures)?;
+    for index in rejected {
+        eprintln!("warning: Bad signature share from service {}", index);
+    }
     for token in &tokens {
         println!("{}", hex_repr(token)?);
     }
@@ -220,7 +234,7 @@ fn input_new(
     let verify_key = obj_from_hex::<df::VerifyKey>(verify_key)?;
     let token_secret = obj_from_hex::<df::TokenSecret>(token_secret)?;
     let token = obj_from_hex::<df::Token>(token)?;
-    let (input, input_secret) = df::Input::new(&coconut, &verify_key, &token, &token_secret);
+    let (input, input_secret) = df::Input::new(&coconut, &verify_key, &token, &token_secret)?;
     Ok((hex_repr(&input).unwrap(), hex_repr(&input_secret).unwrap()))
 }
 
@@ -240,7 +254,7 @@ fn input_setup_secret(
         object
     };
     let input_blind = obj_from_hex::<df::bls::Scalar>(input_blind)?;
-    input_secret.setup(input_blind);
+    input_secret.setup(input_blind)?;
     println!("{}", hex_repr(&input_secret)?);
     Ok(())
 }
@@ -282,7 +296,7 @@ fn input_proof(
 fn output_new(config_dir: &Path, token_secret_str: &str) -> Result<(String, String)> {
     let coconut = get_context(config_dir)?;
     let token_secret = obj_from_hex::<df::TokenSecret>(token_secret_str)?;
-    let (output, output_secret) = df::Output::new(&coconut, &token_secret);
+    let (output, output_secret) = df::Output::new(&coconut, &token_secret)?;
     Ok((
         hex_repr(&output).unwrap(),
         hex_repr(&output_secret).unwrap(),
@@ -294,7 +308,7 @@ fn output_setup_secret(config_dir: &Path, output_secret: &str, output_blind: &st
     let mut output_secret =
         obj_from_hex_with_params::<df::OutputSecret<df::OsRngInstance>>(&coconut, output_secret)?;
     let output_blind = obj_from_hex::<df::bls::Scalar>(output_blind)?;
-    output_secret.setup(output_blind);
+    output_secret.setup(output_blind)?;
     println!("{}", hex_repr(&output_secret)?);
     Ok(())
 }
@@ -5,14 +5,15 @@ fn main() {
     // Initialization
     //
 
-    let number_attributes = 2;
+    let number_attributes = 3;
     let threshold_service = 5;
     let total_services = 7;
 
     let (secret_keys, verify_key) =
-        df::generate_keys(number_attributes, threshold_service, total_services);
+        df::generate_keys(number_attributes, threshold_service, total_services).unwrap();
     let coconut =
-        df::Coconut::<df::OsRngInstance>::new(number_attributes, threshold_service, total_services);
+        df::Coconut::<df::OsRngInstance>::new(number_attributes, threshold_service, total_services)
+            .unwrap();
 
     // Create our services that will sign new credentials
     let mut services: Vec<_> = secret_keys
@@ -27,6 +28,11 @@ fn main() {
             )
         })
         .collect();
+    // Each service publishes its own key so bad signature shares can be spotted
+    let service_verify_keys: Vec<_> = services
+        .iter()
+        .map(|service| service.share_verify_key())
+        .collect();
 
     //
     // Deposit
@@ -42,25 +48,26 @@ fn main() {
         // wallet: Create a new transaction
         let mut tx = df::Transaction::new();
         // wallet: Create a single output
-        let (output, mut output_secret) = df::Output::new(&coconut, &token_secret);
+        let (output, mut output_secret) = df::Output::new(&coconut, &token_secret).unwrap();
 
         // wallet: We are depositing 110, so expect a new token of 110 to be minted
         tx.add_deposit(token_secret.value);
         let output_id = tx.add_output(output);
 
         // Once we have added the inputs and outputs, we must call this function...
-        let (_input_blinds, output_blinds) =
-            tx.compute_pedersens(&coconut, &vec![], &vec![token_value]);
+        let (_input_blinds, output_blinds) = tx
+            .compute_pedersens(&coconut, &vec![], &vec![token_secret.asset_value()])
+            .unwrap();
 
         // wallet: Then for every input/output we created, call this one.
-        output_secret.setup(output_blinds[output_id]);
+        output_secret.setup(output_blinds[output_id]).unwrap();
         // wallet: Now start to generate the proofs
         let output_proof_commits = output_secret.proof_commits();
+        let output_proof_commitish = output_proof_commits.hash();
 
-        let mut hasher = df::HasherToScalar::new();
-        //output_proof_commits.commit(&mut hasher);
-        hasher.add(output_proof_commits.hash());
-        let challenge = hasher.finish();
+        // wallet: The output proofs answer their own challenge
+        let challenge =
+            df::Transaction::output_challenge(&tx.outputs[output_id], &output_proof_commitish);
 
         std::mem::drop(output_proof_commits);
 
@@ -69,8 +76,11 @@ fn main() {
         tx.outputs[output_id].set_proof(output_proofs);
         tx.outputs[output_id].challenge = Some(challenge);
 
-        // Also set the challenge computed from all the proofs in our transaction
-        tx.challenge = challenge;
+        // Also set the challenge computed from the whole transaction
+        // and all the proofs in it
+        let mut transcript = tx.transcript();
+        transcript.append_scalar(b"output", &output_proof_commitish);
+        tx.challenge = transcript.challenge_scalar(b"challenge");
 
         // service: Each service will now validate and sign the transaction
         let output_signatures: Vec<_> = services
@@ -92,7 +102,15 @@ fn main() {
 
         // wallet: Unblind and accept the returned signed token if signed by at least
         //         M of N services.
-        let mut tokens = tx.unblind(&coconut, &vec![&token_secret], output_signatures);
+        let (mut tokens, rejected) = tx
+            .unblind(
+                &coconut,
+                &service_verify_keys,
+                &vec![&token_secret],
+                output_signatures,
+            )
+            .unwrap();
+        assert!(rejected.is_empty());
         assert!(tokens.len() == 1);
         tokens.pop().unwrap()
     };
@@ -124,7 +142,8 @@ fn main() {
                 0,
                 &vec![token_secret.value],
                 &vec![token1_value, token2_value],
-            );
+            )
+            .unwrap();
 
         /////////////////////////////////////////////
         // Bob
@@ -132,14 +151,23 @@ fn main() {
 
         let output1_id = 0;
 
-        let (mut output1, mut output1_secret) = df::Output::new(&coconut, &token1_secret);
+        let (mut output1, mut output1_secret) = df::Output::new(&coconut, &token1_secret).unwrap();
 
-        output1_secret.setup(output_blinds[output1_id]);
+        output1_secret.setup(output_blinds[output1_id]).unwrap();
         let output1_proof_commitish = output1_secret.proof_commits().hash();
 
-        let mut hasher = df::HasherToScalar::new();
-        hasher.add(output1_proof_commitish);
-        let challenge_output1 = hasher.finish();
+        // The challenge covers the whole output, so set the pedersen
+        // Alice will compute for it
+        let asset_value = token1_secret.asset_value();
+        output1.pedersen = df::compute_asset_pedersen(
+            &coconut.params,
+            asset_value.asset,
+            &output_blinds[output1_id],
+            asset_value.value,
+        )
+        .unwrap();
+        let challenge_output1 =
+            df::Transaction::output_challenge(&output1, &output1_proof_commitish);
 
         let output1_proofs = output1_secret.finish(&challenge_output1);
 
@@ -158,9 +186,9 @@ fn main() {
         let mut tx = df::Transaction::new();
         // wallet1: Create input and output
         let (input, mut input_secret) =
-            df::Input::new(&coconut, &verify_key, &token, &token_secret);
+            df::Input::new(&coconut, &verify_key, &token, &token_secret).unwrap();
         // wallet2: Also create another output
-        let (output2, mut output2_secret) = df::Output::new(&coconut, &token2_secret);
+        let (output2, mut output2_secret) = df::Output::new(&coconut, &token2_secret).unwrap();
 
         // We are splitting token of 110 into two new tokens of 100 and 10
         let input_id = tx.add_input(input);
@@ -175,10 +203,11 @@ fn main() {
             deposits_blind,
             withdraws_blind,
             &input_blinds,
-            &vec![token_secret.value],
+            &vec![token_secret.asset_value()],
             &output_blinds,
-            &vec![token1_value, token2_value],
-        );
+            &vec![token1_secret.asset_value(), token2_secret.asset_value()],
+        )
+        .unwrap();
 
         println!("  computed pedersens");
 
@@ -188,11 +217,13 @@ fn main() {
         assert_eq!(input_secret.value, token_secret.value);
         assert_eq!(
             tx.inputs[input_id].pedersen,
-            df::compute_pedersen_with_u64(
+            df::compute_asset_pedersen(
                 &coconut.params,
+                df::DEFAULT_ASSET,
                 &input_blinds[input_id],
                 input_secret.value
             )
+            .unwrap()
         );
         // Debug
         //println!("main(): input.pedersen = {:?}", tx.inputs[input_id].pedersen);
@@ -200,7 +231,7 @@ fn main() {
         //println!("main(): value = {:?}", bls::Scalar::from(input_secret.value));
 
         // wallet1: Now pass this info to our input_secret
-        input_secret.setup(input_blinds[input_id]);
+        input_secret.setup(input_blinds[input_id]).unwrap();
         let input_proof_commits = input_secret.proof_commits();
 
         assert_eq!(output1_id, 0);
@@ -212,40 +243,45 @@ fn main() {
         /*
         assert_eq!(
             tx.outputs[output1_id].pedersen,
-            df::compute_pedersen_with_u64(
+            df::compute_asset_pedersen(
                 &coconut.params,
+                df::DEFAULT_ASSET,
                 &output_blinds[output1_id],
                 output1_secret.value
             )
+            .unwrap()
         );
         */
         assert_eq!(
             tx.outputs[output2_id].pedersen,
-            df::compute_pedersen_with_u64(
+            df::compute_asset_pedersen(
                 &coconut.params,
+                df::DEFAULT_ASSET,
                 &output_blinds[output2_id],
                 output2_secret.value
             )
+            .unwrap()
         );
 
         // wallet1 and wallet2: also do the same for the outputs
-        output2_secret.setup(output_blinds[output2_id]);
+        output2_secret.setup(output_blinds[output2_id]).unwrap();
         // Begin computing the proofs
         let output2_proof_commits = output2_secret.proof_commits();
 
-        // Hash all the proof commits together to generate a single challenge.
-        let mut hasher = df::HasherToScalar::new();
-        //input_proof_commits.commit(&mut hasher);
-        //output1_proof_commits.commit(&mut hasher);
-        //output2_proof_commits.commit(&mut hasher);
-        hasher.add(input_proof_commits.hash());
-        hasher.add(output1_proof_commitish);
-        hasher.add(output2_proof_commits.hash());
-        let challenge = hasher.finish();
+        let challenge_output2 = df::Transaction::output_challenge(
+            &tx.outputs[output2_id],
+            &output2_proof_commits.hash(),
+        );
+        // Output challenges are part of the transaction digest
+        tx.outputs[output2_id].challenge = Some(challenge_output2);
 
-        let mut hasher = df::HasherToScalar::new();
-        hasher.add(output2_proof_commits.hash());
-        let challenge_output2 = hasher.finish();
+        // Hash the transaction and all the proof commits together to
+        // generate a single challenge.
+        let mut transcript = tx.transcript();
+        transcript.append_scalar(b"input", &input_proof_commits.hash());
+        transcript.append_scalar(b"output", &output1_proof_commitish);
+        transcript.append_scalar(b"output", &output2_proof_commits.hash());
+        let challenge = transcript.challenge_scalar(b"challenge");
 
         std::mem::drop(input_proof_commits);
         //std::mem::drop(output1_proof_commits);
@@ -258,7 +294,6 @@ fn main() {
         // wallet2: Also add finished proof to transaction
         let output2_proofs = output2_secret.finish(&challenge_output2);
         tx.outputs[output2_id].set_proof(output2_proofs);
-        tx.outputs[output2_id].challenge = Some(challenge_output2);
 
         // Also add challenge to transaction
         tx.challenge = challenge;
@@ -285,11 +320,15 @@ fn main() {
 
         // Valid output tokens returned from services
         // wallet1 and wallet2: Unblind the tokens
-        let tokens = tx.unblind(
-            &coconut,
-            &vec![&token1_secret, &token2_secret],
-            output_signatures,
-        );
+        let (tokens, rejected) = tx
+            .unblind(
+                &coconut,
+                &service_verify_keys,
+                &vec![&token1_secret, &token2_secret],
+                output_signatures,
+            )
+            .unwrap();
+        assert!(rejected.is_empty());
         println!("  unblinded {} signed tokens", tokens.len());
 
         assert!(tokens.len() == 2);
@@ -306,14 +345,16 @@ fn main() {
         // ... and add a withdrawal representing the money leaving the system.
         let mut tx = df::Transaction::new();
         let (input, mut input_secret) =
-            df::Input::new(&coconut, &verify_key, &token2, &token2_secret);
+            df::Input::new(&coconut, &verify_key, &token2, &token2_secret).unwrap();
 
         // We are wihdrawing a single token
         tx.add_withdraw(token2_secret.value);
         let input_id = tx.add_input(input);
 
         // As before compute the pedersens...
-        let (input_blinds, _) = tx.compute_pedersens(&coconut, &vec![token2_secret.value], &vec![]);
+        let (input_blinds, _) = tx
+            .compute_pedersens(&coconut, &vec![token2_secret.asset_value()], &vec![])
+            .unwrap();
 
         assert_eq!(input_id, 0);
         assert_eq!(input_blinds.len(), 1);
@@ -321,19 +362,21 @@ fn main() {
         assert_eq!(input_secret.value, token2_secret.value);
         assert_eq!(
             tx.inputs[input_id].pedersen,
-            df::compute_pedersen_with_u64(
+            df::compute_asset_pedersen(
                 &coconut.params,
+                df::DEFAULT_ASSET,
                 &input_blinds[input_id],
                 input_secret.value
             )
+            .unwrap()
         );
         // ... then compute the proofs
-        input_secret.setup(input_blinds[input_id]);
+        input_secret.setup(input_blinds[input_id]).unwrap();
         let input_proof_commits = input_secret.proof_commits();
 
-        let mut hasher = df::HasherToScalar::new();
-        hasher.add(input_proof_commits.hash());
-        let challenge = hasher.finish();
+        let mut transcript = tx.transcript();
+        transcript.append_scalar(b"input", &input_proof_commits.hash());
+        let challenge = transcript.challenge_scalar(b"challenge");
 
         std::mem::drop(input_proof_commits);
 
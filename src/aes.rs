@@ -1,26 +1,29 @@
-use aes_gcm::aead::{generic_array::GenericArray, Aead, NewAead};
+use aes_gcm::aead::{generic_array::GenericArray, Aead, NewAead, Payload};
 use aes_gcm::Aes256Gcm;
 use bls12_381 as bls;
+use sha2::{Digest, Sha256};
+
+use crate::bls_extensions::Wipe;
 
 pub type AesKey = [u8; 32];
+// 96-bits = 12 bytes; unique per message
+pub type AesNonce = [u8; 12];
 pub type Plaintext = Vec<u8>;
 pub type Ciphertext = Vec<u8>;
 
+fn ephem_nonce(ephem_public: &bls::G1Projective) -> AesNonce {
+    let ephem_public_bytes = bls::G1Affine::from(ephem_public).to_compressed();
+    let mut nonce = [0u8; 12];
+    nonce.copy_from_slice(&ephem_public_bytes[..12]);
+    nonce
+}
+
 pub fn aes_encrypt(
     shared_secret: &AesKey,
     ephem_public: &bls::G1Projective,
     plaintext: &[u8],
 ) -> Option<Ciphertext> {
-    // Rust is gay, I need to convert to 'GenericArray' whatever the fuck that is...
-    let key = GenericArray::from_slice(&shared_secret[..]);
-    let cipher = Aes256Gcm::new(key);
-
-    // 96-bits = 12 bytes; unique per message
-    let ephem_public_bytes = bls::G1Affine::from(ephem_public).to_compressed();
-    let nonce = GenericArray::from_slice(&ephem_public_bytes[..12]);
-
-    let ciphertext = cipher.encrypt(nonce, plaintext);
-    ciphertext.ok()
+    aes_encrypt_with_nonce(shared_secret, &ephem_nonce(ephem_public), &[], plaintext)
 }
 
 pub fn aes_decrypt(
@@ -28,16 +31,105 @@ pub fn aes_decrypt(
     ephem_public: &bls::G1Projective,
     ciphertext: &Ciphertext,
 ) -> Option<Plaintext> {
+    aes_decrypt_with_nonce(shared_secret, &ephem_nonce(ephem_public), &[], ciphertext)
+}
+
+// The additional data is authenticated but not encrypted
+pub fn aes_encrypt_with_nonce(
+    key: &AesKey,
+    nonce: &AesNonce,
+    aad: &[u8],
+    plaintext: &[u8],
+) -> Option<Ciphertext> {
     // Rust is gay, I need to convert to 'GenericArray' whatever the fuck that is...
-    let key = GenericArray::from_slice(&shared_secret[..]);
-    let cipher = Aes256Gcm::new(key);
+    let cipher = Aes256Gcm::new(GenericArray::from_slice(&key[..]));
+    let payload = Payload {
+        msg: plaintext,
+        aad,
+    };
+    cipher
+        .encrypt(GenericArray::from_slice(&nonce[..]), payload)
+        .ok()
+}
 
-    // 96-bits = 12 bytes; unique per message
-    let ephem_public_bytes = bls::G1Affine::from(ephem_public).to_compressed();
-    let nonce = GenericArray::from_slice(&ephem_public_bytes[..12]);
+pub fn aes_decrypt_with_nonce(
+    key: &AesKey,
+    nonce: &AesNonce,
+    aad: &[u8],
+    ciphertext: &[u8],
+) -> Option<Plaintext> {
+    let cipher = Aes256Gcm::new(GenericArray::from_slice(&key[..]));
+    let payload = Payload {
+        msg: ciphertext,
+        aad,
+    };
+    cipher
+        .decrypt(GenericArray::from_slice(&nonce[..]), payload)
+        .ok()
+}
+
+// HMAC-SHA256 with the padded key already absorbed by each side
+struct HmacSha256 {
+    inner: Sha256,
+    outer: Sha256,
+}
 
-    let plaintext = cipher.decrypt(nonce, ciphertext.as_ref());
-    plaintext.ok()
+impl HmacSha256 {
+    fn new(key: &[u8]) -> Self {
+        let mut block = [0u8; 64];
+        if key.len() > block.len() {
+            block[..32].copy_from_slice(Sha256::digest(key).as_slice());
+        } else {
+            block[..key.len()].copy_from_slice(key);
+        }
+
+        let mut inner = Sha256::new();
+        let mut outer = Sha256::new();
+        inner.input(block.iter().map(|byte| byte ^ 0x36).collect::<Vec<_>>());
+        outer.input(block.iter().map(|byte| byte ^ 0x5c).collect::<Vec<_>>());
+        block.wipe();
+        Self { inner, outer }
+    }
+
+    fn mac(&self, data: &[&[u8]]) -> [u8; 32] {
+        let mut inner = self.inner.clone();
+        for part in data {
+            inner.input(part);
+        }
+        let mut outer = self.outer.clone();
+        outer.input(inner.result());
+        let mut mac = [0u8; 32];
+        mac.copy_from_slice(outer.result().as_slice());
+        mac
+    }
+}
+
+// PBKDF2-HMAC-SHA256 from RFC 8018. One block is exactly one key.
+pub fn derive_passphrase_key(passphrase: &[u8], salt: &[u8], iterations: u32) -> AesKey {
+    let hmac = HmacSha256::new(passphrase);
+    let mut block = hmac.mac(&[salt, &1u32.to_be_bytes()]);
+    let mut key = block;
+    for _ in 1..iterations {
+        block = hmac.mac(&[&block]);
+        for (key_byte, block_byte) in key.iter_mut().zip(block.iter()) {
+            *key_byte ^= block_byte;
+        }
+    }
+    block.wipe();
+    key
+}
+
+#[test]
+fn test_derive_passphrase_key() {
+    // RFC 7914 section 11
+    assert_eq!(
+        hex::encode(derive_passphrase_key(b"passwd", b"salt", 1)),
+        "55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc"
+    );
+    assert_eq!(
+        hex::encode(derive_passphrase_key(b"Password", b"NaCl", 80000)),
+        "4ddcd8f60b98be21830cee5ef22701f9641a4418d04c0414aeff08876b34ab56"
+    );
 }
 
 #[test]
@@ -20,6 +20,33 @@ async fn read_line<R: AsyncBufRead + Unpin>(reader: &mut R) -> io::Result<String
     Ok(buf.trim().to_string())
 }
 
+// Spent tokens are kept in memory unless a directory is passed with
+// --spent-dir, in which case each mint keeps them in its own file there.
+fn open_spent_store(
+    spent_dir: &Option<std::path::PathBuf>,
+    index: u64,
+) -> df::Result<Box<dyn df::SpentStore>> {
+    match spent_dir {
+        Some(spent_dir) => {
+            std::fs::create_dir_all(spent_dir)?;
+            let path = spent_dir.join(format!("spent-{}.db", index));
+            info!("Spent store: {}", path.display());
+            Ok(Box::new(df::FileSpentStore::open(path)?))
+        }
+        None => Ok(Box::new(df::MemorySpentStore::new())),
+    }
+}
+
+fn parse_spent_dir() -> Option<std::path::PathBuf> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--spent-dir" {
+            return args.next().map(std::path::PathBuf::from);
+        }
+    }
+    None
+}
+
 async fn start() -> df::Result<()> {
     let g1 = df::bls::G1Affine::generator();
     //let secret = df::bls::Scalar::new_random::<df::OsRngInstance>();
@@ -30,11 +57,11 @@ async fn start() -> df::Result<()> {
     info!("Secret: {}", secret.to_string());
     info!("Public: {}", public.to_string());
 
-    let number_attributes = 2;
+    let number_attributes = 3;
     let threshold_service = 1;
     let total_services = 2;
     let (secret_keys, verify_key) =
-        df::generate_keys(number_attributes, threshold_service, total_services);
+        df::generate_keys(number_attributes, threshold_service, total_services)?;
     for (i, secret_key) in secret_keys.iter().enumerate() {
         println!("secret_key-{}: {}", i + 1, hex_repr(secret_key).unwrap());
     }
@@ -43,6 +70,32 @@ async fn start() -> df::Result<()> {
         hex_repr(&verify_key).unwrap()
     );
 
+    let coconut = df::Coconut::<df::OsRngInstance>::new(
+        number_attributes,
+        threshold_service,
+        total_services,
+    )?;
+    // Each service is a separate mint with its own registry. They only
+    // agree on burns through the slab log.
+    let spent_dir = parse_spent_dir();
+    let mut registries = Vec::with_capacity(secret_keys.len());
+    let mut services = Vec::with_capacity(secret_keys.len());
+    for (i, secret_key) in secret_keys.iter().enumerate() {
+        let index = (i + 1) as u64;
+        let registry = df::SpentRegistry::new(open_spent_store(&spent_dir, index)?);
+        services.push(df::SigningService::with_registry(
+            &coconut,
+            df::SecretKey {
+                x: secret_key.x,
+                y: secret_key.y.clone(),
+            },
+            df::KeySet::new(df::DEFAULT_EPOCH, verify_key.clone()),
+            index,
+            registry.clone(),
+        ));
+        registries.push(registry);
+    }
+
     let beacon = df::net::fetch_beacon().await?;
     info!("Titan address: {}", beacon.titand_address);
 
@@ -52,12 +105,21 @@ async fn start() -> df::Result<()> {
 
     let mut protocol = df::protocol::Protocol::new(slabman.clone());
     protocol.start(beacon.titand_address);
+    let send_sx = protocol.get_send_pipe();
 
+    let slab_registries = registries.clone();
     let listen_slabs = smol::Task::spawn(async move {
         loop {
             match slab_rx.recv().await {
                 Ok((slab_height, slab)) => {
                     info!("NEW SLAB! {}", slab_height);
+                    for registry in &slab_registries {
+                        match registry.apply_slab(&slab) {
+                            Ok(true) => info!("Burn claim registered"),
+                            Ok(false) => {}
+                            Err(err) => error!("Unable to register burn: {}", err),
+                        }
+                    }
                     let ephem_public = df::bls::G1Projective::from(&slab.ephem_public);
                     let shared_secret = df::derive_shared_secret(&ephem_public, &secret);
 
@@ -83,12 +145,33 @@ async fn start() -> df::Result<()> {
         }
     });
 
+    // Reserved burns are only signed for once their claims come back
+    // through the slab log, so publish them as they're made.
+    let publish_claims = smol::Task::spawn(async move {
+        loop {
+            for registry in &registries {
+                for slab in registry.take_unpublished() {
+                    let message = df::net::Message::Put(df::net::PutMessage {
+                        ephem_public: slab.ephem_public,
+                        scancode: slab.scancode,
+                        ciphertext: slab.ciphertext,
+                    });
+                    if send_sx.send(message).await.is_err() {
+                        return;
+                    }
+                }
+            }
+            df::net::sleep(1).await;
+        }
+    });
+
     let stdin = smol::reader(std::io::stdin());
     let mut stdin = io::BufReader::new(stdin);
     'menu_select: loop {
         println!("[1] Show Shared Key");
         println!("[2] Show All Keys (!!!)");
-        println!("[3] Quit");
+        println!("[3] Show Spent Tokens");
+        println!("[4] Quit");
 
         let buf = read_line(&mut stdin).await?;
 
@@ -99,7 +182,12 @@ async fn start() -> df::Result<()> {
                     println!("secret_key-{}: {}", i + 1, hex_repr(secret_key).unwrap());
                 }
             }
-            "3" => break 'menu_select,
+            "3" => {
+                for service in &services {
+                    println!("service-{}: {} spent", service.index, service.spent_len());
+                }
+            }
+            "4" => break 'menu_select,
             _ => {}
         }
     }
@@ -112,6 +200,7 @@ async fn start() -> df::Result<()> {
     //
     // 6. Put all the df commands in the protocol with the mint
 
+    publish_claims.cancel().await;
     listen_slabs.cancel().await;
     protocol.stop().await;
 
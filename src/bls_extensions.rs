@@ -1,7 +1,9 @@
 use bls12_381 as bls;
 use rand_core::{OsRng, RngCore};
-use sha2::{Digest, Sha256};
+use sha2::{Digest, Sha512};
+use std::fmt;
 use std::io;
+use std::sync::atomic;
 
 use crate::error::{Error, Result};
 use crate::serial::{Decodable, Encodable, ReadExt, WriteExt};
@@ -19,6 +21,73 @@ impl RngInstance for OsRngInstance {
     }
 }
 
+// Deterministic randomness for reproducible runs and known answer tests.
+// Each thread has its own stream, which is SHA256(key || counter) with
+// key = SHA256(seed). Drawing from a thread that was never seeded panics.
+// Only built for tests or with the test-vectors feature, so it can't end
+// up in a wallet by mistake.
+#[cfg(any(test, feature = "test-vectors"))]
+pub struct SeededRngInstance;
+
+#[cfg(any(test, feature = "test-vectors"))]
+struct SeededStream {
+    key: [u8; 32],
+    counter: u64,
+    buffer: Vec<u8>,
+}
+
+#[cfg(any(test, feature = "test-vectors"))]
+impl SeededStream {
+    fn new(seed: &[u8]) -> Self {
+        let mut key = [0u8; 32];
+        key.copy_from_slice(sha2::Sha256::digest(seed).as_slice());
+        Self {
+            key,
+            counter: 0,
+            buffer: Vec::new(),
+        }
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for byte in dest.iter_mut() {
+            if self.buffer.is_empty() {
+                let mut hasher = sha2::Sha256::new();
+                hasher.input(&self.key);
+                hasher.input(&self.counter.to_le_bytes());
+                self.counter += 1;
+
+                self.buffer = hasher.result().to_vec();
+                self.buffer.reverse();
+            }
+            *byte = self.buffer.pop().unwrap();
+        }
+    }
+}
+
+#[cfg(any(test, feature = "test-vectors"))]
+thread_local! {
+    static SEEDED_STREAM: std::cell::RefCell<Option<SeededStream>> =
+        std::cell::RefCell::new(None);
+}
+
+#[cfg(any(test, feature = "test-vectors"))]
+impl SeededRngInstance {
+    // Restart this thread's stream from the seed
+    pub fn seed(seed: &[u8]) {
+        SEEDED_STREAM.with(|stream| *stream.borrow_mut() = Some(SeededStream::new(seed)));
+    }
+}
+
+#[cfg(any(test, feature = "test-vectors"))]
+impl RngInstance for SeededRngInstance {
+    fn fill_bytes(dest: &mut [u8]) {
+        SEEDED_STREAM.with(|stream| match stream.borrow_mut().as_mut() {
+            Some(stream) => stream.fill_bytes(dest),
+            None => panic!("SeededRngInstance used before seed() on this thread"),
+        });
+    }
+}
+
 pub trait RandomScalar {
     fn new_random<R: RngInstance>() -> Self;
 }
@@ -37,89 +106,71 @@ impl RandomScalar for bls::Scalar {
     }
 }
 
-pub struct HasherToScalar {
-    scalar_datas: Vec<[u8; 32]>,
-    g1_datas: Vec<[u8; 48]>,
-    g2_datas: Vec<[u8; 96]>,
-    numbers: Vec<u32>,
+// Bumped whenever the proofs change so challenges made under different
+// versions can never be confused
+pub const PROTOCOL_VERSION: &[u8] = b"darkwallet-proofs-v1";
+
+// Fiat-Shamir transcript in the style of Merlin. Every message is framed
+// with its label and length and kept in the order it was appended.
+// Challenges are reduced from 64 bytes and then appended themselves, so
+// later challenges depend on earlier ones.
+#[derive(Clone)]
+pub struct Transcript {
+    hasher: Sha512,
 }
 
-impl HasherToScalar {
-    pub fn new() -> Self {
-        Self {
-            scalar_datas: Vec::new(),
-            g1_datas: Vec::new(),
-            g2_datas: Vec::new(),
-            numbers: Vec::new(),
-        }
+impl Transcript {
+    pub fn new(label: &'static [u8]) -> Self {
+        let mut transcript = Self {
+            hasher: Sha512::new(),
+        };
+        transcript.append_message(b"dom-sep", label);
+        transcript.append_message(b"protocol-version", PROTOCOL_VERSION);
+        transcript
     }
 
-    pub fn add(&mut self, scalar: bls::Scalar) {
-        let data = scalar.to_bytes();
-        self.scalar_datas.push(data);
+    pub fn append_message(&mut self, label: &'static [u8], message: &[u8]) {
+        self.hasher.input((label.len() as u64).to_le_bytes());
+        self.hasher.input(label);
+        self.hasher.input((message.len() as u64).to_le_bytes());
+        self.hasher.input(message);
     }
 
-    pub fn add_g1(&mut self, point: &bls::G1Projective) {
-        let point = bls::G1Affine::from(point);
-        self.add_g1_affine(&point);
+    pub fn append_u64(&mut self, label: &'static [u8], number: u64) {
+        self.append_message(label, &number.to_le_bytes());
     }
 
-    pub fn add_g2(&mut self, point: &bls::G2Projective) {
-        let point = bls::G2Affine::from(point);
-        self.add_g2_affine(&point);
+    pub fn append_scalar(&mut self, label: &'static [u8], scalar: &bls::Scalar) {
+        self.append_message(label, &scalar.to_bytes());
     }
 
-    pub fn add_g1_affine(&mut self, point: &bls::G1Affine) {
-        let data = point.to_compressed();
-        self.g1_datas.push(data);
+    pub fn append_g1(&mut self, label: &'static [u8], point: &bls::G1Projective) {
+        self.append_g1_affine(label, &bls::G1Affine::from(point));
     }
 
-    pub fn add_g2_affine(&mut self, point: &bls::G2Affine) {
-        let data = point.to_compressed();
-        self.g2_datas.push(data);
+    pub fn append_g2(&mut self, label: &'static [u8], point: &bls::G2Projective) {
+        self.append_g2_affine(label, &bls::G2Affine::from(point));
     }
 
-    pub fn add_u32(&mut self, number: u32) {
-        self.numbers.push(number);
+    pub fn append_g1_affine(&mut self, label: &'static [u8], point: &bls::G1Affine) {
+        self.append_message(label, &point.to_compressed());
     }
 
-    pub fn finish(&self) -> bls::Scalar {
-        for i in 0u32.. {
-            let mut hasher = Sha256::new();
-
-            let i_data = i.to_le_bytes();
-            hasher.input(&i_data);
-
-            for number in &self.numbers {
-                let data = number.to_le_bytes();
-                hasher.input(&data);
-            }
-
-            for data in &self.scalar_datas {
-                hasher.input(&data[0..32]);
-            }
+    pub fn append_g2_affine(&mut self, label: &'static [u8], point: &bls::G2Affine) {
+        self.append_message(label, &point.to_compressed());
+    }
 
-            for data in &self.g1_datas {
-                hasher.input(&data[0..32]);
-                hasher.input(&data[32..]);
-            }
-            for data in &self.g2_datas {
-                hasher.input(&data[0..32]);
-                hasher.input(&data[32..64]);
-                hasher.input(&data[64..]);
-            }
-            let hash_result = hasher.result();
+    pub fn challenge_scalar(&mut self, label: &'static [u8]) -> bls::Scalar {
+        let mut hasher = self.hasher.clone();
+        hasher.input((label.len() as u64).to_le_bytes());
+        hasher.input(label);
 
-            // TODO: how can I fix this? Why not &hash_result[0...32]??
-            let mut hash_data = [0u8; 32];
-            hash_data.copy_from_slice(hash_result.as_slice());
+        let mut wide = [0u8; 64];
+        wide.copy_from_slice(hasher.result().as_slice());
+        let challenge = bls::Scalar::from_bytes_wide(&wide);
 
-            let challenge = bls::Scalar::from_bytes(&hash_data);
-            if challenge.is_some().unwrap_u8() == 1 {
-                return challenge.unwrap();
-            }
-        }
-        unreachable!();
+        self.append_scalar(label, &challenge);
+        challenge
     }
 }
 
@@ -261,6 +312,66 @@ make_serialize_deserialize_test!(serial_test_g1_projective, bls::G1Projective, i
 make_serialize_deserialize_test!(serial_test_g2_affine, bls::G2Affine, identity);
 make_serialize_deserialize_test!(serial_test_g2_projective, bls::G2Projective, identity);
 
+// Overwrites secrets before their memory is released. The writes are
+// volatile so they aren't dropped as dead stores on a value going away.
+pub trait Wipe {
+    fn wipe(&mut self);
+}
+
+fn write_volatile<T: Copy>(dest: &mut T, value: T) {
+    unsafe { std::ptr::write_volatile(dest, value) };
+    atomic::compiler_fence(atomic::Ordering::SeqCst);
+}
+
+impl Wipe for bls::Scalar {
+    fn wipe(&mut self) {
+        write_volatile(self, bls::Scalar::zero());
+    }
+}
+
+impl Wipe for u8 {
+    fn wipe(&mut self) {
+        write_volatile(self, 0);
+    }
+}
+
+impl Wipe for u64 {
+    fn wipe(&mut self) {
+        write_volatile(self, 0);
+    }
+}
+
+impl<T: Wipe> Wipe for Option<T> {
+    fn wipe(&mut self) {
+        if let Some(value) = self {
+            value.wipe();
+        }
+    }
+}
+
+impl<T: Wipe> Wipe for [T] {
+    fn wipe(&mut self) {
+        for value in self.iter_mut() {
+            value.wipe();
+        }
+    }
+}
+
+impl<T: Wipe> Wipe for Vec<T> {
+    fn wipe(&mut self) {
+        self.as_mut_slice().wipe();
+    }
+}
+
+// Stands in for secret fields in Debug output
+pub struct Redacted;
+
+impl fmt::Debug for Redacted {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<redacted>")
+    }
+}
+
 // Why can I not use Borrow<Scalar> here? Complains about it not being Sized
 pub fn sum_scalar<'a, I>(iter: I) -> bls::Scalar
 where
@@ -275,3 +386,75 @@ fn test_sum_scalar() {
     let two = bls::Scalar::one() + bls::Scalar::one();
     assert_eq!(two, sum_scalar(scalars.iter()));
 }
+
+#[test]
+fn test_wipe() {
+    let mut secrets = vec![Some(bls::Scalar::one()), None];
+    secrets.wipe();
+    assert_eq!(secrets, vec![Some(bls::Scalar::zero()), None]);
+
+    let mut value = 110u64;
+    value.wipe();
+    assert_eq!(value, 0);
+
+    assert_eq!(format!("{:?}", Redacted), "<redacted>");
+}
+
+#[test]
+fn test_seeded_rng() {
+    SeededRngInstance::seed(b"darkfi");
+    let mut data = [0u8; 48];
+    // Reads can straddle the hash blocks
+    SeededRngInstance::fill_bytes(&mut data[..16]);
+    SeededRngInstance::fill_bytes(&mut data[16..]);
+    assert_eq!(
+        hex::encode(&data[..]),
+        "10a214b8fc7062db7390dddf59b375df96d94bdc4610a09f\
+         7357be81d433d32acc0ca00f69ce43c497b658572ed9a271"
+    );
+
+    // Reseeding restarts the stream
+    SeededRngInstance::seed(b"darkfi");
+    let scalar = bls::Scalar::new_random::<SeededRngInstance>();
+    assert_eq!(&scalar.to_bytes()[..], &data[..32]);
+
+    // A thread that was never seeded has no stream to draw from
+    let unseeded = std::thread::spawn(|| SeededRngInstance::fill_bytes(&mut [0u8; 8]));
+    assert!(unseeded.join().is_err());
+}
+
+#[test]
+fn test_transcript() {
+    let g1 = bls::G1Projective::generator();
+    let challenge = |first: &'static [u8], second: &'static [u8]| {
+        let mut transcript = Transcript::new(b"test");
+        transcript.append_g1(first, &g1);
+        transcript.append_scalar(second, &bls::Scalar::one());
+        transcript.challenge_scalar(b"challenge")
+    };
+    assert_eq!(challenge(b"a", b"b"), challenge(b"a", b"b"));
+    // Labels are bound into the challenge
+    assert_ne!(challenge(b"a", b"b"), challenge(b"a", b"c"));
+
+    // Order is preserved
+    let mut transcript = Transcript::new(b"test");
+    transcript.append_scalar(b"x", &bls::Scalar::one());
+    transcript.append_g1(b"x", &g1);
+    let mut reordered = Transcript::new(b"test");
+    reordered.append_g1(b"x", &g1);
+    reordered.append_scalar(b"x", &bls::Scalar::one());
+    assert_ne!(
+        transcript.clone().challenge_scalar(b"challenge"),
+        reordered.challenge_scalar(b"challenge")
+    );
+
+    // Each challenge feeds into the next
+    let first = transcript.challenge_scalar(b"challenge");
+    assert_ne!(first, transcript.challenge_scalar(b"challenge"));
+
+    // Domain separated by the transcript label
+    let mut other = Transcript::new(b"other");
+    other.append_g1(b"a", &g1);
+    other.append_scalar(b"b", &bls::Scalar::one());
+    assert_ne!(other.challenge_scalar(b"challenge"), challenge(b"a", b"b"));
+}
@@ -1,12 +1,14 @@
 use bls12_381 as bls;
 use itertools::{chain, izip};
 use std::convert::TryFrom;
+use std::fmt;
 use std::io;
+use subtle::{Choice, ConstantTimeEq};
 
 use crate::bls_extensions::*;
 use crate::elgamal::*;
 //use crate::error::*;
-use crate::error::Result;
+use crate::error::{Error, Result};
 use crate::parameters::*;
 use crate::proofs::credential_proof;
 use crate::proofs::signature_proof;
@@ -23,6 +25,46 @@ pub struct SecretKey {
     pub y: Vec<bls::Scalar>,
 }
 
+impl SecretKey {
+    // vk_i = (x G2, (y_1 G2, ..., y_q G2))
+    pub fn verify_key<R: RngInstance>(&self, params: &Parameters<R>) -> VerifyKey {
+        VerifyKey {
+            alpha: params.g2 * self.x,
+            beta: self.y.iter().map(|y| params.g2 * y).collect(),
+        }
+    }
+}
+
+impl Drop for SecretKey {
+    fn drop(&mut self) {
+        self.x.wipe();
+        self.y.wipe();
+    }
+}
+
+impl fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SecretKey")
+            .field("x", &Redacted)
+            .field("y", &Redacted)
+            .finish()
+    }
+}
+
+impl ConstantTimeEq for SecretKey {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.x.ct_eq(&other.x) & self.y.ct_eq(&other.y)
+    }
+}
+
+impl PartialEq for SecretKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl Eq for SecretKey {}
+
 impl Encodable for SecretKey {
     fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
         let len = self.x.encode(&mut s)?;
@@ -61,11 +103,28 @@ impl Decodable for VerifyKey {
     }
 }
 
+#[derive(Clone)]
 pub struct Attribute {
     pub value: bls::Scalar,
     pub index: u64,
 }
 
+impl Encodable for Attribute {
+    fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
+        let len = self.value.encode(&mut s)?;
+        Ok(len + self.index.encode(s)?)
+    }
+}
+
+impl Decodable for Attribute {
+    fn decode<D: io::Read>(mut d: D) -> Result<Self> {
+        Ok(Self {
+            value: Decodable::decode(&mut d)?,
+            index: Decodable::decode(d)?,
+        })
+    }
+}
+
 pub struct EncryptedAttribute {
     pub value: EncryptedValue,
     pub index: u64,
@@ -90,7 +149,7 @@ impl Decodable for EncryptedAttribute {
 pub struct Coconut<R: RngInstance> {
     pub params: Parameters<R>,
     pub threshold: u32,
-    authorities_total: u32,
+    pub authorities_total: u32,
 }
 
 pub struct BlindSignatureRequest {
@@ -151,13 +210,78 @@ impl Attribute {
     }
 }
 
+// Attribute indexes can come from the wire, so they must fit
+// the parameters and can't repeat.
+pub(crate) fn check_attribute_indexes<'a, I>(attributes_size: usize, indexes: I) -> Result<()>
+where
+    I: Iterator<Item = &'a u64>,
+{
+    let mut seen = vec![false; attributes_size];
+    for index in indexes {
+        match usize::try_from(*index)
+            .ok()
+            .and_then(|index| seen.get_mut(index))
+        {
+            Some(seen) if !*seen => *seen = true,
+            _ => return Err(Error::InvalidAttributeIndex),
+        }
+    }
+    Ok(())
+}
+
 impl<R: RngInstance> Coconut<R> {
-    pub fn new(attributes_size: u32, authorities_threshold: u32, authorities_total: u32) -> Self {
-        Self {
-            params: Parameters::<R>::new(attributes_size),
+    pub fn new(
+        attributes_size: u32,
+        authorities_threshold: u32,
+        authorities_total: u32,
+    ) -> Result<Self> {
+        Self::new_with_assets(
+            attributes_size,
+            authorities_threshold,
+            authorities_total,
+            &vec![DEFAULT_ASSET],
+        )
+    }
+
+    pub fn new_with_assets(
+        attributes_size: u32,
+        authorities_threshold: u32,
+        authorities_total: u32,
+        assets: &Vec<u64>,
+    ) -> Result<Self> {
+        if attributes_size == 0 {
+            return Err(Error::InvalidAttributeCount);
+        }
+        Self::with_parameters(
+            Parameters::<R>::new_with_assets(attributes_size, assets),
+            authorities_threshold,
+            authorities_total,
+        )
+    }
+
+    // For parameters other than the defaults, such as a newer version
+    pub fn with_parameters(
+        params: Parameters<R>,
+        authorities_threshold: u32,
+        authorities_total: u32,
+    ) -> Result<Self> {
+        if params.hs.is_empty() {
+            return Err(Error::InvalidAttributeCount);
+        }
+        if authorities_threshold == 0 || authorities_threshold > authorities_total {
+            return Err(Error::InvalidThreshold);
+        }
+
+        Ok(Self {
+            params,
             threshold: authorities_threshold,
             authorities_total: authorities_total,
-        }
+        })
+    }
+
+    pub fn with_rangeproof_bits(mut self, bits: usize) -> Result<Self> {
+        self.params = self.params.with_rangeproof_bits(bits)?;
+        Ok(self)
     }
 
     pub fn multiparty_keygen(&self) -> (Vec<SecretKey>, Vec<VerifyKey>) {
@@ -204,7 +328,18 @@ impl<R: RngInstance> Coconut<R> {
         (secret_keys, verify_keys)
     }
 
-    pub fn aggregate_keys(&self, verify_keys: &Vec<VerifyKey>) -> VerifyKey {
+    pub fn aggregate_keys(&self, verify_keys: &Vec<VerifyKey>) -> Result<VerifyKey> {
+        if verify_keys.is_empty() {
+            return Err(Error::MissingVerifyKeys);
+        }
+        let attributes_size = verify_keys[0].beta.len();
+        if verify_keys
+            .iter()
+            .any(|key| key.beta.len() != attributes_size)
+        {
+            return Err(Error::InvalidAttributeCount);
+        }
+
         let lagrange = lagrange_basis_from_range(verify_keys.len() as u64);
 
         let (alpha, beta): (Vec<&_>, Vec<&Vec<_>>) = verify_keys
@@ -212,11 +347,6 @@ impl<R: RngInstance> Coconut<R> {
             .map(|key| (&key.alpha, &key.beta))
             .unzip();
 
-        assert!(beta.len() > 0);
-        let attributes_size = beta[0].len();
-
-        assert_eq!(lagrange.len(), alpha.len());
-
         let mut aggregate_alpha = bls::G2Projective::identity();
         for (alpha_i, lagrange_i) in izip!(alpha, &lagrange) {
             aggregate_alpha += alpha_i * lagrange_i;
@@ -232,10 +362,10 @@ impl<R: RngInstance> Coconut<R> {
             })
             .collect();
 
-        return VerifyKey {
+        Ok(VerifyKey {
             alpha: aggregate_alpha,
             beta: aggregate_beta,
-        };
+        })
     }
 
     pub fn make_blind_sign_request(
@@ -243,18 +373,20 @@ impl<R: RngInstance> Coconut<R> {
         shared_attribute_key: &ElGamalPublicKey,
         private_attributes: &Vec<Attribute>,
         public_attributes: &Vec<Attribute>,
-    ) -> (BlindSignatureRequest, signature_proof::BuilderValues) {
-        let blinding_factor = self.params.random_scalar();
-
-        assert_eq!(
+    ) -> Result<(BlindSignatureRequest, signature_proof::BuilderValues)> {
+        if self.params.hs.len() != private_attributes.len() + public_attributes.len() {
+            return Err(Error::InvalidAttributeCount);
+        }
+        check_attribute_indexes(
             self.params.hs.len(),
-            private_attributes.len() + public_attributes.len()
-        );
+            chain(private_attributes, public_attributes).map(|attribute| &attribute.index),
+        )?;
+
+        let blinding_factor = self.params.random_scalar();
 
         let mut attribute_commit = self.params.g1 * blinding_factor;
         for attribute in chain(private_attributes, public_attributes) {
-            let index = usize::try_from(attribute.index).unwrap();
-            attribute_commit += self.params.hs[index] * attribute.value;
+            attribute_commit += self.params.hs[attribute.index as usize] * attribute.value;
         }
 
         let commitish = compute_commitish(&attribute_commit);
@@ -275,7 +407,7 @@ impl<R: RngInstance> Coconut<R> {
             })
             .collect();
 
-        (
+        Ok((
             BlindSignatureRequest {
                 attribute_commit,
                 encrypted_attributes,
@@ -285,7 +417,7 @@ impl<R: RngInstance> Coconut<R> {
                 attribute_keys,
                 blinding_factor,
             },
-        )
+        ))
     }
 
     pub fn aggregate(
@@ -302,13 +434,79 @@ impl<R: RngInstance> Coconut<R> {
         signature
     }
 
+    // Checks e(s_i, G2) = e(h, A_i + sum(m_j B_ij)) for a single authority's
+    // unblinded share against its own verify key.
+    pub fn verify_share(
+        &self,
+        verify_key: &VerifyKey,
+        commitish: &CommitHash,
+        share: &SignatureShare,
+        attributes: &Vec<Attribute>,
+    ) -> bool {
+        let mut kappa = verify_key.alpha;
+        for attribute in attributes {
+            match verify_key.beta.get(attribute.index as usize) {
+                Some(beta) => kappa += beta * attribute.value,
+                None => return false,
+            }
+        }
+
+        let commitish = bls::G1Affine::from(commitish);
+        let share = bls::G1Affine::from(share);
+        !bool::from(commitish.is_identity())
+            && bls::pairing(&share, &self.params.g2)
+                == bls::pairing(&commitish, &bls::G2Affine::from(kappa))
+    }
+
+    // Aggregates only the shares that verify against their authority's key.
+    // Authority indexes start at 1 so share i is checked with verify_keys[i - 1].
+    // Returns the signature together with the indexes of the rejected shares.
+    pub fn aggregate_verified(
+        &self,
+        verify_keys: &Vec<VerifyKey>,
+        commitish: &CommitHash,
+        indexed_shares: Vec<(u64, SignatureShare)>,
+        attributes: &Vec<Attribute>,
+    ) -> Result<(CombinedSignatureShares, Vec<u64>)> {
+        let mut indexes = Vec::new();
+        let mut shares = Vec::new();
+        let mut rejected = Vec::new();
+
+        for (index, share) in indexed_shares {
+            let is_valid = index >= 1
+                && !indexes.contains(&index)
+                && match verify_keys.get(index as usize - 1) {
+                    Some(verify_key) => {
+                        self.verify_share(verify_key, commitish, &share, attributes)
+                    }
+                    None => false,
+                };
+
+            if is_valid {
+                indexes.push(index);
+                shares.push(share);
+            } else {
+                rejected.push(index);
+            }
+        }
+
+        if indexes.len() < self.threshold as usize {
+            return Err(Error::NotEnoughSignatureShares(rejected));
+        }
+
+        Ok((self.aggregate(&shares, indexes), rejected))
+    }
+
     pub fn make_credential(
         &self,
         verify_key: &VerifyKey,
         signature: &Signature,
         attributes: &Vec<Attribute>,
-    ) -> (Credential, credential_proof::BuilderValues) {
-        assert!(attributes.len() <= verify_key.beta.len());
+    ) -> Result<(Credential, credential_proof::BuilderValues)> {
+        check_attribute_indexes(
+            verify_key.beta.len(),
+            attributes.iter().map(|attribute| &attribute.index),
+        )?;
 
         let blind_prime = self.params.random_scalar();
         let (blind_commitish, blind_sigma) = (
@@ -326,7 +524,7 @@ impl<R: RngInstance> Coconut<R> {
         // v = r H_p(C_m)
         let v = blind_commitish * blind;
 
-        (
+        Ok((
             Credential {
                 kappa: kappa,
                 v: v,
@@ -334,7 +532,7 @@ impl<R: RngInstance> Coconut<R> {
                 blind_sigma,
             },
             credential_proof::BuilderValues { blind },
-        )
+        ))
     }
 }
 
@@ -343,17 +541,38 @@ impl BlindSignatureRequest {
         compute_commitish(&self.attribute_commit)
     }
 
+    // The request comes from the wire, so check it fits the parameters
+    // before signing it.
+    pub fn check<R: RngInstance>(
+        &self,
+        params: &Parameters<R>,
+        public_attributes: &Vec<Attribute>,
+    ) -> Result<()> {
+        if self.encrypted_attributes.len() + public_attributes.len() != params.hs.len() {
+            return Err(Error::InvalidAttributeCount);
+        }
+        check_attribute_indexes(
+            params.hs.len(),
+            chain(
+                self.encrypted_attributes
+                    .iter()
+                    .map(|attribute| &attribute.index),
+                public_attributes.iter().map(|attribute| &attribute.index),
+            ),
+        )
+    }
+
     pub fn blind_sign<R: RngInstance>(
         &self,
         params: &Parameters<R>,
         secret_key: &SecretKey,
         public_attributes: &Vec<Attribute>,
-    ) -> PartialSignature {
-        assert_eq!(
-            self.encrypted_attributes.len() + public_attributes.len(),
-            params.hs.len()
-        );
-        // TODO: check indexes are sane as well
+    ) -> Result<PartialSignature> {
+        self.check(params, public_attributes)?;
+        if secret_key.y.len() != params.hs.len() {
+            return Err(Error::InvalidAttributeCount);
+        }
+
         let (a_factors, b_factors): (Vec<&_>, Vec<&_>) = self
             .encrypted_attributes
             .iter()
@@ -388,9 +607,9 @@ impl BlindSignatureRequest {
             signature_b += b * secret_key.y[*index as usize];
         }
 
-        PartialSignature {
+        Ok(PartialSignature {
             encrypted_value: (signature_a, signature_b),
-        }
+        })
     }
 }
 
@@ -420,14 +639,60 @@ impl Credential {
     ) -> bool {
         let mut public_aggregates = bls::G2Projective::identity();
         for attribute in public_attributes {
-            public_aggregates += verify_key.beta[attribute.index as usize] * attribute.value;
+            match verify_key.beta.get(attribute.index as usize) {
+                Some(beta) => public_aggregates += beta * attribute.value,
+                None => return false,
+            }
         }
 
         let kappa = bls::G2Affine::from(self.kappa + public_aggregates);
         let blind_commit = bls::G1Affine::from(self.blind_commitish);
+        // Otherwise both sides are trivially one
+        if bool::from(blind_commit.is_identity()) {
+            return false;
+        }
         let sigma_nu = bls::G1Affine::from(self.blind_sigma + self.v);
         bls::pairing(&blind_commit, &kappa) == bls::pairing(&sigma_nu, &params.g2)
     }
+
+    // Checks e(r_i h_i, K_i) = e(r_i (s_i + v_i), G2) for every credential
+    // with random weights r_i. The right hand sides share G2 so they collapse
+    // into a single term, and everything runs in one multi-miller loop.
+    pub fn batch_verify<R: RngInstance>(
+        params: &Parameters<R>,
+        credentials: &[(&Credential, &VerifyKey, &Vec<Attribute>)],
+    ) -> bool {
+        let mut sigma_nu_sum = bls::G1Projective::identity();
+        let mut terms = Vec::with_capacity(credentials.len() + 1);
+
+        for (credential, verify_key, public_attributes) in credentials {
+            let mut kappa = credential.kappa;
+            for attribute in public_attributes.iter() {
+                match verify_key.beta.get(attribute.index as usize) {
+                    Some(beta) => kappa += beta * attribute.value,
+                    None => return false,
+                }
+            }
+
+            if bool::from(credential.blind_commitish.is_identity()) {
+                return false;
+            }
+
+            let weight = params.random_scalar();
+            sigma_nu_sum += (credential.blind_sigma + credential.v) * weight;
+            terms.push((
+                bls::G1Affine::from(credential.blind_commitish * weight),
+                bls::G2Prepared::from(bls::G2Affine::from(kappa)),
+            ));
+        }
+        terms.push((
+            bls::G1Affine::from(-sigma_nu_sum),
+            bls::G2Prepared::from(params.g2),
+        ));
+
+        let terms: Vec<_> = terms.iter().map(|(g1, g2)| (g1, g2)).collect();
+        bls::multi_miller_loop(&terms).final_exponentiation() == bls::Gt::identity()
+    }
 }
 
 impl Encodable for Credential {
@@ -0,0 +1,377 @@
+use bls12_381 as bls;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+
+use crate::bls_extensions::*;
+use crate::coconut::coconut::*;
+use crate::error::{Error, Result};
+use crate::serial::{Decodable, Encodable};
+use crate::utility::*;
+
+// Distributed key generation for the signing authorities.
+//
+// This is Pedersen's joint Feldman VSS. Every participant acts as a dealer
+// for its own random polynomials (one for x and one for each y_i), broadcasts
+// commitments to the coefficients in G2 and privately sends every other
+// participant their share. The final secret key of participant j is the sum
+// of the shares j received from every qualified dealer, so no single party
+// ever knows the combined secret key.
+//
+// Rounds:
+//   1. Broadcast DealerCommitments, send DealerShare to each participant
+//      over a private channel.
+//   2. Verify received shares against the commitments. Broadcast a Complaint
+//      for every share that fails.
+//   3. Dealers answer complaints by publicly revealing the disputed share.
+//      Dealers that don't answer, or reveal a bad share, are disqualified.
+//   4. Each participant sums the shares from the qualified dealers.
+
+// Commitments to the coefficients of a dealer's polynomials.
+#[derive(Clone)]
+pub struct DealerCommitments {
+    pub dealer: u64,
+    // g2^a_k for the x polynomial
+    pub alpha: Vec<bls::G2Projective>,
+    // g2^b_ik for every y_i polynomial
+    pub beta: Vec<Vec<bls::G2Projective>>,
+}
+
+// Secret share from a dealer for a single recipient.
+#[derive(Clone)]
+pub struct DealerShare {
+    pub dealer: u64,
+    pub recipient: u64,
+    pub x: bls::Scalar,
+    pub y: Vec<bls::Scalar>,
+}
+
+pub struct Complaint {
+    pub accuser: u64,
+    pub dealer: u64,
+}
+
+pub struct Participant<'a, R: RngInstance> {
+    coconut: &'a Coconut<R>,
+    pub index: u64,
+
+    v_poly: Vec<bls::Scalar>,
+    w_poly: Vec<Vec<bls::Scalar>>,
+
+    dealer_commitments: BTreeMap<u64, DealerCommitments>,
+    shares: BTreeMap<u64, DealerShare>,
+    disqualified: BTreeSet<u64>,
+}
+
+// Evaluate the committed polynomial at index in the exponent
+fn evaluate_commitments(commitments: &Vec<bls::G2Projective>, index: u64) -> bls::G2Projective {
+    let x = bls::Scalar::from(index);
+    let mut x_power = bls::Scalar::one();
+
+    let mut result = bls::G2Projective::identity();
+    for commit in commitments {
+        result += commit * x_power;
+        x_power = x_power * x;
+    }
+    result
+}
+
+impl DealerCommitments {
+    pub(crate) fn is_well_formed(&self, threshold: usize, attributes_size: usize) -> bool {
+        self.alpha.len() == threshold
+            && self.beta.len() == attributes_size
+            && self.beta.iter().all(|beta_i| beta_i.len() == threshold)
+    }
+
+    pub fn verify_share<R: RngInstance>(&self, coconut: &Coconut<R>, share: &DealerShare) -> bool {
+        if share.dealer != self.dealer || share.y.len() != self.beta.len() {
+            return false;
+        }
+
+        let g2 = coconut.params.g2;
+        if g2 * share.x != evaluate_commitments(&self.alpha, share.recipient) {
+            return false;
+        }
+        for (y, beta_i) in share.y.iter().zip(&self.beta) {
+            if g2 * y != evaluate_commitments(beta_i, share.recipient) {
+                return false;
+            }
+        }
+        true
+    }
+
+    // This dealer's contribution to the verify key of participant index
+    pub(crate) fn partial_verify_key(&self, index: u64) -> VerifyKey {
+        VerifyKey {
+            alpha: evaluate_commitments(&self.alpha, index),
+            beta: self
+                .beta
+                .iter()
+                .map(|beta_i| evaluate_commitments(beta_i, index))
+                .collect(),
+        }
+    }
+}
+
+impl<'a, R: RngInstance> Participant<'a, R> {
+    pub fn new(coconut: &'a Coconut<R>, index: u64) -> Result<Self> {
+        if index == 0 || index > coconut.authorities_total as u64 {
+            return Err(Error::DkgInvalidIndex);
+        }
+
+        let attributes_size = coconut.params.hs.len();
+        let threshold = coconut.threshold as usize;
+
+        let mut participant = Self {
+            coconut,
+            index,
+
+            v_poly: coconut.params.random_scalars(threshold),
+            w_poly: (0..attributes_size)
+                .map(|_| coconut.params.random_scalars(threshold))
+                .collect(),
+
+            dealer_commitments: BTreeMap::new(),
+            shares: BTreeMap::new(),
+            disqualified: BTreeSet::new(),
+        };
+
+        // We are also one of the dealers
+        let commitments = participant.commitments();
+        let share = participant.share_for(index);
+        participant.dealer_commitments.insert(index, commitments);
+        participant.shares.insert(index, share);
+
+        Ok(participant)
+    }
+
+    // Round 1: broadcast to everybody
+    pub fn commitments(&self) -> DealerCommitments {
+        let g2 = self.coconut.params.g2;
+        DealerCommitments {
+            dealer: self.index,
+            alpha: self.v_poly.iter().map(|a| g2 * a).collect(),
+            beta: self
+                .w_poly
+                .iter()
+                .map(|w_coefficients| w_coefficients.iter().map(|b| g2 * b).collect())
+                .collect(),
+        }
+    }
+
+    // Round 1: send privately to the recipient
+    pub fn share_for(&self, recipient: u64) -> DealerShare {
+        DealerShare {
+            dealer: self.index,
+            recipient,
+            x: compute_polynomial(self.v_poly.iter(), recipient),
+            y: self
+                .w_poly
+                .iter()
+                .map(|w_coefficients| compute_polynomial(w_coefficients.iter(), recipient))
+                .collect(),
+        }
+    }
+
+    // Round 2: check the share we received. If it's bad then the returned
+    // complaint should be broadcast to all participants.
+    pub fn receive(
+        &mut self,
+        commitments: DealerCommitments,
+        share: DealerShare,
+    ) -> Option<Complaint> {
+        let dealer = commitments.dealer;
+        if dealer == self.index
+            || dealer == 0
+            || dealer > self.coconut.authorities_total as u64
+            || self.dealer_commitments.contains_key(&dealer)
+        {
+            return None;
+        }
+
+        // Everybody sees the same broadcast, so a malformed one
+        // disqualifies the dealer without needing a complaint.
+        if !commitments.is_well_formed(
+            self.coconut.threshold as usize,
+            self.coconut.params.hs.len(),
+        ) {
+            self.disqualified.insert(dealer);
+            return None;
+        }
+
+        let is_valid =
+            share.recipient == self.index && commitments.verify_share(self.coconut, &share);
+        self.dealer_commitments.insert(dealer, commitments);
+
+        if !is_valid {
+            return Some(Complaint {
+                accuser: self.index,
+                dealer,
+            });
+        }
+
+        self.shares.insert(dealer, share);
+        None
+    }
+
+    // Round 3: dealers publicly reveal the disputed share
+    pub fn answer(&self, complaint: &Complaint) -> Option<DealerShare> {
+        if complaint.dealer != self.index {
+            return None;
+        }
+        Some(self.share_for(complaint.accuser))
+    }
+
+    // Round 3: everybody checks the answer to every broadcast complaint.
+    // A missing answer is passed as None.
+    pub fn resolve(&mut self, complaint: &Complaint, answer: Option<DealerShare>) {
+        if self.disqualified.contains(&complaint.dealer) {
+            return;
+        }
+
+        let commitments = match self.dealer_commitments.get(&complaint.dealer) {
+            Some(commitments) => commitments,
+            None => {
+                self.disqualified.insert(complaint.dealer);
+                return;
+            }
+        };
+
+        match answer {
+            Some(share)
+                if share.recipient == complaint.accuser
+                    && commitments.verify_share(self.coconut, &share) =>
+            {
+                // The dealer cleared their name and we now know our share
+                if complaint.accuser == self.index {
+                    self.shares.insert(complaint.dealer, share);
+                }
+            }
+            _ => {
+                self.disqualified.insert(complaint.dealer);
+            }
+        }
+    }
+
+    pub fn qualified(&self) -> Vec<u64> {
+        self.dealer_commitments
+            .keys()
+            .filter(|dealer| !self.disqualified.contains(*dealer))
+            .cloned()
+            .collect()
+    }
+
+    fn qualified_commitments(&self) -> Result<Vec<&DealerCommitments>> {
+        let qualified: Vec<_> = self
+            .dealer_commitments
+            .values()
+            .filter(|commitments| !self.disqualified.contains(&commitments.dealer))
+            .collect();
+
+        // At least one of them must be honest
+        if qualified.len() < self.coconut.threshold as usize {
+            return Err(Error::DkgNotEnoughQualified);
+        }
+        Ok(qualified)
+    }
+
+    // Round 4: our secret key is the sum of shares from qualified dealers
+    pub fn finish(&self) -> Result<SecretKey> {
+        let attributes_size = self.coconut.params.hs.len();
+
+        let mut x = bls::Scalar::zero();
+        let mut y = vec![bls::Scalar::zero(); attributes_size];
+
+        for commitments in self.qualified_commitments()? {
+            let share = match self.shares.get(&commitments.dealer) {
+                Some(share) => share,
+                None => return Err(Error::DkgMissingShare),
+            };
+
+            x += share.x;
+            for (y_i, share_y_i) in y.iter_mut().zip(&share.y) {
+                *y_i += share_y_i;
+            }
+        }
+
+        Ok(SecretKey { x, y })
+    }
+
+    // Verify key of any participant, computed from the public commitments
+    pub fn partial_verify_key(&self, index: u64) -> Result<VerifyKey> {
+        let mut verify_key = VerifyKey {
+            alpha: bls::G2Projective::identity(),
+            beta: vec![bls::G2Projective::identity(); self.coconut.params.hs.len()],
+        };
+
+        for commitments in self.qualified_commitments()? {
+            let partial = commitments.partial_verify_key(index);
+            verify_key.alpha += partial.alpha;
+            for (beta_i, partial_beta_i) in verify_key.beta.iter_mut().zip(&partial.beta) {
+                *beta_i += partial_beta_i;
+            }
+        }
+
+        Ok(verify_key)
+    }
+
+    // The aggregated verify key is the polynomial evaluated at zero
+    pub fn verify_key(&self) -> Result<VerifyKey> {
+        self.partial_verify_key(0)
+    }
+}
+
+impl Encodable for DealerCommitments {
+    fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
+        let mut len = 0;
+        len += self.dealer.encode(&mut s)?;
+        len += self.alpha.encode(&mut s)?;
+        Ok(len + self.beta.encode(s)?)
+    }
+}
+
+impl Decodable for DealerCommitments {
+    fn decode<D: io::Read>(mut d: D) -> Result<Self> {
+        Ok(Self {
+            dealer: Decodable::decode(&mut d)?,
+            alpha: Decodable::decode(&mut d)?,
+            beta: Decodable::decode(d)?,
+        })
+    }
+}
+
+impl Encodable for DealerShare {
+    fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
+        let mut len = 0;
+        len += self.dealer.encode(&mut s)?;
+        len += self.recipient.encode(&mut s)?;
+        len += self.x.encode(&mut s)?;
+        Ok(len + self.y.encode(s)?)
+    }
+}
+
+impl Decodable for DealerShare {
+    fn decode<D: io::Read>(mut d: D) -> Result<Self> {
+        Ok(Self {
+            dealer: Decodable::decode(&mut d)?,
+            recipient: Decodable::decode(&mut d)?,
+            x: Decodable::decode(&mut d)?,
+            y: Decodable::decode(d)?,
+        })
+    }
+}
+
+impl Encodable for Complaint {
+    fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
+        let len = self.accuser.encode(&mut s)?;
+        Ok(len + self.dealer.encode(s)?)
+    }
+}
+
+impl Decodable for Complaint {
+    fn decode<D: io::Read>(mut d: D) -> Result<Self> {
+        Ok(Self {
+            accuser: Decodable::decode(&mut d)?,
+            dealer: Decodable::decode(d)?,
+        })
+    }
+}
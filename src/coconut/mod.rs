@@ -1,7 +1,11 @@
 pub mod coconut;
+pub mod dkg;
+pub mod presentation;
+pub mod reshare;
 mod tests;
 
 pub use crate::coconut::coconut::{
     Attribute, BlindSignatureRequest, Coconut, Credential, PartialSignature, SecretKey, Signature,
     VerifyKey,
 };
+pub use crate::coconut::presentation::{AttributeStatement, Predicate, Presentation};
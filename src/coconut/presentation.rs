@@ -0,0 +1,474 @@
+use bls12_381 as bls;
+use itertools::{chain, izip};
+use std::io;
+use std::rc::Rc;
+
+use crate::bls_extensions::*;
+use crate::coconut::coconut::*;
+use crate::error::{Error, Result};
+use crate::parameters::*;
+use crate::pedersen::compute_pedersen;
+use crate::proofs::credential_proof;
+use crate::proofs::pedersen_proof;
+use crate::proofs::proof::*;
+use crate::proofs::range;
+use crate::serial::{Decodable, Encodable};
+
+// Shows a credential without spending it. Hidden attributes are proven in
+// zero knowledge while disclosed ones are checked in the clear. The proof is
+// bound to a context chosen by the verifier such as a nonce or poll id.
+pub struct Presentation {
+    pub credential: Credential,
+    pub hidden_indexes: Vec<u64>,
+    pub disclosed_attributes: Vec<Attribute>,
+    pub proof: credential_proof::Proof,
+    pub attribute_commits: Vec<AttributeCommit>,
+    pub challenge: bls::Scalar,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Predicate {
+    // attribute >= bound
+    AtLeast(u64),
+    // attribute <= bound
+    AtMost(u64),
+}
+
+// What the prover wants to show about one hidden attribute. Two attributes
+// committed with the same blind give the same commit exactly when they're
+// equal, which is how equality across credentials is shown.
+pub struct AttributeStatement {
+    pub index: u64,
+    pub blind: bls::Scalar,
+    pub predicates: Vec<Predicate>,
+}
+
+// Pedersen commit v B + r G1 to a hidden attribute, linked to the credential
+// proof by sharing its response. Each predicate has a range proof over the
+// commit shifted by its bound.
+pub struct AttributeCommit {
+    pub index: u64,
+    pub commit: bls::G1Projective,
+    pub predicates: Vec<Predicate>,
+    pub response_blind: bls::Scalar,
+    pub rangeproofs: Vec<range::Proof>,
+}
+
+impl Predicate {
+    // Value and blind behind the shifted commit the range proof is over
+    fn shift(&self, value: u64, blind: &bls::Scalar) -> Option<(u64, bls::Scalar)> {
+        match self {
+            Predicate::AtLeast(bound) => value.checked_sub(*bound).map(|value| (value, *blind)),
+            Predicate::AtMost(bound) => bound.checked_sub(value).map(|value| (value, -blind)),
+        }
+    }
+
+    fn shift_commit<R: RngInstance>(
+        &self,
+        params: &Parameters<R>,
+        commit: &bls::G1Projective,
+    ) -> bls::G1Projective {
+        let range_base = bls::G1Projective::from(params.range_base);
+        match self {
+            Predicate::AtLeast(bound) => commit - range_base * bls::Scalar::from(*bound),
+            Predicate::AtMost(bound) => range_base * bls::Scalar::from(*bound) - commit,
+        }
+    }
+}
+
+impl AttributeStatement {
+    pub fn new<R: RngInstance>(params: &Parameters<R>, index: u64) -> Self {
+        Self::with_blind(index, params.random_scalar())
+    }
+
+    pub fn with_blind(index: u64, blind: bls::Scalar) -> Self {
+        Self {
+            index,
+            blind,
+            predicates: Vec::new(),
+        }
+    }
+
+    pub fn at_least(mut self, bound: u64) -> Self {
+        self.predicates.push(Predicate::AtLeast(bound));
+        self
+    }
+
+    pub fn at_most(mut self, bound: u64) -> Self {
+        self.predicates.push(Predicate::AtMost(bound));
+        self
+    }
+
+    pub fn in_range(self, lower: u64, upper: u64) -> Self {
+        self.at_least(lower).at_most(upper)
+    }
+}
+
+// Every attribute is either hidden or disclosed, and only once
+fn check_presented_indexes(
+    verify_key: &VerifyKey,
+    hidden_indexes: &Vec<u64>,
+    disclosed_attributes: &Vec<Attribute>,
+) -> Result<()> {
+    if hidden_indexes.len() + disclosed_attributes.len() != verify_key.beta.len() {
+        return Err(Error::InvalidAttributeCount);
+    }
+    check_attribute_indexes(
+        verify_key.beta.len(),
+        chain(
+            hidden_indexes,
+            disclosed_attributes
+                .iter()
+                .map(|attribute| &attribute.index),
+        ),
+    )
+}
+
+// Range proofs need the attribute as an integer
+fn scalar_to_u64(scalar: &bls::Scalar) -> Option<u64> {
+    let bytes = scalar.to_bytes();
+    if bytes[8..].iter().any(|byte| *byte != 0) {
+        return None;
+    }
+    let mut value = [0u8; 8];
+    value.copy_from_slice(&bytes[..8]);
+    Some(u64::from_le_bytes(value))
+}
+
+fn compute_challenge(
+    commitments: &[Box<dyn ProofCommitments + '_>],
+    context: &[u8],
+) -> bls::Scalar {
+    let mut transcript = Transcript::new(b"presentation");
+    for commitment in commitments {
+        commitment.commit(&mut transcript);
+    }
+    transcript.append_message(b"context", context);
+    transcript.challenge_scalar(b"challenge")
+}
+
+impl<R: RngInstance> Coconut<R> {
+    pub fn present(
+        &self,
+        verify_key: &VerifyKey,
+        signature: &Signature,
+        hidden_attributes: &Vec<Attribute>,
+        disclosed_attributes: &Vec<Attribute>,
+        context: &[u8],
+    ) -> Result<Presentation> {
+        self.present_with_statements(
+            verify_key,
+            signature,
+            hidden_attributes,
+            disclosed_attributes,
+            &Vec::new(),
+            context,
+        )
+    }
+
+    pub fn present_with_statements(
+        &self,
+        verify_key: &VerifyKey,
+        signature: &Signature,
+        hidden_attributes: &Vec<Attribute>,
+        disclosed_attributes: &Vec<Attribute>,
+        statements: &Vec<AttributeStatement>,
+        context: &[u8],
+    ) -> Result<Presentation> {
+        let hidden_indexes: Vec<_> = hidden_attributes
+            .iter()
+            .map(|attribute| attribute.index)
+            .collect();
+        check_presented_indexes(verify_key, &hidden_indexes, disclosed_attributes)?;
+        check_attribute_indexes(
+            verify_key.beta.len(),
+            statements.iter().map(|statement| &statement.index),
+        )?;
+
+        // Re-randomized each time so presentations can't be linked
+        let (credential, credential_proof_values) =
+            self.make_credential(verify_key, signature, hidden_attributes)?;
+
+        let witness_attributes: Vec<_> = hidden_attributes
+            .iter()
+            .map(|attribute| Rc::new(Witness::new(&self.params, attribute.value)))
+            .collect();
+        let witness_blind = Rc::new(Witness::new(&self.params, credential_proof_values.blind));
+
+        let builder = credential_proof::Builder::new(
+            &self.params,
+            witness_attributes.clone(),
+            witness_blind.clone(),
+            hidden_indexes.clone(),
+        );
+
+        let range_base = bls::G1Projective::from(self.params.range_base);
+        let mut statement_builders = Vec::with_capacity(statements.len());
+        for statement in statements {
+            let position = hidden_indexes
+                .iter()
+                .position(|index| *index == statement.index)
+                .ok_or(Error::InvalidAttributeIndex)?;
+            let value = &hidden_attributes[position].value;
+
+            let witness_commit_blind = Rc::new(Witness::new(&self.params, statement.blind));
+            let pedersen_builder = pedersen_proof::Builder::new(
+                &self.params,
+                range_base,
+                witness_commit_blind.clone(),
+                witness_attributes[position].clone(),
+            );
+
+            let mut rangeproof_builders = Vec::with_capacity(statement.predicates.len());
+            for predicate in &statement.predicates {
+                let (shifted_value, shifted_blind) = scalar_to_u64(value)
+                    .and_then(|value| predicate.shift(value, &statement.blind))
+                    .filter(|(value, _)| self.params.check_rangeproof_value(*value).is_ok())
+                    .ok_or(Error::UnsatisfiedPredicate)?;
+
+                rangeproof_builders.push(range::Builder::new(
+                    range::DEFAULT_RANGEPROOF_KIND,
+                    &self.params,
+                    &range_base,
+                    &shifted_blind,
+                    shifted_value,
+                )?);
+            }
+
+            statement_builders.push((
+                compute_pedersen(&self.params, &range_base, &statement.blind, value),
+                witness_commit_blind,
+                pedersen_builder,
+                rangeproof_builders,
+            ));
+        }
+
+        let challenge = {
+            let mut commitments =
+                vec![builder.commitments(verify_key, &credential.blind_commitish)];
+            for (_, _, pedersen_builder, rangeproof_builders) in &statement_builders {
+                commitments.push(pedersen_builder.commitments());
+                for rangeproof_builder in rangeproof_builders {
+                    commitments.push(rangeproof_builder.commitments());
+                }
+            }
+            compute_challenge(&commitments, context)
+        };
+
+        let proof = credential_proof::Proof {
+            response_attributes: witness_attributes
+                .iter()
+                .map(|witness| witness.derive(&challenge))
+                .collect(),
+            response_blind: witness_blind.derive(&challenge),
+        };
+
+        let attribute_commits = izip!(statements, statement_builders)
+            .map(
+                |(statement, (commit, witness_commit_blind, _, rangeproof_builders))| {
+                    AttributeCommit {
+                        index: statement.index,
+                        commit,
+                        predicates: statement.predicates.clone(),
+                        response_blind: witness_commit_blind.derive(&challenge),
+                        rangeproofs: rangeproof_builders
+                            .into_iter()
+                            .map(|builder| builder.finish(&challenge))
+                            .collect(),
+                    }
+                },
+            )
+            .collect();
+
+        Ok(Presentation {
+            credential,
+            hidden_indexes,
+            disclosed_attributes: disclosed_attributes.clone(),
+            proof,
+            attribute_commits,
+            challenge,
+        })
+    }
+
+    pub fn verify_presentation(
+        &self,
+        verify_key: &VerifyKey,
+        presentation: &Presentation,
+        context: &[u8],
+    ) -> bool {
+        if check_presented_indexes(
+            verify_key,
+            &presentation.hidden_indexes,
+            &presentation.disclosed_attributes,
+        )
+        .is_err()
+            || check_attribute_indexes(
+                verify_key.beta.len(),
+                presentation
+                    .attribute_commits
+                    .iter()
+                    .map(|attribute_commit| &attribute_commit.index),
+            )
+            .is_err()
+            || presentation.proof.response_attributes.len() != presentation.hidden_indexes.len()
+        {
+            return false;
+        }
+
+        let credential = &presentation.credential;
+        if !credential.verify(&self.params, verify_key, &presentation.disclosed_attributes) {
+            return false;
+        }
+
+        let challenge = &presentation.challenge;
+        let mut commitments = vec![presentation.proof.commitments(
+            &self.params,
+            challenge,
+            verify_key,
+            &credential.blind_commitish,
+            &credential.kappa,
+            &credential.v,
+            &presentation.hidden_indexes,
+        )];
+
+        let range_base = bls::G1Projective::from(self.params.range_base);
+        for attribute_commit in &presentation.attribute_commits {
+            // Only hidden attributes are committed to
+            let position = match presentation
+                .hidden_indexes
+                .iter()
+                .position(|index| *index == attribute_commit.index)
+            {
+                Some(position) => position,
+                None => return false,
+            };
+            if attribute_commit.rangeproofs.len() != attribute_commit.predicates.len() {
+                return false;
+            }
+
+            let pedersen_proof = pedersen_proof::Proof {
+                response_blind: attribute_commit.response_blind,
+                response_value: presentation.proof.response_attributes[position],
+            };
+            commitments.push(pedersen_proof.commitments(
+                &self.params,
+                challenge,
+                &range_base,
+                &attribute_commit.commit,
+            ));
+
+            for (predicate, rangeproof) in
+                izip!(&attribute_commit.predicates, &attribute_commit.rangeproofs)
+            {
+                if rangeproof.value_commit()
+                    != predicate.shift_commit(&self.params, &attribute_commit.commit)
+                {
+                    return false;
+                }
+                commitments.push(rangeproof.commitments(&self.params, challenge, &range_base));
+            }
+        }
+
+        compute_challenge(&commitments, context) == presentation.challenge
+    }
+}
+
+impl Presentation {
+    pub fn attribute_commit(&self, index: u64) -> Option<&AttributeCommit> {
+        self.attribute_commits
+            .iter()
+            .find(|attribute_commit| attribute_commit.index == index)
+    }
+
+    // Whether the hidden attribute proves the predicate. Only meaningful
+    // once the presentation itself has been verified.
+    pub fn proves(&self, index: u64, predicate: Predicate) -> bool {
+        self.attribute_commit(index)
+            .map_or(false, |attribute_commit| {
+                attribute_commit.predicates.contains(&predicate)
+            })
+    }
+
+    // Hidden attributes committed with a shared blind are equal when their
+    // commits match. Both presentations must have been verified.
+    pub fn equal_attributes(&self, index: u64, other: &Presentation, other_index: u64) -> bool {
+        match (
+            self.attribute_commit(index),
+            other.attribute_commit(other_index),
+        ) {
+            (Some(lhs), Some(rhs)) => lhs.commit == rhs.commit,
+            _ => false,
+        }
+    }
+}
+
+impl Encodable for Predicate {
+    fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
+        let (kind, bound) = match self {
+            Predicate::AtLeast(bound) => (0u8, bound),
+            Predicate::AtMost(bound) => (1u8, bound),
+        };
+        let len = kind.encode(&mut s)?;
+        Ok(len + bound.encode(s)?)
+    }
+}
+
+impl Decodable for Predicate {
+    fn decode<D: io::Read>(mut d: D) -> Result<Self> {
+        let kind: u8 = Decodable::decode(&mut d)?;
+        let bound = Decodable::decode(d)?;
+        match kind {
+            0 => Ok(Predicate::AtLeast(bound)),
+            1 => Ok(Predicate::AtMost(bound)),
+            _ => Err(Error::ParseFailed("unknown predicate")),
+        }
+    }
+}
+
+impl Encodable for AttributeCommit {
+    fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
+        let mut len = 0;
+        len += self.index.encode(&mut s)?;
+        len += self.commit.encode(&mut s)?;
+        len += self.predicates.encode(&mut s)?;
+        len += self.response_blind.encode(&mut s)?;
+        Ok(len + self.rangeproofs.encode(s)?)
+    }
+}
+
+impl Decodable for AttributeCommit {
+    fn decode<D: io::Read>(mut d: D) -> Result<Self> {
+        Ok(Self {
+            index: Decodable::decode(&mut d)?,
+            commit: Decodable::decode(&mut d)?,
+            predicates: Decodable::decode(&mut d)?,
+            response_blind: Decodable::decode(&mut d)?,
+            rangeproofs: Decodable::decode(d)?,
+        })
+    }
+}
+
+impl Encodable for Presentation {
+    fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
+        let mut len = 0;
+        len += self.credential.encode(&mut s)?;
+        len += self.hidden_indexes.encode(&mut s)?;
+        len += self.disclosed_attributes.encode(&mut s)?;
+        len += self.proof.encode(&mut s)?;
+        len += self.attribute_commits.encode(&mut s)?;
+        Ok(len + self.challenge.encode(s)?)
+    }
+}
+
+impl Decodable for Presentation {
+    fn decode<D: io::Read>(mut d: D) -> Result<Self> {
+        Ok(Self {
+            credential: Decodable::decode(&mut d)?,
+            hidden_indexes: Decodable::decode(&mut d)?,
+            disclosed_attributes: Decodable::decode(&mut d)?,
+            proof: Decodable::decode(&mut d)?,
+            attribute_commits: Decodable::decode(&mut d)?,
+            challenge: Decodable::decode(d)?,
+        })
+    }
+}
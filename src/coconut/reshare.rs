@@ -0,0 +1,305 @@
+use bls12_381 as bls;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::bls_extensions::*;
+use crate::coconut::coconut::*;
+use crate::coconut::dkg::{Complaint, DealerCommitments, DealerShare};
+use crate::error::{Error, Result};
+use crate::utility::*;
+
+// Proactive re-sharing of the signing keys to a new committee.
+//
+// The old holders move their existing shares to a new set of authorities,
+// possibly with a different threshold, without ever reconstructing the
+// secret key. The aggregated verify key stays the same so credentials
+// issued before the change keep verifying.
+//
+// Every qualified old holder i deals its own share with fresh polynomials
+// of the new degree whose constant terms are x_i and y_i. The constant term
+// commitments must match i's public verify key, so a dealer can't swap in a
+// different secret. New holder j combines the subshares it received using
+// the Lagrange coefficients of the qualified old holders:
+//
+//   x'_j = sum_i lambda_i * f_i(j)
+//
+// Rounds are the same as the DKG: broadcast commitments and send shares,
+// complain about bad shares, answer complaints, then combine.
+
+// Old holder moving its share to the new committee
+pub struct Dealer<'a, R: RngInstance> {
+    coconut: &'a Coconut<R>,
+    pub index: u64,
+
+    v_poly: Vec<bls::Scalar>,
+    w_poly: Vec<Vec<bls::Scalar>>,
+}
+
+// Member of the new committee
+pub struct Recipient<'a, R: RngInstance> {
+    old_coconut: &'a Coconut<R>,
+    coconut: &'a Coconut<R>,
+    pub index: u64,
+
+    // Partial verify keys of the old committee
+    old_verify_keys: Vec<VerifyKey>,
+
+    dealer_commitments: BTreeMap<u64, DealerCommitments>,
+    shares: BTreeMap<u64, DealerShare>,
+    disqualified: BTreeSet<u64>,
+}
+
+// Both committees must sign with the same generators
+fn check_same_parameters<R: RngInstance>(
+    old_coconut: &Coconut<R>,
+    coconut: &Coconut<R>,
+) -> Result<()> {
+    if old_coconut.params.hs != coconut.params.hs || old_coconut.params.g2 != coconut.params.g2 {
+        return Err(Error::ParametersMismatch);
+    }
+    Ok(())
+}
+
+impl<'a, R: RngInstance> Dealer<'a, R> {
+    pub fn new(
+        old_coconut: &Coconut<R>,
+        coconut: &'a Coconut<R>,
+        index: u64,
+        secret_key: &SecretKey,
+    ) -> Result<Self> {
+        check_same_parameters(old_coconut, coconut)?;
+        if index == 0 || index > old_coconut.authorities_total as u64 {
+            return Err(Error::DkgInvalidIndex);
+        }
+        if secret_key.y.len() != coconut.params.hs.len() {
+            return Err(Error::InvalidAttributeCount);
+        }
+
+        // Fresh polynomials of the new degree hiding our existing share
+        let threshold = coconut.threshold as usize;
+        let new_poly = |constant: bls::Scalar| {
+            let mut poly = vec![constant];
+            poly.extend(coconut.params.random_scalars(threshold - 1));
+            poly
+        };
+
+        Ok(Self {
+            coconut,
+            index,
+
+            v_poly: new_poly(secret_key.x),
+            w_poly: secret_key.y.iter().map(|y| new_poly(*y)).collect(),
+        })
+    }
+
+    // Round 1: broadcast to the new committee
+    pub fn commitments(&self) -> DealerCommitments {
+        let g2 = self.coconut.params.g2;
+        DealerCommitments {
+            dealer: self.index,
+            alpha: self.v_poly.iter().map(|a| g2 * a).collect(),
+            beta: self
+                .w_poly
+                .iter()
+                .map(|w_coefficients| w_coefficients.iter().map(|b| g2 * b).collect())
+                .collect(),
+        }
+    }
+
+    // Round 1: send privately to the recipient
+    pub fn share_for(&self, recipient: u64) -> DealerShare {
+        DealerShare {
+            dealer: self.index,
+            recipient,
+            x: compute_polynomial(self.v_poly.iter(), recipient),
+            y: self
+                .w_poly
+                .iter()
+                .map(|w_coefficients| compute_polynomial(w_coefficients.iter(), recipient))
+                .collect(),
+        }
+    }
+
+    // Round 3: publicly reveal the disputed share
+    pub fn answer(&self, complaint: &Complaint) -> Option<DealerShare> {
+        if complaint.dealer != self.index {
+            return None;
+        }
+        Some(self.share_for(complaint.accuser))
+    }
+}
+
+impl<'a, R: RngInstance> Recipient<'a, R> {
+    pub fn new(
+        old_coconut: &'a Coconut<R>,
+        coconut: &'a Coconut<R>,
+        index: u64,
+        old_verify_keys: &Vec<VerifyKey>,
+    ) -> Result<Self> {
+        check_same_parameters(old_coconut, coconut)?;
+        if index == 0 || index > coconut.authorities_total as u64 {
+            return Err(Error::DkgInvalidIndex);
+        }
+        if old_verify_keys.len() != old_coconut.authorities_total as usize {
+            return Err(Error::MissingVerifyKeys);
+        }
+
+        Ok(Self {
+            old_coconut,
+            coconut,
+            index,
+
+            old_verify_keys: old_verify_keys.clone(),
+
+            dealer_commitments: BTreeMap::new(),
+            shares: BTreeMap::new(),
+            disqualified: BTreeSet::new(),
+        })
+    }
+
+    // The dealer must be re-sharing the key it actually holds
+    fn commits_to_old_share(&self, commitments: &DealerCommitments) -> bool {
+        let old_verify_key = &self.old_verify_keys[(commitments.dealer - 1) as usize];
+        let constant = commitments.partial_verify_key(0);
+        constant.alpha == old_verify_key.alpha && constant.beta == old_verify_key.beta
+    }
+
+    // Round 2: check the share we received. If it's bad then the returned
+    // complaint should be broadcast to the new committee.
+    pub fn receive(
+        &mut self,
+        commitments: DealerCommitments,
+        share: DealerShare,
+    ) -> Option<Complaint> {
+        let dealer = commitments.dealer;
+        if dealer == 0
+            || dealer > self.old_coconut.authorities_total as u64
+            || self.dealer_commitments.contains_key(&dealer)
+        {
+            return None;
+        }
+
+        // Public checks, so everybody disqualifies the dealer together
+        if !commitments.is_well_formed(
+            self.coconut.threshold as usize,
+            self.coconut.params.hs.len(),
+        ) || !self.commits_to_old_share(&commitments)
+        {
+            self.disqualified.insert(dealer);
+            return None;
+        }
+
+        let is_valid =
+            share.recipient == self.index && commitments.verify_share(self.coconut, &share);
+        self.dealer_commitments.insert(dealer, commitments);
+
+        if !is_valid {
+            return Some(Complaint {
+                accuser: self.index,
+                dealer,
+            });
+        }
+
+        self.shares.insert(dealer, share);
+        None
+    }
+
+    // Round 3: everybody checks the answer to every broadcast complaint.
+    // A missing answer is passed as None.
+    pub fn resolve(&mut self, complaint: &Complaint, answer: Option<DealerShare>) {
+        if self.disqualified.contains(&complaint.dealer) {
+            return;
+        }
+
+        let commitments = match self.dealer_commitments.get(&complaint.dealer) {
+            Some(commitments) => commitments,
+            None => {
+                self.disqualified.insert(complaint.dealer);
+                return;
+            }
+        };
+
+        match answer {
+            Some(share)
+                if share.recipient == complaint.accuser
+                    && commitments.verify_share(self.coconut, &share) =>
+            {
+                if complaint.accuser == self.index {
+                    self.shares.insert(complaint.dealer, share);
+                }
+            }
+            _ => {
+                self.disqualified.insert(complaint.dealer);
+            }
+        }
+    }
+
+    pub fn qualified(&self) -> Vec<u64> {
+        self.dealer_commitments
+            .keys()
+            .filter(|dealer| !self.disqualified.contains(*dealer))
+            .cloned()
+            .collect()
+    }
+
+    // Qualified dealers with their Lagrange coefficients
+    fn qualified_commitments(&self) -> Result<Vec<(&DealerCommitments, bls::Scalar)>> {
+        let qualified = self.qualified();
+
+        // Enough old shares to interpolate the old secret
+        if qualified.len() < self.old_coconut.threshold as usize {
+            return Err(Error::DkgNotEnoughQualified);
+        }
+
+        let lagrange = lagrange_basis(qualified.iter());
+        Ok(qualified
+            .iter()
+            .map(|dealer| &self.dealer_commitments[dealer])
+            .zip(lagrange)
+            .collect())
+    }
+
+    // Round 4: combine the subshares from the qualified dealers
+    pub fn finish(&self) -> Result<SecretKey> {
+        let attributes_size = self.coconut.params.hs.len();
+
+        let mut x = bls::Scalar::zero();
+        let mut y = vec![bls::Scalar::zero(); attributes_size];
+
+        for (commitments, lagrange) in self.qualified_commitments()? {
+            let share = match self.shares.get(&commitments.dealer) {
+                Some(share) => share,
+                None => return Err(Error::DkgMissingShare),
+            };
+
+            x += share.x * lagrange;
+            for (y_i, share_y_i) in y.iter_mut().zip(&share.y) {
+                *y_i += share_y_i * lagrange;
+            }
+        }
+
+        Ok(SecretKey { x, y })
+    }
+
+    // Verify key of any new committee member, computed from the commitments
+    pub fn partial_verify_key(&self, index: u64) -> Result<VerifyKey> {
+        let mut verify_key = VerifyKey {
+            alpha: bls::G2Projective::identity(),
+            beta: vec![bls::G2Projective::identity(); self.coconut.params.hs.len()],
+        };
+
+        for (commitments, lagrange) in self.qualified_commitments()? {
+            let partial = commitments.partial_verify_key(index);
+            verify_key.alpha += partial.alpha * lagrange;
+            for (beta_i, partial_beta_i) in verify_key.beta.iter_mut().zip(&partial.beta) {
+                *beta_i += partial_beta_i * lagrange;
+            }
+        }
+
+        Ok(verify_key)
+    }
+
+    // Same as the aggregated verify key of the old committee
+    pub fn verify_key(&self) -> Result<VerifyKey> {
+        self.partial_verify_key(0)
+    }
+}
@@ -10,6 +10,10 @@ use crate::bls_extensions::*;
 #[allow(unused_imports)]
 use crate::coconut::coconut::*;
 #[allow(unused_imports)]
+use crate::coconut::dkg;
+#[allow(unused_imports)]
+use crate::coconut::reshare;
+#[allow(unused_imports)]
 use crate::elgamal::*;
 #[allow(unused_imports)]
 use crate::proofs::credential_proof;
@@ -25,11 +29,12 @@ fn test_multiparty_keygen() {
     let attributes_size = 2;
     let (threshold, number_authorities) = (5, 7);
 
-    let coconut = Coconut::<OsRngInstance>::new(attributes_size, threshold, number_authorities);
+    let coconut =
+        Coconut::<OsRngInstance>::new(attributes_size, threshold, number_authorities).unwrap();
 
     let (secret_keys, verify_keys) = coconut.multiparty_keygen();
 
-    let verify_key = coconut.aggregate_keys(&verify_keys);
+    let verify_key = coconut.aggregate_keys(&verify_keys).unwrap();
 
     let sigs_x: Vec<bls::G1Projective> = secret_keys
         .iter()
@@ -52,11 +57,12 @@ fn test_multiparty_coconut() {
     let attributes_size = 3;
     let (threshold, number_authorities) = (5, 7);
 
-    let coconut = Coconut::<OsRngInstance>::new(attributes_size, threshold, number_authorities);
+    let coconut =
+        Coconut::<OsRngInstance>::new(attributes_size, threshold, number_authorities).unwrap();
 
     let (secret_keys, verify_keys) = coconut.multiparty_keygen();
 
-    let verify_key = coconut.aggregate_keys(&verify_keys);
+    let verify_key = coconut.aggregate_keys(&verify_keys).unwrap();
 
     let d = ElGamalPrivateKey::new(&coconut.params);
     let gamma = d.to_public(&coconut.params);
@@ -70,14 +76,16 @@ fn test_multiparty_coconut() {
     //let private_attributes = vec![bls::Scalar::from(110), bls::Scalar::from(4)];
     //let public_attributes = vec![bls::Scalar::from(256)];
 
-    let (sign_request, sign_proof_values) =
-        coconut.make_blind_sign_request(&gamma, &private_attributes, &public_attributes);
+    let (sign_request, sign_proof_values) = coconut
+        .make_blind_sign_request(&gamma, &private_attributes, &public_attributes)
+        .unwrap();
 
     let witness_blind = Rc::new(Witness::new(
         &coconut.params,
         sign_proof_values.blinding_factor.clone(),
     ));
-    let witness_attributes: Vec<_> = chain(&private_attributes, &public_attributes)
+    let witness_attributes: Vec<_> = private_attributes
+        .iter()
         .map(|attribute| Rc::new(Witness::new(&coconut.params, attribute.value.clone())))
         .collect();
     let witness_keys: Vec<_> = sign_proof_values
@@ -85,7 +93,8 @@ fn test_multiparty_coconut() {
         .iter()
         .map(|key| Rc::new(Witness::new(&coconut.params, key.clone())))
         .collect();
-    let attribute_indexes: Vec<_> = chain(&private_attributes, &public_attributes)
+    let attribute_indexes: Vec<_> = private_attributes
+        .iter()
         .map(|attribute| attribute.index)
         .collect();
 
@@ -103,9 +112,9 @@ fn test_multiparty_coconut() {
         &sign_request.attribute_commit,
     );
 
-    let mut sign_hasher = HasherToScalar::new();
-    sign_commitments.commit(&mut sign_hasher);
-    let sign_challenge = sign_hasher.finish();
+    let mut sign_transcript = Transcript::new(b"test");
+    sign_commitments.commit(&mut sign_transcript);
+    let sign_challenge = sign_transcript.challenge_scalar(b"challenge");
 
     // s = k + c x
     let response_blind = witness_blind.derive(&sign_challenge);
@@ -135,14 +144,17 @@ fn test_multiparty_coconut() {
                 &sign_request.attribute_commit,
                 &sign_request.encrypted_attributes,
                 &attribute_indexes,
+                &public_attributes,
             );
-            let mut hasher = HasherToScalar::new();
-            commits.commit(&mut hasher);
-            let challenge = hasher.finish();
+            let mut transcript = Transcript::new(b"test");
+            commits.commit(&mut transcript);
+            let challenge = transcript.challenge_scalar(b"challenge");
 
             assert_eq!(challenge, sign_challenge);
 
-            sign_request.blind_sign(&coconut.params, secret_key, &public_attributes)
+            sign_request
+                .blind_sign(&coconut.params, secret_key, &public_attributes)
+                .unwrap()
         })
         .collect();
 
@@ -171,8 +183,9 @@ fn test_multiparty_coconut() {
     let private_attributes2 = vec![attribute_a, attribute_c];
     let public_attributes2 = vec![attribute_b];
 
-    let (credential, credential_proof_values) =
-        coconut.make_credential(&verify_key, &signature, &private_attributes2);
+    let (credential, credential_proof_values) = coconut
+        .make_credential(&verify_key, &signature, &private_attributes2)
+        .unwrap();
 
     let attribute_indexes: Vec<_> = private_attributes2
         .iter()
@@ -195,9 +208,9 @@ fn test_multiparty_coconut() {
     let credential_commitments =
         credential_proof_builder.commitments(&verify_key, &credential.blind_commitish);
 
-    let mut hasher = HasherToScalar::new();
-    credential_commitments.commit(&mut hasher);
-    let challenge = hasher.finish();
+    let mut transcript = Transcript::new(b"test");
+    credential_commitments.commit(&mut transcript);
+    let challenge = transcript.challenge_scalar(b"challenge");
 
     //Responses
     let response_attributes = witness_attributes2
@@ -223,8 +236,706 @@ fn test_multiparty_coconut() {
         &attribute_indexes,
     );
 
-    let mut verify_hasher = HasherToScalar::new();
-    credential_verify_commitments.commit(&mut verify_hasher);
-    let verify_challenge = verify_hasher.finish();
+    let mut verify_transcript = Transcript::new(b"test");
+    credential_verify_commitments.commit(&mut verify_transcript);
+    let verify_challenge = verify_transcript.challenge_scalar(b"challenge");
     assert_eq!(verify_challenge, challenge);
 }
+
+#[test]
+fn test_distributed_keygen() {
+    let attributes_size = 2;
+    let (threshold, number_authorities) = (3, 5);
+
+    let coconut =
+        Coconut::<OsRngInstance>::new(attributes_size, threshold, number_authorities).unwrap();
+
+    let mut participants: Vec<_> = (1..=number_authorities as u64)
+        .map(|index| dkg::Participant::new(&coconut, index).unwrap())
+        .collect();
+
+    // Round 1 and 2: everybody deals to everybody else
+    let mut complaints = Vec::new();
+    for dealer in 0..participants.len() {
+        for recipient in 0..participants.len() {
+            if dealer == recipient {
+                continue;
+            }
+
+            let commitments = participants[dealer].commitments();
+            let mut share = participants[dealer].share_for(participants[recipient].index);
+
+            // Dealer 2 sends a bad share to participant 4
+            // Dealer 5 sends a bad share to participant 1
+            if (dealer, recipient) == (1, 3) || (dealer, recipient) == (4, 0) {
+                share.x += bls::Scalar::one();
+            }
+
+            if let Some(complaint) = participants[recipient].receive(commitments, share) {
+                complaints.push(complaint);
+            }
+        }
+    }
+    assert_eq!(complaints.len(), 2);
+
+    // Round 3: dealer 2 answers the complaint, but dealer 5 stays silent
+    for complaint in &complaints {
+        let answer = match complaint.dealer {
+            5 => None,
+            dealer => participants[(dealer - 1) as usize].answer(complaint),
+        };
+        for participant in &mut participants {
+            participant.resolve(complaint, answer.clone());
+        }
+    }
+
+    for participant in &participants {
+        assert_eq!(participant.qualified(), vec![1, 2, 3, 4]);
+    }
+
+    // Round 4
+    let secret_keys: Vec<_> = participants
+        .iter()
+        .map(|participant| participant.finish().unwrap())
+        .collect();
+
+    // Everybody agrees on the same verify key
+    let verify_key = participants[0].verify_key().unwrap();
+    for participant in &participants {
+        assert_eq!(participant.verify_key().unwrap().alpha, verify_key.alpha);
+        assert_eq!(participant.verify_key().unwrap().beta, verify_key.beta);
+    }
+
+    // Partial verify keys match the secret keys and aggregate correctly
+    let verify_keys: Vec<_> = participants
+        .iter()
+        .map(|participant| {
+            participants[0]
+                .partial_verify_key(participant.index)
+                .unwrap()
+        })
+        .collect();
+    for (partial_key, secret_key) in izip!(&verify_keys, &secret_keys) {
+        assert_eq!(partial_key.alpha, coconut.params.g2 * secret_key.x);
+    }
+    let aggregate_key = coconut.aggregate_keys(&verify_keys).unwrap();
+    assert_eq!(aggregate_key.alpha, verify_key.alpha);
+
+    // Issue a credential using a threshold of the generated keys
+    let d = ElGamalPrivateKey::new(&coconut.params);
+    let gamma = d.to_public(&coconut.params);
+
+    let private_attributes = vec![
+        Attribute::new(bls::Scalar::from(110), 0),
+        Attribute::new(bls::Scalar::from(4), 1),
+    ];
+
+    let (sign_request, _) = coconut
+        .make_blind_sign_request(&gamma, &private_attributes, &Vec::new())
+        .unwrap();
+
+    let indexes = vec![2u64, 3, 5];
+    let signature_shares: Vec<_> = indexes
+        .iter()
+        .map(|index| {
+            let secret_key = &secret_keys[(*index - 1) as usize];
+            sign_request
+                .blind_sign(&coconut.params, secret_key, &Vec::new())
+                .unwrap()
+                .unblind(&d)
+        })
+        .collect();
+
+    let signature = Signature {
+        commitish: sign_request.compute_commitish(),
+        sigma: coconut.aggregate(&signature_shares, indexes),
+    };
+
+    let (credential, _) = coconut
+        .make_credential(&verify_key, &signature, &private_attributes)
+        .unwrap();
+    assert!(credential.verify(&coconut.params, &verify_key, &Vec::new()));
+}
+
+#[test]
+fn test_resharing() {
+    let attributes_size = 2;
+
+    let old_coconut = Coconut::<OsRngInstance>::new(attributes_size, 3, 5).unwrap();
+    let (old_secret_keys, old_verify_keys) = old_coconut.multiparty_keygen();
+    let verify_key = old_coconut.aggregate_keys(&old_verify_keys).unwrap();
+
+    // Issue a credential with the old committee
+    let d = ElGamalPrivateKey::new(&old_coconut.params);
+    let gamma = d.to_public(&old_coconut.params);
+
+    let private_attributes = vec![
+        Attribute::new(bls::Scalar::from(110), 0),
+        Attribute::new(bls::Scalar::from(4), 1),
+    ];
+
+    let (sign_request, _) = old_coconut
+        .make_blind_sign_request(&gamma, &private_attributes, &Vec::new())
+        .unwrap();
+
+    let indexes = vec![1u64, 2, 3];
+    let signature_shares: Vec<_> = indexes
+        .iter()
+        .map(|index| {
+            sign_request
+                .blind_sign(
+                    &old_coconut.params,
+                    &old_secret_keys[(*index - 1) as usize],
+                    &Vec::new(),
+                )
+                .unwrap()
+                .unblind(&d)
+        })
+        .collect();
+
+    let old_signature = Signature {
+        commitish: sign_request.compute_commitish(),
+        sigma: old_coconut.aggregate(&signature_shares, indexes),
+    };
+
+    // Authority 3 leaves and the committee grows to 4 of 7
+    let coconut = Coconut::<OsRngInstance>::new(attributes_size, 4, 7).unwrap();
+
+    let dealers: Vec<_> = vec![1u64, 2, 4, 5]
+        .into_iter()
+        .map(|index| {
+            let secret_key = &old_secret_keys[(index - 1) as usize];
+            // Dealer 4 tries to re-share a different secret
+            let secret_key = if index == 4 {
+                SecretKey {
+                    x: secret_key.x + bls::Scalar::one(),
+                    y: secret_key.y.clone(),
+                }
+            } else {
+                SecretKey {
+                    x: secret_key.x,
+                    y: secret_key.y.clone(),
+                }
+            };
+            reshare::Dealer::new(&old_coconut, &coconut, index, &secret_key).unwrap()
+        })
+        .collect();
+
+    let mut recipients: Vec<_> = (1..=coconut.authorities_total as u64)
+        .map(|index| {
+            reshare::Recipient::new(&old_coconut, &coconut, index, &old_verify_keys).unwrap()
+        })
+        .collect();
+
+    // Round 1 and 2
+    let mut complaints = Vec::new();
+    for dealer in &dealers {
+        for recipient in &mut recipients {
+            let mut share = dealer.share_for(recipient.index);
+
+            // Dealer 5 sends a bad share to recipient 2
+            if (dealer.index, recipient.index) == (5, 2) {
+                share.y[1] += bls::Scalar::one();
+            }
+
+            if let Some(complaint) = recipient.receive(dealer.commitments(), share) {
+                complaints.push(complaint);
+            }
+        }
+    }
+    assert_eq!(complaints.len(), 1);
+
+    // Round 3: dealer 5 answers
+    for complaint in &complaints {
+        let answer = dealers.iter().find_map(|dealer| dealer.answer(complaint));
+        for recipient in &mut recipients {
+            recipient.resolve(complaint, answer.clone());
+        }
+    }
+
+    for recipient in &recipients {
+        assert_eq!(recipient.qualified(), vec![1, 2, 5]);
+    }
+
+    // Round 4
+    let secret_keys: Vec<_> = recipients
+        .iter()
+        .map(|recipient| recipient.finish().unwrap())
+        .collect();
+
+    // The aggregated verify key didn't change
+    for recipient in &recipients {
+        let reshared_key = recipient.verify_key().unwrap();
+        assert_eq!(reshared_key.alpha, verify_key.alpha);
+        assert_eq!(reshared_key.beta, verify_key.beta);
+    }
+
+    let verify_keys: Vec<_> = recipients
+        .iter()
+        .map(|recipient| recipients[0].partial_verify_key(recipient.index).unwrap())
+        .collect();
+    for (partial_key, secret_key) in izip!(&verify_keys, &secret_keys) {
+        assert_eq!(partial_key.alpha, coconut.params.g2 * secret_key.x);
+    }
+    let aggregate_key = coconut.aggregate_keys(&verify_keys).unwrap();
+    assert_eq!(aggregate_key.alpha, verify_key.alpha);
+
+    // Credentials from before the re-sharing still verify
+    let (credential, _) = coconut
+        .make_credential(&verify_key, &old_signature, &private_attributes)
+        .unwrap();
+    assert!(credential.verify(&coconut.params, &verify_key, &Vec::new()));
+
+    // The new committee signs under the same key
+    let (sign_request, _) = coconut
+        .make_blind_sign_request(&gamma, &private_attributes, &Vec::new())
+        .unwrap();
+
+    let indexes = vec![2u64, 4, 6, 7];
+    let signature_shares: Vec<_> = indexes
+        .iter()
+        .map(|index| {
+            sign_request
+                .blind_sign(
+                    &coconut.params,
+                    &secret_keys[(*index - 1) as usize],
+                    &Vec::new(),
+                )
+                .unwrap()
+                .unblind(&d)
+        })
+        .collect();
+
+    let signature = Signature {
+        commitish: sign_request.compute_commitish(),
+        sigma: coconut.aggregate(&signature_shares, indexes),
+    };
+
+    let (credential, _) = coconut
+        .make_credential(&verify_key, &signature, &private_attributes)
+        .unwrap();
+    assert!(credential.verify(&coconut.params, &verify_key, &Vec::new()));
+
+    // The leftover old shares can't be re-shared with too few dealers
+    let mut recipient =
+        reshare::Recipient::new(&old_coconut, &coconut, 1, &old_verify_keys).unwrap();
+    let dealer = &dealers[0];
+    assert!(recipient
+        .receive(dealer.commitments(), dealer.share_for(1))
+        .is_none());
+    assert!(recipient.finish().is_err());
+
+    // Nor with a committee using other generators
+    let other_coconut = Coconut::<OsRngInstance>::new(attributes_size + 1, 4, 7).unwrap();
+    assert!(matches!(
+        reshare::Dealer::new(&old_coconut, &other_coconut, 1, &old_secret_keys[0]),
+        Err(crate::error::Error::ParametersMismatch)
+    ));
+    assert!(matches!(
+        reshare::Recipient::new(&old_coconut, &other_coconut, 1, &old_verify_keys),
+        Err(crate::error::Error::ParametersMismatch)
+    ));
+}
+
+#[test]
+fn test_known_answers() {
+    use crate::serial::serialize;
+
+    // Everything below is derived from the seed
+    let run = || {
+        SeededRngInstance::seed(b"coconut known answers");
+
+        let coconut = Coconut::<SeededRngInstance>::new(2, 2, 3).unwrap();
+        let (secret_keys, verify_keys) = coconut.multiparty_keygen();
+        let verify_key = coconut.aggregate_keys(&verify_keys).unwrap();
+
+        let d = ElGamalPrivateKey::new(&coconut.params);
+        let gamma = d.to_public(&coconut.params);
+
+        let private_attributes = vec![
+            Attribute::new(bls::Scalar::from(110), 0),
+            Attribute::new(bls::Scalar::from(4), 1),
+        ];
+
+        let (sign_request, _) = coconut
+            .make_blind_sign_request(&gamma, &private_attributes, &Vec::new())
+            .unwrap();
+
+        let indexes = vec![1u64, 3];
+        let partial_signatures: Vec<_> = indexes
+            .iter()
+            .map(|index| {
+                sign_request
+                    .blind_sign(
+                        &coconut.params,
+                        &secret_keys[(*index - 1) as usize],
+                        &Vec::new(),
+                    )
+                    .unwrap()
+            })
+            .collect();
+        let signature_shares: Vec<_> = partial_signatures
+            .iter()
+            .map(|partial| partial.unblind(&d))
+            .collect();
+
+        let signature = Signature {
+            commitish: sign_request.compute_commitish(),
+            sigma: coconut.aggregate(&signature_shares, indexes),
+        };
+
+        let (credential, _) = coconut
+            .make_credential(&verify_key, &signature, &private_attributes)
+            .unwrap();
+        assert!(credential.verify(&coconut.params, &verify_key, &Vec::new()));
+
+        let mut blind_signature = serialize(&verify_key);
+        blind_signature.extend(serialize(&sign_request));
+        for partial in &partial_signatures {
+            blind_signature.extend(serialize(partial));
+        }
+        blind_signature.extend(serialize(&signature));
+
+        (blind_signature, serialize(&credential))
+    };
+
+    let (blind_signature, credential) = run();
+    assert_eq!(run(), (blind_signature.clone(), credential.clone()));
+
+    check_known_answer("coconut_blind_signature", &blind_signature);
+    check_known_answer("coconut_credential", &credential);
+}
+
+#[test]
+fn test_versioned_parameters() {
+    use crate::parameters::{ParameterVersion, Parameters};
+
+    let params = Parameters::<OsRngInstance>::new_versioned(ParameterVersion::V1, 2, &vec![0]);
+    let coconut = Coconut::with_parameters(params, 1, 1).unwrap();
+    let (secret_keys, verify_keys) = coconut.multiparty_keygen();
+    let verify_key = coconut.aggregate_keys(&verify_keys).unwrap();
+
+    let d = ElGamalPrivateKey::new(&coconut.params);
+    let gamma = d.to_public(&coconut.params);
+
+    let private_attributes = vec![Attribute::new(bls::Scalar::from(110), 0)];
+    let public_attributes = vec![Attribute::new(bls::Scalar::from(4), 1)];
+
+    let (sign_request, _) = coconut
+        .make_blind_sign_request(&gamma, &private_attributes, &public_attributes)
+        .unwrap();
+    let signature_share = sign_request
+        .blind_sign(&coconut.params, &secret_keys[0], &public_attributes)
+        .unwrap()
+        .unblind(&d);
+
+    let signature = Signature {
+        commitish: sign_request.compute_commitish(),
+        sigma: coconut.aggregate(&vec![signature_share], vec![1]),
+    };
+
+    let (credential, _) = coconut
+        .make_credential(&verify_key, &signature, &private_attributes)
+        .unwrap();
+    assert!(credential.verify(&coconut.params, &verify_key, &public_attributes));
+
+    let empty = Parameters::<OsRngInstance>::new_versioned(ParameterVersion::V1, 0, &vec![0]);
+    assert!(Coconut::with_parameters(empty, 1, 1).is_err());
+}
+
+#[test]
+fn test_credential_batch_verify() {
+    let attributes_size = 2;
+    let number_credentials = 8;
+
+    let coconut = Coconut::<OsRngInstance>::new(attributes_size, 1, 1).unwrap();
+    let (secret_keys, verify_keys) = coconut.multiparty_keygen();
+    let (secret_key, verify_key) = (&secret_keys[0], &verify_keys[0]);
+
+    // Sign directly with the secret key: sigma = (x + sum(m_i y_i)) h
+    let make_credential = |value: u64| {
+        let private_attributes = vec![Attribute::new(bls::Scalar::from(value), 0)];
+        let public_attributes = vec![Attribute::new(bls::Scalar::from(value + 1), 1)];
+
+        let mut exponent = secret_key.x;
+        for attribute in private_attributes.iter().chain(&public_attributes) {
+            exponent += secret_key.y[attribute.index as usize] * attribute.value;
+        }
+        let commitish = coconut.params.g1 * coconut.params.random_scalar();
+        let signature = Signature {
+            commitish,
+            sigma: commitish * exponent,
+        };
+
+        let (credential, _) = coconut
+            .make_credential(verify_key, &signature, &private_attributes)
+            .unwrap();
+        (credential, public_attributes)
+    };
+
+    let credentials: Vec<_> = (0..number_credentials).map(make_credential).collect();
+    let batch: Vec<_> = credentials
+        .iter()
+        .map(|(credential, public_attributes)| (credential, verify_key, public_attributes))
+        .collect();
+
+    for (credential, public_attributes) in &credentials {
+        assert!(credential.verify(&coconut.params, verify_key, public_attributes));
+    }
+    assert!(Credential::batch_verify(&coconut.params, &batch));
+
+    // A single bad credential fails the whole batch
+    let mut bad_credentials = credentials.clone();
+    bad_credentials[3].0.v += bls::G1Projective::from(coconut.params.g1);
+    let bad_batch: Vec<_> = bad_credentials
+        .iter()
+        .map(|(credential, public_attributes)| (credential, verify_key, public_attributes))
+        .collect();
+    assert!(!Credential::batch_verify(&coconut.params, &bad_batch));
+
+    // So does a wrong public attribute
+    let mut wrong_attributes = credentials[5].1.clone();
+    wrong_attributes[0].value += bls::Scalar::one();
+    let mut wrong_batch = batch.clone();
+    wrong_batch[5].2 = &wrong_attributes;
+    assert!(!Credential::batch_verify(&coconut.params, &wrong_batch));
+}
+
+#[test]
+fn test_invalid_parameters_are_errors() {
+    use crate::error::Error;
+
+    assert!(matches!(
+        Coconut::<OsRngInstance>::new(0, 1, 1),
+        Err(Error::InvalidAttributeCount)
+    ));
+    assert!(matches!(
+        Coconut::<OsRngInstance>::new(2, 0, 3),
+        Err(Error::InvalidThreshold)
+    ));
+    assert!(matches!(
+        Coconut::<OsRngInstance>::new(2, 4, 3),
+        Err(Error::InvalidThreshold)
+    ));
+
+    let coconut = Coconut::<OsRngInstance>::new(2, 1, 1).unwrap();
+    assert!(matches!(
+        coconut.aggregate_keys(&Vec::new()),
+        Err(Error::MissingVerifyKeys)
+    ));
+
+    let (secret_keys, _) = coconut.multiparty_keygen();
+    let gamma = ElGamalPrivateKey::new(&coconut.params).to_public(&coconut.params);
+
+    let request = |private_attributes: Vec<Attribute>, public_attributes: Vec<Attribute>| {
+        coconut
+            .make_blind_sign_request(&gamma, &private_attributes, &public_attributes)
+            .map(|(sign_request, _)| sign_request)
+    };
+    let attribute = |index| Attribute::new(bls::Scalar::from(110), index);
+
+    assert!(matches!(
+        request(vec![attribute(0)], vec![]),
+        Err(Error::InvalidAttributeCount)
+    ));
+    assert!(matches!(
+        request(vec![attribute(0)], vec![attribute(5)]),
+        Err(Error::InvalidAttributeIndex)
+    ));
+    assert!(matches!(
+        request(vec![attribute(1)], vec![attribute(1)]),
+        Err(Error::InvalidAttributeIndex)
+    ));
+
+    // A signer is handed a request pointing past its keys
+    let public_attributes = vec![attribute(1)];
+    let mut sign_request = request(vec![attribute(0)], public_attributes.clone()).unwrap();
+    assert!(sign_request
+        .blind_sign(&coconut.params, &secret_keys[0], &public_attributes)
+        .is_ok());
+    sign_request.encrypted_attributes[0].index = 99;
+    assert!(matches!(
+        sign_request.blind_sign(&coconut.params, &secret_keys[0], &public_attributes),
+        Err(Error::InvalidAttributeIndex)
+    ));
+    assert!(matches!(
+        sign_request.blind_sign(&coconut.params, &secret_keys[0], &Vec::new()),
+        Err(Error::InvalidAttributeCount)
+    ));
+}
+
+#[test]
+fn test_credential_presentation() {
+    use crate::coconut::presentation::Presentation;
+    use crate::serial::{deserialize, serialize};
+
+    let coconut = Coconut::<OsRngInstance>::new(3, 1, 1).unwrap();
+    let (secret_keys, verify_keys) = coconut.multiparty_keygen();
+    let (secret_key, verify_key) = (&secret_keys[0], &verify_keys[0]);
+
+    let hidden_attributes = vec![
+        Attribute::new(bls::Scalar::from(110), 0),
+        Attribute::new(bls::Scalar::from(4), 2),
+    ];
+    let disclosed_attributes = vec![Attribute::new(bls::Scalar::from(256), 1)];
+
+    let mut exponent = secret_key.x;
+    for attribute in hidden_attributes.iter().chain(&disclosed_attributes) {
+        exponent += secret_key.y[attribute.index as usize] * attribute.value;
+    }
+    let commitish = coconut.params.g1 * coconut.params.random_scalar();
+    let signature = Signature {
+        commitish,
+        sigma: commitish * exponent,
+    };
+
+    let present = |context: &[u8]| {
+        coconut
+            .present(
+                verify_key,
+                &signature,
+                &hidden_attributes,
+                &disclosed_attributes,
+                context,
+            )
+            .unwrap()
+    };
+
+    let presentation = present(b"poll 1");
+    assert!(coconut.verify_presentation(verify_key, &presentation, b"poll 1"));
+    // Bound to the context
+    assert!(!coconut.verify_presentation(verify_key, &presentation, b"poll 2"));
+
+    // Presenting twice gives unlinkable credentials
+    let presentation2 = present(b"poll 1");
+    assert_ne!(
+        bls::G2Affine::from(presentation.credential.kappa),
+        bls::G2Affine::from(presentation2.credential.kappa)
+    );
+
+    let presentation: Presentation = deserialize(&serialize(&presentation)).unwrap();
+    assert!(coconut.verify_presentation(verify_key, &presentation, b"poll 1"));
+
+    // Lying about a disclosed attribute
+    let mut lying = presentation;
+    lying.disclosed_attributes[0].value += bls::Scalar::one();
+    assert!(!coconut.verify_presentation(verify_key, &lying, b"poll 1"));
+
+    // Skipping an attribute altogether
+    let mut partial = presentation2;
+    partial.disclosed_attributes.clear();
+    assert!(!coconut.verify_presentation(verify_key, &partial, b"poll 1"));
+
+    // Each attribute is either hidden or disclosed
+    assert!(coconut
+        .present(
+            verify_key,
+            &signature,
+            &hidden_attributes,
+            &hidden_attributes[..1].to_vec(),
+            b"poll 1",
+        )
+        .is_err());
+}
+
+#[test]
+fn test_credential_predicates() {
+    use crate::coconut::presentation::*;
+    use crate::error::Error;
+    use crate::serial::{deserialize, serialize};
+
+    let coconut = Coconut::<OsRngInstance>::new(3, 1, 1).unwrap();
+    let (secret_keys, verify_keys) = coconut.multiparty_keygen();
+    let (secret_key, verify_key) = (&secret_keys[0], &verify_keys[0]);
+
+    let sign = |attributes: &Vec<Attribute>| {
+        let mut exponent = secret_key.x;
+        for attribute in attributes {
+            exponent += secret_key.y[attribute.index as usize] * attribute.value;
+        }
+        let commitish = coconut.params.g1 * coconut.params.random_scalar();
+        Signature {
+            commitish,
+            sigma: commitish * exponent,
+        }
+    };
+
+    // Hidden age and role, disclosed member id
+    let credential_attributes = |age: u64, id: u64| {
+        (
+            vec![
+                Attribute::new(bls::Scalar::from(age), 0),
+                Attribute::new(bls::Scalar::from(2), 1),
+            ],
+            vec![Attribute::new(bls::Scalar::from(id), 2)],
+        )
+    };
+    let (hidden, disclosed) = credential_attributes(30, 1);
+    let signature = sign(&hidden.iter().chain(&disclosed).cloned().collect());
+
+    let present = |statements: Vec<AttributeStatement>| {
+        coconut.present_with_statements(
+            verify_key,
+            &signature,
+            &hidden,
+            &disclosed,
+            &statements,
+            b"door",
+        )
+    };
+
+    let presentation = present(vec![
+        AttributeStatement::new(&coconut.params, 0).at_least(18),
+        AttributeStatement::new(&coconut.params, 1).in_range(1, 3),
+    ])
+    .unwrap();
+    let presentation: Presentation = deserialize(&serialize(&presentation)).unwrap();
+    assert!(coconut.verify_presentation(verify_key, &presentation, b"door"));
+    assert!(presentation.proves(0, Predicate::AtLeast(18)));
+    assert!(presentation.proves(1, Predicate::AtMost(3)));
+    assert!(!presentation.proves(0, Predicate::AtLeast(21)));
+
+    // Claiming a stronger bound than was proven
+    let mut lying = presentation;
+    lying.attribute_commits[0].predicates[0] = Predicate::AtLeast(21);
+    assert!(!coconut.verify_presentation(verify_key, &lying, b"door"));
+
+    assert!(matches!(
+        present(vec![
+            AttributeStatement::new(&coconut.params, 0).at_least(40)
+        ]),
+        Err(Error::UnsatisfiedPredicate)
+    ));
+    assert!(matches!(
+        present(vec![AttributeStatement::new(&coconut.params, 0).at_most(29)]),
+        Err(Error::UnsatisfiedPredicate)
+    ));
+    // Disclosed attributes are already in the clear
+    assert!(matches!(
+        present(vec![AttributeStatement::new(&coconut.params, 2).at_least(1)]),
+        Err(Error::InvalidAttributeIndex)
+    ));
+
+    // Equal ages across credentials show up as equal commits
+    let blind = coconut.params.random_scalar();
+    let present_age = |age| {
+        let (hidden, disclosed) = credential_attributes(age, 2);
+        let signature = sign(&hidden.iter().chain(&disclosed).cloned().collect());
+        let presentation = coconut
+            .present_with_statements(
+                verify_key,
+                &signature,
+                &hidden,
+                &disclosed,
+                &vec![AttributeStatement::with_blind(0, blind)],
+                b"door",
+            )
+            .unwrap();
+        assert!(coconut.verify_presentation(verify_key, &presentation, b"door"));
+        presentation
+    };
+    let (first, same, other) = (present_age(30), present_age(30), present_age(31));
+    assert!(first.equal_attributes(0, &same, 0));
+    assert!(!first.equal_attributes(0, &other, 0));
+    assert!(!first.equal_attributes(1, &same, 1));
+}
@@ -1,5 +1,7 @@
 use bls12_381 as bls;
+use std::fmt;
 use std::io;
+use subtle::{Choice, ConstantTimeEq};
 
 use crate::bls_extensions::*;
 use crate::error::Result;
@@ -36,6 +38,34 @@ impl ElGamalPrivateKey {
     }
 }
 
+impl Drop for ElGamalPrivateKey {
+    fn drop(&mut self) {
+        self.private_key.wipe();
+    }
+}
+
+impl fmt::Debug for ElGamalPrivateKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ElGamalPrivateKey")
+            .field("private_key", &Redacted)
+            .finish()
+    }
+}
+
+impl ConstantTimeEq for ElGamalPrivateKey {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.private_key.ct_eq(&other.private_key)
+    }
+}
+
+impl PartialEq for ElGamalPrivateKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl Eq for ElGamalPrivateKey {}
+
 impl Encodable for ElGamalPrivateKey {
     fn encode<S: io::Write>(&self, s: S) -> Result<usize> {
         Ok(self.private_key.encode(s)?)
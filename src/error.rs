@@ -9,10 +9,35 @@ pub enum Error {
     InvalidCredential,
     TransactionPedersenCheckFailed,
     TokenAlreadySpent,
+    SpendNotConfirmed,
     InputTokenVerifyFailed,
     RangeproofPedersenMatchFailed,
     ProofsFailed,
     MissingProofs,
+    InvalidPublicAttributes,
+    InvalidThreshold,
+    InvalidAttributeCount,
+    InvalidAttributeIndex,
+    ParametersMismatch,
+    MissingVerifyKeys,
+    MissingSignature,
+    EmptyTransaction,
+    UnknownAsset,
+    NotEnoughSignatureShares(Vec<u64>),
+    UnsatisfiedPredicate,
+    InvalidEpoch,
+    UnknownEpoch,
+    ExpiredEpoch,
+    WrongEpoch,
+    InvalidMigration,
+    InsufficientFunds,
+    ValueOverflow,
+    InvalidAggregationSize,
+    InvalidRangeproofBits,
+    ValueOutOfRange,
+    WalletEncryptFailed,
+    WalletDecryptFailed,
+    InvalidWalletIndex,
     Io(std::io::Error),
     /// VarInt was encoded in a non-minimal way
     NonMinimalVarInt,
@@ -21,6 +46,9 @@ pub enum Error {
     AsyncChannelError,
     MalformedPacket,
     AddrParseError,
+    DkgInvalidIndex,
+    DkgMissingShare,
+    DkgNotEnoughQualified,
 }
 
 impl std::error::Error for Error {}
@@ -35,18 +63,74 @@ impl fmt::Display for Error {
                 f.write_str("Transaction pedersens for input and output don't sum up")
             }
             Error::TokenAlreadySpent => f.write_str("This input token is already spent"),
+            Error::SpendNotConfirmed => {
+                f.write_str("Input burns are not yet agreed in the slab log")
+            }
             Error::InputTokenVerifyFailed => f.write_str("Input token verify of credential failed"),
             Error::RangeproofPedersenMatchFailed => {
                 f.write_str("Rangeproof pedersen check for match failed")
             }
             Error::ProofsFailed => f.write_str("Proof validation failed"),
             Error::MissingProofs => f.write_str("Missing proofs"),
+            Error::InvalidPublicAttributes => {
+                f.write_str("Public attributes don't match the token layout")
+            }
+            Error::InvalidThreshold => {
+                f.write_str("Threshold must be between 1 and the number of authorities")
+            }
+            Error::InvalidAttributeCount => {
+                f.write_str("Number of attributes doesn't match the parameters")
+            }
+            Error::InvalidAttributeIndex => {
+                f.write_str("Attribute index is out of range or repeated")
+            }
+            Error::ParametersMismatch => {
+                f.write_str("Parameters don't match the other committee's")
+            }
+            Error::MissingVerifyKeys => f.write_str("No verify keys to aggregate"),
+            Error::MissingSignature => f.write_str("Token has no signature"),
+            Error::EmptyTransaction => f.write_str("Transaction value has nowhere to go"),
+            Error::UnknownAsset => f.write_str("Asset is not registered"),
+            Error::UnsatisfiedPredicate => {
+                f.write_str("Attribute doesn't satisfy the predicate to prove")
+            }
+            Error::InvalidEpoch => f.write_str("Key epochs must increase"),
+            Error::UnknownEpoch => f.write_str("No key for the token's epoch"),
+            Error::ExpiredEpoch => {
+                f.write_str("Token epoch is too old to spend, it must be migrated")
+            }
+            Error::WrongEpoch => f.write_str("Output isn't for the current key epoch"),
+            Error::InvalidMigration => f.write_str("Migration can't deposit or withdraw value"),
+            Error::InsufficientFunds => f.write_str("Not enough funds in the wallet"),
+            Error::ValueOverflow => f.write_str("Values add up to more than a u64 can hold"),
+            Error::InvalidAggregationSize => {
+                f.write_str("Aggregated range proof has no values or too many")
+            }
+            Error::InvalidRangeproofBits => {
+                f.write_str("Range proof width must be a power of two up to 64")
+            }
+            Error::ValueOutOfRange => f.write_str("Value is too wide for the range proof"),
+            Error::WalletEncryptFailed => f.write_str("Wallet is too large to encrypt"),
+            Error::WalletDecryptFailed => {
+                f.write_str("Wrong passphrase or the wallet file is corrupt")
+            }
+            Error::InvalidWalletIndex => f.write_str("Wallet index is out of range or repeated"),
+            Error::NotEnoughSignatureShares(ref rejected) => write!(
+                f,
+                "Not enough valid signature shares, rejected services: {:?}",
+                rejected
+            ),
             Error::Io(ref err) => fmt::Display::fmt(err, f),
             Error::NonMinimalVarInt => f.write_str("non-minimal varint"),
             Error::ParseFailed(ref err) => write!(f, "parse failed: {}", err),
             Error::AsyncChannelError => f.write_str("async_channel error"),
             Error::MalformedPacket => f.write_str("Malformed packet"),
             Error::AddrParseError => f.write_str("Unable to parse address"),
+            Error::DkgInvalidIndex => f.write_str("DKG participant index is out of range"),
+            Error::DkgMissingShare => f.write_str("Missing DKG share from a qualified dealer"),
+            Error::DkgNotEnoughQualified => {
+                f.write_str("Not enough qualified dealers to finish the DKG")
+            }
         }
     }
 }
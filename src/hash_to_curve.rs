@@ -0,0 +1,1683 @@
+use bls12_381 as bls;
+use sha2::{Digest, Sha256};
+use std::ops::{Add, Mul, Neg, Sub};
+
+// Hashing to BLS12-381 as specified in RFC 9380, with the suites
+// BLS12381G1_XMD:SHA-256_SSWU_RO_ and BLS12381G2_XMD:SHA-256_SSWU_RO_.
+//
+// The message is expanded with SHA-256 into two field elements. Each one is
+// mapped by the simplified SWU map onto a curve isogenous to E and then
+// through the isogeny onto E. The sum of both points has its cofactor
+// cleared. Unlike the try-and-increment hash in hashable.rs, the running
+// time doesn't depend on the message.
+//
+// bls12_381 doesn't expose its field types so the field arithmetic lives
+// here. Results are decoded by the library, which checks they are valid.
+
+// p
+const MODULUS: [u64; 6] = [
+    0xb9fe_ffff_ffff_aaab,
+    0x1eab_fffe_b153_ffff,
+    0x6730_d2a0_f6b0_f624,
+    0x6477_4b84_f385_12bf,
+    0x4b1b_a7b6_434b_acd7,
+    0x1a01_11ea_397f_e69a,
+];
+
+// 2^384 mod p, which is one in Montgomery form
+const R: [u64; 6] = [
+    0x7609_0000_0002_fffd,
+    0xebf4_000b_c40c_0002,
+    0x5f48_9857_53c7_58ba,
+    0x77ce_5853_7052_5745,
+    0x5c07_1a97_a256_ec6d,
+    0x15f6_5ec3_fa80_e493,
+];
+
+// 2^768 mod p
+const R2: [u64; 6] = [
+    0xf4df_1f34_1c34_1746,
+    0x0a76_e6a6_09d1_04f1,
+    0x8de5_476c_4c95_b6d5,
+    0x67eb_88a9_939d_83c0,
+    0x9a79_3e85_b519_952d,
+    0x1198_8fe5_92ca_e3aa,
+];
+
+// A' of the curve isogenous to E1
+const G1_SSWU_A: [u64; 6] = [
+    0x5cf4_2808_2d58_4c1d,
+    0x9893_6f8d_a0e0_f97f,
+    0xd8e8_981a_efd8_81ac,
+    0xb0ea_9853_83ee_66a8,
+    0x3d69_3a02_c96d_4982,
+    0x0014_4698_a3b8_e943,
+];
+
+// B' of the curve isogenous to E1
+const G1_SSWU_B: [u64; 6] = [
+    0xd1cc_48e9_8e17_2be0,
+    0x5a23_215a_316c_eaa5,
+    0xa0b9_c14f_cef3_5ef5,
+    0x2016_c1f0_f24f_4070,
+    0x018b_12e8_753e_ee3b,
+    0x12e2_908d_1168_8030,
+];
+
+// Z = 11
+const G1_SSWU_Z: [u64; 6] = [
+    0x0000_0000_0000_000b,
+    0x0000_0000_0000_0000,
+    0x0000_0000_0000_0000,
+    0x0000_0000_0000_0000,
+    0x0000_0000_0000_0000,
+    0x0000_0000_0000_0000,
+];
+
+// 11-isogeny x numerator, lowest degree first
+const G1_ISO_XNUM: [[u64; 6]; 12] = [
+    [
+        0xaeac_1662_7346_49b7,
+        0x5610_c2d5_f2e6_2d6e,
+        0xf262_7b56_cdb4_e2c8,
+        0x6b30_3e88_a2d7_005f,
+        0xb809_101d_d998_1585,
+        0x11a0_5f2b_1e83_3340,
+    ],
+    [
+        0xe834_eef1_b3cb_83bb,
+        0x4838_f2a6_f318_c356,
+        0xf565_e33c_70d1_e86b,
+        0x7c17_e75b_2f6a_8417,
+        0x0588_bab2_2147_a81c,
+        0x1729_4ed3_e943_ab2f,
+    ],
+    [
+        0xe017_9f9d_ac9e_dcb0,
+        0x958c_3e3d_2a09_729f,
+        0x6878_e501_ec68_e25c,
+        0xce03_2473_2959_83e5,
+        0x1d10_48c5_d10a_9a1b,
+        0x0d54_005d_b976_78ec,
+    ],
+    [
+        0xc5b3_8864_1d9b_6861,
+        0x5336_e25c_e310_7193,
+        0xf1b3_3289_f1b3_3083,
+        0xd7f5_e465_6a8d_bf25,
+        0x4e06_09d3_07e5_5412,
+        0x1778_e716_6fcc_6db7,
+    ],
+    [
+        0x5115_4ce9_ac88_95d9,
+        0x985a_286f_301e_77c4,
+        0x086e_eb65_982f_ac18,
+        0x99db_995a_1257_fb3f,
+        0x6642_b4b3_e411_8e54,
+        0x0e99_726a_3199_f443,
+    ],
+    [
+        0xcd13_c1c6_6f65_2983,
+        0xa087_0d2d_cae7_3d19,
+        0x9ed3_ab90_97e6_8f90,
+        0xdb3c_b17d_d952_799b,
+        0x01d1_201b_f7a7_4ab5,
+        0x1630_c325_0d73_13ff,
+    ],
+    [
+        0xddd7_f225_a139_ed84,
+        0x8da2_5128_c105_2eca,
+        0x9008_e218_f9c8_6b2a,
+        0xb115_8626_4f0f_8ce1,
+        0x6a37_26c3_8ae6_52bf,
+        0x0d6e_d655_3fe4_4d29,
+    ],
+    [
+        0x9ccb_5618_e3f0_c88e,
+        0x39b7_c8f8_c8f4_75af,
+        0xa682_c62e_f0f2_7533,
+        0x356d_e5ab_275b_4db1,
+        0xe874_3884_d111_7e53,
+        0x17b8_1e77_01ab_dbe2,
+    ],
+    [
+        0x6d71_986a_8497_e317,
+        0x4fa2_95f2_96b7_4e95,
+        0xa2c5_96c9_28c5_d1de,
+        0xc43b_756c_e79f_5574,
+        0x7b90_b335_63be_990d,
+        0x080d_3cf1_f9a7_8fc4,
+    ],
+    [
+        0x7f24_1067_be39_0c9e,
+        0xa319_0b2e_dc03_2779,
+        0x6763_14ba_f4bb_1b7f,
+        0xdd2e_cb80_3a0c_5c99,
+        0x2e0c_3751_5d13_8f22,
+        0x169b_1f8e_1bcf_a7c4,
+    ],
+    [
+        0xca67_df3f_1605_fb7b,
+        0xf69b_771f_8c28_5dec,
+        0xd50a_f360_03b1_4866,
+        0xfa7d_ccdd_e678_7f96,
+        0x72d8_ec09_d256_5b0d,
+        0x1032_1da0_79ce_07e2,
+    ],
+    [
+        0xa9c8_ba2e_8ba2_d229,
+        0xc24b_1b80_b64d_391f,
+        0x23c0_bf1b_c24c_6b68,
+        0x31d7_9d7e_22c8_37bc,
+        0xbd1e_9623_81ed_ee3d,
+        0x06e0_8c24_8e26_0e70,
+    ],
+];
+
+// 11-isogeny x denominator
+const G1_ISO_XDEN: [[u64; 6]; 11] = [
+    [
+        0x993c_f9fa_40d2_1b1c,
+        0xb558_d681_be34_3df8,
+        0x9c95_8861_7fc8_ac62,
+        0x01d5_ef4b_a35b_48ba,
+        0x18b2_e62f_4bd3_fa6f,
+        0x08ca_8d54_8cff_19ae,
+    ],
+    [
+        0xe5c8_276e_c82b_3bff,
+        0x13da_a884_6cb0_26e9,
+        0x0126_c258_8c48_bf57,
+        0x7041_e8ca_0cf0_800c,
+        0x48b4_7112_98e5_3636,
+        0x1256_1a5d_eb55_9c43,
+    ],
+    [
+        0xfcc2_39ba_5cb8_3e19,
+        0xd6a3_d096_7c94_fedc,
+        0xfca6_4e00_b11a_ceac,
+        0x6f89_416f_5a71_8cd1,
+        0x8137_e629_bff2_991f,
+        0x0b29_62fe_57a3_225e,
+    ],
+    [
+        0x130d_e893_8dc6_2cd8,
+        0x4976_d524_3eec_f5c4,
+        0x54cc_a8ab_c28d_6fd0,
+        0x5b08_243f_16b1_6551,
+        0xc83a_afef_7c40_eb54,
+        0x0342_5581_a58a_e2fe,
+    ],
+    [
+        0x539d_395b_3532_a21e,
+        0x9bd2_9ba8_1f35_781d,
+        0x8d6b_44e8_33b3_06da,
+        0xffdf_c759_a120_62bb,
+        0x0a6f_1d5f_43e7_a07d,
+        0x13a8_e162_0229_14a8,
+    ],
+    [
+        0xc02d_f9a2_9f63_04a5,
+        0x7400_d24b_c422_8f11,
+        0x0a43_bcef_24b8_982f,
+        0x3957_35e9_ce9c_ad4d,
+        0x5539_0f7f_0506_c6e9,
+        0x0e73_55f8_e4e6_67b9,
+    ],
+    [
+        0xec25_7449_6ee8_4a3a,
+        0xea73_b353_8f0d_e06c,
+        0x4e2e_0730_62ae_de9c,
+        0x570f_5799_af53_a189,
+        0x0f3e_0c63_e059_6721,
+        0x0772_caac_f169_3619,
+    ],
+    [
+        0x11f7_d99b_bdcc_5a5e,
+        0x0fa5_b948_9d11_e2d3,
+        0x1996_e1cd_f982_2c58,
+        0x6e7f_63c2_1bca_68a8,
+        0x30b3_f5b0_74cf_0199,
+        0x14a7_ac2a_9d64_a8b2,
+    ],
+    [
+        0x4776_ec3a_79a1_d641,
+        0x0382_6692_abba_4370,
+        0x7410_0da6_7f39_8835,
+        0xe07f_8d1d_7161_366b,
+        0x5e92_0b3d_afc7_a3cc,
+        0x0a10_ecf6_ada5_4f82,
+    ],
+    [
+        0x2d63_84d1_68ec_dd0a,
+        0x9317_4e4b_4b78_6500,
+        0x76df_5339_78f3_1c15,
+        0xf682_b4ee_96f7_d037,
+        0x476d_6e3e_b3a5_6680,
+        0x095f_c13a_b9e9_2ad4,
+    ],
+    [
+        0x0000_0000_0000_0001,
+        0x0000_0000_0000_0000,
+        0x0000_0000_0000_0000,
+        0x0000_0000_0000_0000,
+        0x0000_0000_0000_0000,
+        0x0000_0000_0000_0000,
+    ],
+];
+
+// 11-isogeny y numerator
+const G1_ISO_YNUM: [[u64; 6]; 16] = [
+    [
+        0xbe98_4571_9707_bb33,
+        0xcd0c_7aee_9b3b_a3c2,
+        0x2b52_af6c_9565_43d3,
+        0x11ad_138e_48a8_6952,
+        0x259d_1f09_4980_dcfa,
+        0x090d_97c8_1ba2_4ee0,
+    ],
+    [
+        0xe097_e75a_2e41_c696,
+        0xd6c5_6711_962f_a8bf,
+        0x0f90_6343_eb67_ad34,
+        0x1223_e96c_254f_383d,
+        0xd510_36d7_76fb_4683,
+        0x1349_96a1_04ee_5811,
+    ],
+    [
+        0xb8df_e240_c72d_e1f6,
+        0xd26d_5216_28b0_0523,
+        0xc344_be4b_9140_0da7,
+        0x2552_e2d6_58a3_1ce2,
+        0xf4a3_84c8_6a3b_4994,
+        0x00cc_786b_aa96_6e66,
+    ],
+    [
+        0xa635_5c77_b0e5_f4cb,
+        0xde40_5aba_9ec6_1dec,
+        0x09e4_a3ec_0325_1cf9,
+        0xd42a_a7b9_0eeb_791c,
+        0x7898_751a_d874_6757,
+        0x01f8_6376_e898_1c21,
+    ],
+    [
+        0x41b6_daec_f2e8_fedb,
+        0x2ee7_f8dc_0990_40a8,
+        0x7983_3fd2_2135_1adc,
+        0x1955_36fb_e3ce_50b8,
+        0x5caf_4fe2_a215_29c4,
+        0x08cc_03fd_efe0_ff13,
+    ],
+    [
+        0x99b2_3ab1_3633_a5f0,
+        0x203f_6326_c95a_8072,
+        0x7650_5c3d_3ad5_544e,
+        0x74a7_d0d4_afad_b7bd,
+        0x2211_e11d_b8f0_a6a0,
+        0x1660_3fca_4063_4b6a,
+    ],
+    [
+        0xc961_f885_5fe9_d6f2,
+        0x47a8_7ac2_460f_415e,
+        0x5231_413c_4d63_4f37,
+        0xe75b_b8ca_2be1_84cb,
+        0xb2c9_77d0_2779_6b3c,
+        0x04ab_0b9b_cfac_1bbc,
+    ],
+    [
+        0xa15e_4ca3_1870_fb29,
+        0x42f6_4550_fedf_e935,
+        0xfd03_8da6_c26c_8426,
+        0x170a_05bf_e3bd_d81f,
+        0xde99_26bd_2ca6_c674,
+        0x0987_c8d5_333a_b86f,
+    ],
+    [
+        0x6037_0e57_7bdb_a587,
+        0x69d6_5201_c786_07a3,
+        0x1e8b_6e6a_1f20_cabe,
+        0x8f3a_bd16_679d_c26c,
+        0xe88c_9e22_1e4d_a1bb,
+        0x09fc_4018_bd96_684b,
+    ],
+    [
+        0x2baf_aaeb_ca73_1c30,
+        0x9b3f_7055_dd4e_ba6f,
+        0x0698_5e7e_d1e4_d43b,
+        0xc42a_0ca7_915a_f6fe,
+        0x223a_bde7_ada1_4a23,
+        0x0e1b_ba7a_1186_bdb5,
+    ],
+    [
+        0xe813_711a_d011_c132,
+        0x31bf_3a5c_ce3f_bafc,
+        0xd118_3e41_6389_e610,
+        0xcd2f_cbcb_6caf_493f,
+        0x0dfd_0b8f_1d43_fb93,
+        0x1971_3e47_937c_d1be,
+    ],
+    [
+        0xce07_c8a4_d007_4d8e,
+        0x49d9_cdf4_1b44_d606,
+        0x2e6b_fe7f_911f_6432,
+        0x5235_59b8_aaf0_c246,
+        0xb918_c143_fed2_edcc,
+        0x18b4_6a90_8f36_f6de,
+    ],
+    [
+        0x0d4c_04f0_0b97_1ef8,
+        0x06c8_51c1_9192_11f2,
+        0xc027_10e8_07b4_633f,
+        0x7aa7_b12a_3426_b08e,
+        0xd155_0960_04f5_3f44,
+        0x0b18_2cac_101b_9399,
+    ],
+    [
+        0x42d9_d3f5_db98_0133,
+        0xc6cf_90ad_1c23_2a64,
+        0x13e6_632d_3c40_659c,
+        0x757b_3b08_0d4c_1580,
+        0x72fc_00ae_7be3_15dc,
+        0x0245_a394_ad1e_ca9b,
+    ],
+    [
+        0x866b_1e71_5475_224b,
+        0x6ba1_049b_6579_afb7,
+        0xd9ab_0f5d_396a_7ce4,
+        0x5e67_3d81_d7e8_6568,
+        0x02a1_59f7_48c4_a3fc,
+        0x05c1_2964_5e44_cf11,
+    ],
+    [
+        0x04b4_56be_69c8_b604,
+        0xb665_027e_fec0_1c77,
+        0x57ad_d4fa_95af_01b2,
+        0xcb18_1d8f_8496_5a39,
+        0x4ea5_0b3b_42df_2eb5,
+        0x15e6_be4e_990f_03ce,
+    ],
+];
+
+// 11-isogeny y denominator
+const G1_ISO_YDEN: [[u64; 6]; 16] = [
+    [
+        0x0147_9253_b036_63c1,
+        0x07f3_688e_f60c_206d,
+        0xeec3_232b_5be7_2e7a,
+        0x601a_6de5_7898_0be6,
+        0x5218_1140_fad0_eae9,
+        0x1611_2c4c_3a9c_98b2,
+    ],
+    [
+        0x32f6_102c_2e49_a03d,
+        0x78a4_2607_6352_9e35,
+        0xa4a1_0356_f453_e01f,
+        0x85c8_4ff7_31c4_d59c,
+        0x1a0c_bd6c_43c3_48b8,
+        0x1962_d75c_2381_201e,
+    ],
+    [
+        0x1e25_38b5_3dbf_67f2,
+        0xa675_7cd6_36f9_6f89,
+        0x0c35_a5dd_279c_d2ec,
+        0x78c4_8555_51ae_7f31,
+        0x6faa_ae7d_6e8e_b157,
+        0x058d_f330_6640_da27,
+    ],
+    [
+        0xa8d2_6d98_445f_5416,
+        0x7273_64f2_c282_97ad,
+        0x123d_a489_e726_af41,
+        0xd115_c5db_ddbc_d30e,
+        0xf20d_23bf_89ed_b4d1,
+        0x16b7_d288_798e_5395,
+    ],
+    [
+        0xda39_1423_11a5_001d,
+        0xa20b_15dc_0fd2_eded,
+        0x542e_da0f_c9de_c916,
+        0xc6d1_9c9f_0f69_bbb0,
+        0xb00c_c912_f822_8ddc,
+        0x0be0_e079_545f_43e4,
+    ],
+    [
+        0x02c6_477f_aaf9_b7ac,
+        0x49f3_8db9_dfa9_cce2,
+        0xc5ec_d87b_6f0f_5a64,
+        0xb701_52c6_5550_d881,
+        0x9fb2_66ea_ac78_3182,
+        0x08d9_e529_7186_db2d,
+    ],
+    [
+        0x3d1a_1399_126a_775c,
+        0xd5fa_9c01_a58b_1fb9,
+        0x5dd3_65bc_400a_0051,
+        0x5eec_fdfa_8d0c_f8ef,
+        0xc3ba_8734_ace9_824b,
+        0x1660_07c0_8a99_db2f,
+    ],
+    [
+        0x60ee_415a_1581_2ed9,
+        0xb920_f5b0_0801_dee4,
+        0xfeb3_4fd2_0635_7132,
+        0xe5a4_375e_fa1f_4fd7,
+        0x03bc_ddfa_bba6_ff6e,
+        0x16a3_ef08_be3e_a7ea,
+    ],
+    [
+        0x6b23_3d9d_5553_5d4a,
+        0x52cf_e2f7_bb92_4883,
+        0xabc5_750c_4bf3_9b48,
+        0xf9fb_0ce4_c6af_5920,
+        0x1a1b_e54f_d1d7_4cc4,
+        0x1866_c8ed_336c_6123,
+    ],
+    [
+        0x346e_f48b_b891_3f55,
+        0xc738_5ea3_d529_b35e,
+        0x5308_592e_7ea7_d4fb,
+        0x3216_f763_e13d_87bb,
+        0xea82_0597_d94a_8490,
+        0x167a_55cd_a70a_6e1c,
+    ],
+    [
+        0x00f8_b49c_ba8f_6aa8,
+        0x71a5_c29f_4f83_0604,
+        0x0e59_1b36_e636_a5c8,
+        0x9c6d_d039_bb61_a629,
+        0x48f0_10a0_1ad2_911d,
+        0x04d2_f259_eea4_05bd,
+    ],
+    [
+        0x9684_b529_e256_1092,
+        0x16f9_6898_6f7e_bbea,
+        0x8c0f_9a88_cea7_9135,
+        0x7f94_ff8a_efce_42d2,
+        0xf585_2c1e_48c5_0c47,
+        0x0acc_bb67_481d_033f,
+    ],
+    [
+        0x1e99_b138_5733_45cc,
+        0x9300_0763_e3b9_0ac1,
+        0x7d5c_eef9_a00d_9b86,
+        0x5433_46d9_8adf_0226,
+        0xc361_3144_b45f_1496,
+        0x0ad6_b951_4c76_7fe3,
+    ],
+    [
+        0xd1fa_dc13_26ed_06f7,
+        0x4205_17bd_8714_cc80,
+        0xcb74_8df2_7942_480e,
+        0xbf56_5b94_e729_27c1,
+        0x628b_dd0d_53cd_76f2,
+        0x0266_0400_eb2e_4f3b,
+    ],
+    [
+        0x4415_473a_1d63_4b8f,
+        0x5ca2_f570_f134_9780,
+        0x324e_fcd6_356c_aa20,
+        0x71c4_0f65_e273_b853,
+        0x6b24_255e_0d78_19c1,
+        0x0e0f_a1d8_16dd_c03e,
+    ],
+    [
+        0x0000_0000_0000_0001,
+        0x0000_0000_0000_0000,
+        0x0000_0000_0000_0000,
+        0x0000_0000_0000_0000,
+        0x0000_0000_0000_0000,
+        0x0000_0000_0000_0000,
+    ],
+];
+
+// A' = 240i
+const G2_SSWU_A: [[u64; 6]; 2] = [
+    [
+        0x0000_0000_0000_0000,
+        0x0000_0000_0000_0000,
+        0x0000_0000_0000_0000,
+        0x0000_0000_0000_0000,
+        0x0000_0000_0000_0000,
+        0x0000_0000_0000_0000,
+    ],
+    [
+        0x0000_0000_0000_00f0,
+        0x0000_0000_0000_0000,
+        0x0000_0000_0000_0000,
+        0x0000_0000_0000_0000,
+        0x0000_0000_0000_0000,
+        0x0000_0000_0000_0000,
+    ],
+];
+
+// B' = 1012(1 + i)
+const G2_SSWU_B: [[u64; 6]; 2] = [
+    [
+        0x0000_0000_0000_03f4,
+        0x0000_0000_0000_0000,
+        0x0000_0000_0000_0000,
+        0x0000_0000_0000_0000,
+        0x0000_0000_0000_0000,
+        0x0000_0000_0000_0000,
+    ],
+    [
+        0x0000_0000_0000_03f4,
+        0x0000_0000_0000_0000,
+        0x0000_0000_0000_0000,
+        0x0000_0000_0000_0000,
+        0x0000_0000_0000_0000,
+        0x0000_0000_0000_0000,
+    ],
+];
+
+// Z = -(2 + i)
+const G2_SSWU_Z: [[u64; 6]; 2] = [
+    [
+        0xb9fe_ffff_ffff_aaa9,
+        0x1eab_fffe_b153_ffff,
+        0x6730_d2a0_f6b0_f624,
+        0x6477_4b84_f385_12bf,
+        0x4b1b_a7b6_434b_acd7,
+        0x1a01_11ea_397f_e69a,
+    ],
+    [
+        0xb9fe_ffff_ffff_aaaa,
+        0x1eab_fffe_b153_ffff,
+        0x6730_d2a0_f6b0_f624,
+        0x6477_4b84_f385_12bf,
+        0x4b1b_a7b6_434b_acd7,
+        0x1a01_11ea_397f_e69a,
+    ],
+];
+
+// 3-isogeny x numerator, lowest degree first
+const G2_ISO_XNUM: [[[u64; 6]; 2]; 4] = [
+    [
+        [
+            0x6238_aaaa_aaaa_97d6,
+            0x5c26_38e3_43d9_c71c,
+            0x88b5_8423_c50a_e15d,
+            0x32c5_2d39_fd3a_042a,
+            0xbb5b_7a9a_47d7_ed85,
+            0x05c7_5950_7e8e_333e,
+        ],
+        [
+            0x6238_aaaa_aaaa_97d6,
+            0x5c26_38e3_43d9_c71c,
+            0x88b5_8423_c50a_e15d,
+            0x32c5_2d39_fd3a_042a,
+            0xbb5b_7a9a_47d7_ed85,
+            0x05c7_5950_7e8e_333e,
+        ],
+    ],
+    [
+        [
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+        ],
+        [
+            0x26a9_ffff_ffff_c71a,
+            0x1472_aaa9_cb8d_5555,
+            0x9a20_8c6b_4f20_a418,
+            0x984f_87ad_f7ae_0c7f,
+            0x3212_6fce_d787_c88f,
+            0x1156_0bf1_7baa_99bc,
+        ],
+    ],
+    [
+        [
+            0x26a9_ffff_ffff_c71e,
+            0x1472_aaa9_cb8d_5555,
+            0x9a20_8c6b_4f20_a418,
+            0x984f_87ad_f7ae_0c7f,
+            0x3212_6fce_d787_c88f,
+            0x1156_0bf1_7baa_99bc,
+        ],
+        [
+            0x9354_ffff_ffff_e38d,
+            0x0a39_5554_e5c6_aaaa,
+            0xcd10_4635_a790_520c,
+            0xcc27_c3d6_fbd7_063f,
+            0x1909_37e7_6bc3_e447,
+            0x08ab_05f8_bdd5_4cde,
+        ],
+    ],
+    [
+        [
+            0x88e2_aaaa_aaaa_5ed1,
+            0x7098_e38d_0f67_1c71,
+            0x22d6_108f_142b_8575,
+            0xcb14_b4e7_f4e8_10aa,
+            0xed6d_ea69_1f5f_b614,
+            0x171d_6541_fa38_ccfa,
+        ],
+        [
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+        ],
+    ],
+];
+
+// 3-isogeny x denominator
+const G2_ISO_XDEN: [[[u64; 6]; 2]; 3] = [
+    [
+        [
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+        ],
+        [
+            0xb9fe_ffff_ffff_aa63,
+            0x1eab_fffe_b153_ffff,
+            0x6730_d2a0_f6b0_f624,
+            0x6477_4b84_f385_12bf,
+            0x4b1b_a7b6_434b_acd7,
+            0x1a01_11ea_397f_e69a,
+        ],
+    ],
+    [
+        [
+            0x0000_0000_0000_000c,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+        ],
+        [
+            0xb9fe_ffff_ffff_aa9f,
+            0x1eab_fffe_b153_ffff,
+            0x6730_d2a0_f6b0_f624,
+            0x6477_4b84_f385_12bf,
+            0x4b1b_a7b6_434b_acd7,
+            0x1a01_11ea_397f_e69a,
+        ],
+    ],
+    [
+        [
+            0x0000_0000_0000_0001,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+        ],
+        [
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+        ],
+    ],
+];
+
+// 3-isogeny y numerator
+const G2_ISO_YNUM: [[[u64; 6]; 2]; 4] = [
+    [
+        [
+            0x12cf_c71c_71c6_d706,
+            0xfc8c_25eb_f8c9_2f68,
+            0xf544_39d8_7d27_e500,
+            0x0f7d_a5d4_a07f_649b,
+            0x59a4_c18b_076d_1193,
+            0x1530_477c_7ab4_113b,
+        ],
+        [
+            0x12cf_c71c_71c6_d706,
+            0xfc8c_25eb_f8c9_2f68,
+            0xf544_39d8_7d27_e500,
+            0x0f7d_a5d4_a07f_649b,
+            0x59a4_c18b_076d_1193,
+            0x1530_477c_7ab4_113b,
+        ],
+    ],
+    [
+        [
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+        ],
+        [
+            0x6238_aaaa_aaaa_97be,
+            0x5c26_38e3_43d9_c71c,
+            0x88b5_8423_c50a_e15d,
+            0x32c5_2d39_fd3a_042a,
+            0xbb5b_7a9a_47d7_ed85,
+            0x05c7_5950_7e8e_333e,
+        ],
+    ],
+    [
+        [
+            0x26a9_ffff_ffff_c71c,
+            0x1472_aaa9_cb8d_5555,
+            0x9a20_8c6b_4f20_a418,
+            0x984f_87ad_f7ae_0c7f,
+            0x3212_6fce_d787_c88f,
+            0x1156_0bf1_7baa_99bc,
+        ],
+        [
+            0x9354_ffff_ffff_e38f,
+            0x0a39_5554_e5c6_aaaa,
+            0xcd10_4635_a790_520c,
+            0xcc27_c3d6_fbd7_063f,
+            0x1909_37e7_6bc3_e447,
+            0x08ab_05f8_bdd5_4cde,
+        ],
+    ],
+    [
+        [
+            0xe1b3_71c7_1c71_8b10,
+            0x4e79_097a_56dc_4bd9,
+            0xb0e9_77c6_9aa2_7452,
+            0x761b_0f37_a1e2_6286,
+            0xfbf7_043d_e381_1ad0,
+            0x124c_9ad4_3b6c_f79b,
+        ],
+        [
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+        ],
+    ],
+];
+
+// 3-isogeny y denominator
+const G2_ISO_YDEN: [[[u64; 6]; 2]; 4] = [
+    [
+        [
+            0xb9fe_ffff_ffff_a8fb,
+            0x1eab_fffe_b153_ffff,
+            0x6730_d2a0_f6b0_f624,
+            0x6477_4b84_f385_12bf,
+            0x4b1b_a7b6_434b_acd7,
+            0x1a01_11ea_397f_e69a,
+        ],
+        [
+            0xb9fe_ffff_ffff_a8fb,
+            0x1eab_fffe_b153_ffff,
+            0x6730_d2a0_f6b0_f624,
+            0x6477_4b84_f385_12bf,
+            0x4b1b_a7b6_434b_acd7,
+            0x1a01_11ea_397f_e69a,
+        ],
+    ],
+    [
+        [
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+        ],
+        [
+            0xb9fe_ffff_ffff_a9d3,
+            0x1eab_fffe_b153_ffff,
+            0x6730_d2a0_f6b0_f624,
+            0x6477_4b84_f385_12bf,
+            0x4b1b_a7b6_434b_acd7,
+            0x1a01_11ea_397f_e69a,
+        ],
+    ],
+    [
+        [
+            0x0000_0000_0000_0012,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+        ],
+        [
+            0xb9fe_ffff_ffff_aa99,
+            0x1eab_fffe_b153_ffff,
+            0x6730_d2a0_f6b0_f624,
+            0x6477_4b84_f385_12bf,
+            0x4b1b_a7b6_434b_acd7,
+            0x1a01_11ea_397f_e69a,
+        ],
+    ],
+    [
+        [
+            0x0000_0000_0000_0001,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+        ],
+        [
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0000,
+        ],
+    ],
+];
+
+// sqrt(i)
+const SQRT_I: [[u64; 6]; 2] = [
+    [
+        0xc810_84fb_ede3_cc09,
+        0xee67_992f_72ec_05f4,
+        0x77f7_6e17_0092_41c5,
+        0x4839_5dab_c2d3_435e,
+        0x6831_e36d_6bd1_7ffe,
+        0x06af_0e04_37ff_400b,
+    ],
+    [
+        0xf1ee_7b04_121b_dea2,
+        0x3044_66cf_3e67_fa0a,
+        0xef39_6489_f61e_b45e,
+        0x1c3d_edd9_30b1_cf60,
+        0xe2e9_c448_d77a_2cd9,
+        0x1352_03e6_0180_a68e,
+    ],
+];
+
+// sqrt(-i)
+const SQRT_MINUS_I: [[u64; 6]; 2] = [
+    [
+        0xc810_84fb_ede3_cc09,
+        0xee67_992f_72ec_05f4,
+        0x77f7_6e17_0092_41c5,
+        0x4839_5dab_c2d3_435e,
+        0x6831_e36d_6bd1_7ffe,
+        0x06af_0e04_37ff_400b,
+    ],
+    [
+        0xc810_84fb_ede3_cc09,
+        0xee67_992f_72ec_05f4,
+        0x77f7_6e17_0092_41c5,
+        0x4839_5dab_c2d3_435e,
+        0x6831_e36d_6bd1_7ffe,
+        0x06af_0e04_37ff_400b,
+    ],
+];
+
+// (p - 2) for inversion
+const P_MINUS_2: [u64; 6] = [
+    0xb9fe_ffff_ffff_aaa9,
+    0x1eab_fffe_b153_ffff,
+    0x6730_d2a0_f6b0_f624,
+    0x6477_4b84_f385_12bf,
+    0x4b1b_a7b6_434b_acd7,
+    0x1a01_11ea_397f_e69a,
+];
+
+// (p + 1) / 4 for square roots since p = 3 mod 4
+const P_PLUS_1_DIV_4: [u64; 6] = [
+    0xee7f_bfff_ffff_eaab,
+    0x07aa_ffff_ac54_ffff,
+    0xd9cc_34a8_3dac_3d89,
+    0xd91d_d2e1_3ce1_44af,
+    0x92c6_e9ed_90d2_eb35,
+    0x0680_447a_8e5f_f9a6,
+];
+
+// (p^2 + 7) / 16 for square roots since p^2 = 9 mod 16
+const P2_PLUS_7_DIV_16: [u64; 12] = [
+    0xb26a_a000_01c7_18e4,
+    0xd7ce_d6b1_d763_82ea,
+    0x3162_c338_3621_13cf,
+    0x966b_f91e_d3e7_1b74,
+    0xb292_e85a_8709_1a04,
+    0x11d6_8619_c861_85c7,
+    0xef53_1493_3097_8ef0,
+    0x050a_62cf_d16d_dca6,
+    0x466e_59e4_9349_e8bd,
+    0x9e2d_c90e_50e7_046b,
+    0x74bd_278e_aa22_f25e,
+    0x002a_437a_4b8c_35fc,
+];
+
+// Effective cofactor for G2
+const G2_H_EFF: [u64; 10] = [
+    0xe802_0005_aaa9_5551,
+    0x5989_4c0a_debb_f6b4,
+    0xe954_cbc0_6689_f6a3,
+    0x2ec0_ec69_d747_7c1a,
+    0x6d82_bf01_5d12_12b0,
+    0x329c_2f17_8731_db95,
+    0x9986_ff03_1508_ffe1,
+    0x88e2_a8e9_145a_d768,
+    0x584c_6a0e_a91b_3528,
+    0x0bc6_9f08_f2ee_75b3,
+];
+
+// -p^-1 mod 2^64
+const INV: u64 = 0x89f3_fffc_fffc_fffd;
+
+// Effective cofactor for G1
+const G1_H_EFF: u64 = 0xd201_0000_0001_0001;
+
+// a + b + carry, returning the result and the new carry
+#[inline]
+fn adc(a: u64, b: u64, carry: u64) -> (u64, u64) {
+    let ret = (a as u128) + (b as u128) + (carry as u128);
+    (ret as u64, (ret >> 64) as u64)
+}
+
+// a - (b + borrow), returning the result and the new borrow
+#[inline]
+fn sbb(a: u64, b: u64, borrow: u64) -> (u64, u64) {
+    let ret = (a as u128).wrapping_sub((b as u128) + ((borrow >> 63) as u128));
+    (ret as u64, (ret >> 64) as u64)
+}
+
+// a + b * c + carry, returning the result and the new carry
+#[inline]
+fn mac(a: u64, b: u64, c: u64, carry: u64) -> (u64, u64) {
+    let ret = (a as u128) + ((b as u128) * (c as u128)) + (carry as u128);
+    (ret as u64, (ret >> 64) as u64)
+}
+
+// All ones when choice is set
+#[inline]
+fn mask(choice: bool) -> u64 {
+    0u64.wrapping_sub(choice as u64)
+}
+
+trait Field:
+    Copy + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self> + Neg<Output = Self>
+{
+    fn zero() -> Self;
+    fn one() -> Self;
+    fn is_zero(&self) -> bool;
+    fn ct_eq(&self, other: &Self) -> bool;
+    // b when choice is set, otherwise a
+    fn select(a: &Self, b: &Self, choice: bool) -> Self;
+    fn sgn0(&self) -> bool;
+    // Zero maps to zero
+    fn invert(&self) -> Self;
+    // The square root when there is one. Callers check by squaring.
+    fn sqrt_candidate(&self) -> Self;
+
+    fn square(&self) -> Self {
+        *self * *self
+    }
+
+    fn pow(&self, exponent: &[u64]) -> Self {
+        let mut result = Self::one();
+        for limb in exponent.iter().rev() {
+            for i in (0..64).rev() {
+                result = result.square();
+                let product = result * *self;
+                result = Self::select(&result, &product, (limb >> i) & 1 == 1);
+            }
+        }
+        result
+    }
+}
+
+// Base field element in Montgomery form, always fully reduced
+#[derive(Clone, Copy)]
+struct Fp([u64; 6]);
+
+// Quadratic extension with i^2 = -1
+#[derive(Clone, Copy)]
+struct Fp2 {
+    c0: Fp,
+    c1: Fp,
+}
+
+impl Fp {
+    // From the little endian limbs of a value below 2^384
+    fn from_raw(limbs: [u64; 6]) -> Self {
+        Fp(limbs) * Fp(R2)
+    }
+
+    // Little endian limbs of the canonical value
+    fn to_raw(self) -> [u64; 6] {
+        (self * Fp([1, 0, 0, 0, 0, 0])).0
+    }
+
+    // Reduce 64 big endian bytes modulo p
+    fn from_okm(okm: &[u8]) -> Self {
+        let from_be = |bytes: &[u8]| {
+            let mut limbs = [0u64; 6];
+            for (i, byte) in bytes.iter().rev().enumerate() {
+                limbs[i / 8] |= (*byte as u64) << (8 * (i % 8));
+            }
+            Self::from_raw(limbs)
+        };
+        // high * 2^384 + low
+        from_be(&okm[..16]) * Self::from_raw(R) + from_be(&okm[16..])
+    }
+
+    fn to_bytes(self) -> [u8; 48] {
+        let mut bytes = [0u8; 48];
+        for (chunk, limb) in bytes.chunks_mut(8).rev().zip(&self.to_raw()) {
+            chunk.copy_from_slice(&limb.to_be_bytes());
+        }
+        bytes
+    }
+
+    fn subtract(self, rhs: Self) -> Self {
+        let mut limbs = [0u64; 6];
+        let mut borrow = 0;
+        for (limb, (a, b)) in limbs.iter_mut().zip(self.0.iter().zip(&rhs.0)) {
+            let (value, new_borrow) = sbb(*a, *b, borrow);
+            *limb = value;
+            borrow = new_borrow;
+        }
+
+        // Add p back if we went below zero
+        let mut carry = 0;
+        for (limb, p) in limbs.iter_mut().zip(&MODULUS) {
+            let (value, new_carry) = adc(*limb, p & borrow, carry);
+            *limb = value;
+            carry = new_carry;
+        }
+        Fp(limbs)
+    }
+
+    // Values are below 2p so one subtraction is enough
+    fn subtract_p(self) -> Self {
+        let mut limbs = [0u64; 6];
+        let mut borrow = 0;
+        for (limb, (a, p)) in limbs.iter_mut().zip(self.0.iter().zip(&MODULUS)) {
+            let (value, new_borrow) = sbb(*a, *p, borrow);
+            *limb = value;
+            borrow = new_borrow;
+        }
+        // Keep the original when it was already below p
+        Self::select(&Fp(limbs), &self, borrow != 0)
+    }
+}
+
+impl Add for Fp {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        let mut limbs = [0u64; 6];
+        let mut carry = 0;
+        for (limb, (a, b)) in limbs.iter_mut().zip(self.0.iter().zip(&rhs.0)) {
+            let (value, new_carry) = adc(*a, *b, carry);
+            *limb = value;
+            carry = new_carry;
+        }
+        Fp(limbs).subtract_p()
+    }
+}
+
+impl Sub for Fp {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        self.subtract(rhs)
+    }
+}
+
+impl Neg for Fp {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::zero() - self
+    }
+}
+
+impl Mul for Fp {
+    type Output = Self;
+
+    // Montgomery multiplication, coarsely integrated operand scanning
+    fn mul(self, rhs: Self) -> Self {
+        let mut t = [0u64; 8];
+        for a in &self.0 {
+            let mut carry = 0;
+            for (t_j, b) in t.iter_mut().zip(&rhs.0) {
+                let (value, new_carry) = mac(*t_j, *a, *b, carry);
+                *t_j = value;
+                carry = new_carry;
+            }
+            let (value, overflow) = adc(t[6], carry, 0);
+            t[6] = value;
+            t[7] = overflow;
+
+            let m = t[0].wrapping_mul(INV);
+            let (_, mut carry) = mac(t[0], m, MODULUS[0], 0);
+            for j in 1..6 {
+                let (value, new_carry) = mac(t[j], m, MODULUS[j], carry);
+                t[j - 1] = value;
+                carry = new_carry;
+            }
+            let (value, overflow) = adc(t[6], carry, 0);
+            t[5] = value;
+            t[6] = t[7] + overflow;
+        }
+
+        let mut limbs = [0u64; 6];
+        limbs.copy_from_slice(&t[..6]);
+        Fp(limbs).subtract_p()
+    }
+}
+
+impl Field for Fp {
+    fn zero() -> Self {
+        Fp([0; 6])
+    }
+
+    fn one() -> Self {
+        Fp(R)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0.iter().fold(0, |acc, limb| acc | limb) == 0
+    }
+
+    fn ct_eq(&self, other: &Self) -> bool {
+        (*self - *other).is_zero()
+    }
+
+    fn select(a: &Self, b: &Self, choice: bool) -> Self {
+        let mut limbs = a.0;
+        for (limb, b) in limbs.iter_mut().zip(&b.0) {
+            *limb ^= mask(choice) & (*limb ^ b);
+        }
+        Fp(limbs)
+    }
+
+    fn sgn0(&self) -> bool {
+        self.to_raw()[0] & 1 == 1
+    }
+
+    fn invert(&self) -> Self {
+        self.pow(&P_MINUS_2)
+    }
+
+    fn sqrt_candidate(&self) -> Self {
+        self.pow(&P_PLUS_1_DIV_4)
+    }
+}
+
+impl Fp2 {
+    fn from_raw(limbs: [[u64; 6]; 2]) -> Self {
+        Self {
+            c0: Fp::from_raw(limbs[0]),
+            c1: Fp::from_raw(limbs[1]),
+        }
+    }
+
+    fn from_okm(okm: &[u8]) -> Self {
+        Self {
+            c0: Fp::from_okm(&okm[..64]),
+            c1: Fp::from_okm(&okm[64..]),
+        }
+    }
+}
+
+impl Add for Fp2 {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self {
+            c0: self.c0 + rhs.c0,
+            c1: self.c1 + rhs.c1,
+        }
+    }
+}
+
+impl Sub for Fp2 {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self {
+            c0: self.c0 - rhs.c0,
+            c1: self.c1 - rhs.c1,
+        }
+    }
+}
+
+impl Neg for Fp2 {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self {
+            c0: -self.c0,
+            c1: -self.c1,
+        }
+    }
+}
+
+impl Mul for Fp2 {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self {
+            c0: self.c0 * rhs.c0 - self.c1 * rhs.c1,
+            c1: self.c0 * rhs.c1 + self.c1 * rhs.c0,
+        }
+    }
+}
+
+impl Field for Fp2 {
+    fn zero() -> Self {
+        Self {
+            c0: Fp::zero(),
+            c1: Fp::zero(),
+        }
+    }
+
+    fn one() -> Self {
+        Self {
+            c0: Fp::one(),
+            c1: Fp::zero(),
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        self.c0.is_zero() & self.c1.is_zero()
+    }
+
+    fn ct_eq(&self, other: &Self) -> bool {
+        self.c0.ct_eq(&other.c0) & self.c1.ct_eq(&other.c1)
+    }
+
+    fn select(a: &Self, b: &Self, choice: bool) -> Self {
+        Self {
+            c0: Fp::select(&a.c0, &b.c0, choice),
+            c1: Fp::select(&a.c1, &b.c1, choice),
+        }
+    }
+
+    fn sgn0(&self) -> bool {
+        self.c0.sgn0() | (self.c0.is_zero() & self.c1.sgn0())
+    }
+
+    // 1 / (c0 + c1 i) = (c0 - c1 i) / (c0^2 + c1^2)
+    fn invert(&self) -> Self {
+        let norm_inverse = (self.c0.square() + self.c1.square()).invert();
+        Self {
+            c0: self.c0 * norm_inverse,
+            c1: -self.c1 * norm_inverse,
+        }
+    }
+
+    // RFC 9380 appendix I.3, since p^2 = 9 mod 16
+    fn sqrt_candidate(&self) -> Self {
+        let i = Self {
+            c0: Fp::zero(),
+            c1: Fp::one(),
+        };
+
+        let tv1 = self.pow(&P2_PLUS_7_DIV_16);
+        let tv2 = i * tv1;
+        let tv3 = Self::from_raw(SQRT_I) * tv1;
+        let tv4 = Self::from_raw(SQRT_MINUS_I) * tv1;
+
+        let tv1 = Self::select(&tv1, &tv2, tv2.square().ct_eq(self));
+        let tv2 = Self::select(&tv4, &tv3, tv3.square().ct_eq(self));
+        Self::select(&tv1, &tv2, tv2.square().ct_eq(self))
+    }
+}
+
+// Point on y^2 = x^3 + b in homogeneous projective coordinates
+#[derive(Clone, Copy)]
+struct Point<F: Field> {
+    x: F,
+    y: F,
+    z: F,
+}
+
+impl<F: Field> Point<F> {
+    fn identity() -> Self {
+        Self {
+            x: F::zero(),
+            y: F::one(),
+            z: F::zero(),
+        }
+    }
+
+    // Complete addition, algorithm 7 of https://eprint.iacr.org/2015/1060
+    fn add(&self, rhs: &Self, b3: &F) -> Self {
+        let t0 = self.x * rhs.x;
+        let t1 = self.y * rhs.y;
+        let t2 = self.z * rhs.z;
+        let t3 = (self.x + self.y) * (rhs.x + rhs.y) - (t0 + t1);
+        let t4 = (self.y + self.z) * (rhs.y + rhs.z) - (t1 + t2);
+        let y3 = (self.x + self.z) * (rhs.x + rhs.z) - (t0 + t2);
+        let t0 = t0 + t0 + t0;
+        let t2 = *b3 * t2;
+        let z3 = t1 + t2;
+        let t1 = t1 - t2;
+        let y3 = *b3 * y3;
+        Self {
+            x: t3 * t1 - t4 * y3,
+            y: t1 * z3 + y3 * t0,
+            z: z3 * t4 + t0 * t3,
+        }
+    }
+
+    // Double and add over every bit, so the steps only depend on the scalar
+    fn mul(&self, scalar: &[u64], b3: &F) -> Self {
+        let mut result = Self::identity();
+        for limb in scalar.iter().rev() {
+            for i in (0..64).rev() {
+                result = result.add(&result, b3);
+                let sum = result.add(self, b3);
+                result = Self::select(&result, &sum, (limb >> i) & 1 == 1);
+            }
+        }
+        result
+    }
+
+    fn select(a: &Self, b: &Self, choice: bool) -> Self {
+        Self {
+            x: F::select(&a.x, &b.x, choice),
+            y: F::select(&a.y, &b.y, choice),
+            z: F::select(&a.z, &b.z, choice),
+        }
+    }
+
+    // None for the identity
+    fn to_affine(self) -> Option<(F, F)> {
+        if self.z.is_zero() {
+            return None;
+        }
+        let z_inverse = self.z.invert();
+        Some((self.x * z_inverse, self.y * z_inverse))
+    }
+}
+
+// Evaluate a polynomial with coefficients in increasing degree
+fn evaluate<F: Field>(coefficients: &[F], x: &F) -> F {
+    coefficients
+        .iter()
+        .rev()
+        .fold(F::zero(), |result, coefficient| result * *x + *coefficient)
+}
+
+// Constants of a hash to curve suite
+struct Suite<F: Field> {
+    // Curve y^2 = x^3 + A'x + B' isogenous to E and the SWU parameter Z
+    sswu_a: F,
+    sswu_b: F,
+    sswu_z: F,
+    iso_x_num: Vec<F>,
+    iso_x_den: Vec<F>,
+    iso_y_num: Vec<F>,
+    iso_y_den: Vec<F>,
+    // 3b for the addition formulas on E
+    b3: F,
+    cofactor: Vec<u64>,
+}
+
+impl<F: Field> Suite<F> {
+    // Simplified SWU onto the isogenous curve, RFC 9380 section 6.6.2
+    fn map_to_isogenous_curve(&self, u: &F) -> (F, F) {
+        let (a, b, z) = (self.sswu_a, self.sswu_b, self.sswu_z);
+
+        let zu2 = z * u.square();
+        let tv1 = (zu2.square() + zu2).invert();
+        let x1 = -b * a.invert() * (F::one() + tv1);
+        // Exceptional case when Z^2 u^4 + Z u^2 = 0
+        let x1 = F::select(&x1, &(b * (z * a).invert()), tv1.is_zero());
+        let gx1 = (x1.square() + a) * x1 + b;
+        let x2 = zu2 * x1;
+        let gx2 = (x2.square() + a) * x2 + b;
+
+        let y1 = gx1.sqrt_candidate();
+        let y2 = gx2.sqrt_candidate();
+        let is_gx1_square = y1.square().ct_eq(&gx1);
+        let x = F::select(&x2, &x1, is_gx1_square);
+        let y = F::select(&y2, &y1, is_gx1_square);
+
+        // y gets the same sign as u
+        (x, F::select(&y, &-y, u.sgn0() != y.sgn0()))
+    }
+
+    // The isogeny onto E. A zero denominator maps to the identity.
+    fn iso_map(&self, x: &F, y: &F) -> Point<F> {
+        let x_den = evaluate(&self.iso_x_den, x);
+        let y_den = evaluate(&self.iso_y_den, x);
+        let point = Point {
+            x: evaluate(&self.iso_x_num, x) * y_den,
+            y: *y * evaluate(&self.iso_y_num, x) * x_den,
+            z: x_den * y_den,
+        };
+        Point::select(&point, &Point::identity(), point.z.is_zero())
+    }
+
+    fn hash_to_curve(&self, u: &[F; 2]) -> Option<(F, F)> {
+        let (x0, y0) = self.map_to_isogenous_curve(&u[0]);
+        let (x1, y1) = self.map_to_isogenous_curve(&u[1]);
+        let q0 = self.iso_map(&x0, &y0);
+        let q1 = self.iso_map(&x1, &y1);
+        q0.add(&q1, &self.b3)
+            .mul(&self.cofactor, &self.b3)
+            .to_affine()
+    }
+}
+
+fn g1_suite() -> Suite<Fp> {
+    let to_field = |coefficients: &[[u64; 6]]| -> Vec<Fp> {
+        coefficients.iter().map(|c| Fp::from_raw(*c)).collect()
+    };
+    Suite {
+        sswu_a: Fp::from_raw(G1_SSWU_A),
+        sswu_b: Fp::from_raw(G1_SSWU_B),
+        sswu_z: Fp::from_raw(G1_SSWU_Z),
+        iso_x_num: to_field(&G1_ISO_XNUM),
+        iso_x_den: to_field(&G1_ISO_XDEN),
+        iso_y_num: to_field(&G1_ISO_YNUM),
+        iso_y_den: to_field(&G1_ISO_YDEN),
+        // b = 4
+        b3: Fp::from_raw([12, 0, 0, 0, 0, 0]),
+        cofactor: vec![G1_H_EFF],
+    }
+}
+
+fn g2_suite() -> Suite<Fp2> {
+    let to_field = |coefficients: &[[[u64; 6]; 2]]| -> Vec<Fp2> {
+        coefficients.iter().map(|c| Fp2::from_raw(*c)).collect()
+    };
+    Suite {
+        sswu_a: Fp2::from_raw(G2_SSWU_A),
+        sswu_b: Fp2::from_raw(G2_SSWU_B),
+        sswu_z: Fp2::from_raw(G2_SSWU_Z),
+        iso_x_num: to_field(&G2_ISO_XNUM),
+        iso_x_den: to_field(&G2_ISO_XDEN),
+        iso_y_num: to_field(&G2_ISO_YNUM),
+        iso_y_den: to_field(&G2_ISO_YDEN),
+        // b = 4(1 + i)
+        b3: Fp2::from_raw([[12, 0, 0, 0, 0, 0], [12, 0, 0, 0, 0, 0]]),
+        cofactor: G2_H_EFF.to_vec(),
+    }
+}
+
+// RFC 9380 section 5.3.1
+fn expand_message_xmd(message: &[u8], dst: &[u8], len: usize) -> Vec<u8> {
+    // Long tags are hashed down first
+    let long_dst;
+    let dst = if dst.len() > 255 {
+        let mut hasher = Sha256::new();
+        hasher.input(b"H2C-OVERSIZE-DST-");
+        hasher.input(dst);
+        long_dst = hasher.result();
+        &long_dst[..]
+    } else {
+        dst
+    };
+
+    let blocks = len.div_ceil(32);
+    assert!(blocks <= 255);
+
+    let mut hasher = Sha256::new();
+    hasher.input([0u8; 64]);
+    hasher.input(message);
+    hasher.input((len as u16).to_be_bytes());
+    hasher.input([0u8]);
+    hasher.input(dst);
+    hasher.input([dst.len() as u8]);
+    let b_0 = hasher.result();
+
+    let mut result = Vec::with_capacity(blocks * 32);
+    let mut b_i = [0u8; 32];
+    for i in 1..=blocks {
+        // b_1 hashes b_0, the rest hash b_0 xor b_(i - 1)
+        let mut chained = [0u8; 32];
+        for (byte, (b_0, b_i)) in chained.iter_mut().zip(b_0.iter().zip(&b_i)) {
+            *byte = b_0 ^ b_i;
+        }
+
+        let mut hasher = Sha256::new();
+        hasher.input(chained);
+        hasher.input([i as u8]);
+        hasher.input(dst);
+        hasher.input([dst.len() as u8]);
+        b_i.copy_from_slice(hasher.result().as_slice());
+        result.extend_from_slice(&b_i);
+    }
+    result.truncate(len);
+    result
+}
+
+pub fn hash_to_g1(message: &[u8], dst: &[u8]) -> bls::G1Affine {
+    let okm = expand_message_xmd(message, dst, 128);
+    let u = [Fp::from_okm(&okm[..64]), Fp::from_okm(&okm[64..])];
+
+    match g1_suite().hash_to_curve(&u) {
+        Some((x, y)) => {
+            let mut data = [0u8; 96];
+            data[..48].copy_from_slice(&x.to_bytes());
+            data[48..].copy_from_slice(&y.to_bytes());
+            // Always in G1 once the cofactor is cleared
+            bls::G1Affine::from_uncompressed(&data).unwrap()
+        }
+        None => bls::G1Affine::identity(),
+    }
+}
+
+pub fn hash_to_g2(message: &[u8], dst: &[u8]) -> bls::G2Affine {
+    let okm = expand_message_xmd(message, dst, 256);
+    let u = [Fp2::from_okm(&okm[..128]), Fp2::from_okm(&okm[128..])];
+
+    match g2_suite().hash_to_curve(&u) {
+        Some((x, y)) => {
+            let mut data = [0u8; 192];
+            data[..48].copy_from_slice(&x.c1.to_bytes());
+            data[48..96].copy_from_slice(&x.c0.to_bytes());
+            data[96..144].copy_from_slice(&y.c1.to_bytes());
+            data[144..].copy_from_slice(&y.c0.to_bytes());
+            bls::G2Affine::from_uncompressed(&data).unwrap()
+        }
+        None => bls::G2Affine::identity(),
+    }
+}
+
+#[test]
+fn test_expand_message_xmd() {
+    let dst = b"QUUX-V01-CS02-with-expander-SHA256-128";
+    assert_eq!(
+        hex::encode(expand_message_xmd(b"", dst, 0x20)),
+        "68a985b87eb6b46952128911f2a4412bbc302a9d759667f87f7a21d803f07235"
+    );
+    assert_eq!(
+        hex::encode(expand_message_xmd(b"abc", dst, 0x20)),
+        "d8ccab23b5985ccea865c6c97b6e5b8350e794e603b4b97902f53a8a0d605615"
+    );
+
+    // Tags longer than 255 bytes are hashed
+    let mut long_dst = b"QUUX-V01-CS02-with-expander-SHA256-128-long-DST-".to_vec();
+    long_dst.extend(vec![b'1'; 208]);
+    assert_eq!(
+        hex::encode(expand_message_xmd(b"", &long_dst, 0x20)),
+        "e8dc0c8b686b7ef2074086fbdd2f30e3f8bfbd3bdf177f73f04b97ce618a3ed3"
+    );
+    assert_eq!(
+        hex::encode(expand_message_xmd(b"abc", &long_dst, 0x20)),
+        "52dbf4f36cf560fca57dedec2ad924ee9c266341d8f3d6afe5171733b16bbb12"
+    );
+}
+
+#[cfg(test)]
+fn test_messages() -> Vec<Vec<u8>> {
+    let mut q128 = b"q128_".to_vec();
+    q128.extend(vec![b'q'; 128]);
+    let mut a512 = b"a512_".to_vec();
+    a512.extend(vec![b'a'; 512]);
+    vec![
+        b"".to_vec(),
+        b"abc".to_vec(),
+        b"abcdef0123456789".to_vec(),
+        q128,
+        a512,
+    ]
+}
+
+// RFC 9380 appendix J.9.1
+#[test]
+fn test_hash_to_g1() {
+    let dst = b"QUUX-V01-CS02-with-BLS12381G1_XMD:SHA-256_SSWU_RO_";
+    let expected = [
+        "052926add2207b76ca4fa57a8734416c8dc95e24501772c814278700eed6d1e4e8cf62d9c09db0fac349612b759e79a1\
+         08ba738453bfed09cb546dbb0783dbb3a5f1f566ed67bb6be0e8c67e2e81a4cc68ee29813bb7994998f3eae0c9c6a265",
+        "03567bc5ef9c690c2ab2ecdf6a96ef1c139cc0b2f284dca0a9a7943388a49a3aee664ba5379a7655d3c68900be2f6903\
+         0b9c15f3fe6e5cf4211f346271d7b01c8f3b28be689c8429c85b67af215533311f0b8dfaaa154fa6b88176c229f2885d",
+        "11e0b079dea29a68f0383ee94fed1b940995272407e3bb916bbf268c263ddd57a6a27200a784cbc248e84f357ce82d98\
+         03a87ae2caf14e8ee52e51fa2ed8eefe80f02457004ba4d486d6aa1f517c0889501dc7413753f9599b099ebcbbd2d709",
+        "15f68eaa693b95ccb85215dc65fa81038d69629f70aeee0d0f677cf22285e7bf58d7cb86eefe8f2e9bc3f8cb84fac488\
+         1807a1d50c29f430b8cafc4f8638dfeeadf51211e1602a5f184443076715f91bb90a48ba1e370edce6ae1062f5e6dd38",
+        "082aabae8b7dedb0e78aeb619ad3bfd9277a2f77ba7fad20ef6aabdc6c31d19ba5a6d12283553294c1825c4b3ca2dcfe\
+         05b84ae5a942248eea39e1d91030458c40153f3b654ab7872d779ad1e942856a20c438e8d99bc8abfbf74729ce1f7ac8",
+    ];
+
+    for (message, expected) in test_messages().iter().zip(&expected) {
+        let point = hash_to_g1(message, dst);
+        assert_eq!(hex::encode(&point.to_uncompressed()[..]), *expected);
+    }
+}
+
+// RFC 9380 appendix J.10.1
+#[test]
+fn test_hash_to_g2() {
+    let dst = b"QUUX-V01-CS02-with-BLS12381G2_XMD:SHA-256_SSWU_RO_";
+    let expected = [
+        "05cb8437535e20ecffaef7752baddf98034139c38452458baeefab379ba13dff5bf5dd71b72418717047f5b0f37da03d\
+         0141ebfbdca40eb85b87142e130ab689c673cf60f1a3e98d69335266f30d9b8d4ac44c1038e9dcdd5393faf5c41fb78a\
+         12424ac32561493f3fe3c260708a12b7c620e7be00099a974e259ddc7d1f6395c3c811cdd19f1e8dbf3e9ecfdcbab8d6\
+         0503921d7f6a12805e72940b963c0cf3471c7b2a524950ca195d11062ee75ec076daf2d4bc358c4b190c0c98064fdd92",
+        "139cddbccdc5e91b9623efd38c49f81a6f83f175e80b06fc374de9eb4b41dfe4ca3a230ed250fbe3a2acf73a41177fd8\
+         02c2d18e033b960562aae3cab37a27ce00d80ccd5ba4b7fe0e7a210245129dbec7780ccc7954725f4168aff2787776e6\
+         00aa65dae3c8d732d10ecd2c50f8a1baf3001578f71c694e03866e9f3d49ac1e1ce70dd94a733534f106d4cec0eddd16\
+         1787327b68159716a37440985269cf584bcb1e621d3a7202be6ea05c4cfe244aeb197642555a0645fb87bf7466b2ba48",
+        "190d119345b94fbd15497bcba94ecf7db2cbfd1e1fe7da034d26cbba169fb3968288b3fafb265f9ebd380512a71c3f2c\
+         121982811d2491fde9ba7ed31ef9ca474f0e1501297f68c298e9f4c0028add35aea8bb83d53c08cfc007c1e005723cd0\
+         0bb5e7572275c567462d91807de765611490205a941a5a6af3b1691bfe596c31225d3aabdf15faff860cb4ef17c7c3be\
+         05571a0f8d3c08d094576981f4a3b8eda0a8e771fcdcc8ecceaf1356a6acf17574518acb506e435b639353c2e14827c8",
+        "0934aba516a52d8ae479939a91998299c76d39cc0c035cd18813bec433f587e2d7a4fef038260eef0cef4d02aae3eb91\
+         19a84dd7248a1066f737cc34502ee5555bd3c19f2ecdb3c7d9e24dc65d4e25e50d83f0f77105e955d78f4762d33c17da\
+         09bcccfa036b4847c9950780733633f13619994394c23ff0b32fa6b795844f4a0673e20282d07bc69641cee04f5e5662\
+         14f81cd421617428bc3b9fe25afbb751d934a00493524bc4e065635b0555084dd54679df1536101b2c979c0152d09192",
+        "11fca2ff525572795a801eed17eb12785887c7b63fb77a42be46ce4a34131d71f7a73e95fee3f812aea3de78b4d01569\
+         01a6ba2f9a11fa5598b2d8ace0fbe0a0eacb65deceb476fbbcb64fd24557c2f4b18ecfc5663e54ae16a84f5ab7f62534\
+         03a47f8e6d1763ba0cad63d6114c0accbef65707825a511b251a660a9b3994249ae4e63fac38b23da0c398689ee2ab52\
+         0b6798718c8aed24bc19cb27f866f1c9effcdbf92397ad6448b5c9db90d2b9da6cbabf48adc1adf59a1a28344e79d57e",
+    ];
+
+    for (message, expected) in test_messages().iter().zip(&expected) {
+        let point = hash_to_g2(message, dst);
+        assert_eq!(hex::encode(&point.to_uncompressed()[..]), *expected);
+    }
+}
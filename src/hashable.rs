@@ -83,6 +83,7 @@ pub trait HashableGenerator {
     /// Take a message slice and hash to a point on the curve.
     /// Uses an incrementing nonce to keep iterating hashing the message,
     /// until it obtains a valid point on the curve. Naive implementation.
+    /// Parameters from ParameterVersion::V1 use hash_to_curve.rs instead.
     fn hash_to_point(message: &[u8]) -> Self;
 }
 
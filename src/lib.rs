@@ -6,6 +6,7 @@ pub mod coconut;
 pub mod elgamal;
 pub mod endian;
 pub mod error;
+pub mod hash_to_curve;
 pub mod hashable;
 pub mod net;
 pub mod parameters;
@@ -20,23 +21,34 @@ pub mod stealth;
 pub mod stealth_address;
 pub mod utility;
 
-pub use crate::aes::{aes_decrypt, aes_encrypt, AesKey, Ciphertext, Plaintext};
+pub use crate::aes::{
+    aes_decrypt, aes_decrypt_with_nonce, aes_encrypt, aes_encrypt_with_nonce,
+    derive_passphrase_key, AesKey, AesNonce, Ciphertext, Plaintext,
+};
 
+#[cfg(any(test, feature = "test-vectors"))]
+pub use crate::bls_extensions::SeededRngInstance;
 pub use crate::bls_extensions::{
-    BlsStringConversion, HasherToScalar, OsRngInstance, RandomScalar, RngInstance,
+    BlsStringConversion, OsRngInstance, RandomScalar, RngInstance, Transcript, PROTOCOL_VERSION,
 };
 pub use crate::coconut::{
-    Attribute, BlindSignatureRequest, Coconut, Credential, PartialSignature, SecretKey, Signature,
-    VerifyKey,
+    Attribute, AttributeStatement, BlindSignatureRequest, Coconut, Credential, PartialSignature,
+    Predicate, Presentation, SecretKey, Signature, VerifyKey,
 };
 pub use crate::error::{Error, Result};
-pub use crate::pedersen::{compute_pedersen, compute_pedersen_blinds, compute_pedersen_with_u64};
+pub use crate::parameters::{ParameterVersion, DEFAULT_ASSET, DEFAULT_PARAMETER_VERSION};
+pub use crate::pedersen::{
+    compute_asset_pedersen, compute_pedersen, compute_pedersen_blinds, compute_pedersen_with_u64,
+};
 pub use crate::runtime::smol_auto_run;
 pub use crate::schema::service::{generate_keys, SigningService};
-pub use crate::schema::token::{Token, TokenSecret};
+pub use crate::schema::token::{AssetValue, Token, TokenSecret};
 pub use crate::schema::{
-    Input, InputProofs, InputSecret, Output, OutputProofs, OutputSecret, OutputSignature,
-    Transaction,
+    burn_slab, slab_burn, BalanceProof, BuiltTransaction, Component, Epoch, FileSpentStore, Input,
+    InputProofs, InputSecret, KeySet, MemorySpentStore, Output, OutputProofs, OutputSecret,
+    OutputSignature, OwnedToken, PendingOutput, SpentRegistry, SpentStore, Transaction,
+    TransactionBuilder, TransactionDigest, TransactionKind, TransactionPart, TransactionSecret,
+    VerifyFailure, VerifyReport, Wallet, DEFAULT_EPOCH, TRANSACTION_VERSION, WALLET_VERSION,
 };
 pub use crate::serial::{encode_with_size, Decodable, Encodable, WriteExt};
 pub use crate::slab::{Slab, SlabsManager, SlabsManagerSafe};
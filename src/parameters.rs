@@ -1,35 +1,171 @@
 use bls12_381 as bls;
+use std::collections::BTreeMap;
 
 use crate::bls_extensions::*;
+use crate::error::{Error, Result};
+use crate::hash_to_curve::hash_to_g1;
 use crate::hashable::*;
 
+// Asset used when none is specified
+pub const DEFAULT_ASSET: u64 = 0;
+
+// Number of vector generators precomputed for bulletproofs.
+// Aggregated proofs hash the extra ones they need on demand.
+pub const BULLETPROOF_GENERATORS: usize = 64;
+
+// Range proofs cover values below 2^bits
+pub const DEFAULT_RANGEPROOF_BITS: usize = 64;
+
+// How the generators are hashed to the curve. Changing it changes every
+// generator, so keys and tokens only work with the version they were made for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParameterVersion {
+    // Try and increment over Sha512
+    V0,
+    // RFC 9380 hash to curve
+    V1,
+}
+
+// Existing deployments use V0
+pub const DEFAULT_PARAMETER_VERSION: ParameterVersion = ParameterVersion::V0;
+
+const GENERATOR_DST: &[u8] = b"DARKWALLET-V01-CS01-with-BLS12381G1_XMD:SHA-256_SSWU_RO_";
+
+impl ParameterVersion {
+    pub fn hash_to_point(&self, message: &[u8]) -> bls::G1Affine {
+        match self {
+            ParameterVersion::V0 => bls::G1Affine::hash_to_point(message),
+            ParameterVersion::V1 => hash_to_g1(message, GENERATOR_DST),
+        }
+    }
+}
+
 pub struct Parameters<R: RngInstance> {
     pub g1: bls::G1Affine,
     pub hs: Vec<bls::G1Affine>,
     pub g2: bls::G2Affine,
+    // Registry of allowed assets and their value generators
+    pub assets: BTreeMap<u64, bls::G1Affine>,
+    // Vector generators and inner product base for bulletproofs
+    pub bulletproof_gs: Vec<bls::G1Affine>,
+    pub bulletproof_hs: Vec<bls::G1Affine>,
+    pub bulletproof_q: bls::G1Affine,
+    // Common value generator for the output range commits,
+    // so the outputs of a transaction can share one range proof.
+    pub range_base: bls::G1Affine,
+    // Bit width every range proof must have. Deployments with small
+    // value caps can use smaller proofs.
+    pub rangeproof_bits: usize,
+    pub version: ParameterVersion,
     _marker: std::marker::PhantomData<R>,
 }
 
+pub fn compute_asset_generator(version: ParameterVersion, asset: u64) -> bls::G1Affine {
+    let message = format!("asset{}", asset);
+    version.hash_to_point(message.as_bytes())
+}
+
+fn compute_bulletproof_generator(
+    version: ParameterVersion,
+    prefix: &str,
+    i: usize,
+) -> bls::G1Affine {
+    let message = format!("{}{}", prefix, i);
+    version.hash_to_point(message.as_bytes())
+}
+
 impl<R: RngInstance> Parameters<R> {
     pub fn new(attributes_size: u32) -> Self {
+        Self::new_with_assets(attributes_size, &vec![DEFAULT_ASSET])
+    }
+
+    pub fn new_with_assets(attributes_size: u32, assets: &Vec<u64>) -> Self {
+        Self::new_versioned(DEFAULT_PARAMETER_VERSION, attributes_size, assets)
+    }
+
+    pub fn new_versioned(
+        version: ParameterVersion,
+        attributes_size: u32,
+        assets: &Vec<u64>,
+    ) -> Self {
         let g1 = bls::G1Affine::generator();
         let g2 = bls::G2Affine::generator();
 
         let hs = (0..attributes_size)
             .map(|i| {
                 let message = format!("h{}", i);
-                bls::G1Affine::hash_to_point(message.as_bytes())
+                version.hash_to_point(message.as_bytes())
             })
             .collect();
 
+        let assets = assets
+            .iter()
+            .map(|asset| (*asset, compute_asset_generator(version, *asset)))
+            .collect();
+
+        let compute_generators = |prefix: &str| -> Vec<bls::G1Affine> {
+            (0..BULLETPROOF_GENERATORS)
+                .map(|i| compute_bulletproof_generator(version, prefix, i))
+                .collect()
+        };
+
         Parameters {
             g1: g1,
             hs: hs,
             g2: g2,
+            assets,
+            bulletproof_gs: compute_generators("bulletproof_g"),
+            bulletproof_hs: compute_generators("bulletproof_h"),
+            bulletproof_q: version.hash_to_point(b"bulletproof_q"),
+            range_base: version.hash_to_point(b"range_base"),
+            rangeproof_bits: DEFAULT_RANGEPROOF_BITS,
+            version,
             _marker: std::marker::PhantomData,
         }
     }
 
+    // Bulletproofs need a power of two
+    pub fn with_rangeproof_bits(mut self, bits: usize) -> Result<Self> {
+        if !bits.is_power_of_two() || bits > 64 {
+            return Err(Error::InvalidRangeproofBits);
+        }
+        self.rangeproof_bits = bits;
+        Ok(self)
+    }
+
+    pub fn check_rangeproof_value(&self, value: u64) -> Result<()> {
+        if self.rangeproof_bits < 64 && value >> self.rangeproof_bits != 0 {
+            return Err(Error::ValueOutOfRange);
+        }
+        Ok(())
+    }
+
+    pub fn asset_generator(&self, asset: u64) -> Option<&bls::G1Affine> {
+        self.assets.get(&asset)
+    }
+
+    pub fn bulletproof_generators(
+        &self,
+        count: usize,
+    ) -> (Vec<bls::G1Projective>, Vec<bls::G1Projective>) {
+        let extend = |precomputed: &Vec<bls::G1Affine>, prefix: &str| {
+            (0..count)
+                .map(|i| match precomputed.get(i) {
+                    Some(generator) => bls::G1Projective::from(generator),
+                    None => bls::G1Projective::from(compute_bulletproof_generator(
+                        self.version,
+                        prefix,
+                        i,
+                    )),
+                })
+                .collect()
+        };
+        (
+            extend(&self.bulletproof_gs, "bulletproof_g"),
+            extend(&self.bulletproof_hs, "bulletproof_h"),
+        )
+    }
+
     pub fn random_scalar(&self) -> bls::Scalar {
         bls::Scalar::new_random::<R>()
     }
@@ -93,3 +229,38 @@ fn test_parameters() {
         .unwrap()
     );
 }
+
+#[test]
+fn test_parameter_versions() {
+    let params = Parameters::<OsRngInstance>::new(2);
+    assert_eq!(params.version, ParameterVersion::V0);
+
+    let params_v1 = Parameters::<OsRngInstance>::new_versioned(ParameterVersion::V1, 2, &vec![0]);
+    assert_eq!(params_v1.g1, params.g1);
+    assert_ne!(params_v1.hs[0], params.hs[0]);
+
+    assert_eq!(
+        params_v1.hs[0],
+        bls::G1Affine::from_compressed(&[
+            0x8c, 0xad, 0x27, 0x4f, 0x27, 0x69, 0x20, 0x09, 0x13, 0x43, 0x0d, 0x28, 0x15, 0x6f,
+            0x38, 0x0b, 0xf9, 0x32, 0x64, 0x53, 0x77, 0xfd, 0x95, 0xa4, 0x82, 0xf3, 0x50, 0x61,
+            0x5d, 0xb6, 0x7e, 0x92, 0x34, 0x8e, 0x80, 0xe1, 0x79, 0xc1, 0xaf, 0xce, 0x1e, 0x55,
+            0x30, 0x79, 0x36, 0xed, 0xd7, 0xf2,
+        ])
+        .unwrap()
+    );
+    assert_eq!(
+        params_v1.assets[&0],
+        compute_asset_generator(ParameterVersion::V1, 0)
+    );
+
+    // Generators past the precomputed ones use the same version
+    let (gs, _) = params_v1.bulletproof_generators(BULLETPROOF_GENERATORS + 1);
+    assert_eq!(
+        gs[BULLETPROOF_GENERATORS],
+        bls::G1Projective::from(hash_to_g1(
+            format!("bulletproof_g{}", BULLETPROOF_GENERATORS).as_bytes(),
+            GENERATOR_DST
+        ))
+    );
+}
@@ -2,27 +2,41 @@ use bls12_381 as bls;
 
 use crate::bls_extensions::*;
 use crate::coconut::Coconut;
+use crate::error::{Error, Result};
 use crate::parameters::*;
 
 pub type PedersenCommit = bls::G1Projective;
 
+// The value base is either an asset generator H_a, or a blinded
+// asset tag H_a + s G1 which hides the asset.
 pub fn compute_pedersen<R: RngInstance>(
     params: &Parameters<R>,
+    value_base: &bls::G1Projective,
     blind: &bls::Scalar,
     value: &bls::Scalar,
 ) -> PedersenCommit {
-    assert!(params.hs.len() > 0);
-    params.g1 * blind + params.hs[0] * value
+    params.g1 * blind + value_base * value
 }
 
 pub fn compute_pedersen_with_u64<R: RngInstance>(
     params: &Parameters<R>,
+    value_base: &bls::G1Projective,
     blind: &bls::Scalar,
     value: u64,
 ) -> PedersenCommit {
-    assert!(params.hs.len() > 0);
     let value = bls::Scalar::from(value);
-    compute_pedersen(params, blind, &value)
+    compute_pedersen(params, value_base, blind, &value)
+}
+
+// Commit to a value of a known asset. Returns None for unregistered assets.
+pub fn compute_asset_pedersen<R: RngInstance>(
+    params: &Parameters<R>,
+    asset: u64,
+    blind: &bls::Scalar,
+    value: u64,
+) -> Option<PedersenCommit> {
+    let value_base = bls::G1Projective::from(params.asset_generator(asset)?);
+    Some(compute_pedersen_with_u64(params, &value_base, blind, value))
 }
 
 pub fn compute_pedersen_blinds<R: RngInstance>(
@@ -31,9 +45,18 @@ pub fn compute_pedersen_blinds<R: RngInstance>(
     withdraws: u64,
     input_values: &Vec<u64>,
     output_values: &Vec<u64>,
-) -> (bls::Scalar, bls::Scalar, Vec<bls::Scalar>, Vec<bls::Scalar>) {
+) -> Result<(bls::Scalar, bls::Scalar, Vec<bls::Scalar>, Vec<bls::Scalar>)> {
     let params = &coconut.params;
-    assert!(params.hs.len() > 0);
+
+    // Transaction must have either an input or output
+    // Otherwise it's nonsense
+    if input_values.is_empty() && output_values.is_empty() {
+        return Err(Error::EmptyTransaction);
+    }
+    // Inputs must go somewhere
+    if !input_values.is_empty() && output_values.is_empty() && withdraws == 0 {
+        return Err(Error::EmptyTransaction);
+    }
 
     // deposits + sum(inputs) == withdraws + sum(outputs)
 
@@ -52,13 +75,8 @@ pub fn compute_pedersen_blinds<R: RngInstance>(
     let mut input_blinds = params.random_scalars(input_values.len());
     let mut output_blinds = params.random_scalars(output_values.len());
 
-    // Transaction must have either an input or output
-    // Otherwise it's nonsense
-    assert!(!input_values.is_empty() && !output_values.is_empty());
-
     if !input_values.is_empty() {
         // Transaction has >=1 inputs and any number of outputs
-        assert!(!output_values.is_empty() || withdraws > 0);
 
         // rhs = withdraws + sum(outputs)
         let rhs = withdraws_blind + sum_scalar(output_blinds.iter());
@@ -84,5 +102,5 @@ pub fn compute_pedersen_blinds<R: RngInstance>(
         withdraws_blind + sum_scalar(output_blinds.iter())
     );
 
-    (deposits_blind, withdraws_blind, input_blinds, output_blinds)
+    Ok((deposits_blind, withdraws_blind, input_blinds, output_blinds))
 }
@@ -0,0 +1,348 @@
+use bls12_381 as bls;
+use itertools::izip;
+use std::io;
+
+use crate::bls_extensions::*;
+use crate::error::{Error, Result};
+use crate::parameters::*;
+use crate::proofs::proof::*;
+use crate::serial::VarInt;
+use crate::serial::{Decodable, DecodableWithParams, Encodable};
+
+// Prove the asset tag and asset commit refer to the same registered asset
+// without revealing which one.
+//
+//   A = H_k + s G1
+//   D = k H + u G1
+//
+// where H_k is the value generator for asset k and H is the generator of
+// the asset attribute. This is an OR proof over every asset in the registry.
+// The branches we can't prove are simulated, and the branch challenges
+// must sum to the main challenge.
+
+pub const ASSET_ATTRIBUTE_INDEX: usize = 2;
+
+pub struct Builder<'a, R: RngInstance> {
+    params: &'a Parameters<R>,
+
+    asset_tag: bls::G1Projective,
+    asset_commit: bls::G1Projective,
+
+    secret_index: usize,
+    tag_blind: bls::Scalar,
+    commit_blind: bls::Scalar,
+    witness_tag: bls::Scalar,
+    witness_commit: bls::Scalar,
+
+    // Values for the simulated branches
+    challenges: Vec<bls::Scalar>,
+    responses_tag: Vec<bls::Scalar>,
+    responses_commit: Vec<bls::Scalar>,
+}
+
+pub struct Commitments<'a, R: RngInstance> {
+    params: &'a Parameters<R>,
+
+    asset_tag: bls::G1Projective,
+    asset_commit: bls::G1Projective,
+
+    commits: Vec<(bls::G1Projective, bls::G1Projective)>,
+}
+
+pub struct Proof {
+    // The challenge for the last branch is derived from the others
+    challenges: Vec<bls::Scalar>,
+    responses_tag: Vec<bls::Scalar>,
+    responses_commit: Vec<bls::Scalar>,
+}
+
+// For each asset k: (A - H_k, D - k H)
+fn branch_keys<R: RngInstance>(
+    params: &Parameters<R>,
+    asset_tag: &bls::G1Projective,
+    asset_commit: &bls::G1Projective,
+) -> Vec<(bls::G1Projective, bls::G1Projective)> {
+    let asset_base = params.hs[ASSET_ATTRIBUTE_INDEX];
+    params
+        .assets
+        .iter()
+        .map(|(asset, generator)| {
+            (
+                asset_tag - generator,
+                asset_commit - asset_base * bls::Scalar::from(*asset),
+            )
+        })
+        .collect()
+}
+
+impl<'a, R: RngInstance> Builder<'a, R> {
+    pub fn new(
+        params: &'a Parameters<R>,
+        asset: u64,
+        tag_blind: bls::Scalar,
+        commit_blind: bls::Scalar,
+    ) -> Result<Self> {
+        let secret_index = params
+            .assets
+            .keys()
+            .position(|registered| *registered == asset)
+            .ok_or(Error::UnknownAsset)?;
+
+        let asset_tag = params.g1 * tag_blind + params.assets[&asset];
+        let asset_commit =
+            params.g1 * commit_blind + params.hs[ASSET_ATTRIBUTE_INDEX] * bls::Scalar::from(asset);
+
+        let branches_len = params.assets.len();
+        Ok(Self {
+            params,
+
+            asset_tag,
+            asset_commit,
+
+            secret_index,
+            tag_blind,
+            commit_blind,
+            witness_tag: params.random_scalar(),
+            witness_commit: params.random_scalar(),
+
+            challenges: params.random_scalars(branches_len),
+            responses_tag: params.random_scalars(branches_len),
+            responses_commit: params.random_scalars(branches_len),
+        })
+    }
+
+    pub fn asset_tag(&self) -> bls::G1Projective {
+        self.asset_tag
+    }
+
+    pub fn asset_commit(&self) -> bls::G1Projective {
+        self.asset_commit
+    }
+
+    pub fn commitments(&self) -> Box<dyn ProofCommitments + 'a> {
+        let keys = branch_keys(self.params, &self.asset_tag, &self.asset_commit);
+        let g1 = self.params.g1;
+
+        let commits = keys
+            .iter()
+            .enumerate()
+            .map(|(i, (key_tag, key_commit))| {
+                if i == self.secret_index {
+                    (g1 * self.witness_tag, g1 * self.witness_commit)
+                } else {
+                    (
+                        g1 * self.responses_tag[i] + key_tag * self.challenges[i],
+                        g1 * self.responses_commit[i] + key_commit * self.challenges[i],
+                    )
+                }
+            })
+            .collect();
+
+        Box::new(Commitments {
+            params: self.params,
+            asset_tag: self.asset_tag,
+            asset_commit: self.asset_commit,
+            commits,
+        })
+    }
+
+    pub fn finish(&self, challenge: &bls::Scalar) -> Proof {
+        let mut challenges = self.challenges.clone();
+        let simulated_sum = challenges
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != self.secret_index)
+            .fold(bls::Scalar::zero(), |acc, (_, c)| acc + c);
+        let secret_challenge = challenge - simulated_sum;
+
+        let mut responses_tag = self.responses_tag.clone();
+        let mut responses_commit = self.responses_commit.clone();
+        responses_tag[self.secret_index] = self.witness_tag - secret_challenge * self.tag_blind;
+        responses_commit[self.secret_index] =
+            self.witness_commit - secret_challenge * self.commit_blind;
+
+        challenges[self.secret_index] = secret_challenge;
+        challenges.pop();
+
+        Proof {
+            challenges,
+            responses_tag,
+            responses_commit,
+        }
+    }
+}
+
+impl<'a, R: RngInstance> Drop for Builder<'a, R> {
+    fn drop(&mut self) {
+        self.tag_blind.wipe();
+        self.commit_blind.wipe();
+        self.witness_tag.wipe();
+        self.witness_commit.wipe();
+    }
+}
+
+impl<'a, R: RngInstance> Encodable for Builder<'a, R> {
+    fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
+        let mut len = 0;
+        len += self.asset_tag.encode(&mut s)?;
+        len += self.asset_commit.encode(&mut s)?;
+        len += VarInt(self.secret_index as u64).encode(&mut s)?;
+        len += self.tag_blind.encode(&mut s)?;
+        len += self.commit_blind.encode(&mut s)?;
+        len += self.witness_tag.encode(&mut s)?;
+        len += self.witness_commit.encode(&mut s)?;
+        len += self.challenges.encode(&mut s)?;
+        len += self.responses_tag.encode(&mut s)?;
+        Ok(len + self.responses_commit.encode(s)?)
+    }
+}
+
+impl<'a, R: RngInstance> DecodableWithParams<'a, R> for Builder<'a, R> {
+    fn decode<D: io::Read>(mut d: D, params: &'a Parameters<R>) -> Result<Self> {
+        Ok(Self {
+            params,
+            asset_tag: Decodable::decode(&mut d)?,
+            asset_commit: Decodable::decode(&mut d)?,
+            secret_index: VarInt::decode(&mut d)?.0 as usize,
+            tag_blind: Decodable::decode(&mut d)?,
+            commit_blind: Decodable::decode(&mut d)?,
+            witness_tag: Decodable::decode(&mut d)?,
+            witness_commit: Decodable::decode(&mut d)?,
+            challenges: Decodable::decode(&mut d)?,
+            responses_tag: Decodable::decode(&mut d)?,
+            responses_commit: Decodable::decode(d)?,
+        })
+    }
+}
+
+impl<'a, R: RngInstance> ProofCommitments for Commitments<'a, R> {
+    fn commit(&self, transcript: &mut Transcript) {
+        transcript.append_g1_affine(b"g1", &self.params.g1);
+        transcript.append_g1_affine(b"h-asset", &self.params.hs[ASSET_ATTRIBUTE_INDEX]);
+        for generator in self.params.assets.values() {
+            transcript.append_g1_affine(b"asset-generator", generator);
+        }
+        transcript.append_g1(b"asset-tag", &self.asset_tag);
+        transcript.append_g1(b"asset-commit", &self.asset_commit);
+
+        for (commit_tag, commit_commit) in &self.commits {
+            transcript.append_g1(b"commit-tag", commit_tag);
+            transcript.append_g1(b"commit-commit", commit_commit);
+        }
+    }
+}
+
+impl Proof {
+    pub fn commitments<'a, R: RngInstance>(
+        &self,
+        params: &'a Parameters<R>,
+        challenge: &bls::Scalar,
+        asset_tag: &bls::G1Projective,
+        asset_commit: &bls::G1Projective,
+    ) -> Box<dyn ProofCommitments + 'a> {
+        let keys = branch_keys(params, asset_tag, asset_commit);
+
+        // A proof with the wrong number of branches commits to nothing,
+        // which never matches the prover's commitments.
+        if self.challenges.len() + 1 != keys.len()
+            || self.responses_tag.len() != keys.len()
+            || self.responses_commit.len() != keys.len()
+        {
+            return Box::new(Commitments {
+                params,
+                asset_tag: *asset_tag,
+                asset_commit: *asset_commit,
+                commits: Vec::new(),
+            });
+        }
+
+        let last_challenge = challenge - sum_scalar(self.challenges.iter());
+        let challenges = self
+            .challenges
+            .iter()
+            .chain(std::iter::once(&last_challenge));
+
+        let g1 = params.g1;
+        let commits = izip!(
+            &keys,
+            challenges,
+            &self.responses_tag,
+            &self.responses_commit
+        )
+        .map(
+            |((key_tag, key_commit), c, response_tag, response_commit)| {
+                (
+                    g1 * response_tag + key_tag * c,
+                    g1 * response_commit + key_commit * c,
+                )
+            },
+        )
+        .collect();
+
+        Box::new(Commitments {
+            params,
+            asset_tag: *asset_tag,
+            asset_commit: *asset_commit,
+            commits,
+        })
+    }
+}
+
+impl Encodable for Proof {
+    fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
+        let mut len = 0;
+        len += self.challenges.encode(&mut s)?;
+        len += self.responses_tag.encode(&mut s)?;
+        Ok(len + self.responses_commit.encode(s)?)
+    }
+}
+
+impl Decodable for Proof {
+    fn decode<D: io::Read>(mut d: D) -> Result<Self> {
+        Ok(Self {
+            challenges: Decodable::decode(&mut d)?,
+            responses_tag: Decodable::decode(&mut d)?,
+            responses_commit: Decodable::decode(d)?,
+        })
+    }
+}
+
+#[test]
+fn test_asset_proof() {
+    let params = Parameters::<OsRngInstance>::new_with_assets(3, &vec![0, 4, 7]);
+
+    for asset in &[0, 4, 7] {
+        let builder = Builder::new(
+            &params,
+            *asset,
+            params.random_scalar(),
+            params.random_scalar(),
+        )
+        .unwrap();
+        let (asset_tag, asset_commit) = (builder.asset_tag(), builder.asset_commit());
+
+        let mut transcript = Transcript::new(b"test");
+        builder.commitments().commit(&mut transcript);
+        let challenge = transcript.challenge_scalar(b"challenge");
+
+        let proof = builder.finish(&challenge);
+
+        let commits2 = proof.commitments(&params, &challenge, &asset_tag, &asset_commit);
+        let mut transcript2 = Transcript::new(b"test");
+        commits2.commit(&mut transcript2);
+        assert_eq!(challenge, transcript2.challenge_scalar(b"challenge"));
+
+        // Swapping in the commit for another asset fails
+        let other_commit = asset_commit + params.hs[ASSET_ATTRIBUTE_INDEX];
+        let commits3 = proof.commitments(&params, &challenge, &asset_tag, &other_commit);
+        let mut transcript3 = Transcript::new(b"test");
+        commits3.commit(&mut transcript3);
+        assert_ne!(challenge, transcript3.challenge_scalar(b"challenge"));
+    }
+
+    // Only registered assets can be proven
+    assert!(matches!(
+        Builder::new(&params, 5, params.random_scalar(), params.random_scalar()),
+        Err(Error::UnknownAsset)
+    ));
+}
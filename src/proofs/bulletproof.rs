@@ -0,0 +1,824 @@
+use bls12_381 as bls;
+use itertools::izip;
+use std::io;
+
+use crate::bls_extensions::*;
+use crate::error::{Error, Result};
+use crate::parameters::*;
+use crate::proofs::proof::*;
+#[allow(unused_imports)]
+use crate::serial::{deserialize, serialize, Decodable, Encodable};
+
+// Bulletproofs range proof (Bünz et al.) over the G1 generators.
+//
+//   V = v B + gamma G1
+//
+// where B is the value generator. The prover commits to the bits of v in A
+// and to blinding vectors in S. Those go into the transaction challenge
+// like any other proof commitment. Every later challenge is derived from
+// it, and the bits are checked with an inner product argument, so the
+// proof is logarithmic in the number of bits.
+//
+// Several values sharing the same value generator can be proven together.
+// Their bits are concatenated, padded with zero values up to a power of two,
+// so m values only add log2(m) points to the proof.
+//
+// The bit width n comes from the parameters and is part of the proof.
+// Verifiers reject proofs of any other width.
+
+// Bounds the generators a verifier has to compute for one proof
+pub const MAX_AGGREGATED_VALUES: usize = 64;
+
+pub struct Builder<'a, R: RngInstance> {
+    params: &'a Parameters<R>,
+
+    value_base: bls::G1Projective,
+    bit_size: usize,
+    values: Vec<u64>,
+    blinds: Vec<bls::Scalar>,
+
+    alpha: bls::Scalar,
+    rho: bls::Scalar,
+    s_l: Vec<bls::Scalar>,
+    s_r: Vec<bls::Scalar>,
+
+    value_commits: Vec<bls::G1Projective>,
+    a: bls::G1Projective,
+    s: bls::G1Projective,
+}
+
+pub struct Commitments {
+    value_base: bls::G1Projective,
+    value_commits: Vec<bls::G1Projective>,
+    a: bls::G1Projective,
+    s: bls::G1Projective,
+    // The verifier only reproduces the prover's commitments
+    // when the rest of the proof checks out.
+    is_valid: bool,
+}
+
+pub struct Proof {
+    bit_size: u8,
+    value_commits: Vec<bls::G1Projective>,
+    a: bls::G1Projective,
+    s: bls::G1Projective,
+    t1: bls::G1Projective,
+    t2: bls::G1Projective,
+    tau_x: bls::Scalar,
+    mu: bls::Scalar,
+    t_hat: bls::Scalar,
+    // Inner product argument
+    l_points: Vec<bls::G1Projective>,
+    r_points: Vec<bls::G1Projective>,
+    ipa_a: bls::Scalar,
+    ipa_b: bls::Scalar,
+}
+
+fn inner_product(a: &[bls::Scalar], b: &[bls::Scalar]) -> bls::Scalar {
+    a.iter()
+        .zip(b)
+        .fold(bls::Scalar::zero(), |acc, (a_i, b_i)| acc + a_i * b_i)
+}
+
+fn multiscalar_mul<'a, P>(scalars: &[bls::Scalar], points: &'a [P]) -> bls::G1Projective
+where
+    &'a P: std::ops::Mul<bls::Scalar, Output = bls::G1Projective>,
+{
+    scalars
+        .iter()
+        .zip(points)
+        .fold(bls::G1Projective::identity(), |acc, (scalar, point)| {
+            acc + point * *scalar
+        })
+}
+
+fn powers(base: &bls::Scalar, n: usize) -> Vec<bls::Scalar> {
+    let mut result = Vec::with_capacity(n);
+    let mut power = bls::Scalar::one();
+    for _ in 0..n {
+        result.push(power);
+        power *= base;
+    }
+    result
+}
+
+fn challenge_scalar(
+    previous: &bls::Scalar,
+    points: &[&bls::G1Projective],
+    scalars: &[&bls::Scalar],
+) -> bls::Scalar {
+    let mut transcript = Transcript::new(b"bulletproof-round");
+    transcript.append_scalar(b"previous", previous);
+    for point in points {
+        transcript.append_g1(b"point", point);
+    }
+    for scalar in scalars {
+        transcript.append_scalar(b"scalar", scalar);
+    }
+    transcript.challenge_scalar(b"challenge")
+}
+
+// The y and z challenges are derived from the transaction challenge
+fn compute_yz(
+    challenge: &bls::Scalar,
+    a: &bls::G1Projective,
+    s: &bls::G1Projective,
+) -> (bls::Scalar, bls::Scalar) {
+    let y = challenge_scalar(challenge, &[a, s], &[]);
+    let z = challenge_scalar(&y, &[a, s], &[]);
+    (y, z)
+}
+
+// Value j is weighted by z^(2 + j)
+fn compute_value_weights(z: &bls::Scalar, aggregation_size: usize) -> Vec<bls::Scalar> {
+    let z2 = z * z;
+    powers(z, aggregation_size)
+        .iter()
+        .map(|z_j| z2 * z_j)
+        .collect()
+}
+
+// delta(y, z) = (z - z^2) <1, y^nm> - sum_j z^(3 + j) <1, 2^n>
+fn compute_delta(
+    y_powers: &[bls::Scalar],
+    two_powers: &[bls::Scalar],
+    value_weights: &[bls::Scalar],
+    z: &bls::Scalar,
+) -> bls::Scalar {
+    let z2 = z * z;
+    let sum_y = y_powers
+        .iter()
+        .fold(bls::Scalar::zero(), |acc, y_i| acc + y_i);
+    let sum_2 = two_powers
+        .iter()
+        .fold(bls::Scalar::zero(), |acc, two_i| acc + two_i);
+    let sum_weights = value_weights
+        .iter()
+        .fold(bls::Scalar::zero(), |acc, weight| acc + weight);
+    (z - z2) * sum_y - z * sum_2 * sum_weights
+}
+
+// H'_i = y^-i H_i
+fn compute_h_primes(
+    hs: &[bls::G1Projective],
+    y_powers: &[bls::Scalar],
+) -> Option<Vec<bls::G1Projective>> {
+    let y_inverse = y_powers.get(1)?.invert();
+    if bool::from(y_inverse.is_none()) {
+        return None;
+    }
+    let y_inverse_powers = powers(&y_inverse.unwrap(), y_powers.len());
+    Some(
+        hs.iter()
+            .zip(y_inverse_powers)
+            .map(|(h, y_inverse_i)| h * y_inverse_i)
+            .collect(),
+    )
+}
+
+// Bit i of the concatenated (and zero padded) values
+fn value_bit(values: &[u64], bit_size: usize, i: usize) -> u64 {
+    values
+        .get(i / bit_size)
+        .map_or(0, |value| (value >> (i % bit_size)) & 1)
+}
+
+impl<'a, R: RngInstance> Builder<'a, R> {
+    pub fn new(
+        params: &'a Parameters<R>,
+        value_base: &bls::G1Projective,
+        blind: &bls::Scalar,
+        value: u64,
+    ) -> Result<Self> {
+        params.check_rangeproof_value(value)?;
+        Ok(Self::prove_values(params, value_base, &[*blind], &[value]))
+    }
+
+    pub fn new_aggregated(
+        params: &'a Parameters<R>,
+        value_base: &bls::G1Projective,
+        blinds: &[bls::Scalar],
+        values: &[u64],
+    ) -> Result<Self> {
+        if values.is_empty() || values.len() > MAX_AGGREGATED_VALUES {
+            return Err(Error::InvalidAggregationSize);
+        }
+        if blinds.len() != values.len() {
+            return Err(Error::InvalidAttributeCount);
+        }
+        for value in values {
+            params.check_rangeproof_value(*value)?;
+        }
+        Ok(Self::prove_values(params, value_base, blinds, values))
+    }
+
+    fn prove_values(
+        params: &'a Parameters<R>,
+        value_base: &bls::G1Projective,
+        blinds: &[bls::Scalar],
+        values: &[u64],
+    ) -> Self {
+        let bit_size = params.rangeproof_bits;
+        let size = bit_size * values.len().next_power_of_two();
+        let (gs, hs) = params.bulletproof_generators(size);
+
+        let alpha = params.random_scalar();
+        let rho = params.random_scalar();
+        let s_l = params.random_scalars(size);
+        let s_r = params.random_scalars(size);
+
+        // A = alpha G1 + <a_L, G> + <a_R, H>
+        // where a_L are the bits of v and a_R = a_L - 1
+        let mut a = params.g1 * alpha;
+        for (i, (g, h)) in gs.iter().zip(&hs).enumerate() {
+            if value_bit(values, bit_size, i) == 1 {
+                a += g;
+            } else {
+                a -= h;
+            }
+        }
+
+        // S = rho G1 + <s_L, G> + <s_R, H>
+        let s = params.g1 * rho + multiscalar_mul(&s_l, &gs) + multiscalar_mul(&s_r, &hs);
+
+        let value_commits = blinds
+            .iter()
+            .zip(values)
+            .map(|(blind, value)| params.g1 * blind + value_base * bls::Scalar::from(*value))
+            .collect();
+
+        Self {
+            params,
+            value_base: *value_base,
+            bit_size,
+            values: values.to_vec(),
+            blinds: blinds.to_vec(),
+            alpha,
+            rho,
+            s_l,
+            s_r,
+            value_commits,
+            a,
+            s,
+        }
+    }
+
+    pub fn commitments(&self) -> Box<dyn ProofCommitments + 'a> {
+        Box::new(Commitments {
+            value_base: self.value_base,
+            value_commits: self.value_commits.clone(),
+            a: self.a,
+            s: self.s,
+            is_valid: true,
+        })
+    }
+
+    pub fn finish(mut self, challenge: &bls::Scalar) -> Proof {
+        let params = self.params;
+        let bit_size = self.bit_size;
+        let aggregation_size = self.values.len().next_power_of_two();
+        let size = bit_size * aggregation_size;
+
+        let (y, z) = compute_yz(challenge, &self.a, &self.s);
+        let y_powers = powers(&y, size);
+        let two_powers = powers(&bls::Scalar::from(2), bit_size);
+        let value_weights = compute_value_weights(&z, aggregation_size);
+
+        let bits: Vec<_> = (0..size)
+            .map(|i| bls::Scalar::from(value_bit(&self.values, bit_size, i)))
+            .collect();
+
+        // l(X) = (a_L - z 1) + s_L X
+        // r(X) = y^nm o (a_R + z 1 + s_R X) + sum_j z^(2 + j) (0^jn || 2^n || 0^(m-j-1)n)
+        let l0: Vec<_> = bits.iter().map(|bit| bit - z).collect();
+        let l1 = &self.s_l;
+        let r0: Vec<_> = izip!(&bits, &y_powers)
+            .enumerate()
+            .map(|(i, (bit, y_i))| {
+                y_i * (bit - bls::Scalar::one() + z)
+                    + value_weights[i / bit_size] * two_powers[i % bit_size]
+            })
+            .collect();
+        let r1: Vec<_> = izip!(&self.s_r, &y_powers)
+            .map(|(s_r_i, y_i)| y_i * s_r_i)
+            .collect();
+
+        // t(X) = <l(X), r(X)> = t0 + t1 X + t2 X^2
+        let t1 = inner_product(&l0, &r1) + inner_product(l1, &r0);
+        let t2 = inner_product(l1, &r1);
+
+        let tau1 = params.random_scalar();
+        let tau2 = params.random_scalar();
+        let t1_commit = self.value_base * t1 + params.g1 * tau1;
+        let t2_commit = self.value_base * t2 + params.g1 * tau2;
+
+        let x = challenge_scalar(&z, &[&t1_commit, &t2_commit], &[]);
+
+        let l: Vec<_> = l0
+            .iter()
+            .zip(l1)
+            .map(|(l0_i, l1_i)| l0_i + l1_i * x)
+            .collect();
+        let r: Vec<_> = r0
+            .iter()
+            .zip(&r1)
+            .map(|(r0_i, r1_i)| r0_i + r1_i * x)
+            .collect();
+
+        let t_hat = inner_product(&l, &r);
+        let tau_x = tau2 * x * x
+            + tau1 * x
+            + inner_product(&value_weights[..self.blinds.len()], &self.blinds);
+        let mu = self.alpha + self.rho * x;
+
+        let w = challenge_scalar(&x, &[], &[&tau_x, &mu, &t_hat]);
+        let q = params.bulletproof_q * w;
+
+        let (gs, hs) = params.bulletproof_generators(size);
+        let h_primes = compute_h_primes(&hs, &y_powers).expect("challenge is never zero");
+
+        let (l_points, r_points, ipa_a, ipa_b) = prove_inner_product(&w, &q, gs, h_primes, l, r);
+
+        Proof {
+            bit_size: bit_size as u8,
+            value_commits: std::mem::take(&mut self.value_commits),
+            a: self.a,
+            s: self.s,
+            t1: t1_commit,
+            t2: t2_commit,
+            tau_x,
+            mu,
+            t_hat,
+            l_points,
+            r_points,
+            ipa_a,
+            ipa_b,
+        }
+    }
+
+    pub fn decode<D: io::Read>(mut d: D, params: &'a Parameters<R>) -> Result<Self> {
+        Ok(Self {
+            params,
+            value_base: Decodable::decode(&mut d)?,
+            bit_size: {
+                let bit_size: u32 = Decodable::decode(&mut d)?;
+                bit_size as usize
+            },
+            values: Decodable::decode(&mut d)?,
+            blinds: Decodable::decode(&mut d)?,
+            alpha: Decodable::decode(&mut d)?,
+            rho: Decodable::decode(&mut d)?,
+            s_l: Decodable::decode(&mut d)?,
+            s_r: Decodable::decode(&mut d)?,
+            value_commits: Decodable::decode(&mut d)?,
+            a: Decodable::decode(&mut d)?,
+            s: Decodable::decode(d)?,
+        })
+    }
+}
+
+// Proves P = <a, G> + <b, H> + <a, b> Q by halving the vectors each round
+fn prove_inner_product(
+    challenge: &bls::Scalar,
+    q: &bls::G1Projective,
+    mut gs: Vec<bls::G1Projective>,
+    mut hs: Vec<bls::G1Projective>,
+    mut a: Vec<bls::Scalar>,
+    mut b: Vec<bls::Scalar>,
+) -> (
+    Vec<bls::G1Projective>,
+    Vec<bls::G1Projective>,
+    bls::Scalar,
+    bls::Scalar,
+) {
+    let mut l_points = Vec::new();
+    let mut r_points = Vec::new();
+    let mut u_previous = *challenge;
+
+    while a.len() > 1 {
+        let half = a.len() / 2;
+        let (a_lo, a_hi) = a.split_at(half);
+        let (b_lo, b_hi) = b.split_at(half);
+        let (g_lo, g_hi) = gs.split_at(half);
+        let (h_lo, h_hi) = hs.split_at(half);
+
+        let c_l = inner_product(a_lo, b_hi);
+        let c_r = inner_product(a_hi, b_lo);
+
+        let l_point = multiscalar_mul(a_lo, g_hi) + multiscalar_mul(b_hi, h_lo) + q * c_l;
+        let r_point = multiscalar_mul(a_hi, g_lo) + multiscalar_mul(b_lo, h_hi) + q * c_r;
+
+        let u = challenge_scalar(&u_previous, &[&l_point, &r_point], &[]);
+        let u_inverse = u.invert().unwrap();
+
+        a = izip!(a_lo, a_hi)
+            .map(|(lo, hi)| lo * u + hi * u_inverse)
+            .collect();
+        b = izip!(b_lo, b_hi)
+            .map(|(lo, hi)| lo * u_inverse + hi * u)
+            .collect();
+        gs = izip!(g_lo, g_hi)
+            .map(|(lo, hi)| lo * u_inverse + hi * u)
+            .collect();
+        hs = izip!(h_lo, h_hi)
+            .map(|(lo, hi)| lo * u + hi * u_inverse)
+            .collect();
+
+        l_points.push(l_point);
+        r_points.push(r_point);
+        u_previous = u;
+    }
+
+    (l_points, r_points, a[0], b[0])
+}
+
+impl<'a, R: RngInstance> Drop for Builder<'a, R> {
+    fn drop(&mut self) {
+        self.values.wipe();
+        self.blinds.wipe();
+        self.alpha.wipe();
+        self.rho.wipe();
+        self.s_l.wipe();
+        self.s_r.wipe();
+    }
+}
+
+impl<'a, R: RngInstance> Encodable for Builder<'a, R> {
+    fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
+        let mut len = 0;
+        len += self.value_base.encode(&mut s)?;
+        len += (self.bit_size as u32).encode(&mut s)?;
+        len += self.values.encode(&mut s)?;
+        len += self.blinds.encode(&mut s)?;
+        len += self.alpha.encode(&mut s)?;
+        len += self.rho.encode(&mut s)?;
+        len += self.s_l.encode(&mut s)?;
+        len += self.s_r.encode(&mut s)?;
+        len += self.value_commits.encode(&mut s)?;
+        len += self.a.encode(&mut s)?;
+        Ok(len + self.s.encode(s)?)
+    }
+}
+
+impl ProofCommitments for Commitments {
+    fn commit(&self, transcript: &mut Transcript) {
+        transcript.append_g1(b"value-base", &self.value_base);
+        for value_commit in &self.value_commits {
+            transcript.append_g1(b"value-commit", value_commit);
+        }
+        transcript.append_g1(b"a", &self.a);
+        transcript.append_g1(b"s", &self.s);
+        transcript.append_u64(b"is-valid", self.is_valid as u64);
+    }
+}
+
+impl Proof {
+    pub fn commitments<'a, R: RngInstance>(
+        &self,
+        params: &'a Parameters<R>,
+        challenge: &bls::Scalar,
+        value_base: &bls::G1Projective,
+    ) -> Box<dyn ProofCommitments + 'a> {
+        Box::new(Commitments {
+            value_base: *value_base,
+            value_commits: self.value_commits.clone(),
+            a: self.a,
+            s: self.s,
+            is_valid: self.verify(params, challenge, value_base),
+        })
+    }
+
+    pub fn verify<R: RngInstance>(
+        &self,
+        params: &Parameters<R>,
+        challenge: &bls::Scalar,
+        value_base: &bls::G1Projective,
+    ) -> bool {
+        if self.value_commits.is_empty() || self.value_commits.len() > MAX_AGGREGATED_VALUES {
+            return false;
+        }
+        // The width must match our policy
+        let bit_size = self.bit_size as usize;
+        if bit_size != params.rangeproof_bits || !bit_size.is_power_of_two() {
+            return false;
+        }
+        let aggregation_size = self.value_commits.len().next_power_of_two();
+        let size = bit_size * aggregation_size;
+        if self.l_points.len() != self.r_points.len()
+            || self.l_points.len() != size.trailing_zeros() as usize
+        {
+            return false;
+        }
+
+        let (y, z) = compute_yz(challenge, &self.a, &self.s);
+        let y_powers = powers(&y, size);
+        let two_powers = powers(&bls::Scalar::from(2), bit_size);
+        let value_weights = compute_value_weights(&z, aggregation_size);
+        let x = challenge_scalar(&z, &[&self.t1, &self.t2], &[]);
+
+        // t_hat B + tau_x G1 = sum_j z^(2 + j) V_j + delta(y, z) B + x T1 + x^2 T2
+        // The padding values are commits to zero with no blind.
+        let delta = compute_delta(&y_powers, &two_powers, &value_weights, &z);
+        if value_base * self.t_hat + params.g1 * self.tau_x
+            != multiscalar_mul(&value_weights, &self.value_commits)
+                + value_base * delta
+                + self.t1 * x
+                + self.t2 * (x * x)
+        {
+            return false;
+        }
+
+        let w = challenge_scalar(&x, &[], &[&self.tau_x, &self.mu, &self.t_hat]);
+        let q = params.bulletproof_q * w;
+
+        let (mut gs, hs) = params.bulletproof_generators(size);
+        let mut hs = match compute_h_primes(&hs, &y_powers) {
+            Some(h_primes) => h_primes,
+            None => return false,
+        };
+
+        // P = A + x S - z <1, G> + <z y^nm + sum_j z^(2 + j) 2^n, H'> - mu G1 + t_hat Q
+        let mut p = self.a + self.s * x - params.g1 * self.mu + q * self.t_hat;
+        for (i, (g, h, y_i)) in izip!(&gs, &hs, &y_powers).enumerate() {
+            p += h * (z * y_i + value_weights[i / bit_size] * two_powers[i % bit_size]) - g * z;
+        }
+
+        let mut u_previous = w;
+        for (l_point, r_point) in self.l_points.iter().zip(&self.r_points) {
+            let u = challenge_scalar(&u_previous, &[l_point, r_point], &[]);
+            let u_inverse = u.invert();
+            if bool::from(u_inverse.is_none()) {
+                return false;
+            }
+            let u_inverse = u_inverse.unwrap();
+
+            p += l_point * (u * u) + r_point * (u_inverse * u_inverse);
+
+            let half = gs.len() / 2;
+            let (g_lo, g_hi) = gs.split_at(half);
+            let (h_lo, h_hi) = hs.split_at(half);
+            let folded_gs = izip!(g_lo, g_hi)
+                .map(|(lo, hi)| lo * u_inverse + hi * u)
+                .collect();
+            let folded_hs = izip!(h_lo, h_hi)
+                .map(|(lo, hi)| lo * u + hi * u_inverse)
+                .collect();
+            gs = folded_gs;
+            hs = folded_hs;
+
+            u_previous = u;
+        }
+
+        p == gs[0] * self.ipa_a + hs[0] * self.ipa_b + q * (self.ipa_a * self.ipa_b)
+    }
+
+    pub fn value_commit(&self) -> bls::G1Projective {
+        self.value_commits[0]
+    }
+
+    pub fn value_commits(&self) -> &Vec<bls::G1Projective> {
+        &self.value_commits
+    }
+
+    pub fn bit_size(&self) -> usize {
+        self.bit_size as usize
+    }
+}
+
+impl Encodable for Proof {
+    fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
+        let mut len = 0;
+        len += self.bit_size.encode(&mut s)?;
+        len += self.value_commits.encode(&mut s)?;
+        len += self.a.encode(&mut s)?;
+        len += self.s.encode(&mut s)?;
+        len += self.t1.encode(&mut s)?;
+        len += self.t2.encode(&mut s)?;
+        len += self.tau_x.encode(&mut s)?;
+        len += self.mu.encode(&mut s)?;
+        len += self.t_hat.encode(&mut s)?;
+        len += self.l_points.encode(&mut s)?;
+        len += self.r_points.encode(&mut s)?;
+        len += self.ipa_a.encode(&mut s)?;
+        Ok(len + self.ipa_b.encode(s)?)
+    }
+}
+
+impl Decodable for Proof {
+    fn decode<D: io::Read>(mut d: D) -> Result<Self> {
+        let bit_size = Decodable::decode(&mut d)?;
+        let value_commits: Vec<bls::G1Projective> = Decodable::decode(&mut d)?;
+        if value_commits.is_empty() {
+            return Err(Error::ParseFailed("range proof without value commits"));
+        }
+        Ok(Self {
+            bit_size,
+            value_commits,
+            a: Decodable::decode(&mut d)?,
+            s: Decodable::decode(&mut d)?,
+            t1: Decodable::decode(&mut d)?,
+            t2: Decodable::decode(&mut d)?,
+            tau_x: Decodable::decode(&mut d)?,
+            mu: Decodable::decode(&mut d)?,
+            t_hat: Decodable::decode(&mut d)?,
+            l_points: Decodable::decode(&mut d)?,
+            r_points: Decodable::decode(&mut d)?,
+            ipa_a: Decodable::decode(&mut d)?,
+            ipa_b: Decodable::decode(d)?,
+        })
+    }
+}
+
+#[test]
+fn test_bulletproof() {
+    let params = Parameters::<OsRngInstance>::new(2);
+
+    let blind = params.random_scalar();
+    let value = 127832u64;
+    let value_base = bls::G1Projective::from(params.hs[0]);
+    let value_commit = params.g1 * blind + value_base * bls::Scalar::from(value);
+
+    let builder = Builder::new(&params, &value_base, &blind, value).unwrap();
+    let commits = builder.commitments();
+
+    let mut transcript = Transcript::new(b"test");
+    commits.commit(&mut transcript);
+    let challenge = transcript.challenge_scalar(b"challenge");
+
+    let proof = builder.finish(&challenge);
+
+    assert_eq!(proof.value_commit(), value_commit);
+    assert_eq!(proof.l_points.len(), 6);
+
+    let commits2 = proof.commitments(&params, &challenge, &value_base);
+    let mut transcript2 = Transcript::new(b"test");
+    commits2.commit(&mut transcript2);
+    assert_eq!(challenge, transcript2.challenge_scalar(b"challenge"));
+
+    // The proof is only valid for the value base it was made with
+    let other_base = bls::G1Projective::from(params.hs[1]);
+    assert!(!proof.verify(&params, &challenge, &other_base));
+
+    // Tampering with any part of the proof breaks it
+    let mut bad_proof = deserialize::<Proof>(&serialize(&proof)).unwrap();
+    bad_proof.t_hat += bls::Scalar::one();
+    assert!(!bad_proof.verify(&params, &challenge, &value_base));
+
+    let mut bad_proof = deserialize::<Proof>(&serialize(&proof)).unwrap();
+    bad_proof.ipa_a += bls::Scalar::one();
+    assert!(!bad_proof.verify(&params, &challenge, &value_base));
+
+    let mut bad_proof = deserialize::<Proof>(&serialize(&proof)).unwrap();
+    bad_proof.l_points.pop();
+    assert!(!bad_proof.verify(&params, &challenge, &value_base));
+}
+
+#[test]
+fn test_bulletproof_fail() {
+    let overflowed_value = bls::Scalar::from(u64::MAX) + bls::Scalar::one();
+
+    let params = Parameters::<OsRngInstance>::new(2);
+
+    let blind = params.random_scalar();
+    let value_base = bls::G1Projective::from(params.hs[0]);
+    let value_commit = params.g1 * blind + value_base * overflowed_value;
+
+    let builder = Builder::new(&params, &value_base, &blind, u64::MAX).unwrap();
+    let commits = builder.commitments();
+
+    let mut transcript = Transcript::new(b"test");
+    commits.commit(&mut transcript);
+    let challenge = transcript.challenge_scalar(b"challenge");
+
+    let proof = builder.finish(&challenge);
+    assert!(proof.verify(&params, &challenge, &value_base));
+    assert_ne!(proof.value_commit(), value_commit);
+
+    // Claiming a commit to a value out of range doesn't verify
+    let mut bad_proof = deserialize::<Proof>(&serialize(&proof)).unwrap();
+    bad_proof.value_commits[0] = value_commit;
+    assert!(!bad_proof.verify(&params, &challenge, &value_base));
+}
+
+#[test]
+fn test_bulletproof_aggregated() {
+    let params = Parameters::<OsRngInstance>::new(2);
+    let value_base = bls::G1Projective::from(params.range_base);
+
+    // Three values are padded to four
+    let values = [110u64, 0, u64::MAX];
+    let blinds = params.random_scalars(values.len());
+
+    let builder = Builder::new_aggregated(&params, &value_base, &blinds, &values).unwrap();
+    let mut transcript = Transcript::new(b"test");
+    builder.commitments().commit(&mut transcript);
+    let challenge = transcript.challenge_scalar(b"challenge");
+
+    let proof = builder.finish(&challenge);
+    assert_eq!(proof.value_commits().len(), 3);
+    assert_eq!(proof.l_points.len(), 8);
+    for (value_commit, blind, value) in izip!(proof.value_commits(), &blinds, &values) {
+        assert_eq!(
+            *value_commit,
+            params.g1 * blind + value_base * bls::Scalar::from(*value)
+        );
+    }
+
+    let mut transcript2 = Transcript::new(b"test");
+    proof
+        .commitments(&params, &challenge, &value_base)
+        .commit(&mut transcript2);
+    assert_eq!(challenge, transcript2.challenge_scalar(b"challenge"));
+
+    // The commits are bound to their position
+    let mut bad_proof = deserialize::<Proof>(&serialize(&proof)).unwrap();
+    bad_proof.value_commits.swap(0, 2);
+    assert!(!bad_proof.verify(&params, &challenge, &value_base));
+
+    // Dropping a value changes the padding
+    let mut bad_proof = deserialize::<Proof>(&serialize(&proof)).unwrap();
+    bad_proof.value_commits.pop();
+    assert!(!bad_proof.verify(&params, &challenge, &value_base));
+
+    // A proof must cover at least one value
+    let mut bad_proof = deserialize::<Proof>(&serialize(&proof)).unwrap();
+    bad_proof.value_commits.clear();
+    assert!(deserialize::<Proof>(&serialize(&bad_proof)).is_err());
+
+    // Nor can a builder be made for none or too many
+    assert!(matches!(
+        Builder::new_aggregated(&params, &value_base, &[], &[]),
+        Err(Error::InvalidAggregationSize)
+    ));
+    let values = vec![1u64; MAX_AGGREGATED_VALUES + 1];
+    let blinds = params.random_scalars(values.len());
+    assert!(matches!(
+        Builder::new_aggregated(&params, &value_base, &blinds, &values),
+        Err(Error::InvalidAggregationSize)
+    ));
+    assert!(matches!(
+        Builder::new_aggregated(&params, &value_base, &blinds[..1], &values[..2]),
+        Err(Error::InvalidAttributeCount)
+    ));
+}
+
+#[test]
+fn test_bulletproof_bits() {
+    let params = Parameters::<OsRngInstance>::new(2)
+        .with_rangeproof_bits(32)
+        .unwrap();
+    let value_base = bls::G1Projective::from(params.range_base);
+
+    let blind = params.random_scalar();
+    let builder = Builder::new(&params, &value_base, &blind, u32::MAX as u64).unwrap();
+    let mut transcript = Transcript::new(b"test");
+    builder.commitments().commit(&mut transcript);
+    let challenge = transcript.challenge_scalar(b"challenge");
+
+    let proof = builder.finish(&challenge);
+    assert_eq!(proof.bit_size(), 32);
+    assert_eq!(proof.l_points.len(), 5);
+    assert!(proof.verify(&params, &challenge, &value_base));
+
+    // Verifiers only accept the width they were configured with
+    let params64 = Parameters::<OsRngInstance>::new(2);
+    assert!(!proof.verify(&params64, &challenge, &value_base));
+
+    let mut bad_proof = deserialize::<Proof>(&serialize(&proof)).unwrap();
+    bad_proof.bit_size = 64;
+    assert!(!bad_proof.verify(&params, &challenge, &value_base));
+
+    // 2^32 doesn't fit in 32 bits
+    assert!(matches!(
+        Builder::new(&params, &value_base, &blind, 1 << 32),
+        Err(Error::ValueOutOfRange)
+    ));
+    assert!(matches!(
+        Builder::new_aggregated(&params, &value_base, &[blind, blind], &[1, 1 << 32]),
+        Err(Error::ValueOutOfRange)
+    ));
+}
+
+#[test]
+fn test_bulletproof_known_answer() {
+    let prove = || {
+        SeededRngInstance::seed(b"bulletproof known answer");
+        let params = Parameters::<SeededRngInstance>::new(2);
+
+        let blind = params.random_scalar();
+        let value_base = bls::G1Projective::from(params.hs[0]);
+
+        let builder = Builder::new(&params, &value_base, &blind, 127832).unwrap();
+        let mut transcript = Transcript::new(b"test");
+        builder.commitments().commit(&mut transcript);
+        let challenge = transcript.challenge_scalar(b"challenge");
+
+        let proof = builder.finish(&challenge);
+        assert!(proof.verify(&params, &challenge, &value_base));
+        serialize(&proof)
+    };
+
+    let proof = prove();
+    assert_eq!(prove(), proof);
+    crate::utility::check_known_answer("bulletproof", &proof);
+}
@@ -83,22 +83,22 @@ impl<'a, R: RngInstance> Builder<'a, R> {
 }
 
 impl<'a, R: RngInstance> ProofCommitments for Commitments<'a, R> {
-    fn commit(&self, hasher: &mut HasherToScalar) {
+    fn commit(&self, transcript: &mut Transcript) {
         // Add base points we use
-        hasher.add_g1_affine(&self.params.g1);
-        hasher.add_g2_affine(&self.params.g2);
+        transcript.append_g1_affine(b"g1", &self.params.g1);
+        transcript.append_g2_affine(b"g2", &self.params.g2);
         for h in &self.params.hs {
-            hasher.add_g1_affine(h);
+            transcript.append_g1_affine(b"h", h);
         }
 
-        hasher.add_g2(&self.verify_key.alpha);
+        transcript.append_g2(b"alpha", &self.verify_key.alpha);
         for beta in &self.verify_key.beta {
-            hasher.add_g2(beta);
+            transcript.append_g2(b"beta", beta);
         }
-        hasher.add_g1(self.blind_commitish);
+        transcript.append_g1(b"blind-commitish", self.blind_commitish);
 
-        hasher.add_g2(&self.commit_kappa);
-        hasher.add_g1(&self.commit_blind);
+        transcript.append_g2(b"commit-kappa", &self.commit_kappa);
+        transcript.append_g1(b"commit-blind", &self.commit_blind);
     }
 }
 
@@ -154,3 +154,19 @@ impl Proof {
         })
     }
 }
+
+impl Encodable for Proof {
+    fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
+        let len = self.response_attributes.encode(&mut s)?;
+        Ok(len + self.response_blind.encode(s)?)
+    }
+}
+
+impl Decodable for Proof {
+    fn decode<D: io::Read>(mut d: D) -> Result<Self> {
+        Ok(Self {
+            response_attributes: Decodable::decode(&mut d)?,
+            response_blind: Decodable::decode(d)?,
+        })
+    }
+}
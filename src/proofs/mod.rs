@@ -1,7 +1,10 @@
+pub mod asset_proof;
+pub mod bulletproof;
 pub mod credential_proof;
 pub mod ownership_proof;
 pub mod pedersen_proof;
 pub mod proof;
+pub mod range;
 pub mod rangeproof;
 pub mod signature_proof;
 pub mod simple_or;
@@ -37,9 +37,9 @@ impl<'a, R: RngInstance> Builder<'a, R> {
 }
 
 impl<'a, R: RngInstance> ProofCommitments for Commitments<'a, R> {
-    fn commit(&self, hasher: &mut HasherToScalar) {
-        hasher.add_g1_affine(&self.params.g1);
-        hasher.add_g1(&self.commit);
+    fn commit(&self, transcript: &mut Transcript) {
+        transcript.append_g1_affine(b"g1", &self.params.g1);
+        transcript.append_g1(b"commit", &self.commit);
     }
 }
 
@@ -84,16 +84,16 @@ fn test_ownership_proof() {
     let builder = Builder::new(&params, witness.clone());
     let commits = builder.commitments();
 
-    let mut hasher = HasherToScalar::new();
-    commits.commit(&mut hasher);
-    let challenge = hasher.finish();
+    let mut transcript = Transcript::new(b"test");
+    commits.commit(&mut transcript);
+    let challenge = transcript.challenge_scalar(b"challenge");
 
     let response = witness.derive(&challenge);
     let proof = Proof { response };
 
     let commits2 = proof.commitments(&params, &challenge, &public);
-    let mut hasher2 = HasherToScalar::new();
-    commits2.commit(&mut hasher2);
-    let challenge2 = hasher2.finish();
+    let mut transcript2 = Transcript::new(b"test");
+    commits2.commit(&mut transcript2);
+    let challenge2 = transcript2.challenge_scalar(b"challenge");
     assert_eq!(challenge, challenge2);
 }
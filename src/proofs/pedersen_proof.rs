@@ -11,6 +11,7 @@ use crate::serial::{Decodable, DecodableWithParams, Encodable};
 
 pub struct Builder<'a, R: RngInstance> {
     params: &'a Parameters<R>,
+    value_base: bls::G1Projective,
 
     witness_blind: Rc<Witness>,
     witness_value: Rc<Witness>,
@@ -18,6 +19,7 @@ pub struct Builder<'a, R: RngInstance> {
 
 pub struct Commitments<'a, R: RngInstance> {
     params: &'a Parameters<R>,
+    value_base: bls::G1Projective,
     commit_pedersen: bls::G1Projective,
 }
 
@@ -29,22 +31,25 @@ pub struct Proof {
 impl<'a, R: RngInstance> Builder<'a, R> {
     pub fn new(
         params: &'a Parameters<R>,
+        value_base: bls::G1Projective,
         witness_blind: Rc<Witness>,
         witness_value: Rc<Witness>,
     ) -> Self {
         Self {
             params,
+            value_base,
             witness_blind,
             witness_value,
         }
     }
 
     pub fn commitments(&self) -> Box<dyn ProofCommitments + 'a> {
-        assert!(self.params.hs.len() > 0);
         Box::new(Commitments {
             params: self.params,
+            value_base: self.value_base,
             commit_pedersen: compute_pedersen(
                 self.params,
+                &self.value_base,
                 self.witness_blind.get(),
                 self.witness_value.get(),
             ),
@@ -54,7 +59,9 @@ impl<'a, R: RngInstance> Builder<'a, R> {
 
 impl<'a, R: RngInstance> Encodable for Builder<'a, R> {
     fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
-        let len = self.witness_blind.encode(&mut s)?;
+        let mut len = 0;
+        len += self.value_base.encode(&mut s)?;
+        len += self.witness_blind.encode(&mut s)?;
         Ok(len + self.witness_value.encode(s)?)
     }
 }
@@ -63,6 +70,7 @@ impl<'a, R: RngInstance> DecodableWithParams<'a, R> for Builder<'a, R> {
     fn decode<D: io::Read>(mut d: D, params: &'a Parameters<R>) -> Result<Self> {
         Ok(Self {
             params,
+            value_base: Decodable::decode(&mut d)?,
             witness_blind: Decodable::decode(&mut d)?,
             witness_value: Decodable::decode(&mut d)?,
         })
@@ -70,11 +78,10 @@ impl<'a, R: RngInstance> DecodableWithParams<'a, R> for Builder<'a, R> {
 }
 
 impl<'a, R: RngInstance> ProofCommitments for Commitments<'a, R> {
-    fn commit(&self, hasher: &mut HasherToScalar) {
-        assert!(self.params.hs.len() > 0);
-        hasher.add_g1_affine(&self.params.g1);
-        hasher.add_g1_affine(&self.params.hs[0]);
-        hasher.add_g1(&self.commit_pedersen);
+    fn commit(&self, transcript: &mut Transcript) {
+        transcript.append_g1_affine(b"g1", &self.params.g1);
+        transcript.append_g1(b"value-base", &self.value_base);
+        transcript.append_g1(b"commit-pedersen", &self.commit_pedersen);
     }
 }
 
@@ -83,13 +90,18 @@ impl Proof {
         &self,
         params: &'a Parameters<R>,
         challenge: &bls::Scalar,
+        value_base: &bls::G1Projective,
         pedersen: &bls::G1Projective,
     ) -> Box<dyn ProofCommitments + 'a> {
-        assert!(params.hs.len() > 0);
         Box::new(Commitments {
             params,
-            commit_pedersen: compute_pedersen(params, &self.response_blind, &self.response_value)
-                + pedersen * challenge,
+            value_base: *value_base,
+            commit_pedersen: compute_pedersen(
+                params,
+                value_base,
+                &self.response_blind,
+                &self.response_value,
+            ) + pedersen * challenge,
         })
     }
 }
@@ -100,17 +112,23 @@ fn test_pedersen_proof() {
 
     let blind = params.random_scalar();
     let value = bls::Scalar::from(110);
-    let pedersen = params.g1 * blind + params.hs[0] * value;
+    let value_base = bls::G1Projective::from(params.hs[0]);
+    let pedersen = params.g1 * blind + value_base * value;
 
     let witness_blind = Rc::new(Witness::new(&params, blind));
     let witness_value = Rc::new(Witness::new(&params, value));
 
-    let builder = Builder::new(&params, witness_blind.clone(), witness_value.clone());
+    let builder = Builder::new(
+        &params,
+        value_base,
+        witness_blind.clone(),
+        witness_value.clone(),
+    );
     let commits = builder.commitments();
 
-    let mut hasher = HasherToScalar::new();
-    commits.commit(&mut hasher);
-    let challenge = hasher.finish();
+    let mut transcript = Transcript::new(b"test");
+    commits.commit(&mut transcript);
+    let challenge = transcript.challenge_scalar(b"challenge");
 
     let response_blind = witness_blind.derive(&challenge);
     let response_value = witness_value.derive(&challenge);
@@ -119,9 +137,9 @@ fn test_pedersen_proof() {
         response_value,
     };
 
-    let commits2 = proof.commitments(&params, &challenge, &pedersen);
-    let mut hasher2 = HasherToScalar::new();
-    commits2.commit(&mut hasher2);
-    let challenge2 = hasher2.finish();
+    let commits2 = proof.commitments(&params, &challenge, &value_base, &pedersen);
+    let mut transcript2 = Transcript::new(b"test");
+    commits2.commit(&mut transcript2);
+    let challenge2 = transcript2.challenge_scalar(b"challenge");
     assert_eq!(challenge, challenge2);
 }
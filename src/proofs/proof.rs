@@ -1,4 +1,5 @@
 use bls12_381 as bls;
+use std::fmt;
 use std::io;
 use std::rc::Rc;
 
@@ -16,11 +17,11 @@ pub trait ProofBuilder {
 }
 
 pub trait ProofCommitments {
-    fn commit(&self, hasher: &mut HasherToScalar);
+    fn commit(&self, transcript: &mut Transcript);
 }
 
 pub struct Witness {
-    pub secret: bls::Scalar,
+    secret: bls::Scalar,
     witness: bls::Scalar,
 }
 
@@ -36,11 +37,31 @@ impl Witness {
         &self.witness
     }
 
+    pub fn secret(&self) -> &bls::Scalar {
+        &self.secret
+    }
+
     pub fn derive(&self, challenge: &bls::Scalar) -> bls::Scalar {
         self.witness - challenge * self.secret
     }
 }
 
+impl Drop for Witness {
+    fn drop(&mut self) {
+        self.secret.wipe();
+        self.witness.wipe();
+    }
+}
+
+impl fmt::Debug for Witness {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Witness")
+            .field("secret", &Redacted)
+            .field("witness", &Redacted)
+            .finish()
+    }
+}
+
 impl Encodable for Rc<Witness> {
     fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
         let len = self.secret.encode(&mut s)?;
@@ -65,18 +86,18 @@ fn test_witness() {
 
     let commit = params.g1 * witness.get();
 
-    let mut hasher = HasherToScalar::new();
-    hasher.add_g1(&commit);
-    let challenge = hasher.finish();
+    let mut transcript = Transcript::new(b"test");
+    transcript.append_g1(b"commit", &commit);
+    let challenge = transcript.challenge_scalar(b"challenge");
 
     let response = witness.derive(&challenge);
 
     let public = params.g1 * secret;
 
     let commit2 = params.g1 * response + public * challenge;
-    let mut hasher2 = HasherToScalar::new();
-    hasher2.add_g1(&commit2);
-    let challenge2 = hasher2.finish();
+    let mut transcript2 = Transcript::new(b"test");
+    transcript2.append_g1(b"commit", &commit2);
+    let challenge2 = transcript2.challenge_scalar(b"challenge");
 
     assert_eq!(challenge, challenge2);
 }
@@ -0,0 +1,222 @@
+use bls12_381 as bls;
+use std::io;
+
+use crate::bls_extensions::*;
+use crate::error::{Error, Result};
+use crate::parameters::*;
+use crate::proofs::bulletproof;
+use crate::proofs::proof::*;
+use crate::proofs::rangeproof;
+use crate::serial::{Decodable, Encodable};
+
+// Range proof of either kind. The encoding starts with a version byte
+// so older bit decomposition proofs can still be read.
+// Bulletproofs are boxed since they're much bigger than the Vec based proofs.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RangeProofKind {
+    // 64 OR proofs, one for each bit
+    BitDecomposition,
+    // Logarithmic size inner product proof
+    Bulletproof,
+}
+
+pub const DEFAULT_RANGEPROOF_KIND: RangeProofKind = RangeProofKind::Bulletproof;
+
+impl RangeProofKind {
+    fn version(&self) -> u8 {
+        match self {
+            RangeProofKind::BitDecomposition => 0,
+            RangeProofKind::Bulletproof => 1,
+        }
+    }
+
+    fn from_version(version: u8) -> Result<Self> {
+        match version {
+            0 => Ok(RangeProofKind::BitDecomposition),
+            1 => Ok(RangeProofKind::Bulletproof),
+            _ => Err(Error::ParseFailed("unknown range proof version")),
+        }
+    }
+}
+
+pub enum Builder<'a, R: RngInstance> {
+    BitDecomposition(rangeproof::Builder<'a>),
+    Bulletproof(Box<bulletproof::Builder<'a, R>>),
+}
+
+pub enum Proof {
+    BitDecomposition(rangeproof::Proof),
+    Bulletproof(Box<bulletproof::Proof>),
+}
+
+impl<'a, R: RngInstance> Builder<'a, R> {
+    pub fn new(
+        kind: RangeProofKind,
+        params: &'a Parameters<R>,
+        value_base: &bls::G1Projective,
+        blind: &bls::Scalar,
+        value: u64,
+    ) -> Result<Self> {
+        Ok(match kind {
+            RangeProofKind::BitDecomposition => Builder::BitDecomposition(
+                rangeproof::Builder::new(params, value_base, blind, value)?,
+            ),
+            RangeProofKind::Bulletproof => Builder::Bulletproof(Box::new(
+                bulletproof::Builder::new(params, value_base, blind, value)?,
+            )),
+        })
+    }
+
+    pub fn kind(&self) -> RangeProofKind {
+        match self {
+            Builder::BitDecomposition(_) => RangeProofKind::BitDecomposition,
+            Builder::Bulletproof(_) => RangeProofKind::Bulletproof,
+        }
+    }
+
+    pub fn commitments(&self) -> Box<dyn ProofCommitments + 'a> {
+        match self {
+            Builder::BitDecomposition(builder) => builder.commitments(),
+            Builder::Bulletproof(builder) => builder.commitments(),
+        }
+    }
+
+    pub fn finish(self, challenge: &bls::Scalar) -> Proof {
+        match self {
+            Builder::BitDecomposition(builder) => {
+                Proof::BitDecomposition(builder.finish(challenge))
+            }
+            Builder::Bulletproof(builder) => {
+                Proof::Bulletproof(Box::new(builder.finish(challenge)))
+            }
+        }
+    }
+
+    pub fn decode<D: io::Read>(mut d: D, params: &'a Parameters<R>) -> Result<Self> {
+        let version: u8 = Decodable::decode(&mut d)?;
+        Ok(match RangeProofKind::from_version(version)? {
+            RangeProofKind::BitDecomposition => {
+                Builder::BitDecomposition(rangeproof::Builder::decode(d, params)?)
+            }
+            RangeProofKind::Bulletproof => {
+                Builder::Bulletproof(Box::new(bulletproof::Builder::decode(d, params)?))
+            }
+        })
+    }
+}
+
+impl<'a, R: RngInstance> Encodable for Builder<'a, R> {
+    fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
+        let len = self.kind().version().encode(&mut s)?;
+        Ok(len
+            + match self {
+                Builder::BitDecomposition(builder) => builder.encode(s)?,
+                Builder::Bulletproof(builder) => builder.encode(s)?,
+            })
+    }
+}
+
+impl Proof {
+    pub fn kind(&self) -> RangeProofKind {
+        match self {
+            Proof::BitDecomposition(_) => RangeProofKind::BitDecomposition,
+            Proof::Bulletproof(_) => RangeProofKind::Bulletproof,
+        }
+    }
+
+    pub fn commitments<'a, R: RngInstance>(
+        &self,
+        params: &'a Parameters<R>,
+        challenge: &bls::Scalar,
+        value_base: &bls::G1Projective,
+    ) -> Box<dyn ProofCommitments + 'a> {
+        match self {
+            Proof::BitDecomposition(proof) => proof.commitments(params, challenge, value_base),
+            Proof::Bulletproof(proof) => proof.commitments(params, challenge, value_base),
+        }
+    }
+
+    // The parts that can be checked without the rest of the transcript.
+    // Bit decomposition proofs are made of OR proofs which only answer the
+    // challenge, so just their size is checked.
+    pub fn verify<R: RngInstance>(
+        &self,
+        params: &Parameters<R>,
+        challenge: &bls::Scalar,
+        value_base: &bls::G1Projective,
+    ) -> bool {
+        match self {
+            Proof::BitDecomposition(proof) => proof.is_well_formed(params),
+            Proof::Bulletproof(proof) => proof.verify(params, challenge, value_base),
+        }
+    }
+
+    pub fn value_commit(&self) -> bls::G1Projective {
+        match self {
+            Proof::BitDecomposition(proof) => proof.value_commit(),
+            Proof::Bulletproof(proof) => proof.value_commit(),
+        }
+    }
+}
+
+impl Encodable for Proof {
+    fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
+        let len = self.kind().version().encode(&mut s)?;
+        Ok(len
+            + match self {
+                Proof::BitDecomposition(proof) => proof.encode(s)?,
+                Proof::Bulletproof(proof) => proof.encode(s)?,
+            })
+    }
+}
+
+impl Decodable for Proof {
+    fn decode<D: io::Read>(mut d: D) -> Result<Self> {
+        let version: u8 = Decodable::decode(&mut d)?;
+        Ok(match RangeProofKind::from_version(version)? {
+            RangeProofKind::BitDecomposition => Proof::BitDecomposition(Decodable::decode(d)?),
+            RangeProofKind::Bulletproof => Proof::Bulletproof(Box::new(Decodable::decode(d)?)),
+        })
+    }
+}
+
+#[test]
+fn test_range_versions() {
+    use crate::serial::{deserialize, serialize};
+
+    let params = Parameters::<OsRngInstance>::new(2);
+    let value_base = bls::G1Projective::from(params.hs[0]);
+
+    for kind in &[
+        RangeProofKind::BitDecomposition,
+        RangeProofKind::Bulletproof,
+    ] {
+        let blind = params.random_scalar();
+        let builder = Builder::new(*kind, &params, &value_base, &blind, 110).unwrap();
+
+        let mut transcript = Transcript::new(b"test");
+        builder.commitments().commit(&mut transcript);
+        let challenge = transcript.challenge_scalar(b"challenge");
+
+        let proof = builder.finish(&challenge);
+        let data = serialize(&proof);
+        assert_eq!(data[0], kind.version());
+
+        let proof = deserialize::<Proof>(&data).unwrap();
+        assert_eq!(proof.kind(), *kind);
+        assert_eq!(
+            proof.value_commit(),
+            params.g1 * blind + value_base * bls::Scalar::from(110)
+        );
+
+        let mut transcript2 = Transcript::new(b"test");
+        proof
+            .commitments(&params, &challenge, &value_base)
+            .commit(&mut transcript2);
+        assert_eq!(challenge, transcript2.challenge_scalar(b"challenge"));
+    }
+
+    // Unknown versions are rejected
+    assert!(deserialize::<Proof>(&[2u8]).is_err());
+}
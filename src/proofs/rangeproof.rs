@@ -9,8 +9,6 @@ use crate::proofs::proof::*;
 use crate::proofs::simple_or;
 use crate::serial::{Decodable, Encodable};
 
-const BIT_SIZE: usize = 64;
-
 fn summate<'a, I>(iter: I) -> bls::Scalar
 where
     I: Iterator<Item = &'a bls::Scalar>,
@@ -25,16 +23,27 @@ pub struct Builder<'a> {
 
 pub struct Commitments<'a> {
     commitments: Vec<Box<dyn ProofCommitments + 'a>>,
+    // False when the proof doesn't have the bit width we expect
+    is_valid: bool,
 }
 
+// One OR proof per bit, so the bit width is the length of the proof
 pub struct Proof {
     proofs: Vec<simple_or::Proof>,
     bit_commits: Vec<bls::G1Projective>,
 }
 
 impl<'a> Builder<'a> {
-    pub fn new<R: RngInstance>(params: &'a Parameters<R>, blind: &bls::Scalar, value: u64) -> Self {
-        let mut blind_parts = params.random_scalars(BIT_SIZE);
+    pub fn new<R: RngInstance>(
+        params: &'a Parameters<R>,
+        value_base: &bls::G1Projective,
+        blind: &bls::Scalar,
+        value: u64,
+    ) -> Result<Self> {
+        params.check_rangeproof_value(value)?;
+
+        let bit_size = params.rangeproof_bits;
+        let mut blind_parts = params.random_scalars(bit_size);
 
         //let sum = blinds.iter().skip(1).sum::<bls::Scalar>();
         let sum = summate(blind_parts.iter().skip(1));
@@ -42,10 +51,10 @@ impl<'a> Builder<'a> {
 
         assert_eq!(summate(blind_parts.iter()), *blind);
 
-        let mut bit_builders = Vec::with_capacity(BIT_SIZE);
-        let mut bit_commits = Vec::with_capacity(BIT_SIZE);
+        let mut bit_builders = Vec::with_capacity(bit_size);
+        let mut bit_commits = Vec::with_capacity(bit_size);
 
-        for i in 0..BIT_SIZE {
+        for i in 0..bit_size {
             let bit_2i = 1 << i;
             let bit_value = value & bit_2i;
             let index = if bit_value > 0 { 1 } else { 0 };
@@ -53,13 +62,13 @@ impl<'a> Builder<'a> {
             let bit_value_scalar = bls::Scalar::from(bit_value);
 
             // Either the second part is zero or non-zero
-            let bit_commit = params.g1 * blind_parts[i] + params.hs[0] * bit_value_scalar;
+            let bit_commit = params.g1 * blind_parts[i] + value_base * bit_value_scalar;
             if index == 0 {
                 assert_eq!(bit_commit, params.g1 * blind_parts[i]);
             }
             bit_commits.push(bit_commit.clone());
 
-            let commit_2i = bit_commit - params.hs[0] * bls::Scalar::from(bit_2i);
+            let commit_2i = bit_commit - value_base * bls::Scalar::from(bit_2i);
             let public_keys = vec![bit_commit, commit_2i];
 
             let builder = simple_or::Builder::new(params, public_keys, blind_parts[i], index);
@@ -70,13 +79,13 @@ impl<'a> Builder<'a> {
             bit_commits
                 .iter()
                 .fold(bls::G1Projective::identity(), |acc, item| acc + item),
-            params.g1 * blind + params.hs[0] * bls::Scalar::from(value)
+            params.g1 * blind + value_base * bls::Scalar::from(value)
         );
 
-        Self {
+        Ok(Self {
             bit_builders,
             bit_commits,
-        }
+        })
     }
 
     pub fn commitments(&self) -> Box<dyn ProofCommitments + 'a> {
@@ -86,6 +95,7 @@ impl<'a> Builder<'a> {
                 .iter()
                 .map(|builder| builder.commitments())
                 .collect(),
+            is_valid: true,
         })
     }
 
@@ -119,10 +129,11 @@ impl<'a> Encodable for Builder<'a> {
 }
 
 impl<'a> ProofCommitments for Commitments<'a> {
-    fn commit(&self, hasher: &mut HasherToScalar) {
+    fn commit(&self, transcript: &mut Transcript) {
         for commitment in &self.commitments {
-            commitment.commit(hasher);
+            commitment.commit(transcript);
         }
+        transcript.append_u64(b"is-valid", self.is_valid as u64);
     }
 }
 
@@ -131,21 +142,35 @@ impl Proof {
         &self,
         params: &'a Parameters<R>,
         challenge: &bls::Scalar,
+        value_base: &bls::G1Projective,
     ) -> Box<dyn ProofCommitments + 'a> {
+        let is_valid = self.is_well_formed(params);
+
         Box::new(Commitments {
             commitments: izip!(&self.proofs, &self.bit_commits)
                 .enumerate()
                 .map(|(i, (proof, bit_commit))| {
                     let bit_2i = 1 << i;
-                    let commit_2i = bit_commit - params.hs[0] * bls::Scalar::from(bit_2i);
+                    let commit_2i = bit_commit - value_base * bls::Scalar::from(bit_2i);
                     let public_keys = vec![bit_commit.clone(), commit_2i];
 
                     proof.commitments(params, challenge, &public_keys)
                 })
                 .collect(),
+            is_valid,
         })
     }
 
+    // The width must match our policy
+    pub fn is_well_formed<R: RngInstance>(&self, params: &Parameters<R>) -> bool {
+        self.proofs.len() == params.rangeproof_bits
+            && self.bit_commits.len() == params.rangeproof_bits
+    }
+
+    pub fn bit_size(&self) -> usize {
+        self.bit_commits.len()
+    }
+
     pub fn value_commit(&self) -> bls::G1Projective {
         self.bit_commits
             .iter()
@@ -175,50 +200,94 @@ fn test_rangeproof() {
 
     let blind = params.random_scalar();
     let value = 127832u64;
-    let value_commit = params.g1 * blind + params.hs[0] * bls::Scalar::from(value);
+    let value_base = bls::G1Projective::from(params.hs[0]);
+    let value_commit = params.g1 * blind + value_base * bls::Scalar::from(value);
 
-    let builder = Builder::new(&params, &blind, value);
+    let builder = Builder::new(&params, &value_base, &blind, value).unwrap();
     let commits = builder.commitments();
 
-    let mut hasher = HasherToScalar::new();
-    commits.commit(&mut hasher);
-    let challenge = hasher.finish();
+    let mut transcript = Transcript::new(b"test");
+    commits.commit(&mut transcript);
+    let challenge = transcript.challenge_scalar(b"challenge");
 
     let proof = builder.finish(&challenge);
 
     assert_eq!(proof.value_commit(), value_commit);
 
-    let commits2 = proof.commitments(&params, &challenge);
-    let mut hasher2 = HasherToScalar::new();
-    commits2.commit(&mut hasher2);
-    let challenge2 = hasher2.finish();
+    let commits2 = proof.commitments(&params, &challenge, &value_base);
+    let mut transcript2 = Transcript::new(b"test");
+    commits2.commit(&mut transcript2);
+    let challenge2 = transcript2.challenge_scalar(b"challenge");
     assert_eq!(challenge, challenge2);
 }
 
 #[test]
 fn test_rangeproof_fail() {
-    assert_eq!(BIT_SIZE, 64);
     let overflowed_value = bls::Scalar::from(u64::MAX) + bls::Scalar::one();
 
     let params = Parameters::<OsRngInstance>::new(2);
+    assert_eq!(params.rangeproof_bits, 64);
 
     let blind = params.random_scalar();
-    let value_commit = params.g1 * blind + params.hs[0] * overflowed_value;
+    let value_base = bls::G1Projective::from(params.hs[0]);
+    let value_commit = params.g1 * blind + value_base * overflowed_value;
 
-    let builder = Builder::new(&params, &blind, u64::MAX);
+    let builder = Builder::new(&params, &value_base, &blind, u64::MAX).unwrap();
     let commits = builder.commitments();
 
-    let mut hasher = HasherToScalar::new();
-    commits.commit(&mut hasher);
-    let challenge = hasher.finish();
+    let mut transcript = Transcript::new(b"test");
+    commits.commit(&mut transcript);
+    let challenge = transcript.challenge_scalar(b"challenge");
 
     let proof = builder.finish(&challenge);
 
     assert_ne!(proof.value_commit(), value_commit);
 
-    let commits2 = proof.commitments(&params, &challenge);
-    let mut hasher2 = HasherToScalar::new();
-    commits2.commit(&mut hasher2);
-    let challenge2 = hasher2.finish();
+    let commits2 = proof.commitments(&params, &challenge, &value_base);
+    let mut transcript2 = Transcript::new(b"test");
+    commits2.commit(&mut transcript2);
+    let challenge2 = transcript2.challenge_scalar(b"challenge");
     assert_eq!(challenge, challenge2);
 }
+
+#[test]
+fn test_rangeproof_bits() {
+    let params = Parameters::<OsRngInstance>::new(2)
+        .with_rangeproof_bits(16)
+        .unwrap();
+
+    let blind = params.random_scalar();
+    let value_base = bls::G1Projective::from(params.hs[0]);
+
+    let builder = Builder::new(&params, &value_base, &blind, 65535).unwrap();
+    let mut transcript = Transcript::new(b"test");
+    builder.commitments().commit(&mut transcript);
+    let challenge = transcript.challenge_scalar(b"challenge");
+
+    let proof = builder.finish(&challenge);
+    assert_eq!(proof.bit_size(), 16);
+    assert_eq!(
+        proof.value_commit(),
+        params.g1 * blind + value_base * bls::Scalar::from(65535)
+    );
+
+    let mut transcript2 = Transcript::new(b"test");
+    proof
+        .commitments(&params, &challenge, &value_base)
+        .commit(&mut transcript2);
+    assert_eq!(challenge, transcript2.challenge_scalar(b"challenge"));
+
+    // A verifier expecting 64 bit proofs rejects it
+    let params64 = Parameters::<OsRngInstance>::new(2);
+    let mut transcript3 = Transcript::new(b"test");
+    proof
+        .commitments(&params64, &challenge, &value_base)
+        .commit(&mut transcript3);
+    assert_ne!(challenge, transcript3.challenge_scalar(b"challenge"));
+
+    // Values past the width can't be proven
+    assert!(Builder::new(&params, &value_base, &blind, 65536).is_err());
+    assert!(Parameters::<OsRngInstance>::new(2)
+        .with_rangeproof_bits(12)
+        .is_err());
+}
@@ -4,7 +4,7 @@ use std::io;
 use std::rc::Rc;
 
 use crate::bls_extensions::*;
-use crate::coconut::coconut::EncryptedAttribute;
+use crate::coconut::coconut::{Attribute, EncryptedAttribute};
 use crate::elgamal::*;
 use crate::error::Result;
 use crate::parameters::*;
@@ -57,7 +57,8 @@ impl<'a, R: RngInstance> Builder<'a, R> {
         witness_keys: Vec<Rc<Witness>>,
         attribute_indexes: Vec<u64>,
     ) -> Self {
-        assert_eq!(params.hs.len(), witness_attributes.len());
+        // Public attributes are not witnesses
+        assert!(witness_attributes.len() <= params.hs.len());
 
         Self {
             params,
@@ -75,8 +76,8 @@ impl<'a, R: RngInstance> Builder<'a, R> {
         commitish: &'a bls::G1Projective,
         attribute_commit: &'a bls::G1Projective,
     ) -> Box<Commitments<'a, R>> {
-        assert_eq!(self.witness_attributes.len(), self.params.hs.len());
-        assert!(self.witness_attributes.len() >= self.witness_keys.len());
+        assert!(self.witness_attributes.len() <= self.params.hs.len());
+        assert_eq!(self.witness_attributes.len(), self.witness_keys.len());
 
         // w_o G_1 + sum(w_m H_i)
         let mut commit_attributes = self.params.g1 * self.witness_blind.get();
@@ -98,8 +99,7 @@ impl<'a, R: RngInstance> Builder<'a, R> {
                         // w_k_i G_1
                         self.params.g1 * witness_key.get(),
                         // w_m_i h + w_k_i Y
-                        commitish * witness_attribute.get()
-                            + gamma.public_key * witness_key.get(),
+                        commitish * witness_attribute.get() + gamma.public_key * witness_key.get(),
                     )
                 })
                 .collect(),
@@ -131,22 +131,22 @@ impl<'a, R: RngInstance> DecodableWithParams<'a, R> for Builder<'a, R> {
 }
 
 impl<'a, R: RngInstance> ProofCommitments for Commitments<'a, R> {
-    fn commit(&self, hasher: &mut HasherToScalar) {
+    fn commit(&self, transcript: &mut Transcript) {
         // Add base points we use
-        hasher.add_g1_affine(&self.params.g1);
-        hasher.add_g2_affine(&self.params.g2);
+        transcript.append_g1_affine(b"g1", &self.params.g1);
+        transcript.append_g2_affine(b"g2", &self.params.g2);
         for h in &self.params.hs {
-            hasher.add_g1_affine(h);
+            transcript.append_g1_affine(b"h", h);
         }
-        hasher.add_g1(&self.gamma.public_key);
-        hasher.add_g1(self.commitish);
-        hasher.add_g1(self.attribute_commit);
+        transcript.append_g1(b"gamma", &self.gamma.public_key);
+        transcript.append_g1(b"commitish", self.commitish);
+        transcript.append_g1(b"attribute-commit", self.attribute_commit);
 
-        hasher.add_g1(&self.commit_attributes);
+        transcript.append_g1(b"commit-attributes", &self.commit_attributes);
 
         for (commit_a, commit_b) in &self.commit_keys {
-            hasher.add_g1(&commit_a);
-            hasher.add_g1(&commit_b);
+            transcript.append_g1(b"commit-a", &commit_a);
+            transcript.append_g1(b"commit-b", &commit_b);
         }
     }
 }
@@ -161,9 +161,17 @@ impl Proof {
         attribute_commit: &'a bls::G1Projective,
         encrypted_attributes: &Vec<EncryptedAttribute>,
         attribute_indexes: &Vec<u64>,
+        public_attributes: &Vec<Attribute>,
     ) -> Box<Commitments<'a, R>> {
+        // Remove the public attributes from the commit
+        // c_m - sum(m_j H_j)
+        let mut private_commit = attribute_commit.clone();
+        for attribute in public_attributes {
+            private_commit -= params.hs[attribute.index as usize] * attribute.value;
+        }
+
         // c c_m + r_r G_1 + sum(r_m_i H_i)
-        let mut commit_attributes = attribute_commit * challenge + params.g1 * self.response_blind;
+        let mut commit_attributes = private_commit * challenge + params.g1 * self.response_blind;
         for (index, response) in izip!(attribute_indexes, &self.response_attributes) {
             commit_attributes += params.hs[*index as usize] * response;
         }
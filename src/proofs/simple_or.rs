@@ -1,12 +1,12 @@
 use bls12_381 as bls;
-use std::io;
+use std::{cmp, io};
 
 use crate::bls_extensions::*;
 use crate::error::Result;
 use crate::parameters::*;
 use crate::proofs::proof::*;
-use crate::serial::VarInt;
 use crate::serial::{Decodable, Encodable};
+use crate::serial::{VarInt, MAX_PREALLOCATION};
 
 pub struct Builder<'a> {
     base: &'a bls::G1Affine,
@@ -28,10 +28,10 @@ pub struct Proof {
 }
 
 fn hash_point(commit: &bls::G1Projective, index: u32) -> bls::Scalar {
-    let mut hasher = HasherToScalar::new();
-    hasher.add_g1(commit);
-    hasher.add_u32(index);
-    hasher.finish()
+    let mut transcript = Transcript::new(b"simple-or");
+    transcript.append_g1(b"commit", commit);
+    transcript.append_u64(b"index", index as u64);
+    transcript.challenge_scalar(b"challenge")
 }
 
 impl<'a> Builder<'a> {
@@ -66,13 +66,13 @@ impl<'a> Builder<'a> {
         })
     }
 
-    pub fn finish(self, challenge: &bls::Scalar) -> Proof {
+    pub fn finish(mut self, challenge: &bls::Scalar) -> Proof {
         let mut challenge = challenge.clone();
         for i in 0..self.secret_index {
             let commit = self.base * self.responses[i] + self.public_keys[i] * challenge;
             challenge = hash_point(&commit, (i + 1) as u32);
         }
-        let mut responses = self.responses;
+        let mut responses = std::mem::take(&mut self.responses);
         responses[self.secret_index] = self.witness - challenge * self.secret;
         assert_eq!(
             self.base * responses[self.secret_index]
@@ -94,6 +94,13 @@ impl<'a> Builder<'a> {
     }
 }
 
+impl<'a> Drop for Builder<'a> {
+    fn drop(&mut self) {
+        self.secret.wipe();
+        self.witness.wipe();
+    }
+}
+
 impl<'a> Encodable for Builder<'a> {
     fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
         let mut len = 0;
@@ -124,7 +131,7 @@ pub trait DecodableVec<'a>: Sized {
 impl<'a> DecodableVec<'a> for Vec<Builder<'a>> {
     fn decode<D: io::Read, R: RngInstance>(mut d: D, params: &'a Parameters<R>) -> Result<Self> {
         let len = VarInt::decode(&mut d)?.0;
-        let mut ret = Vec::with_capacity(len as usize);
+        let mut ret = Vec::with_capacity(cmp::min(len as usize, MAX_PREALLOCATION));
         for _ in 0..len {
             ret.push(Builder::decode(&mut d, params)?);
         }
@@ -133,9 +140,9 @@ impl<'a> DecodableVec<'a> for Vec<Builder<'a>> {
 }
 
 impl<'a> ProofCommitments for Commitments<'a> {
-    fn commit(&self, hasher: &mut HasherToScalar) {
-        hasher.add_g1_affine(self.base);
-        hasher.add_g1(&self.commit);
+    fn commit(&self, transcript: &mut Transcript) {
+        transcript.append_g1_affine(b"base", self.base);
+        transcript.append_g1(b"commit", &self.commit);
     }
 }
 
@@ -146,7 +153,13 @@ impl Proof {
         challenge: &bls::Scalar,
         public_keys: &Vec<bls::G1Projective>,
     ) -> Box<dyn ProofCommitments + 'a> {
-        assert_eq!(self.responses.len(), public_keys.len());
+        // Malformed proofs commit to the identity, which never matches
+        if self.responses.len() != public_keys.len() {
+            return Box::new(Commitments {
+                base: &params.g1,
+                commit: bls::G1Projective::identity(),
+            });
+        }
         let mut commit = bls::G1Projective::identity();
         let mut challenge = challenge.clone();
         for i in 0..public_keys.len() {
@@ -188,16 +201,16 @@ fn test_simple_or_basic() {
     let builder = Builder::new(&params, public_keys.clone(), secret, 1);
     let commits = builder.commitments();
 
-    let mut hasher = HasherToScalar::new();
-    commits.commit(&mut hasher);
-    let challenge = hasher.finish();
+    let mut transcript = Transcript::new(b"test");
+    commits.commit(&mut transcript);
+    let challenge = transcript.challenge_scalar(b"challenge");
 
     let proof = builder.finish(&challenge);
 
     let commits2 = proof.commitments(&params, &challenge, &public_keys);
-    let mut hasher2 = HasherToScalar::new();
-    commits2.commit(&mut hasher2);
-    let challenge2 = hasher2.finish();
+    let mut transcript2 = Transcript::new(b"test");
+    commits2.commit(&mut transcript2);
+    let challenge2 = transcript2.challenge_scalar(b"challenge");
     assert_eq!(challenge, challenge2);
 }
 
@@ -211,15 +224,15 @@ fn test_simple_or_zero_index() {
     let builder = Builder::new(&params, public_keys.clone(), secret, 0);
     let commits = builder.commitments();
 
-    let mut hasher = HasherToScalar::new();
-    commits.commit(&mut hasher);
-    let challenge = hasher.finish();
+    let mut transcript = Transcript::new(b"test");
+    commits.commit(&mut transcript);
+    let challenge = transcript.challenge_scalar(b"challenge");
 
     let proof = builder.finish(&challenge);
 
     let commits2 = proof.commitments(&params, &challenge, &public_keys);
-    let mut hasher2 = HasherToScalar::new();
-    commits2.commit(&mut hasher2);
-    let challenge2 = hasher2.finish();
+    let mut transcript2 = Transcript::new(b"test");
+    commits2.commit(&mut transcript2);
+    let challenge2 = transcript2.challenge_scalar(b"challenge");
     assert_eq!(challenge, challenge2);
 }
@@ -272,9 +272,9 @@ fn test_signature_request_proof() {
         proof_builder.commitments(&values.gamma, &values.commitish, &values.attribute_commit);
 
     // c = H(R || ...)
-    let mut hasher = HasherToScalar::new();
-    commitments.commit(&mut hasher);
-    let challenge = hasher.finish();
+    let mut transcript = Transcript::new(b"test");
+    commitments.commit(&mut transcript);
+    let challenge = transcript.challenge_scalar(b"challenge");
 
     // s = k + c x
     let response_blind = witness_blind.derive(&challenge);
@@ -305,12 +305,13 @@ fn test_signature_request_proof() {
         &values.attribute_commit,
         &values.ciphertext,
         &attribute_indexes,
+        &Vec::new(),
     );
 
     // c = H(R || ...)
-    let mut verify_hasher = HasherToScalar::new();
-    verify_commitments.commit(&mut verify_hasher);
-    let verify_challenge = verify_hasher.finish();
+    let mut verify_transcript = Transcript::new(b"test");
+    verify_commitments.commit(&mut verify_transcript);
+    let verify_challenge = verify_transcript.challenge_scalar(b"challenge");
     // c == c'
     assert_eq!(challenge, verify_challenge);
 }
@@ -347,9 +348,9 @@ fn test_credential_proof() {
     let commitments = proof_builder.commitments(&values.verify_key, &values.blind_commitish);
 
     // c = H(R || ...)
-    let mut hasher = HasherToScalar::new();
-    commitments.commit(&mut hasher);
-    let challenge = hasher.finish();
+    let mut transcript = Transcript::new(b"test");
+    commitments.commit(&mut transcript);
+    let challenge = transcript.challenge_scalar(b"challenge");
 
     // s = k + c x
     let response_attributes = witness_attributes
@@ -378,9 +379,9 @@ fn test_credential_proof() {
     );
 
     // c = H(R || ...)
-    let mut verify_hasher = HasherToScalar::new();
-    verify_commitments.commit(&mut verify_hasher);
-    let verify_challenge = verify_hasher.finish();
+    let mut verify_transcript = Transcript::new(b"test");
+    verify_commitments.commit(&mut verify_transcript);
+    let verify_challenge = verify_transcript.challenge_scalar(b"challenge");
     // c == c'
     assert_eq!(challenge, verify_challenge);
 }
@@ -0,0 +1,222 @@
+use crate::bls_extensions::*;
+use crate::coconut::coconut::*;
+use crate::error::{Error, Result};
+use crate::parameters::DEFAULT_ASSET;
+use crate::proofs::bulletproof::MAX_AGGREGATED_VALUES;
+use crate::schema::epoch::{Epoch, KeySet};
+use crate::schema::input::*;
+use crate::schema::output::*;
+use crate::schema::token::*;
+use crate::schema::transaction::*;
+use crate::schema::wallet::{select_coins_where, OwnedToken};
+
+// Spends owned tokens of one asset to pay some amounts and optionally
+// withdraw, with whatever is left over going to a change output.
+// Only tokens the keys can still spend are picked, each verified with
+// the key of its epoch.
+//
+//   let built = TransactionBuilder::new(&coconut, &keys)
+//       .with_payments(vec![30, 20])
+//       .with_withdraw(15)
+//       .build(&wallet.tokens)?;
+//   let spent = wallet.take_tokens(&built.inputs)?;
+pub struct TransactionBuilder<'a, R: RngInstance> {
+    coconut: &'a Coconut<R>,
+    keys: &'a KeySet,
+
+    asset: u64,
+    payments: Vec<u64>,
+    withdraw: u64,
+    // Outputs are signed for this key epoch
+    epoch: Epoch,
+    aggregate_outputs: bool,
+}
+
+pub struct BuiltTransaction {
+    pub transaction: Transaction,
+    // Indexes of the spent tokens in the set passed to build()
+    pub inputs: Vec<usize>,
+    // One for each output: the payments in order, then the change
+    pub output_secrets: Vec<TokenSecret>,
+    // Index of the change output, when there is any change
+    pub change: Option<usize>,
+}
+
+impl<'a, R: RngInstance> TransactionBuilder<'a, R> {
+    // Outputs are signed for the current epoch of the keys by default
+    pub fn new(coconut: &'a Coconut<R>, keys: &'a KeySet) -> Self {
+        Self {
+            coconut,
+            keys,
+            asset: DEFAULT_ASSET,
+            payments: Vec::new(),
+            withdraw: 0,
+            epoch: keys.current_epoch(),
+            aggregate_outputs: false,
+        }
+    }
+
+    pub fn with_asset(mut self, asset: u64) -> Self {
+        self.asset = asset;
+        self
+    }
+
+    pub fn with_payments(mut self, payments: Vec<u64>) -> Self {
+        self.payments = payments;
+        self
+    }
+
+    pub fn with_withdraw(mut self, withdraw: u64) -> Self {
+        self.withdraw = withdraw;
+        self
+    }
+
+    pub fn with_epoch(mut self, epoch: Epoch) -> Self {
+        self.epoch = epoch;
+        self
+    }
+
+    // Cover every output with one range proof instead of one each
+    pub fn with_aggregated_rangeproof(mut self) -> Self {
+        self.aggregate_outputs = true;
+        self
+    }
+
+    pub fn build(&self, tokens: &[OwnedToken]) -> Result<BuiltTransaction> {
+        let total = self
+            .payments
+            .iter()
+            .try_fold(self.withdraw, |total, value| total.checked_add(*value))
+            .ok_or(Error::ValueOverflow)?;
+        if total == 0 {
+            return Err(Error::EmptyTransaction);
+        }
+
+        let inputs = select_coins_where(tokens, self.asset, total, |owned| {
+            self.keys.is_spendable(owned.token.epoch)
+        })?;
+        // Can't overflow, select_coins_where() checks the running total
+        let selected: u64 = inputs.iter().map(|index| tokens[*index].secret.value).sum();
+
+        let mut output_secrets: Vec<_> = self
+            .payments
+            .iter()
+            .map(|value| TokenSecret::generate_asset(self.asset, *value, self.coconut))
+            .collect();
+        let change = if selected > total {
+            output_secrets.push(TokenSecret::generate_asset(
+                self.asset,
+                selected - total,
+                self.coconut,
+            ));
+            Some(output_secrets.len() - 1)
+        } else {
+            None
+        };
+        if self.aggregate_outputs && output_secrets.len() > MAX_AGGREGATED_VALUES {
+            return Err(Error::InvalidAggregationSize);
+        }
+
+        let mut tx = Transaction::new();
+        if self.withdraw > 0 {
+            tx.add_asset_withdraw(self.asset, self.withdraw);
+        }
+
+        let mut input_secrets = Vec::with_capacity(inputs.len());
+        let mut input_values = Vec::with_capacity(inputs.len());
+        for index in &inputs {
+            let owned = &tokens[*index];
+            let verify_key = self
+                .keys
+                .get(owned.token.epoch)
+                .ok_or(Error::UnknownEpoch)?;
+            let (input, input_secret) =
+                Input::new(self.coconut, verify_key, &owned.token, &owned.secret)?;
+            tx.add_input(input);
+            input_secrets.push(input_secret);
+            input_values.push(owned.secret.asset_value());
+        }
+
+        let mut secrets = Vec::with_capacity(output_secrets.len());
+        let mut output_values = Vec::with_capacity(output_secrets.len());
+        for token_secret in &output_secrets {
+            let (output, output_secret) =
+                Output::new_in_epoch(self.coconut, token_secret, self.epoch)?;
+            tx.add_output(output);
+            secrets.push(output_secret);
+            output_values.push(token_secret.asset_value());
+        }
+
+        let (input_blinds, output_blinds) =
+            tx.compute_pedersens(self.coconut, &input_values, &output_values)?;
+        for (input_secret, blind) in input_secrets.iter_mut().zip(input_blinds) {
+            input_secret.setup(blind)?;
+        }
+        for (output_secret, blind) in secrets.iter_mut().zip(output_blinds) {
+            if self.aggregate_outputs {
+                output_secret.setup_for_aggregation(blind);
+            } else {
+                output_secret.setup(blind)?;
+            }
+        }
+
+        self.prove(&mut tx, input_secrets, secrets)?;
+
+        Ok(BuiltTransaction {
+            transaction: tx,
+            inputs,
+            output_secrets,
+            change,
+        })
+    }
+
+    // Output challenges first since they are part of the transaction
+    // digest, then the transaction challenge over every proof.
+    fn prove(
+        &self,
+        tx: &mut Transaction,
+        input_secrets: Vec<InputSecret<'a, R>>,
+        output_secrets: Vec<OutputSecret<'a, R>>,
+    ) -> Result<()> {
+        let outputs_rangeproof_builder = if self.aggregate_outputs {
+            let output_secrets: Vec<_> = output_secrets.iter().collect();
+            Some(Transaction::outputs_rangeproof_builder(
+                self.coconut,
+                &output_secrets,
+            )?)
+        } else {
+            None
+        };
+
+        let output_hashes: Vec<_> = output_secrets
+            .iter()
+            .map(|output_secret| output_secret.proof_commits().hash())
+            .collect();
+        for (output, output_hash) in tx.outputs.iter_mut().zip(&output_hashes) {
+            output.challenge = Some(Transaction::output_challenge(output, output_hash));
+        }
+
+        let mut transcript = tx.transcript();
+        for input_secret in &input_secrets {
+            transcript.append_scalar(b"input", &input_secret.proof_commits().hash());
+        }
+        for output_hash in &output_hashes {
+            transcript.append_scalar(b"output", output_hash);
+        }
+        if let Some(builder) = &outputs_rangeproof_builder {
+            builder.commitments().commit(&mut transcript);
+        }
+        tx.challenge = transcript.challenge_scalar(b"challenge");
+
+        for (input, input_secret) in tx.inputs.iter_mut().zip(input_secrets) {
+            input.set_proof(input_secret.finish(&tx.challenge));
+        }
+        for (output, output_secret) in tx.outputs.iter_mut().zip(output_secrets) {
+            let challenge = output.challenge.unwrap();
+            output.set_proof(output_secret.finish(&challenge));
+        }
+        tx.outputs_rangeproof =
+            outputs_rangeproof_builder.map(|builder| builder.finish(&tx.challenge));
+        Ok(())
+    }
+}
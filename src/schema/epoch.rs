@@ -0,0 +1,99 @@
+use std::collections::BTreeMap;
+use std::io;
+
+use crate::coconut::coconut::VerifyKey;
+use crate::error::{Error, Result};
+use crate::serial::{Decodable, Encodable, VarInt};
+
+// Mints rotate their keys in epochs. Tokens remember the epoch they were
+// signed in so the right key is used to verify them when spent.
+pub type Epoch = u64;
+
+pub const DEFAULT_EPOCH: Epoch = 0;
+
+// Aggregated verify keys by epoch. New tokens are signed in the latest one.
+#[derive(Clone)]
+pub struct KeySet {
+    keys: BTreeMap<Epoch, VerifyKey>,
+}
+
+impl KeySet {
+    pub fn new(epoch: Epoch, verify_key: VerifyKey) -> Self {
+        let mut keys = BTreeMap::new();
+        keys.insert(epoch, verify_key);
+        Self { keys }
+    }
+
+    // Epochs only move forward
+    pub fn rotate(&mut self, epoch: Epoch, verify_key: VerifyKey) -> Result<()> {
+        if epoch <= self.current_epoch() {
+            return Err(Error::InvalidEpoch);
+        }
+        self.keys.insert(epoch, verify_key);
+        Ok(())
+    }
+
+    pub fn current_epoch(&self) -> Epoch {
+        // Never empty
+        *self.keys.keys().next_back().unwrap()
+    }
+
+    pub fn previous_epoch(&self) -> Option<Epoch> {
+        self.keys.keys().rev().nth(1).copied()
+    }
+
+    pub fn current(&self) -> &VerifyKey {
+        &self.keys[&self.current_epoch()]
+    }
+
+    pub fn get(&self, epoch: Epoch) -> Option<&VerifyKey> {
+        self.keys.get(&epoch)
+    }
+
+    // Tokens from the current and previous epochs can be spent normally.
+    // Anything older has to be migrated first.
+    pub fn is_spendable(&self, epoch: Epoch) -> bool {
+        epoch == self.current_epoch() || Some(epoch) == self.previous_epoch()
+    }
+
+    pub fn epochs(&self) -> Vec<Epoch> {
+        self.keys.keys().copied().collect()
+    }
+
+    // Forget keys from before the given epoch. The current key always stays.
+    pub fn prune_before(&mut self, epoch: Epoch) {
+        let epoch = std::cmp::min(epoch, self.current_epoch());
+        self.keys = self.keys.split_off(&epoch);
+    }
+}
+
+impl Encodable for KeySet {
+    fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
+        let mut len = VarInt(self.keys.len() as u64).encode(&mut s)?;
+        for (epoch, verify_key) in &self.keys {
+            len += epoch.encode(&mut s)?;
+            len += verify_key.encode(&mut s)?;
+        }
+        Ok(len)
+    }
+}
+
+impl Decodable for KeySet {
+    fn decode<D: io::Read>(mut d: D) -> Result<Self> {
+        let len = VarInt::decode(&mut d)?.0;
+        let mut keys = BTreeMap::new();
+        for _ in 0..len {
+            let epoch: Epoch = Decodable::decode(&mut d)?;
+            let verify_key = Decodable::decode(&mut d)?;
+            // Sorted and unique so there's only one encoding
+            if keys.keys().next_back().map_or(false, |last| *last >= epoch) {
+                return Err(Error::ParseFailed("key set epochs out of order"));
+            }
+            keys.insert(epoch, verify_key);
+        }
+        if keys.is_empty() {
+            return Err(Error::ParseFailed("empty key set"));
+        }
+        Ok(Self { keys })
+    }
+}
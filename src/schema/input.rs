@@ -1,4 +1,5 @@
 use bls12_381 as bls;
+use std::fmt;
 use std::io;
 use std::rc::Rc;
 
@@ -7,16 +8,22 @@ use crate::coconut::coconut::*;
 use crate::error::{Error, Result};
 use crate::parameters::*;
 use crate::pedersen::*;
+use crate::proofs::asset_proof;
 use crate::proofs::credential_proof;
 use crate::proofs::ownership_proof;
 use crate::proofs::pedersen_proof;
 use crate::proofs::proof::*;
-use crate::proofs::rangeproof;
+use crate::proofs::range;
+use crate::schema::epoch::Epoch;
 use crate::schema::token::*;
 use crate::serial::{Decodable, DecodableWithParams, Encodable};
 
 pub struct Input {
     pub pedersen: PedersenCommit,
+    // Blinded value generator of the asset: H_a + s G1
+    pub asset_tag: bls::G1Projective,
+    // Commit to the asset attribute: a H + u G1
+    pub asset_commit: bls::G1Projective,
     pub request: InputRequest,
     pub proofs: Option<InputProofs>,
 }
@@ -24,6 +31,9 @@ pub struct Input {
 pub struct InputRequest {
     pub burn_value: bls::G1Projective,
     pub credential: Credential,
+    pub public_attributes: Vec<Attribute>,
+    // Picks the key the credential is verified with
+    pub epoch: Epoch,
 }
 
 pub struct InputSecret<'a, R: RngInstance> {
@@ -31,18 +41,24 @@ pub struct InputSecret<'a, R: RngInstance> {
 
     pub value: u64,
     pedersen_blind: Option<bls::Scalar>,
+    asset_tag: bls::G1Projective,
+    asset_tag_blind: bls::Scalar,
 
     credential_proof_builder: credential_proof::Builder<'a, R>,
     verify_key: &'a VerifyKey,
     credential_blind_commitish: CommitHash,
 
     serial_proof_builder: ownership_proof::Builder<'a, R>,
+    asset_proof_builder: asset_proof::Builder<'a, R>,
+    asset_opening_builder: pedersen_proof::Builder<'a, R>,
     pedersen_proof_builder: Option<pedersen_proof::Builder<'a, R>>,
-    rangeproof_builder: Option<rangeproof::Builder<'a>>,
+    rangeproof_builder: Option<range::Builder<'a, R>>,
 
     // Witnesses
     witness_serial: Rc<Witness>,
     witness_value: Rc<Witness>,
+    witness_asset: Rc<Witness>,
+    witness_asset_blind: Rc<Witness>,
     witness_credential_blind: Rc<Witness>,
     witness_pedersen_blind: Option<Rc<Witness>>,
 }
@@ -50,6 +66,8 @@ pub struct InputSecret<'a, R: RngInstance> {
 pub struct InputProofCommits<'a> {
     credential: Box<dyn ProofCommitments + 'a>,
     serial: Box<dyn ProofCommitments + 'a>,
+    asset: Box<dyn ProofCommitments + 'a>,
+    asset_opening: Box<dyn ProofCommitments + 'a>,
     pedersen: Box<dyn ProofCommitments + 'a>,
     rangeproof: Box<dyn ProofCommitments + 'a>,
 }
@@ -57,9 +75,12 @@ pub struct InputProofCommits<'a> {
 pub struct InputProofs {
     response_serial: bls::Scalar,
     response_value: bls::Scalar,
+    response_asset: bls::Scalar,
+    response_asset_blind: bls::Scalar,
     response_credential_blind: bls::Scalar,
     response_pedersen_blind: bls::Scalar,
-    pub rangeproof: rangeproof::Proof,
+    pub asset_proof: asset_proof::Proof,
+    pub rangeproof: range::Proof,
 }
 
 impl Input {
@@ -68,28 +89,38 @@ impl Input {
         verify_key: &'a VerifyKey,
         token: &Token,
         token_secret: &TokenSecret,
-    ) -> (Self, InputSecret<'a, R>) {
+    ) -> Result<(Self, InputSecret<'a, R>)> {
+        token_secret.check(&coconut.params)?;
+        let token_signature = match &token.signature {
+            Some(signature) => signature,
+            None => return Err(Error::MissingSignature),
+        };
+
         let burn_value = coconut.params.g1 * token_secret.serial;
 
-        let private_attributes = vec![
-            Attribute::new(token_secret.serial, 0),
-            Attribute::new(bls::Scalar::from(token_secret.value), 1),
-        ];
+        let private_attributes = token_secret.private_attributes();
+        let public_attributes = token_secret.public_attributes();
 
-        let token_signature = &token.signature.as_ref().unwrap();
         let (credential, credential_proof_values) =
-            coconut.make_credential(verify_key, token_signature, &private_attributes);
+            coconut.make_credential(verify_key, token_signature, &private_attributes)?;
 
-        assert!(credential.verify(&coconut.params, verify_key, &Vec::new()));
+        if !credential.verify(&coconut.params, verify_key, &public_attributes) {
+            return Err(Error::InvalidCredential);
+        }
 
         let witness_serial = Rc::new(Witness::new(&coconut.params, private_attributes[0].value));
         let witness_value = Rc::new(Witness::new(&coconut.params, private_attributes[1].value));
+        let witness_asset = Rc::new(Witness::new(&coconut.params, private_attributes[2].value));
 
-        let witness_attributes = vec![witness_serial.clone(), witness_value.clone()];
+        let witness_attributes = vec![
+            witness_serial.clone(),
+            witness_value.clone(),
+            witness_asset.clone(),
+        ];
         let witness_credential_blind =
             Rc::new(Witness::new(&coconut.params, credential_proof_values.blind));
 
-        let attribute_indexes = vec![0, 1];
+        let attribute_indexes = vec![0, 1, 2];
 
         let credential_proof_builder = credential_proof::Builder::new(
             &coconut.params,
@@ -103,12 +134,39 @@ impl Input {
         let serial_proof_builder =
             ownership_proof::Builder::new(&coconut.params, witness_serial.clone());
 
-        (
+        // Hide which asset this input holds
+        let asset_tag_blind = coconut.params.random_scalar();
+        let witness_asset_blind = Rc::new(Witness::new(
+            &coconut.params,
+            coconut.params.random_scalar(),
+        ));
+        let asset_proof_builder = asset_proof::Builder::new(
+            &coconut.params,
+            token_secret.asset,
+            asset_tag_blind,
+            *witness_asset_blind.secret(),
+        )?;
+        let asset_tag = asset_proof_builder.asset_tag();
+        let asset_commit = asset_proof_builder.asset_commit();
+
+        // The asset commit opens to the same asset as the credential
+        let asset_opening_builder = pedersen_proof::Builder::new(
+            &coconut.params,
+            coconut.params.hs[asset_proof::ASSET_ATTRIBUTE_INDEX].into(),
+            witness_asset_blind.clone(),
+            witness_asset.clone(),
+        );
+
+        Ok((
             Self {
                 pedersen: PedersenCommit::identity(),
+                asset_tag,
+                asset_commit,
                 request: InputRequest {
                     burn_value,
                     credential,
+                    public_attributes,
+                    epoch: token.epoch,
                 },
                 proofs: None,
             },
@@ -117,21 +175,27 @@ impl Input {
 
                 value: token_secret.value,
                 pedersen_blind: None,
+                asset_tag,
+                asset_tag_blind,
 
                 credential_proof_builder,
                 credential_blind_commitish,
                 verify_key,
 
                 serial_proof_builder,
+                asset_proof_builder,
+                asset_opening_builder,
                 pedersen_proof_builder: None,
                 rangeproof_builder: None,
 
                 witness_serial,
                 witness_value,
+                witness_asset,
+                witness_asset_blind,
                 witness_credential_blind,
                 witness_pedersen_blind: None,
             },
-        )
+        ))
     }
 
     pub fn set_proof(&mut self, proofs: InputProofs) {
@@ -144,11 +208,20 @@ impl Input {
     }
 }
 
-impl Encodable for Input {
-    fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
+impl Input {
+    // Everything but the proofs
+    pub(crate) fn encode_body<S: io::Write>(&self, mut s: S) -> Result<usize> {
         let mut len = 0;
         len += self.pedersen.encode(&mut s)?;
-        len += self.request.encode(&mut s)?;
+        len += self.asset_tag.encode(&mut s)?;
+        len += self.asset_commit.encode(&mut s)?;
+        Ok(len + self.request.encode(s)?)
+    }
+}
+
+impl Encodable for Input {
+    fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
+        let mut len = self.encode_body(&mut s)?;
         match &self.proofs {
             None => {
                 len += 0u8.encode(s)?;
@@ -165,10 +238,14 @@ impl Encodable for Input {
 impl Decodable for Input {
     fn decode<D: io::Read>(mut d: D) -> Result<Self> {
         let pedersen: PedersenCommit = Decodable::decode(&mut d)?;
+        let asset_tag: bls::G1Projective = Decodable::decode(&mut d)?;
+        let asset_commit: bls::G1Projective = Decodable::decode(&mut d)?;
         let request: InputRequest = Decodable::decode(&mut d)?;
         let option: u8 = Decodable::decode(&mut d)?;
         Ok(Self {
             pedersen,
+            asset_tag,
+            asset_commit,
             request,
             proofs: match option {
                 0u8 => None,
@@ -181,8 +258,11 @@ impl Decodable for Input {
 
 impl Encodable for InputRequest {
     fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
-        let len = self.burn_value.encode(&mut s)?;
-        Ok(len + self.credential.encode(s)?)
+        let mut len = 0;
+        len += self.burn_value.encode(&mut s)?;
+        len += self.credential.encode(&mut s)?;
+        len += self.public_attributes.encode(&mut s)?;
+        Ok(len + self.epoch.encode(s)?)
     }
 }
 
@@ -190,13 +270,27 @@ impl Decodable for InputRequest {
     fn decode<D: io::Read>(mut d: D) -> Result<Self> {
         Ok(Self {
             burn_value: Decodable::decode(&mut d)?,
-            credential: Decodable::decode(d)?,
+            credential: Decodable::decode(&mut d)?,
+            public_attributes: Decodable::decode(&mut d)?,
+            epoch: Decodable::decode(d)?,
         })
     }
 }
 
 impl<'a, R: RngInstance> InputSecret<'a, R> {
-    pub fn setup(&mut self, blind: bls::Scalar) {
+    // The blind is for the value committed with the unblinded asset generator:
+    //   v H_a + b G1 = v (H_a + s G1) + (b - v s) G1
+    pub fn setup(&mut self, blind: bls::Scalar) -> Result<()> {
+        self.setup_with_rangeproof(blind, range::DEFAULT_RANGEPROOF_KIND)
+    }
+
+    pub fn setup_with_rangeproof(
+        &mut self,
+        blind: bls::Scalar,
+        kind: range::RangeProofKind,
+    ) -> Result<()> {
+        self.params.check_rangeproof_value(self.value)?;
+        let blind = blind - bls::Scalar::from(self.value) * self.asset_tag_blind;
         self.pedersen_blind = Some(blind);
 
         self.witness_pedersen_blind = Some(Rc::new(Witness::new(
@@ -216,15 +310,19 @@ impl<'a, R: RngInstance> InputSecret<'a, R> {
 
         self.pedersen_proof_builder = Some(pedersen_proof::Builder::new(
             self.params,
+            self.asset_tag,
             self.witness_pedersen_blind.as_ref().unwrap().clone(),
             self.witness_value.clone(),
         ));
 
-        self.rangeproof_builder = Some(rangeproof::Builder::new(
+        self.rangeproof_builder = Some(range::Builder::new(
+            kind,
             self.params,
+            &self.asset_tag,
             &self.pedersen_blind.unwrap(),
             self.value,
-        ));
+        )?);
+        Ok(())
     }
 
     pub fn proof_commits(&'a self) -> InputProofCommits<'a> {
@@ -236,18 +334,27 @@ impl<'a, R: RngInstance> InputSecret<'a, R> {
                 .credential_proof_builder
                 .commitments(self.verify_key, &self.credential_blind_commitish),
             serial: self.serial_proof_builder.commitments(),
+            asset: self.asset_proof_builder.commitments(),
+            asset_opening: self.asset_opening_builder.commitments(),
             pedersen: self.pedersen_proof_builder.as_ref().unwrap().commitments(),
             rangeproof: self.rangeproof_builder.as_ref().unwrap().commitments(),
         }
     }
 
-    pub fn finish(self, challenge: &bls::Scalar) -> InputProofs {
+    pub fn finish(mut self, challenge: &bls::Scalar) -> InputProofs {
         InputProofs {
             response_serial: self.witness_serial.derive(challenge),
             response_value: self.witness_value.derive(challenge),
+            response_asset: self.witness_asset.derive(challenge),
+            response_asset_blind: self.witness_asset_blind.derive(challenge),
             response_credential_blind: self.witness_credential_blind.derive(challenge),
-            response_pedersen_blind: self.witness_pedersen_blind.unwrap().derive(challenge),
-            rangeproof: self.rangeproof_builder.unwrap().finish(challenge),
+            response_pedersen_blind: self
+                .witness_pedersen_blind
+                .as_ref()
+                .unwrap()
+                .derive(challenge),
+            asset_proof: self.asset_proof_builder.finish(challenge),
+            rangeproof: self.rangeproof_builder.take().unwrap().finish(challenge),
         }
     }
 
@@ -264,10 +371,14 @@ impl<'a, R: RngInstance> InputSecret<'a, R> {
                 1u8 => Some(Decodable::decode(&mut d)?),
                 _ => return Err(Error::ParseFailed("wrong option byte for input")),
             },
+            asset_tag: Decodable::decode(&mut d)?,
+            asset_tag_blind: Decodable::decode(&mut d)?,
             credential_proof_builder: DecodableWithParams::decode(&mut d, params)?,
             verify_key,
             credential_blind_commitish: Decodable::decode(&mut d)?,
             serial_proof_builder: DecodableWithParams::decode(&mut d, params)?,
+            asset_proof_builder: DecodableWithParams::decode(&mut d, params)?,
+            asset_opening_builder: DecodableWithParams::decode(&mut d, params)?,
             pedersen_proof_builder: match Decodable::decode(&mut d)? {
                 0u8 => None,
                 1u8 => Some(DecodableWithParams::decode(&mut d, params)?),
@@ -275,11 +386,13 @@ impl<'a, R: RngInstance> InputSecret<'a, R> {
             },
             rangeproof_builder: match Decodable::decode(&mut d)? {
                 0u8 => None,
-                1u8 => Some(rangeproof::Builder::decode(&mut d, params)?),
+                1u8 => Some(range::Builder::decode(&mut d, params)?),
                 _ => return Err(Error::ParseFailed("wrong option byte for input")),
             },
             witness_serial: Decodable::decode(&mut d)?,
             witness_value: Decodable::decode(&mut d)?,
+            witness_asset: Decodable::decode(&mut d)?,
+            witness_asset_blind: Decodable::decode(&mut d)?,
             witness_credential_blind: Decodable::decode(&mut d)?,
             witness_pedersen_blind: match Decodable::decode(&mut d)? {
                 0u8 => None,
@@ -290,6 +403,24 @@ impl<'a, R: RngInstance> InputSecret<'a, R> {
     }
 }
 
+// The witnesses wipe themselves once the last builder holding them goes
+impl<'a, R: RngInstance> Drop for InputSecret<'a, R> {
+    fn drop(&mut self) {
+        self.value.wipe();
+        self.pedersen_blind.wipe();
+        self.asset_tag_blind.wipe();
+    }
+}
+
+impl<'a, R: RngInstance> fmt::Debug for InputSecret<'a, R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("InputSecret")
+            .field("value", &Redacted)
+            .field("asset_tag", &self.asset_tag)
+            .finish()
+    }
+}
+
 impl<'a, R: RngInstance> Encodable for InputSecret<'a, R> {
     fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
         let mut len = 0;
@@ -301,9 +432,13 @@ impl<'a, R: RngInstance> Encodable for InputSecret<'a, R> {
                 len += blind.encode(&mut s)?;
             }
         }
+        len += self.asset_tag.encode(&mut s)?;
+        len += self.asset_tag_blind.encode(&mut s)?;
         len += self.credential_proof_builder.encode(&mut s)?;
         len += self.credential_blind_commitish.encode(&mut s)?;
         len += self.serial_proof_builder.encode(&mut s)?;
+        len += self.asset_proof_builder.encode(&mut s)?;
+        len += self.asset_opening_builder.encode(&mut s)?;
         match &self.pedersen_proof_builder {
             None => len += 0u8.encode(&mut s)?,
             Some(builder) => {
@@ -320,6 +455,8 @@ impl<'a, R: RngInstance> Encodable for InputSecret<'a, R> {
         }
         len += self.witness_serial.encode(&mut s)?;
         len += self.witness_value.encode(&mut s)?;
+        len += self.witness_asset.encode(&mut s)?;
+        len += self.witness_asset_blind.encode(&mut s)?;
         len += self.witness_credential_blind.encode(&mut s)?;
         match &self.witness_pedersen_blind {
             None => len += 0u8.encode(&mut s)?,
@@ -333,17 +470,19 @@ impl<'a, R: RngInstance> Encodable for InputSecret<'a, R> {
 }
 
 impl<'a> InputProofCommits<'a> {
-    pub fn commit(&self, hasher: &mut HasherToScalar) {
-        self.credential.commit(hasher);
-        self.serial.commit(hasher);
-        self.pedersen.commit(hasher);
-        self.rangeproof.commit(hasher);
+    pub fn commit(&self, transcript: &mut Transcript) {
+        self.credential.commit(transcript);
+        self.serial.commit(transcript);
+        self.asset.commit(transcript);
+        self.asset_opening.commit(transcript);
+        self.pedersen.commit(transcript);
+        self.rangeproof.commit(transcript);
     }
 
     pub fn hash(&self) -> ProofHash {
-        let mut hasher = HasherToScalar::new();
-        self.commit(&mut hasher);
-        hasher.finish()
+        let mut transcript = Transcript::new(b"input");
+        self.commit(&mut transcript);
+        transcript.challenge_scalar(b"hash")
     }
 }
 
@@ -357,11 +496,18 @@ impl InputProofs {
         credential: &'a Credential,
         // Serial proof
         serial_public: &bls::G1Projective,
+        // Asset proof
+        asset_tag: &bls::G1Projective,
+        asset_commit: &bls::G1Projective,
         // Pedersen proof
         pedersen: &PedersenCommit,
     ) -> InputProofCommits<'a> {
         let credential_proof = credential_proof::Proof {
-            response_attributes: vec![self.response_serial.clone(), self.response_value.clone()],
+            response_attributes: vec![
+                self.response_serial.clone(),
+                self.response_value.clone(),
+                self.response_asset.clone(),
+            ],
             response_blind: self.response_credential_blind.clone(),
         };
 
@@ -369,12 +515,18 @@ impl InputProofs {
             response: self.response_serial.clone(),
         };
 
+        let asset_opening_proof = pedersen_proof::Proof {
+            response_blind: self.response_asset_blind.clone(),
+            response_value: self.response_asset.clone(),
+        };
+
         let pedersen_proof = pedersen_proof::Proof {
             response_blind: self.response_pedersen_blind.clone(),
             response_value: self.response_value.clone(),
         };
 
-        let attribute_indexes = vec![0, 1];
+        let attribute_indexes = vec![0, 1, 2];
+        let asset_base: bls::G1Projective = params.hs[asset_proof::ASSET_ATTRIBUTE_INDEX].into();
 
         //println!("proof.commits(): pedersen = {:?}", pedersen);
         InputProofCommits {
@@ -388,8 +540,17 @@ impl InputProofs {
                 &attribute_indexes,
             ),
             serial: serial_proof.commitments(params, challenge, serial_public),
-            pedersen: pedersen_proof.commitments(params, challenge, pedersen),
-            rangeproof: self.rangeproof.commitments(params, challenge),
+            asset: self
+                .asset_proof
+                .commitments(params, challenge, asset_tag, asset_commit),
+            asset_opening: asset_opening_proof.commitments(
+                params,
+                challenge,
+                &asset_base,
+                asset_commit,
+            ),
+            pedersen: pedersen_proof.commitments(params, challenge, asset_tag, pedersen),
+            rangeproof: self.rangeproof.commitments(params, challenge, asset_tag),
         }
     }
 }
@@ -399,8 +560,11 @@ impl Encodable for InputProofs {
         let mut len = 0;
         len += self.response_serial.encode(&mut s)?;
         len += self.response_value.encode(&mut s)?;
+        len += self.response_asset.encode(&mut s)?;
+        len += self.response_asset_blind.encode(&mut s)?;
         len += self.response_credential_blind.encode(&mut s)?;
         len += self.response_pedersen_blind.encode(&mut s)?;
+        len += self.asset_proof.encode(&mut s)?;
         Ok(len + self.rangeproof.encode(s)?)
     }
 }
@@ -410,8 +574,11 @@ impl Decodable for InputProofs {
         Ok(Self {
             response_serial: Decodable::decode(&mut d)?,
             response_value: Decodable::decode(&mut d)?,
+            response_asset: Decodable::decode(&mut d)?,
+            response_asset_blind: Decodable::decode(&mut d)?,
             response_credential_blind: Decodable::decode(&mut d)?,
             response_pedersen_blind: Decodable::decode(&mut d)?,
+            asset_proof: Decodable::decode(&mut d)?,
             rangeproof: Decodable::decode(d)?,
         })
     }
@@ -1,10 +1,24 @@
+pub mod builder;
+pub mod epoch;
 pub mod input;
 pub mod output;
+pub mod registry;
 pub mod service;
+pub mod spent;
 pub mod tests;
 pub mod token;
 pub mod transaction;
+pub mod verify;
+pub mod wallet;
 
+pub use crate::schema::builder::{BuiltTransaction, TransactionBuilder};
+pub use crate::schema::epoch::{Epoch, KeySet, DEFAULT_EPOCH};
 pub use crate::schema::input::{Input, InputProofs, InputSecret};
 pub use crate::schema::output::{Output, OutputProofs, OutputSecret, OutputSignature};
-pub use crate::schema::transaction::Transaction;
+pub use crate::schema::registry::{burn_slab, slab_burn, SpentRegistry, TransactionDigest};
+pub use crate::schema::spent::{FileSpentStore, MemorySpentStore, SpentStore};
+pub use crate::schema::transaction::{
+    BalanceProof, Transaction, TransactionKind, TransactionSecret, TRANSACTION_VERSION,
+};
+pub use crate::schema::verify::{Component, TransactionPart, VerifyFailure, VerifyReport};
+pub use crate::schema::wallet::{OwnedToken, PendingOutput, Wallet, WALLET_VERSION};
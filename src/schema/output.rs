@@ -1,4 +1,5 @@
 use bls12_381 as bls;
+use std::fmt;
 use std::io;
 use std::rc::Rc;
 
@@ -8,15 +9,24 @@ use crate::elgamal::*;
 use crate::error::{Error, Result};
 use crate::parameters::*;
 use crate::pedersen::*;
+use crate::proofs::asset_proof;
 use crate::proofs::pedersen_proof;
 use crate::proofs::proof::*;
-use crate::proofs::rangeproof;
+use crate::proofs::range;
 use crate::proofs::signature_proof;
+use crate::schema::epoch::{Epoch, DEFAULT_EPOCH};
 use crate::schema::token::*;
 use crate::serial::{Decodable, DecodableWithParams, Encodable};
 
 pub struct Output {
     pub pedersen: PedersenCommit,
+    // The same value under the common range generator: v B + r G1
+    // Range proofs are made on this commit.
+    pub range_commit: bls::G1Projective,
+    // Blinded value generator of the asset: H_a + s G1
+    pub asset_tag: bls::G1Projective,
+    // Commit to the asset attribute: a H + u G1
+    pub asset_commit: bls::G1Projective,
     pub request: OutputRequest,
     pub proofs: Option<OutputProofs>,
     pub challenge: Option<bls::Scalar>,
@@ -25,7 +35,9 @@ pub struct Output {
 pub struct OutputRequest {
     pub sign_request: BlindSignatureRequest,
     pub gamma: ElGamalPublicKey,
-    //public_attributes: Vec<bls::Scalar>,
+    pub public_attributes: Vec<Attribute>,
+    // Services only sign for their current epoch
+    pub epoch: Epoch,
 }
 
 pub struct OutputSignature {
@@ -54,67 +66,97 @@ pub struct OutputSecret<'a, R: RngInstance> {
 
     pub value: u64,
     pedersen_blind: Option<bls::Scalar>,
+    asset_tag: bls::G1Projective,
+    asset_tag_blind: bls::Scalar,
+    range_blind: bls::Scalar,
 
     signature_proof_builder: signature_proof::Builder<'a, R>,
     gamma: ElGamalPublicKey,
     commitish: bls::G1Projective,
     attribute_commit: bls::G1Projective,
 
+    asset_proof_builder: asset_proof::Builder<'a, R>,
+    asset_opening_builder: pedersen_proof::Builder<'a, R>,
+    range_opening_builder: pedersen_proof::Builder<'a, R>,
     pedersen_proof_builder: Option<pedersen_proof::Builder<'a, R>>,
-    rangeproof_builder: Option<rangeproof::Builder<'a>>,
+    rangeproof_builder: Option<range::Builder<'a, R>>,
 
     // Witnesses
     witness_signature_blind: Rc<Witness>,
     witness_serial: Rc<Witness>,
     witness_value: Rc<Witness>,
+    witness_asset: Rc<Witness>,
+    witness_asset_blind: Rc<Witness>,
+    witness_range_blind: Rc<Witness>,
     witness_keys: Vec<Rc<Witness>>,
     witness_pedersen_blind: Option<Rc<Witness>>,
 }
 
 pub struct OutputProofCommits<'a> {
     signature: Box<dyn ProofCommitments + 'a>,
+    asset: Box<dyn ProofCommitments + 'a>,
+    asset_opening: Box<dyn ProofCommitments + 'a>,
+    range_opening: Box<dyn ProofCommitments + 'a>,
     pedersen: Box<dyn ProofCommitments + 'a>,
-    rangeproof: Box<dyn ProofCommitments + 'a>,
+    rangeproof: Option<Box<dyn ProofCommitments + 'a>>,
 }
 
 pub struct OutputProofs {
     response_signature_blind: bls::Scalar,
     response_serial: bls::Scalar,
     response_value: bls::Scalar,
+    response_asset: bls::Scalar,
+    response_asset_blind: bls::Scalar,
+    response_range_blind: bls::Scalar,
     response_keys: Vec<bls::Scalar>,
     response_pedersen_blind: bls::Scalar,
-    pub rangeproof: rangeproof::Proof,
+    pub asset_proof: asset_proof::Proof,
+    // None when the transaction range proof covers this output
+    pub rangeproof: Option<range::Proof>,
 }
 
 impl Output {
     pub fn new<'a, R: RngInstance>(
         coconut: &'a Coconut<R>,
         token_secret: &TokenSecret,
-    ) -> (Self, OutputSecret<'a, R>) {
-        let private_attributes = vec![
-            Attribute::new(token_secret.serial, 0),
-            Attribute::new(bls::Scalar::from(token_secret.value), 1),
-        ];
+    ) -> Result<(Self, OutputSecret<'a, R>)> {
+        Self::new_in_epoch(coconut, token_secret, DEFAULT_EPOCH)
+    }
+
+    pub fn new_in_epoch<'a, R: RngInstance>(
+        coconut: &'a Coconut<R>,
+        token_secret: &TokenSecret,
+        epoch: Epoch,
+    ) -> Result<(Self, OutputSecret<'a, R>)> {
+        token_secret.check(&coconut.params)?;
+
+        let private_attributes = token_secret.private_attributes();
+        let public_attributes = token_secret.public_attributes();
 
         let gamma = token_secret.private_key.to_public(&coconut.params);
 
         let (sign_request, sign_proof_values) =
-            coconut.make_blind_sign_request(&gamma, &private_attributes, &Vec::new());
+            coconut.make_blind_sign_request(&gamma, &private_attributes, &public_attributes)?;
 
         let witness_serial = Rc::new(Witness::new(&coconut.params, private_attributes[0].value));
         let witness_value = Rc::new(Witness::new(&coconut.params, private_attributes[1].value));
+        let witness_asset = Rc::new(Witness::new(&coconut.params, private_attributes[2].value));
 
         let witness_signature_blind = Rc::new(Witness::new(
             &coconut.params,
             sign_proof_values.blinding_factor.clone(),
         ));
-        let witness_attributes = vec![witness_serial.clone(), witness_value.clone()];
+        let witness_attributes = vec![
+            witness_serial.clone(),
+            witness_value.clone(),
+            witness_asset.clone(),
+        ];
         let witness_keys: Vec<_> = sign_proof_values
             .attribute_keys
             .iter()
             .map(|key| Rc::new(Witness::new(&coconut.params, key.clone())))
             .collect();
-        let attribute_indexes = vec![0, 1];
+        let attribute_indexes = vec![0, 1, 2];
 
         let signature_proof_builder = signature_proof::Builder::new(
             &coconut.params,
@@ -127,12 +169,58 @@ impl Output {
         let commitish = sign_proof_values.commitish;
         let attribute_commit = sign_request.attribute_commit.clone();
 
-        (
+        // Hide which asset this output holds
+        let asset_tag_blind = coconut.params.random_scalar();
+        let witness_asset_blind = Rc::new(Witness::new(
+            &coconut.params,
+            coconut.params.random_scalar(),
+        ));
+        let asset_proof_builder = asset_proof::Builder::new(
+            &coconut.params,
+            token_secret.asset,
+            asset_tag_blind,
+            *witness_asset_blind.secret(),
+        )?;
+        let asset_tag = asset_proof_builder.asset_tag();
+        let asset_commit = asset_proof_builder.asset_commit();
+
+        // The asset commit opens to the same asset that is signed
+        let asset_opening_builder = pedersen_proof::Builder::new(
+            &coconut.params,
+            coconut.params.hs[asset_proof::ASSET_ATTRIBUTE_INDEX].into(),
+            witness_asset_blind.clone(),
+            witness_asset.clone(),
+        );
+
+        let range_blind = coconut.params.random_scalar();
+        let witness_range_blind = Rc::new(Witness::new(&coconut.params, range_blind));
+        let range_base = bls::G1Projective::from(coconut.params.range_base);
+        let range_commit = compute_pedersen_with_u64(
+            &coconut.params,
+            &range_base,
+            &range_blind,
+            token_secret.value,
+        );
+
+        // The range commit holds the same value that is signed
+        let range_opening_builder = pedersen_proof::Builder::new(
+            &coconut.params,
+            range_base,
+            witness_range_blind.clone(),
+            witness_value.clone(),
+        );
+
+        Ok((
             Self {
                 pedersen: PedersenCommit::identity(),
+                range_commit,
+                asset_tag,
+                asset_commit,
                 request: OutputRequest {
                     sign_request,
                     gamma: gamma.clone(),
+                    public_attributes,
+                    epoch,
                 },
                 proofs: None,
                 challenge: None,
@@ -144,34 +232,46 @@ impl Output {
 
                 value: token_secret.value,
                 pedersen_blind: None,
+                asset_tag,
+                asset_tag_blind,
+                range_blind,
 
                 signature_proof_builder,
                 gamma,
                 commitish,
                 attribute_commit,
 
+                asset_proof_builder,
+                asset_opening_builder,
+                range_opening_builder,
                 pedersen_proof_builder: None,
                 rangeproof_builder: None,
 
                 witness_signature_blind,
                 witness_serial,
                 witness_value,
+                witness_asset,
+                witness_asset_blind,
+                witness_range_blind,
                 witness_keys,
                 witness_pedersen_blind: None,
             },
-        )
+        ))
     }
 
     pub fn set_proof(&mut self, proofs: OutputProofs) {
         self.proofs = Some(proofs);
     }
 
+    // Shares that don't verify against their service's verify key are
+    // dropped, and their service indexes returned alongside the token.
     pub fn unblind<R: RngInstance>(
         &self,
         coconut: &Coconut<R>,
+        verify_keys: &Vec<VerifyKey>,
         token_secret: &TokenSecret,
         signatures: Vec<OutputSignature>,
-    ) -> Token {
+    ) -> Result<(Token, Vec<u64>)> {
         let shares: Vec<_> = signatures
             .iter()
             .map(|signature| {
@@ -182,16 +282,49 @@ impl Output {
             })
             .collect();
 
-        let (indexes, shares): (Vec<_>, Vec<_>) = shares.into_iter().unzip();
+        let attributes: Vec<_> = token_secret
+            .private_attributes()
+            .into_iter()
+            .chain(self.request.public_attributes.iter().cloned())
+            .collect();
+
+        let commitish = self.request.get_hash();
+        let (sigma, rejected) =
+            coconut.aggregate_verified(verify_keys, &commitish, shares, &attributes)?;
 
-        let signature = Signature {
-            commitish: self.request.get_hash(),
-            sigma: coconut.aggregate(&shares, indexes),
+        let token = Token {
+            signature: Some(Signature { commitish, sigma }),
+            epoch: self.request.epoch,
         };
+        Ok((token, rejected))
+    }
+}
+
+impl Output {
+    // Everything but the proofs. The challenge is included since the
+    // output proofs answer it rather than the transaction challenge.
+    pub(crate) fn encode_body<S: io::Write>(&self, mut s: S) -> Result<usize> {
+        let len = self.encode_statement(&mut s)?;
+        Ok(len + self.encode_challenge(s)?)
+    }
+
+    // What the output proofs are about, which their challenge is derived from
+    pub(crate) fn encode_statement<S: io::Write>(&self, mut s: S) -> Result<usize> {
+        let mut len = 0;
+        len += self.pedersen.encode(&mut s)?;
+        len += self.range_commit.encode(&mut s)?;
+        len += self.asset_tag.encode(&mut s)?;
+        len += self.asset_commit.encode(&mut s)?;
+        Ok(len + self.request.encode(s)?)
+    }
 
-        //partial_token.signature = Some(signature);
-        Token {
-            signature: Some(signature),
+    fn encode_challenge<S: io::Write>(&self, mut s: S) -> Result<usize> {
+        match &self.challenge {
+            None => 0u8.encode(s),
+            Some(challenge) => {
+                let len = 1u8.encode(&mut s)?;
+                Ok(len + challenge.encode(s)?)
+            }
         }
     }
 }
@@ -200,6 +333,9 @@ impl Encodable for Output {
     fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
         let mut len = 0;
         len += self.pedersen.encode(&mut s)?;
+        len += self.range_commit.encode(&mut s)?;
+        len += self.asset_tag.encode(&mut s)?;
+        len += self.asset_commit.encode(&mut s)?;
         len += self.request.encode(&mut s)?;
         match &self.proofs {
             None => {
@@ -210,16 +346,7 @@ impl Encodable for Output {
                 len += proofs.encode(&mut s)?;
             }
         }
-        match &self.challenge {
-            None => {
-                len += 0u8.encode(s)?;
-            }
-            Some(challenge) => {
-                len += 1u8.encode(&mut s)?;
-                len += challenge.encode(s)?;
-            }
-        }
-        Ok(len)
+        Ok(len + self.encode_challenge(s)?)
     }
 }
 
@@ -227,6 +354,9 @@ impl Decodable for Output {
     fn decode<D: io::Read>(mut d: D) -> Result<Self> {
         Ok(Self {
             pedersen: Decodable::decode(&mut d)?,
+            range_commit: Decodable::decode(&mut d)?,
+            asset_tag: Decodable::decode(&mut d)?,
+            asset_commit: Decodable::decode(&mut d)?,
             request: Decodable::decode(&mut d)?,
             proofs: match Decodable::decode(&mut d)? {
                 0u8 => None,
@@ -250,8 +380,11 @@ impl OutputRequest {
 
 impl Encodable for OutputRequest {
     fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
-        let len = self.sign_request.encode(&mut s)?;
-        Ok(len + self.gamma.encode(s)?)
+        let mut len = 0;
+        len += self.sign_request.encode(&mut s)?;
+        len += self.gamma.encode(&mut s)?;
+        len += self.public_attributes.encode(&mut s)?;
+        Ok(len + self.epoch.encode(s)?)
     }
 }
 
@@ -259,13 +392,42 @@ impl Decodable for OutputRequest {
     fn decode<D: io::Read>(mut d: D) -> Result<Self> {
         Ok(Self {
             sign_request: Decodable::decode(&mut d)?,
-            gamma: Decodable::decode(d)?,
+            gamma: Decodable::decode(&mut d)?,
+            public_attributes: Decodable::decode(&mut d)?,
+            epoch: Decodable::decode(d)?,
         })
     }
 }
 
 impl<'a, R: RngInstance> OutputSecret<'a, R> {
-    pub fn setup(&mut self, blind: bls::Scalar) {
+    // The blind is for the value committed with the unblinded asset generator:
+    //   v H_a + b G1 = v (H_a + s G1) + (b - v s) G1
+    pub fn setup(&mut self, blind: bls::Scalar) -> Result<()> {
+        self.setup_with_rangeproof(blind, range::DEFAULT_RANGEPROOF_KIND)
+    }
+
+    pub fn setup_with_rangeproof(
+        &mut self,
+        blind: bls::Scalar,
+        kind: range::RangeProofKind,
+    ) -> Result<()> {
+        self.params.check_rangeproof_value(self.value)?;
+        self.setup_for_aggregation(blind);
+
+        let range_base = bls::G1Projective::from(self.params.range_base);
+        self.rangeproof_builder = Some(range::Builder::new(
+            kind,
+            self.params,
+            &range_base,
+            &self.range_blind,
+            self.value,
+        )?);
+        Ok(())
+    }
+
+    // Leaves the range proof to Transaction::outputs_rangeproof_builder()
+    pub fn setup_for_aggregation(&mut self, blind: bls::Scalar) {
+        let blind = blind - bls::Scalar::from(self.value) * self.asset_tag_blind;
         self.pedersen_blind = Some(blind);
 
         self.witness_pedersen_blind = Some(Rc::new(Witness::new(
@@ -275,20 +437,19 @@ impl<'a, R: RngInstance> OutputSecret<'a, R> {
 
         self.pedersen_proof_builder = Some(pedersen_proof::Builder::new(
             self.params,
+            self.asset_tag,
             self.witness_pedersen_blind.as_ref().unwrap().clone(),
             self.witness_value.clone(),
         ));
+        self.rangeproof_builder = None;
+    }
 
-        self.rangeproof_builder = Some(rangeproof::Builder::new(
-            self.params,
-            &self.pedersen_blind.unwrap(),
-            self.value,
-        ));
+    pub fn range_blind(&self) -> bls::Scalar {
+        self.range_blind
     }
 
     pub fn proof_commits(&'a self) -> OutputProofCommits<'a> {
         assert!(self.pedersen_proof_builder.is_some());
-        assert!(self.rangeproof_builder.is_some());
 
         OutputProofCommits {
             signature: self.signature_proof_builder.commitments(
@@ -296,27 +457,63 @@ impl<'a, R: RngInstance> OutputSecret<'a, R> {
                 &self.commitish,
                 &self.attribute_commit,
             ),
+            asset: self.asset_proof_builder.commitments(),
+            asset_opening: self.asset_opening_builder.commitments(),
+            range_opening: self.range_opening_builder.commitments(),
             pedersen: self.pedersen_proof_builder.as_ref().unwrap().commitments(),
-            rangeproof: self.rangeproof_builder.as_ref().unwrap().commitments(),
+            rangeproof: self
+                .rangeproof_builder
+                .as_ref()
+                .map(|builder| builder.commitments()),
         }
     }
 
-    pub fn finish(self, challenge: &bls::Scalar) -> OutputProofs {
+    pub fn finish(mut self, challenge: &bls::Scalar) -> OutputProofs {
         OutputProofs {
             response_signature_blind: self.witness_signature_blind.derive(challenge),
             response_serial: self.witness_serial.derive(challenge),
             response_value: self.witness_value.derive(challenge),
+            response_asset: self.witness_asset.derive(challenge),
+            response_asset_blind: self.witness_asset_blind.derive(challenge),
+            response_range_blind: self.witness_range_blind.derive(challenge),
             response_keys: self
                 .witness_keys
                 .iter()
                 .map(|witness| witness.derive(challenge))
                 .collect(),
-            response_pedersen_blind: self.witness_pedersen_blind.unwrap().derive(challenge),
-            rangeproof: self.rangeproof_builder.unwrap().finish(challenge),
+            response_pedersen_blind: self
+                .witness_pedersen_blind
+                .as_ref()
+                .unwrap()
+                .derive(challenge),
+            asset_proof: self.asset_proof_builder.finish(challenge),
+            rangeproof: self
+                .rangeproof_builder
+                .take()
+                .map(|builder| builder.finish(challenge)),
         }
     }
 }
 
+// The witnesses wipe themselves once the last builder holding them goes
+impl<'a, R: RngInstance> Drop for OutputSecret<'a, R> {
+    fn drop(&mut self) {
+        self.value.wipe();
+        self.pedersen_blind.wipe();
+        self.asset_tag_blind.wipe();
+        self.range_blind.wipe();
+    }
+}
+
+impl<'a, R: RngInstance> fmt::Debug for OutputSecret<'a, R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("OutputSecret")
+            .field("value", &Redacted)
+            .field("asset_tag", &self.asset_tag)
+            .finish()
+    }
+}
+
 impl<'a, R: RngInstance> Encodable for OutputSecret<'a, R> {
     fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
         let mut len = 0;
@@ -328,10 +525,16 @@ impl<'a, R: RngInstance> Encodable for OutputSecret<'a, R> {
                 len += blind.encode(&mut s)?;
             }
         }
+        len += self.asset_tag.encode(&mut s)?;
+        len += self.asset_tag_blind.encode(&mut s)?;
+        len += self.range_blind.encode(&mut s)?;
         len += self.signature_proof_builder.encode(&mut s)?;
         len += self.gamma.encode(&mut s)?;
         len += self.commitish.encode(&mut s)?;
         len += self.attribute_commit.encode(&mut s)?;
+        len += self.asset_proof_builder.encode(&mut s)?;
+        len += self.asset_opening_builder.encode(&mut s)?;
+        len += self.range_opening_builder.encode(&mut s)?;
         match &self.pedersen_proof_builder {
             None => len += 0u8.encode(&mut s)?,
             Some(builder) => {
@@ -349,6 +552,9 @@ impl<'a, R: RngInstance> Encodable for OutputSecret<'a, R> {
         len += self.witness_signature_blind.encode(&mut s)?;
         len += self.witness_serial.encode(&mut s)?;
         len += self.witness_value.encode(&mut s)?;
+        len += self.witness_asset.encode(&mut s)?;
+        len += self.witness_asset_blind.encode(&mut s)?;
+        len += self.witness_range_blind.encode(&mut s)?;
         len += self.witness_keys.encode(&mut s)?;
         match &self.witness_pedersen_blind {
             None => len += 0u8.encode(&mut s)?,
@@ -371,10 +577,16 @@ impl<'a, R: RngInstance> DecodableWithParams<'a, R> for OutputSecret<'a, R> {
                 1u8 => Some(Decodable::decode(&mut d)?),
                 _ => return Err(Error::ParseFailed("wrong option byte for input")),
             },
+            asset_tag: Decodable::decode(&mut d)?,
+            asset_tag_blind: Decodable::decode(&mut d)?,
+            range_blind: Decodable::decode(&mut d)?,
             signature_proof_builder: DecodableWithParams::decode(&mut d, params)?,
             gamma: Decodable::decode(&mut d)?,
             commitish: Decodable::decode(&mut d)?,
             attribute_commit: Decodable::decode(&mut d)?,
+            asset_proof_builder: DecodableWithParams::decode(&mut d, params)?,
+            asset_opening_builder: DecodableWithParams::decode(&mut d, params)?,
+            range_opening_builder: DecodableWithParams::decode(&mut d, params)?,
             pedersen_proof_builder: match Decodable::decode(&mut d)? {
                 0u8 => None,
                 1u8 => Some(DecodableWithParams::decode(&mut d, params)?),
@@ -382,12 +594,15 @@ impl<'a, R: RngInstance> DecodableWithParams<'a, R> for OutputSecret<'a, R> {
             },
             rangeproof_builder: match Decodable::decode(&mut d)? {
                 0u8 => None,
-                1u8 => Some(rangeproof::Builder::decode(&mut d, params)?),
+                1u8 => Some(range::Builder::decode(&mut d, params)?),
                 _ => return Err(Error::ParseFailed("wrong option byte for input")),
             },
             witness_signature_blind: Decodable::decode(&mut d)?,
             witness_serial: Decodable::decode(&mut d)?,
             witness_value: Decodable::decode(&mut d)?,
+            witness_asset: Decodable::decode(&mut d)?,
+            witness_asset_blind: Decodable::decode(&mut d)?,
+            witness_range_blind: Decodable::decode(&mut d)?,
             witness_keys: Decodable::decode(&mut d)?,
             witness_pedersen_blind: match Decodable::decode(&mut d)? {
                 0u8 => None,
@@ -399,16 +614,21 @@ impl<'a, R: RngInstance> DecodableWithParams<'a, R> for OutputSecret<'a, R> {
 }
 
 impl<'a> OutputProofCommits<'a> {
-    pub fn commit(&self, hasher: &mut HasherToScalar) {
-        self.signature.commit(hasher);
-        self.pedersen.commit(hasher);
-        self.rangeproof.commit(hasher);
+    pub fn commit(&self, transcript: &mut Transcript) {
+        self.signature.commit(transcript);
+        self.asset.commit(transcript);
+        self.asset_opening.commit(transcript);
+        self.range_opening.commit(transcript);
+        self.pedersen.commit(transcript);
+        if let Some(rangeproof) = &self.rangeproof {
+            rangeproof.commit(transcript);
+        }
     }
 
     pub fn hash(&self) -> ProofHash {
-        let mut hasher = HasherToScalar::new();
-        self.commit(&mut hasher);
-        hasher.finish()
+        let mut transcript = Transcript::new(b"output");
+        self.commit(&mut transcript);
+        transcript.challenge_scalar(b"hash")
     }
 }
 
@@ -433,21 +653,42 @@ impl OutputProofs {
         commitish: &'a bls::G1Projective,
         attribute_commit: &'a bls::G1Projective,
         encrypted_attributes: &'a Vec<EncryptedAttribute>,
+        public_attributes: &Vec<Attribute>,
+        // Asset proof
+        asset_tag: &bls::G1Projective,
+        asset_commit: &bls::G1Projective,
         // Pedersen proof
         pedersen: &PedersenCommit,
+        range_commit: &bls::G1Projective,
     ) -> OutputProofCommits<'a> {
         let signature_proof = signature_proof::Proof {
             response_blind: self.response_signature_blind.clone(),
-            response_attributes: vec![self.response_serial.clone(), self.response_value.clone()],
+            response_attributes: vec![
+                self.response_serial.clone(),
+                self.response_value.clone(),
+                self.response_asset.clone(),
+            ],
             response_keys: self.response_keys.clone(),
         };
 
+        let asset_opening_proof = pedersen_proof::Proof {
+            response_blind: self.response_asset_blind.clone(),
+            response_value: self.response_asset.clone(),
+        };
+
+        let range_opening_proof = pedersen_proof::Proof {
+            response_blind: self.response_range_blind.clone(),
+            response_value: self.response_value.clone(),
+        };
+
         let pedersen_proof = pedersen_proof::Proof {
             response_blind: self.response_pedersen_blind.clone(),
             response_value: self.response_value.clone(),
         };
 
-        let attribute_indexes = vec![0, 1];
+        let attribute_indexes = vec![0, 1, 2];
+        let asset_base: bls::G1Projective = params.hs[asset_proof::ASSET_ATTRIBUTE_INDEX].into();
+        let range_base: bls::G1Projective = params.range_base.into();
 
         OutputProofCommits {
             signature: signature_proof.commitments(
@@ -458,9 +699,28 @@ impl OutputProofs {
                 attribute_commit,
                 encrypted_attributes,
                 &attribute_indexes,
+                public_attributes,
+            ),
+            asset: self
+                .asset_proof
+                .commitments(params, challenge, asset_tag, asset_commit),
+            asset_opening: asset_opening_proof.commitments(
+                params,
+                challenge,
+                &asset_base,
+                asset_commit,
+            ),
+            range_opening: range_opening_proof.commitments(
+                params,
+                challenge,
+                &range_base,
+                range_commit,
             ),
-            pedersen: pedersen_proof.commitments(params, challenge, pedersen),
-            rangeproof: self.rangeproof.commitments(params, challenge),
+            pedersen: pedersen_proof.commitments(params, challenge, asset_tag, pedersen),
+            rangeproof: self
+                .rangeproof
+                .as_ref()
+                .map(|rangeproof| rangeproof.commitments(params, challenge, &range_base)),
         }
     }
 }
@@ -471,9 +731,20 @@ impl Encodable for OutputProofs {
         len += self.response_signature_blind.encode(&mut s)?;
         len += self.response_serial.encode(&mut s)?;
         len += self.response_value.encode(&mut s)?;
+        len += self.response_asset.encode(&mut s)?;
+        len += self.response_asset_blind.encode(&mut s)?;
+        len += self.response_range_blind.encode(&mut s)?;
         len += self.response_keys.encode(&mut s)?;
         len += self.response_pedersen_blind.encode(&mut s)?;
-        Ok(len + self.rangeproof.encode(s)?)
+        len += self.asset_proof.encode(&mut s)?;
+        match &self.rangeproof {
+            None => len += 0u8.encode(s)?,
+            Some(rangeproof) => {
+                len += 1u8.encode(&mut s)?;
+                len += rangeproof.encode(s)?;
+            }
+        }
+        Ok(len)
     }
 }
 
@@ -483,9 +754,17 @@ impl Decodable for OutputProofs {
             response_signature_blind: Decodable::decode(&mut d)?,
             response_serial: Decodable::decode(&mut d)?,
             response_value: Decodable::decode(&mut d)?,
+            response_asset: Decodable::decode(&mut d)?,
+            response_asset_blind: Decodable::decode(&mut d)?,
+            response_range_blind: Decodable::decode(&mut d)?,
             response_keys: Decodable::decode(&mut d)?,
             response_pedersen_blind: Decodable::decode(&mut d)?,
-            rangeproof: Decodable::decode(d)?,
+            asset_proof: Decodable::decode(&mut d)?,
+            rangeproof: match Decodable::decode(&mut d)? {
+                0u8 => None,
+                1u8 => Some(Decodable::decode(d)?),
+                _ => return Err(Error::ParseFailed("wrong option byte for output proofs")),
+            },
         })
     }
 }
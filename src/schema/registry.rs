@@ -0,0 +1,164 @@
+use bls12_381 as bls;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::error::{Error, Result};
+use crate::schema::spent::{spent_key, SpentKey, SpentStore};
+use crate::slab::Slab;
+use crate::stealth::ScanCode;
+
+// Double-spend registry of one mint, kept in agreement with the other
+// mints through the slab log.
+//
+// Before signing a transaction a mint reserves its burns by putting a
+// claim for each in the log, naming the transaction digest. Every mint
+// applies the claims in log order and the first claim for a burn wins.
+// A mint only signs once all the burns of the transaction are claimed
+// for it, so two groups of mints can't both sign a double spend even if
+// they see it at the same time.
+
+// Nobody derives this scancode from a shared secret, so wallets
+// scanning the log never mistake a burn for a payment.
+pub const BURN_SCANCODE: ScanCode = [0xff; 4];
+
+pub type TransactionDigest = [u8; 32];
+
+pub fn burn_slab(burn_value: &bls::G1Projective, digest: &TransactionDigest) -> Slab {
+    Slab {
+        ephem_public: bls::G1Affine::from(burn_value),
+        scancode: BURN_SCANCODE,
+        ciphertext: digest.to_vec(),
+    }
+}
+
+pub fn slab_burn(slab: &Slab) -> Option<(bls::G1Projective, TransactionDigest)> {
+    if slab.scancode != BURN_SCANCODE || slab.ciphertext.len() != 32 {
+        return None;
+    }
+    let mut digest = [0u8; 32];
+    digest.copy_from_slice(&slab.ciphertext);
+    Some((bls::G1Projective::from(slab.ephem_public), digest))
+}
+
+struct RegistryState {
+    // Every burn claimed in the log
+    store: Box<dyn SpentStore>,
+    // Which transaction won each burn. Only known for claims applied
+    // since startup, older burns in the store count as spent elsewhere.
+    claims: HashMap<SpentKey, TransactionDigest>,
+    // Burns this mint reserved and is waiting to see in the log
+    reserved: HashMap<SpentKey, TransactionDigest>,
+    // Claims made here but not yet put in the slab log
+    unpublished: Vec<Slab>,
+}
+
+impl RegistryState {
+    // Err when a burn went to another transaction
+    fn claimed_for(
+        &self,
+        burn_value: &bls::G1Projective,
+        digest: &TransactionDigest,
+    ) -> Result<bool> {
+        match self.claims.get(&spent_key(burn_value)) {
+            Some(claim) if claim == digest => Ok(true),
+            Some(_) => Err(Error::TokenAlreadySpent),
+            None if self.store.contains(burn_value)? => Err(Error::TokenAlreadySpent),
+            None => Ok(false),
+        }
+    }
+}
+
+// Clones share the same state, so the slab listener and the
+// mint's signing service can each hold one.
+#[derive(Clone)]
+pub struct SpentRegistry {
+    state: Arc<Mutex<RegistryState>>,
+}
+
+impl SpentRegistry {
+    pub fn new(store: Box<dyn SpentStore>) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(RegistryState {
+                store,
+                claims: HashMap::new(),
+                reserved: HashMap::new(),
+                unpublished: Vec::new(),
+            })),
+        }
+    }
+
+    // Queues claims for the burns of a transaction. Reserving the same
+    // transaction again does nothing, but a burn reserved or claimed
+    // for another transaction is already spent.
+    pub fn reserve(
+        &self,
+        burn_values: &[bls::G1Projective],
+        digest: &TransactionDigest,
+    ) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        for burn_value in burn_values {
+            state.claimed_for(burn_value, digest)?;
+            match state.reserved.get(&spent_key(burn_value)) {
+                Some(reserved) if reserved != digest => return Err(Error::TokenAlreadySpent),
+                _ => {}
+            }
+        }
+
+        for burn_value in burn_values {
+            let key = spent_key(burn_value);
+            if state.reserved.contains_key(&key) || state.claims.contains_key(&key) {
+                continue;
+            }
+            state.reserved.insert(key, *digest);
+            state.unpublished.push(burn_slab(burn_value, digest));
+        }
+        Ok(())
+    }
+
+    // True once every burn is claimed in the log for this transaction
+    pub fn is_confirmed(
+        &self,
+        burn_values: &[bls::G1Projective],
+        digest: &TransactionDigest,
+    ) -> Result<bool> {
+        let state = self.state.lock().unwrap();
+        let mut is_confirmed = true;
+        for burn_value in burn_values {
+            is_confirmed &= state.claimed_for(burn_value, digest)?;
+        }
+        Ok(is_confirmed)
+    }
+
+    // Slabs must be applied in log order, including our own claims.
+    // Returns true if the slab was the first claim for a burn.
+    pub fn apply_slab(&self, slab: &Slab) -> Result<bool> {
+        let (burn_value, digest) = match slab_burn(slab) {
+            Some(claim) => claim,
+            None => return Ok(false),
+        };
+
+        let mut state = self.state.lock().unwrap();
+        let key = spent_key(&burn_value);
+        state.reserved.remove(&key);
+        if state.store.contains(&burn_value)? {
+            return Ok(false);
+        }
+        state.store.insert(&burn_value)?;
+        state.claims.insert(key, digest);
+        Ok(true)
+    }
+
+    // Claims for the slab log
+    pub fn take_unpublished(&self) -> Vec<Slab> {
+        let mut state = self.state.lock().unwrap();
+        state.unpublished.drain(..).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().store.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
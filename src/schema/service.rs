@@ -1,29 +1,40 @@
-use bls12_381 as bls;
-
 use crate::bls_extensions::*;
 use crate::coconut::coconut::*;
 use crate::error;
-use crate::schema::input::*;
+use crate::schema::epoch::{Epoch, KeySet, DEFAULT_EPOCH};
 use crate::schema::output::*;
+use crate::schema::registry::SpentRegistry;
+use crate::schema::spent::{MemorySpentStore, SpentStore};
 use crate::schema::transaction::*;
 
-type SpentBurns = Vec<bls::G1Projective>;
-
-pub fn generate_keys(attributes: u32, threshold: u32, total: u32) -> (Vec<SecretKey>, VerifyKey) {
-    let coconut = Coconut::<OsRngInstance>::new(attributes, threshold, total);
+pub fn generate_keys(
+    attributes: u32,
+    threshold: u32,
+    total: u32,
+) -> Result<(Vec<SecretKey>, VerifyKey), error::Error> {
+    let coconut = Coconut::<OsRngInstance>::new(attributes, threshold, total)?;
 
     let (secret_keys, verify_keys) = coconut.multiparty_keygen();
-    let verify_key = coconut.aggregate_keys(&verify_keys);
+    let verify_key = coconut.aggregate_keys(&verify_keys)?;
 
-    (secret_keys, verify_key)
+    Ok((secret_keys, verify_key))
+}
+
+// Where a service keeps the tokens it has seen burned
+enum Burns {
+    // This service alone decides. Inputs are burned once it signs.
+    Local(Box<dyn SpentStore>),
+    // Burns are agreed with the other mints in the slab log before signing
+    Shared(SpentRegistry),
 }
 
 pub struct SigningService<'a, R: RngInstance> {
     coconut: &'a Coconut<R>,
+    // Share of the key for the current epoch
     secret: SecretKey,
-    verify_key: VerifyKey,
+    keys: KeySet,
     pub index: u64,
-    spent: SpentBurns,
+    spent: Burns,
 }
 
 impl<'a, R: RngInstance> SigningService<'a, R> {
@@ -33,136 +44,154 @@ impl<'a, R: RngInstance> SigningService<'a, R> {
         verify_key: VerifyKey,
         index: u64,
     ) -> Self {
-        Self {
+        Self::with_spent_store(
             coconut,
             secret,
             verify_key,
             index,
-            spent: SpentBurns::new(),
-        }
+            Box::new(MemorySpentStore::new()),
+        )
     }
 
-    pub fn process(
-        &mut self,
-        transaction: &Transaction,
-    ) -> Result<Vec<OutputSignature>, error::Error> {
-        if !transaction.check(self.coconut) {
-            return Err(error::Error::TransactionPedersenCheckFailed);
-        }
-
-        let mut hasher = HasherToScalar::new();
-
-        for input in &transaction.inputs {
-            self.process_input(input, &transaction.challenge, &mut hasher)?;
-        }
+    pub fn with_spent_store(
+        coconut: &'a Coconut<R>,
+        secret: SecretKey,
+        verify_key: VerifyKey,
+        index: u64,
+        spent: Box<dyn SpentStore>,
+    ) -> Self {
+        Self::with_keyset(
+            coconut,
+            secret,
+            KeySet::new(DEFAULT_EPOCH, verify_key),
+            index,
+            spent,
+        )
+    }
 
-        let mut output_signatures = Vec::with_capacity(transaction.outputs.len());
-        for output in &transaction.outputs {
-            if output.challenge.is_none() {
-                return Err(error::Error::InvalidCredential);
-            }
-            let signature = self.process_output(output, &output.challenge.unwrap(), &mut hasher)?;
-            output_signatures.push(signature);
+    // The secret is this service's share of the keyset's current key
+    pub fn with_keyset(
+        coconut: &'a Coconut<R>,
+        secret: SecretKey,
+        keys: KeySet,
+        index: u64,
+        spent: Box<dyn SpentStore>,
+    ) -> Self {
+        Self {
+            coconut,
+            secret,
+            keys,
+            index,
+            spent: Burns::Local(spent),
         }
+    }
 
-        let challenge2 = hasher.finish();
-        if transaction.challenge != challenge2 {
-            return Err(error::Error::ProofsFailed);
+    // Each mint needs its own registry. The slabs from the log must be
+    // applied to it and its unpublished claims put in the log.
+    pub fn with_registry(
+        coconut: &'a Coconut<R>,
+        secret: SecretKey,
+        keys: KeySet,
+        index: u64,
+        registry: SpentRegistry,
+    ) -> Self {
+        Self {
+            coconut,
+            secret,
+            keys,
+            index,
+            spent: Burns::Shared(registry),
         }
-
-        Ok(output_signatures)
     }
 
-    fn process_input(
+    // Switch to a new key. Tokens from the epoch before stay spendable.
+    pub fn rotate(
         &mut self,
-        input: &Input,
-        challenge: &bls::Scalar,
-        hasher: &mut HasherToScalar,
+        epoch: Epoch,
+        secret: SecretKey,
+        verify_key: VerifyKey,
     ) -> Result<(), error::Error> {
-        if self.spent.contains(&input.request.burn_value) {
-            return Err(error::Error::TokenAlreadySpent);
-        }
+        self.keys.rotate(epoch, verify_key)?;
+        self.secret = secret;
+        Ok(())
+    }
 
-        if !input.request.credential.verify(
-            &self.coconut.params,
-            &self.verify_key,
-            &Vec::new(),
-            //vec![burn_commits],
-        ) {
-            return Err(error::Error::InputTokenVerifyFailed);
+    pub fn epoch(&self) -> Epoch {
+        self.keys.current_epoch()
+    }
+
+    pub fn keyset(&self) -> &KeySet {
+        &self.keys
+    }
+
+    // This service's own verify key, used by wallets to check its signature shares
+    pub fn share_verify_key(&self) -> VerifyKey {
+        self.secret.verify_key(&self.coconut.params)
+    }
+
+    pub fn spent_len(&self) -> usize {
+        match &self.spent {
+            Burns::Local(spent) => spent.len(),
+            Burns::Shared(registry) => registry.len(),
         }
+    }
 
-        match &input.proofs {
-            Some(proofs) => {
-                // Rangeproof pedersen check
-                if proofs.rangeproof.value_commit() != input.pedersen {
-                    return Err(error::Error::RangeproofPedersenMatchFailed);
+    // Nothing is burned unless the whole transaction is valid.
+    // With a registry the first call reserves the burns and fails with
+    // SpendNotConfirmed. Process the transaction again once the claims
+    // have come back through the slab log.
+    pub fn process(
+        &mut self,
+        transaction: &Transaction,
+    ) -> Result<Vec<OutputSignature>, error::Error> {
+        transaction.verify(self.coconut, &self.keys).into_result()?;
+
+        let burn_values: Vec<_> = transaction
+            .inputs
+            .iter()
+            .map(|input| input.request.burn_value)
+            .collect();
+
+        match &self.spent {
+            Burns::Local(spent) => {
+                for burn_value in &burn_values {
+                    if spent.contains(burn_value)? {
+                        return Err(error::Error::TokenAlreadySpent);
+                    }
                 }
-
-                // Validate remaining proofs
-                let commits = proofs.commits(
-                    &self.coconut.params,
-                    challenge,
-                    &self.verify_key,
-                    &input.request.credential,
-                    &input.request.burn_value,
-                    &input.pedersen,
-                );
-
-                //commits.commit(hasher);
-                hasher.add(commits.hash());
             }
-            None => {
-                return Err(error::Error::MissingProofs);
+            Burns::Shared(registry) => {
+                let digest = transaction.digest();
+                registry.reserve(&burn_values, &digest)?;
+                if !registry.is_confirmed(&burn_values, &digest)? {
+                    return Err(error::Error::SpendNotConfirmed);
+                }
             }
         }
 
-        // To avoid double spends of the same coin
-        self.spent.push(input.request.burn_value);
+        // Sign before burning so a failure leaves nothing burned
+        let signatures = transaction
+            .outputs
+            .iter()
+            .map(|output| self.sign_output(output))
+            .collect::<Result<Vec<_>, _>>()?;
 
-        Ok(())
-    }
-
-    fn process_output(
-        &self,
-        output: &Output,
-        challenge: &bls::Scalar,
-        hasher: &mut HasherToScalar,
-    ) -> Result<OutputSignature, error::Error> {
-        match &output.proofs {
-            Some(proofs) => {
-                // Rangeproof pedersen check
-                if proofs.rangeproof.value_commit() != output.pedersen {
-                    return Err(error::Error::RangeproofPedersenMatchFailed);
-                }
-
-                let commitish = output.request.sign_request.compute_commitish();
-
-                // Validate remaining proofs
-                let commits = proofs.commits(
-                    &self.coconut.params,
-                    challenge,
-                    &output.request.gamma,
-                    &commitish,
-                    &output.request.sign_request.attribute_commit,
-                    &output.request.sign_request.encrypted_attributes,
-                    &output.pedersen,
-                );
-
-                //commits.commit(hasher);
-                hasher.add(commits.hash());
-            }
-            None => {
-                return Err(error::Error::MissingProofs);
+        // To avoid double spends of the same coin
+        if let Burns::Local(spent) = &mut self.spent {
+            for burn_value in &burn_values {
+                spent.insert(burn_value)?;
             }
         }
 
+        Ok(signatures)
+    }
+
+    fn sign_output(&self, output: &Output) -> Result<OutputSignature, error::Error> {
         let signature_share = output.request.sign_request.blind_sign(
             &self.coconut.params,
             &self.secret,
-            //&output.request.public_attributes,
-            &Vec::new(),
-        );
+            &output.request.public_attributes,
+        )?;
 
         Ok(OutputSignature {
             index: self.index,
@@ -0,0 +1,137 @@
+use bls12_381 as bls;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::error::Result;
+
+// Burn values are stored in their compressed form
+pub type SpentKey = [u8; 48];
+
+// Each record in the file store is the key followed by
+// the first 4 bytes of its sha256.
+const RECORD_SIZE: usize = 48 + 4;
+
+pub fn spent_key(burn_value: &bls::G1Projective) -> SpentKey {
+    bls::G1Affine::from(burn_value).to_compressed()
+}
+
+pub trait SpentStore: Send {
+    fn contains(&self, burn_value: &bls::G1Projective) -> Result<bool>;
+    fn insert(&mut self, burn_value: &bls::G1Projective) -> Result<()>;
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[derive(Default)]
+pub struct MemorySpentStore {
+    spent: HashSet<SpentKey>,
+}
+
+impl MemorySpentStore {
+    pub fn new() -> Self {
+        Self {
+            spent: HashSet::new(),
+        }
+    }
+}
+
+impl SpentStore for MemorySpentStore {
+    fn contains(&self, burn_value: &bls::G1Projective) -> Result<bool> {
+        Ok(self.spent.contains(&spent_key(burn_value)))
+    }
+
+    fn insert(&mut self, burn_value: &bls::G1Projective) -> Result<()> {
+        self.spent.insert(spent_key(burn_value));
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.spent.len()
+    }
+}
+
+// Append-only log of spent burn values. Every insert is synced to disk
+// before it returns. A torn write at the end of the file from a crash
+// fails its checksum and gets truncated away when the store is reopened.
+pub struct FileSpentStore {
+    file: File,
+    spent: HashSet<SpentKey>,
+}
+
+fn record_checksum(key: &SpentKey) -> [u8; 4] {
+    let mut hasher = Sha256::new();
+    hasher.input(&key[..]);
+    let hash = hasher.result();
+
+    let mut checksum = [0u8; 4];
+    checksum.copy_from_slice(&hash[..4]);
+    checksum
+}
+
+impl FileSpentStore {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+
+        let mut spent = HashSet::new();
+        let mut valid_len = 0;
+        for record in data.chunks_exact(RECORD_SIZE) {
+            let mut key = [0u8; 48];
+            key.copy_from_slice(&record[..48]);
+            if record_checksum(&key) != record[48..] {
+                break;
+            }
+            spent.insert(key);
+            valid_len += RECORD_SIZE;
+        }
+
+        // Drop whatever was left half written
+        if valid_len != data.len() {
+            file.set_len(valid_len as u64)?;
+            file.sync_all()?;
+        }
+        file.seek(SeekFrom::End(0))?;
+
+        Ok(Self { file, spent })
+    }
+}
+
+impl SpentStore for FileSpentStore {
+    fn contains(&self, burn_value: &bls::G1Projective) -> Result<bool> {
+        Ok(self.spent.contains(&spent_key(burn_value)))
+    }
+
+    fn insert(&mut self, burn_value: &bls::G1Projective) -> Result<()> {
+        let key = spent_key(burn_value);
+        if self.spent.contains(&key) {
+            return Ok(());
+        }
+
+        let mut record = [0u8; RECORD_SIZE];
+        record[..48].copy_from_slice(&key[..]);
+        record[48..].copy_from_slice(&record_checksum(&key)[..]);
+
+        self.file.write_all(&record)?;
+        self.file.sync_data()?;
+
+        // Only mark as spent once it's safely on disk
+        self.spent.insert(key);
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.spent.len()
+    }
+}
@@ -1,8 +1,17 @@
+#[allow(unused_imports)]
+use bls12_381 as bls;
+#[allow(unused_imports)]
+use itertools::izip;
+
 #[allow(unused_imports)]
 use crate::bls_extensions::*;
 #[allow(unused_imports)]
 use crate::coconut::coconut::*;
 #[allow(unused_imports)]
+use crate::error::Error;
+#[allow(unused_imports)]
+use crate::parameters::DEFAULT_ASSET;
+#[allow(unused_imports)]
 use crate::pedersen::*;
 #[allow(unused_imports)]
 use crate::proofs::proof::*;
@@ -16,6 +25,8 @@ use crate::schema::service::*;
 use crate::schema::token::*;
 #[allow(unused_imports)]
 use crate::schema::transaction::*;
+#[allow(unused_imports)]
+use crate::serial::{Decodable, Encodable};
 
 #[test]
 fn test_schema_asset() {
@@ -23,14 +34,15 @@ fn test_schema_asset() {
     // Initialization
     //
 
-    let number_attributes = 2;
+    let number_attributes = 3;
     let threshold_service = 5;
     let total_services = 5;
 
     let (secret_keys, verify_key) =
-        generate_keys(number_attributes, threshold_service, total_services);
+        generate_keys(number_attributes, threshold_service, total_services).unwrap();
     let coconut =
-        Coconut::<OsRngInstance>::new(number_attributes, threshold_service, total_services);
+        Coconut::<OsRngInstance>::new(number_attributes, threshold_service, total_services)
+            .unwrap();
 
     // Create our services that will sign new credentials
     let mut services: Vec<_> = secret_keys
@@ -40,6 +52,10 @@ fn test_schema_asset() {
             SigningService::from_secret(&coconut, secret, verify_key.clone(), (index + 1) as u64)
         })
         .collect();
+    let service_verify_keys: Vec<_> = services
+        .iter()
+        .map(|service| service.share_verify_key())
+        .collect();
 
     //
     // Deposit
@@ -55,25 +71,26 @@ fn test_schema_asset() {
         // wallet: Create a new transaction
         let mut tx = Transaction::new();
         // wallet: Create a single output
-        let (output, mut output_secret) = Output::new(&coconut, &token_secret);
+        let (output, mut output_secret) = Output::new(&coconut, &token_secret).unwrap();
 
         // wallet: We are depositing 110, so expect a new token of 110 to be minted
         tx.add_deposit(token_secret.value);
         let output_id = tx.add_output(output);
 
         // Once we have added the inputs and outputs, we must call this function...
-        let (_input_blinds, output_blinds) =
-            tx.compute_pedersens(&coconut, &vec![], &vec![token_value]);
+        let (_input_blinds, output_blinds) = tx
+            .compute_pedersens(&coconut, &vec![], &vec![token_secret.asset_value()])
+            .unwrap();
 
         // wallet: Then for every input/output we created, call this one.
-        output_secret.setup(output_blinds[output_id]);
+        output_secret.setup(output_blinds[output_id]).unwrap();
         // wallet: Now start to generate the proofs
         let output_proof_commits = output_secret.proof_commits();
+        let output_proof_commitish = output_proof_commits.hash();
 
-        let mut hasher = HasherToScalar::new();
-        //output_proof_commits.commit(&mut hasher);
-        hasher.add(output_proof_commits.hash());
-        let challenge = hasher.finish();
+        // wallet: The output proofs answer their own challenge
+        let challenge =
+            Transaction::output_challenge(&tx.outputs[output_id], &output_proof_commitish);
 
         std::mem::drop(output_proof_commits);
 
@@ -82,8 +99,11 @@ fn test_schema_asset() {
         tx.outputs[output_id].set_proof(output_proofs);
         tx.outputs[output_id].challenge = Some(challenge);
 
-        // Also set the challenge computed from all the proofs in our transaction
-        tx.challenge = challenge;
+        // Also set the challenge computed from the whole transaction
+        // and all the proofs in it
+        let mut transcript = tx.transcript();
+        transcript.append_scalar(b"output", &output_proof_commitish);
+        tx.challenge = transcript.challenge_scalar(b"challenge");
 
         // service: Each service will now validate and sign the transaction
         let output_signatures: Vec<_> = services
@@ -105,7 +125,14 @@ fn test_schema_asset() {
 
         // wallet: Unblind and accept the returned signed token if signed by at least
         //         M of N services.
-        let mut tokens = tx.unblind(&coconut, &vec![&token_secret], output_signatures);
+        let (mut tokens, _) = tx
+            .unblind(
+                &coconut,
+                &service_verify_keys,
+                &vec![&token_secret],
+                output_signatures,
+            )
+            .unwrap();
         assert!(tokens.len() == 1);
         tokens.pop().unwrap()
     };
@@ -137,7 +164,8 @@ fn test_schema_asset() {
                 0,
                 &vec![token_secret.value],
                 &vec![token1_value, token2_value],
-            );
+            )
+            .unwrap();
 
         /////////////////////////////////////////////
         // Bob
@@ -145,14 +173,22 @@ fn test_schema_asset() {
 
         let output1_id = 0;
 
-        let (mut output1, mut output1_secret) = Output::new(&coconut, &token1_secret);
+        let (mut output1, mut output1_secret) = Output::new(&coconut, &token1_secret).unwrap();
 
-        output1_secret.setup(output_blinds[output1_id]);
+        output1_secret.setup(output_blinds[output1_id]).unwrap();
         let output1_proof_commitish = output1_secret.proof_commits().hash();
 
-        let mut hasher = HasherToScalar::new();
-        hasher.add(output1_proof_commitish);
-        let challenge_output1 = hasher.finish();
+        // The challenge covers the whole output, so set the pedersen
+        // Alice will compute for it
+        let asset_value = token1_secret.asset_value();
+        output1.pedersen = compute_asset_pedersen(
+            &coconut.params,
+            asset_value.asset,
+            &output_blinds[output1_id],
+            asset_value.value,
+        )
+        .unwrap();
+        let challenge_output1 = Transaction::output_challenge(&output1, &output1_proof_commitish);
 
         let output1_proofs = output1_secret.finish(&challenge_output1);
 
@@ -171,9 +207,9 @@ fn test_schema_asset() {
         let mut tx = Transaction::new();
         // wallet1: Create input and output
         let (input, mut input_secret) =
-            Input::new(&coconut, &verify_key, &token, &token_secret);
+            Input::new(&coconut, &verify_key, &token, &token_secret).unwrap();
         // wallet2: Also create another output
-        let (output2, mut output2_secret) = Output::new(&coconut, &token2_secret);
+        let (output2, mut output2_secret) = Output::new(&coconut, &token2_secret).unwrap();
 
         // We are splitting token of 110 into two new tokens of 100 and 10
         let input_id = tx.add_input(input);
@@ -188,10 +224,11 @@ fn test_schema_asset() {
             deposits_blind,
             withdraws_blind,
             &input_blinds,
-            &vec![token_secret.value],
+            &vec![token_secret.asset_value()],
             &output_blinds,
-            &vec![token1_value, token2_value],
-        );
+            &vec![token1_secret.asset_value(), token2_secret.asset_value()],
+        )
+        .unwrap();
 
         println!("  computed pedersens");
 
@@ -201,11 +238,13 @@ fn test_schema_asset() {
         assert_eq!(input_secret.value, token_secret.value);
         assert_eq!(
             tx.inputs[input_id].pedersen,
-            compute_pedersen_with_u64(
+            compute_asset_pedersen(
                 &coconut.params,
+                DEFAULT_ASSET,
                 &input_blinds[input_id],
                 input_secret.value
             )
+            .unwrap()
         );
         // Debug
         //println!("main(): input.pedersen = {:?}", tx.inputs[input_id].pedersen);
@@ -213,7 +252,7 @@ fn test_schema_asset() {
         //println!("main(): value = {:?}", bls::Scalar::from(input_secret.value));
 
         // wallet1: Now pass this info to our input_secret
-        input_secret.setup(input_blinds[input_id]);
+        input_secret.setup(input_blinds[input_id]).unwrap();
         let input_proof_commits = input_secret.proof_commits();
 
         assert_eq!(output1_id, 0);
@@ -225,40 +264,43 @@ fn test_schema_asset() {
         /*
         assert_eq!(
             tx.outputs[output1_id].pedersen,
-            compute_pedersen_with_u64(
+            compute_asset_pedersen(
                 &coconut.params,
+                DEFAULT_ASSET,
                 &output_blinds[output1_id],
                 output1_secret.value
             )
+            .unwrap()
         );
         */
         assert_eq!(
             tx.outputs[output2_id].pedersen,
-            compute_pedersen_with_u64(
+            compute_asset_pedersen(
                 &coconut.params,
+                DEFAULT_ASSET,
                 &output_blinds[output2_id],
                 output2_secret.value
             )
+            .unwrap()
         );
 
         // wallet1 and wallet2: also do the same for the outputs
-        output2_secret.setup(output_blinds[output2_id]);
+        output2_secret.setup(output_blinds[output2_id]).unwrap();
         // Begin computing the proofs
         let output2_proof_commits = output2_secret.proof_commits();
 
-        // Hash all the proof commits together to generate a single challenge.
-        let mut hasher = HasherToScalar::new();
-        //input_proof_commits.commit(&mut hasher);
-        //output1_proof_commits.commit(&mut hasher);
-        //output2_proof_commits.commit(&mut hasher);
-        hasher.add(input_proof_commits.hash());
-        hasher.add(output1_proof_commitish);
-        hasher.add(output2_proof_commits.hash());
-        let challenge = hasher.finish();
+        let challenge_output2 =
+            Transaction::output_challenge(&tx.outputs[output2_id], &output2_proof_commits.hash());
+        // Output challenges are part of the transaction digest
+        tx.outputs[output2_id].challenge = Some(challenge_output2);
 
-        let mut hasher = HasherToScalar::new();
-        hasher.add(output2_proof_commits.hash());
-        let challenge_output2 = hasher.finish();
+        // Hash the transaction and all the proof commits together to
+        // generate a single challenge.
+        let mut transcript = tx.transcript();
+        transcript.append_scalar(b"input", &input_proof_commits.hash());
+        transcript.append_scalar(b"output", &output1_proof_commitish);
+        transcript.append_scalar(b"output", &output2_proof_commits.hash());
+        let challenge = transcript.challenge_scalar(b"challenge");
 
         std::mem::drop(input_proof_commits);
         //std::mem::drop(output1_proof_commits);
@@ -271,7 +313,6 @@ fn test_schema_asset() {
         // wallet2: Also add finished proof to transaction
         let output2_proofs = output2_secret.finish(&challenge_output2);
         tx.outputs[output2_id].set_proof(output2_proofs);
-        tx.outputs[output2_id].challenge = Some(challenge_output2);
 
         // Also add challenge to transaction
         tx.challenge = challenge;
@@ -298,11 +339,14 @@ fn test_schema_asset() {
 
         // Valid output tokens returned from services
         // wallet1 and wallet2: Unblind the tokens
-        let tokens = tx.unblind(
-            &coconut,
-            &vec![&token1_secret, &token2_secret],
-            output_signatures,
-        );
+        let (tokens, _) = tx
+            .unblind(
+                &coconut,
+                &service_verify_keys,
+                &vec![&token1_secret, &token2_secret],
+                output_signatures,
+            )
+            .unwrap();
         println!("  unblinded {} signed tokens", tokens.len());
 
         assert!(tokens.len() == 2);
@@ -319,14 +363,16 @@ fn test_schema_asset() {
         // ... and add a withdrawal representing the money leaving the system.
         let mut tx = Transaction::new();
         let (input, mut input_secret) =
-            Input::new(&coconut, &verify_key, &token2, &token2_secret);
+            Input::new(&coconut, &verify_key, &token2, &token2_secret).unwrap();
 
         // We are wihdrawing a single token
         tx.add_withdraw(token2_secret.value);
         let input_id = tx.add_input(input);
 
         // As before compute the pedersens...
-        let (input_blinds, _) = tx.compute_pedersens(&coconut, &vec![token2_secret.value], &vec![]);
+        let (input_blinds, _) = tx
+            .compute_pedersens(&coconut, &vec![token2_secret.asset_value()], &vec![])
+            .unwrap();
 
         assert_eq!(input_id, 0);
         assert_eq!(input_blinds.len(), 1);
@@ -334,19 +380,21 @@ fn test_schema_asset() {
         assert_eq!(input_secret.value, token2_secret.value);
         assert_eq!(
             tx.inputs[input_id].pedersen,
-            compute_pedersen_with_u64(
+            compute_asset_pedersen(
                 &coconut.params,
+                DEFAULT_ASSET,
                 &input_blinds[input_id],
                 input_secret.value
             )
+            .unwrap()
         );
         // ... then compute the proofs
-        input_secret.setup(input_blinds[input_id]);
+        input_secret.setup(input_blinds[input_id]).unwrap();
         let input_proof_commits = input_secret.proof_commits();
 
-        let mut hasher = HasherToScalar::new();
-        hasher.add(input_proof_commits.hash());
-        let challenge = hasher.finish();
+        let mut transcript = tx.transcript();
+        transcript.append_scalar(b"input", &input_proof_commits.hash());
+        let challenge = transcript.challenge_scalar(b"challenge");
 
         std::mem::drop(input_proof_commits);
 
@@ -369,3 +417,2044 @@ fn test_schema_asset() {
         }
     }
 }
+
+#[test]
+fn test_schema_public_attributes() {
+    // serial, value, asset and a public expiry epoch
+    let number_attributes = 4;
+    let threshold_service = 3;
+    let total_services = 5;
+
+    let (secret_keys, verify_key) =
+        generate_keys(number_attributes, threshold_service, total_services).unwrap();
+    let coconut =
+        Coconut::<OsRngInstance>::new(number_attributes, threshold_service, total_services)
+            .unwrap();
+
+    let mut services: Vec<_> = secret_keys
+        .into_iter()
+        .enumerate()
+        .map(|(index, secret)| {
+            SigningService::from_secret(&coconut, secret, verify_key.clone(), (index + 1) as u64)
+        })
+        .collect();
+    let service_verify_keys: Vec<_> = services
+        .iter()
+        .map(|service| service.share_verify_key())
+        .collect();
+
+    let expiry = bls::Scalar::from(7);
+    let token_value = 50;
+    let token_secret =
+        TokenSecret::generate_with_public(DEFAULT_ASSET, token_value, vec![expiry], &coconut);
+
+    // Deposit
+    let token = {
+        let mut tx = Transaction::new();
+        let (output, mut output_secret) = Output::new(&coconut, &token_secret).unwrap();
+        assert_eq!(output.request.public_attributes.len(), 1);
+        assert_eq!(output.request.public_attributes[0].index, 3);
+
+        tx.add_deposit(token_value);
+        let output_id = tx.add_output(output);
+        let (_, output_blinds) = tx
+            .compute_pedersens(&coconut, &vec![], &vec![token_secret.asset_value()])
+            .unwrap();
+
+        output_secret.setup(output_blinds[output_id]).unwrap();
+        let output_hash = output_secret.proof_commits().hash();
+        let challenge = Transaction::output_challenge(&tx.outputs[output_id], &output_hash);
+
+        let output_proofs = output_secret.finish(&challenge);
+        tx.outputs[output_id].set_proof(output_proofs);
+        tx.outputs[output_id].challenge = Some(challenge);
+
+        let mut transcript = tx.transcript();
+        transcript.append_scalar(b"output", &output_hash);
+        tx.challenge = transcript.challenge_scalar(b"challenge");
+
+        // Public attributes survive serialization
+        let mut data = Vec::new();
+        tx.encode(&mut data).unwrap();
+        let tx = Transaction::decode(&data[..]).unwrap();
+        assert_eq!(tx.outputs[0].request.public_attributes[0].value, expiry);
+
+        let output_signatures: Vec<_> = services
+            .iter_mut()
+            .take(threshold_service as usize)
+            .map(|service| service.process(&tx).unwrap())
+            .collect();
+
+        let (mut tokens, _) = tx
+            .unblind(
+                &coconut,
+                &service_verify_keys,
+                &vec![&token_secret],
+                output_signatures,
+            )
+            .unwrap();
+        tokens.pop().unwrap()
+    };
+
+    // Withdraw
+    let make_withdraw = |public_attributes: Vec<Attribute>| {
+        let mut tx = Transaction::new();
+        let (mut input, mut input_secret) =
+            Input::new(&coconut, &verify_key, &token, &token_secret).unwrap();
+        input.request.public_attributes = public_attributes;
+
+        tx.add_withdraw(token_value);
+        let input_id = tx.add_input(input);
+        let (input_blinds, _) = tx
+            .compute_pedersens(&coconut, &vec![token_secret.asset_value()], &vec![])
+            .unwrap();
+
+        input_secret.setup(input_blinds[input_id]).unwrap();
+        let mut transcript = tx.transcript();
+        transcript.append_scalar(b"input", &input_secret.proof_commits().hash());
+        let challenge = transcript.challenge_scalar(b"challenge");
+
+        let input_proofs = input_secret.finish(&challenge);
+        tx.inputs[input_id].set_proof(input_proofs);
+        tx.challenge = challenge;
+        tx
+    };
+
+    // Lying about the expiry invalidates the credential
+    let tx = make_withdraw(vec![Attribute::new(bls::Scalar::from(8), 3)]);
+    match services[0].process(&tx) {
+        Err(Error::InputTokenVerifyFailed) => {}
+        _ => panic!("tampered public attribute was accepted"),
+    }
+
+    // Public attributes must be in the expected slots
+    let tx = make_withdraw(vec![Attribute::new(expiry, 0)]);
+    match services[0].process(&tx) {
+        Err(Error::InvalidPublicAttributes) => {}
+        _ => panic!("misplaced public attribute was accepted"),
+    }
+
+    let tx = make_withdraw(token_secret.public_attributes());
+    for service in &mut services {
+        assert!(service.process(&tx).is_ok());
+    }
+}
+
+// Build a fully proven transaction. The pedersen commits are computed from
+// the passed values which might not match the tokens.
+#[allow(dead_code)]
+fn make_transaction<'a, R: RngInstance>(
+    coconut: &'a Coconut<R>,
+    verify_key: &'a VerifyKey,
+    deposits: Vec<AssetValue>,
+    withdraws: Vec<AssetValue>,
+    inputs: Vec<(&Token, &TokenSecret)>,
+    input_values: Vec<AssetValue>,
+    outputs: Vec<&TokenSecret>,
+    output_values: Vec<AssetValue>,
+) -> Transaction {
+    make_transaction_with_rangeproofs(
+        coconut,
+        verify_key,
+        deposits,
+        withdraws,
+        inputs,
+        input_values,
+        outputs,
+        output_values,
+        false,
+    )
+}
+
+fn make_transaction_with_rangeproofs<'a, R: RngInstance>(
+    coconut: &'a Coconut<R>,
+    verify_key: &'a VerifyKey,
+    deposits: Vec<AssetValue>,
+    withdraws: Vec<AssetValue>,
+    inputs: Vec<(&Token, &TokenSecret)>,
+    input_values: Vec<AssetValue>,
+    outputs: Vec<&TokenSecret>,
+    output_values: Vec<AssetValue>,
+    aggregate_outputs: bool,
+) -> Transaction {
+    make_transaction_with(
+        coconut,
+        verify_key,
+        deposits,
+        withdraws,
+        inputs,
+        input_values,
+        outputs,
+        output_values,
+        aggregate_outputs,
+        |_| {},
+    )
+}
+
+// The setup can change fields of the transaction before they are bound
+// by the proofs.
+fn make_transaction_with<'a, R: RngInstance, F: FnOnce(&mut Transaction)>(
+    coconut: &'a Coconut<R>,
+    verify_key: &'a VerifyKey,
+    deposits: Vec<AssetValue>,
+    withdraws: Vec<AssetValue>,
+    inputs: Vec<(&Token, &TokenSecret)>,
+    input_values: Vec<AssetValue>,
+    outputs: Vec<&TokenSecret>,
+    output_values: Vec<AssetValue>,
+    aggregate_outputs: bool,
+    setup: F,
+) -> Transaction {
+    let mut tx = Transaction::new();
+    for deposit in deposits {
+        tx.add_asset_deposit(deposit.asset, deposit.value);
+    }
+    for withdraw in withdraws {
+        tx.add_asset_withdraw(withdraw.asset, withdraw.value);
+    }
+
+    let mut input_secrets = Vec::new();
+    for (token, token_secret) in inputs {
+        let (input, input_secret) = Input::new(coconut, verify_key, token, token_secret).unwrap();
+        tx.add_input(input);
+        input_secrets.push(input_secret);
+    }
+    let mut output_secrets = Vec::new();
+    for token_secret in outputs {
+        let (output, output_secret) = Output::new(coconut, token_secret).unwrap();
+        tx.add_output(output);
+        output_secrets.push(output_secret);
+    }
+    setup(&mut tx);
+
+    let (deposits_blind, withdraws_blind, input_blinds, output_blinds) = compute_pedersen_blinds(
+        coconut,
+        tx.total_deposits(),
+        tx.total_withdraws(),
+        &input_values.iter().map(|value| value.value).collect(),
+        &output_values.iter().map(|value| value.value).collect(),
+    )
+    .unwrap();
+    tx.set_blinds(
+        coconut,
+        deposits_blind,
+        withdraws_blind,
+        &input_blinds,
+        &input_values,
+        &output_blinds,
+        &output_values,
+    )
+    .unwrap();
+
+    for (input_secret, blind) in input_secrets.iter_mut().zip(&input_blinds) {
+        input_secret.setup(*blind).unwrap();
+    }
+    for (output_secret, blind) in output_secrets.iter_mut().zip(&output_blinds) {
+        if aggregate_outputs {
+            output_secret.setup_for_aggregation(*blind);
+        } else {
+            output_secret.setup(*blind).unwrap();
+        }
+    }
+    let outputs_rangeproof_builder = if aggregate_outputs {
+        let output_secrets: Vec<_> = output_secrets.iter().collect();
+        Some(Transaction::outputs_rangeproof_builder(coconut, &output_secrets).unwrap())
+    } else {
+        None
+    };
+
+    let output_hashes: Vec<_> = output_secrets
+        .iter()
+        .map(|output_secret| output_secret.proof_commits().hash())
+        .collect();
+    for (output, output_hash) in tx.outputs.iter_mut().zip(&output_hashes) {
+        output.challenge = Some(Transaction::output_challenge(output, output_hash));
+    }
+
+    let mut transcript = tx.transcript();
+    for input_secret in &input_secrets {
+        transcript.append_scalar(b"input", &input_secret.proof_commits().hash());
+    }
+    for output_hash in &output_hashes {
+        transcript.append_scalar(b"output", output_hash);
+    }
+    if let Some(builder) = &outputs_rangeproof_builder {
+        builder.commitments().commit(&mut transcript);
+    }
+    tx.challenge = transcript.challenge_scalar(b"challenge");
+
+    for (input, input_secret) in tx.inputs.iter_mut().zip(input_secrets) {
+        input.set_proof(input_secret.finish(&tx.challenge));
+    }
+    for (output, output_secret) in tx.outputs.iter_mut().zip(output_secrets) {
+        let challenge = output.challenge.unwrap();
+        output.set_proof(output_secret.finish(&challenge));
+    }
+    tx.outputs_rangeproof = outputs_rangeproof_builder.map(|builder| builder.finish(&tx.challenge));
+
+    tx
+}
+
+#[test]
+fn test_schema_multi_asset() {
+    let (gold, silver) = (1, 2);
+
+    let number_attributes = 3;
+    let threshold_service = 3;
+    let total_services = 5;
+
+    let (secret_keys, verify_key) =
+        generate_keys(number_attributes, threshold_service, total_services).unwrap();
+    let coconut = Coconut::<OsRngInstance>::new_with_assets(
+        number_attributes,
+        threshold_service,
+        total_services,
+        &vec![DEFAULT_ASSET, gold, silver],
+    )
+    .unwrap();
+
+    let mut services: Vec<_> = secret_keys
+        .into_iter()
+        .enumerate()
+        .map(|(index, secret)| {
+            SigningService::from_secret(&coconut, secret, verify_key.clone(), (index + 1) as u64)
+        })
+        .collect();
+    let service_verify_keys: Vec<_> = services
+        .iter()
+        .map(|service| service.share_verify_key())
+        .collect();
+
+    let sign = |services: &mut Vec<SigningService<OsRngInstance>>, tx: &Transaction| {
+        services
+            .iter_mut()
+            .take(threshold_service as usize)
+            .map(|service| service.process(tx).unwrap())
+            .collect::<Vec<_>>()
+    };
+
+    // Deposit gold and silver in a single transaction
+    let gold_secret = TokenSecret::generate_asset(gold, 100, &coconut);
+    let silver_secret = TokenSecret::generate_asset(silver, 30, &coconut);
+
+    let tx = make_transaction(
+        &coconut,
+        &verify_key,
+        vec![AssetValue::new(gold, 100), AssetValue::new(silver, 30)],
+        vec![],
+        vec![],
+        vec![],
+        vec![&gold_secret, &silver_secret],
+        vec![gold_secret.asset_value(), silver_secret.asset_value()],
+    );
+    // The outputs don't reveal their asset
+    assert_ne!(tx.outputs[0].asset_tag, tx.outputs[1].asset_tag);
+    let signatures = sign(&mut services, &tx);
+    let (tokens, _) = tx
+        .unblind(
+            &coconut,
+            &service_verify_keys,
+            &vec![&gold_secret, &silver_secret],
+            signatures,
+        )
+        .unwrap();
+    let (gold_token, silver_token) = (&tokens[0], &tokens[1]);
+
+    // Split the gold and pass the silver through in a single transaction
+    let gold1_secret = TokenSecret::generate_asset(gold, 60, &coconut);
+    let gold2_secret = TokenSecret::generate_asset(gold, 40, &coconut);
+    let silver2_secret = TokenSecret::generate_asset(silver, 30, &coconut);
+
+    let tx = make_transaction(
+        &coconut,
+        &verify_key,
+        vec![],
+        vec![],
+        vec![(gold_token, &gold_secret), (silver_token, &silver_secret)],
+        vec![gold_secret.asset_value(), silver_secret.asset_value()],
+        vec![&gold1_secret, &gold2_secret, &silver2_secret],
+        vec![
+            gold1_secret.asset_value(),
+            gold2_secret.asset_value(),
+            silver2_secret.asset_value(),
+        ],
+    );
+    let signatures = sign(&mut services, &tx);
+    let (tokens, _) = tx
+        .unblind(
+            &coconut,
+            &service_verify_keys,
+            &vec![&gold1_secret, &gold2_secret, &silver2_secret],
+            signatures,
+        )
+        .unwrap();
+    let silver2_token = &tokens[2];
+
+    // Turning silver into gold doesn't balance
+    let fake_gold_secret = TokenSecret::generate_asset(gold, 30, &coconut);
+    let tx = make_transaction(
+        &coconut,
+        &verify_key,
+        vec![],
+        vec![],
+        vec![(silver2_token, &silver2_secret)],
+        vec![silver2_secret.asset_value()],
+        vec![&fake_gold_secret],
+        vec![fake_gold_secret.asset_value()],
+    );
+    match services[0].process(&tx) {
+        Err(Error::TransactionPedersenCheckFailed) => {}
+        _ => panic!("unbalanced assets were accepted"),
+    }
+
+    // Lying about the asset in the commit breaks the proofs
+    let tx = make_transaction(
+        &coconut,
+        &verify_key,
+        vec![],
+        vec![],
+        vec![(silver2_token, &silver2_secret)],
+        vec![silver2_secret.asset_value()],
+        vec![&fake_gold_secret],
+        vec![AssetValue::new(silver, 30)],
+    );
+    assert!(services[0].process(&tx).is_err());
+
+    // Withdrawing the wrong asset doesn't balance either
+    let tx = make_transaction(
+        &coconut,
+        &verify_key,
+        vec![],
+        vec![AssetValue::new(gold, 30)],
+        vec![(silver2_token, &silver2_secret)],
+        vec![silver2_secret.asset_value()],
+        vec![],
+        vec![],
+    );
+    match services[1].process(&tx) {
+        Err(Error::TransactionPedersenCheckFailed) => {}
+        _ => panic!("unbalanced withdraw was accepted"),
+    }
+}
+
+#[test]
+fn test_spent_store_file() {
+    use crate::schema::spent::*;
+    use std::io::Write;
+
+    let path = std::env::temp_dir().join(format!("df-spent-{}.db", rand::random::<u64>()));
+    let params = crate::parameters::Parameters::<OsRngInstance>::new(1);
+    let burn1 = params.g1 * params.random_scalar();
+    let burn2 = params.g1 * params.random_scalar();
+
+    {
+        let mut store = FileSpentStore::open(&path).unwrap();
+        assert!(store.is_empty());
+        store.insert(&burn1).unwrap();
+        assert!(store.contains(&burn1).unwrap());
+        assert!(!store.contains(&burn2).unwrap());
+    }
+
+    // Simulate a crash in the middle of writing a record
+    {
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(&[0xab; 20]).unwrap();
+    }
+
+    {
+        let mut store = FileSpentStore::open(&path).unwrap();
+        assert_eq!(store.len(), 1);
+        assert!(store.contains(&burn1).unwrap());
+        store.insert(&burn2).unwrap();
+    }
+
+    let store = FileSpentStore::open(&path).unwrap();
+    assert_eq!(store.len(), 2);
+    assert!(store.contains(&burn1).unwrap());
+    assert!(store.contains(&burn2).unwrap());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_schema_cross_mint_double_spend() {
+    use crate::schema::epoch::*;
+    use crate::schema::registry::*;
+    use crate::schema::spent::*;
+
+    let number_attributes = 3;
+    let threshold_service = 2;
+    let total_services = 4;
+
+    let (secret_keys, verify_key) =
+        generate_keys(number_attributes, threshold_service, total_services).unwrap();
+    let coconut =
+        Coconut::<OsRngInstance>::new(number_attributes, threshold_service, total_services)
+            .unwrap();
+
+    // Every mint has its own registry and they only share the slab log
+    let registries: Vec<_> = (0..total_services)
+        .map(|_| SpentRegistry::new(Box::new(MemorySpentStore::new())))
+        .collect();
+    let mut services: Vec<_> = secret_keys
+        .into_iter()
+        .enumerate()
+        .map(|(index, secret)| {
+            SigningService::with_registry(
+                &coconut,
+                secret,
+                KeySet::new(DEFAULT_EPOCH, verify_key.clone()),
+                (index + 1) as u64,
+                registries[index].clone(),
+            )
+        })
+        .collect();
+    let service_verify_keys: Vec<_> = services
+        .iter()
+        .map(|service| service.share_verify_key())
+        .collect();
+
+    let token_secret = TokenSecret::generate(110, &coconut);
+    let tx = make_transaction(
+        &coconut,
+        &verify_key,
+        vec![token_secret.asset_value()],
+        vec![],
+        vec![],
+        vec![],
+        vec![&token_secret],
+        vec![token_secret.asset_value()],
+    );
+    let signatures: Vec<_> = services[..2]
+        .iter_mut()
+        .map(|service| service.process(&tx).unwrap())
+        .collect();
+    let (tokens, _) = tx
+        .unblind(
+            &coconut,
+            &service_verify_keys,
+            &vec![&token_secret],
+            signatures,
+        )
+        .unwrap();
+    let token = &tokens[0];
+
+    let spend = |output_secret: &TokenSecret| {
+        make_transaction(
+            &coconut,
+            &verify_key,
+            vec![],
+            vec![],
+            vec![(token, &token_secret)],
+            vec![token_secret.asset_value()],
+            vec![output_secret],
+            vec![output_secret.asset_value()],
+        )
+    };
+
+    // Mints 1 and 2 see one spend while mints 3 and 4 see another
+    let output1_secret = TokenSecret::generate(110, &coconut);
+    let tx1 = spend(&output1_secret);
+    let output2_secret = TokenSecret::generate(110, &coconut);
+    let tx2 = spend(&output2_secret);
+    for (index, service) in services.iter_mut().enumerate() {
+        let tx = if index < 2 { &tx1 } else { &tx2 };
+        match service.process(tx) {
+            Err(Error::SpendNotConfirmed) => {}
+            _ => panic!("signed before the burns were in the log"),
+        }
+    }
+
+    // Mints 1 and 2 publish their claims first
+    let mut log = Vec::new();
+    for registry in &registries {
+        let slabs = registry.take_unpublished();
+        assert_eq!(slabs.len(), 1);
+        for slab in &slabs {
+            let (burn_value, _) = slab_burn(slab).unwrap();
+            assert_eq!(burn_value, tx1.inputs[0].request.burn_value);
+        }
+        log.extend(slabs);
+    }
+    for registry in &registries {
+        for slab in &log {
+            registry.apply_slab(slab).unwrap();
+        }
+        assert!(registry.take_unpublished().is_empty());
+        assert_eq!(registry.len(), 1);
+    }
+
+    // Only the first claim in the log is signed
+    let signatures: Vec<_> = services[..2]
+        .iter_mut()
+        .map(|service| service.process(&tx1).unwrap())
+        .collect();
+    for service in &mut services[2..] {
+        match service.process(&tx2) {
+            Err(Error::TokenAlreadySpent) => {}
+            _ => panic!("cross mint double spend was accepted"),
+        }
+    }
+    let (tokens, _) = tx1
+        .unblind(
+            &coconut,
+            &service_verify_keys,
+            &vec![&output1_secret],
+            signatures,
+        )
+        .unwrap();
+    assert_eq!(tokens.len(), 1);
+}
+
+#[test]
+fn test_schema_process_is_atomic() {
+    let number_attributes = 3;
+    let threshold_service = 2;
+    let total_services = 3;
+
+    let (secret_keys, verify_key) =
+        generate_keys(number_attributes, threshold_service, total_services).unwrap();
+    let coconut =
+        Coconut::<OsRngInstance>::new(number_attributes, threshold_service, total_services)
+            .unwrap();
+    let g1 = bls::G1Projective::from(coconut.params.g1);
+
+    let mut services: Vec<_> = secret_keys
+        .into_iter()
+        .enumerate()
+        .map(|(index, secret)| {
+            SigningService::from_secret(&coconut, secret, verify_key.clone(), (index + 1) as u64)
+        })
+        .collect();
+    let service_verify_keys: Vec<_> = services
+        .iter()
+        .map(|service| service.share_verify_key())
+        .collect();
+
+    let token_secret = TokenSecret::generate(110, &coconut);
+    let tx = make_transaction(
+        &coconut,
+        &verify_key,
+        vec![token_secret.asset_value()],
+        vec![],
+        vec![],
+        vec![],
+        vec![&token_secret],
+        vec![token_secret.asset_value()],
+    );
+    let signatures: Vec<_> = services[..2]
+        .iter_mut()
+        .map(|service| service.process(&tx).unwrap())
+        .collect();
+    let (tokens, _) = tx
+        .unblind(
+            &coconut,
+            &service_verify_keys,
+            &vec![&token_secret],
+            signatures,
+        )
+        .unwrap();
+    let token = &tokens[0];
+
+    let output_secret = TokenSecret::generate(110, &coconut);
+    let spend = || {
+        make_transaction(
+            &coconut,
+            &verify_key,
+            vec![],
+            vec![],
+            vec![(token, &token_secret)],
+            vec![token_secret.asset_value()],
+            vec![&output_secret],
+            vec![output_secret.asset_value()],
+        )
+    };
+
+    let service = &mut services[0];
+    let mut check_rejected = |tx: Transaction, expected: fn(&Error) -> bool| {
+        match service.process(&tx) {
+            Err(err) => assert!(expected(&err), "unexpected error: {}", err),
+            Ok(_) => panic!("invalid transaction was accepted"),
+        }
+        // The token wasn't burned
+        assert_eq!(service.spent_len(), 0);
+    };
+
+    let mut tx = spend();
+    tx.outputs[0].pedersen += g1;
+    check_rejected(tx, |err| {
+        matches!(err, Error::TransactionPedersenCheckFailed)
+    });
+
+    let mut tx = spend();
+    tx.inputs[0].request.public_attributes.push(Attribute {
+        value: bls::Scalar::one(),
+        index: TOKEN_PRIVATE_ATTRIBUTES,
+    });
+    check_rejected(tx, |err| matches!(err, Error::InvalidPublicAttributes));
+
+    let mut tx = spend();
+    tx.outputs[0].request.public_attributes.push(Attribute {
+        value: bls::Scalar::one(),
+        index: TOKEN_PRIVATE_ATTRIBUTES,
+    });
+    check_rejected(tx, |err| matches!(err, Error::InvalidPublicAttributes));
+
+    let mut tx = spend();
+    tx.inputs[0].request.credential.v += g1;
+    check_rejected(tx, |err| matches!(err, Error::InputTokenVerifyFailed));
+
+    // Keep the pedersens balanced so only the rangeproof check fails
+    let mut tx = spend();
+    tx.inputs[0].pedersen += g1;
+    tx.outputs[0].pedersen += g1;
+    check_rejected(tx, |err| {
+        matches!(err, Error::RangeproofPedersenMatchFailed)
+    });
+
+    let mut tx = spend();
+    tx.inputs[0].proofs = None;
+    check_rejected(tx, |err| matches!(err, Error::MissingProofs));
+
+    let mut tx = spend();
+    tx.outputs[0].proofs = None;
+    check_rejected(tx, |err| matches!(err, Error::MissingProofs));
+
+    let mut tx = spend();
+    tx.outputs[0].challenge = None;
+    check_rejected(tx, |err| matches!(err, Error::InvalidCredential));
+
+    // Input proofs are checked against the wrong challenge
+    let mut tx = spend();
+    tx.challenge = coconut.params.random_scalar();
+    check_rejected(tx, |err| matches!(err, Error::ProofsFailed));
+
+    // Using the same token twice in one transaction
+    let double_secret = TokenSecret::generate(220, &coconut);
+    let tx = make_transaction(
+        &coconut,
+        &verify_key,
+        vec![],
+        vec![],
+        vec![(token, &token_secret), (token, &token_secret)],
+        vec![token_secret.asset_value(), token_secret.asset_value()],
+        vec![&double_secret],
+        vec![double_secret.asset_value()],
+    );
+    check_rejected(tx, |err| matches!(err, Error::TokenAlreadySpent));
+
+    // After all that the token can still be spent, but only once
+    let tx = spend();
+    services[0].process(&tx).unwrap();
+    assert_eq!(services[0].spent_len(), 1);
+    match services[0].process(&tx) {
+        Err(Error::TokenAlreadySpent) => {}
+        _ => panic!("double spend was accepted"),
+    }
+}
+
+#[test]
+fn test_schema_aggregated_rangeproof() {
+    use crate::serial::serialize;
+
+    let number_attributes = 3;
+    let threshold_service = 2;
+    let total_services = 3;
+
+    let (secret_keys, verify_key) =
+        generate_keys(number_attributes, threshold_service, total_services).unwrap();
+    let coconut =
+        Coconut::<OsRngInstance>::new(number_attributes, threshold_service, total_services)
+            .unwrap();
+
+    let mut services: Vec<_> = secret_keys
+        .into_iter()
+        .enumerate()
+        .map(|(index, secret)| {
+            SigningService::from_secret(&coconut, secret, verify_key.clone(), (index + 1) as u64)
+        })
+        .collect();
+    let service_verify_keys: Vec<_> = services
+        .iter()
+        .map(|service| service.share_verify_key())
+        .collect();
+
+    let token_secrets: Vec<_> = [10, 20, 30, 40]
+        .iter()
+        .map(|value| TokenSecret::generate(*value, &coconut))
+        .collect();
+    let output_values: Vec<_> = token_secrets
+        .iter()
+        .map(|token_secret| token_secret.asset_value())
+        .collect();
+
+    let deposit = |aggregate_outputs| {
+        make_transaction_with_rangeproofs(
+            &coconut,
+            &verify_key,
+            vec![AssetValue::new(DEFAULT_ASSET, 100)],
+            vec![],
+            vec![],
+            vec![],
+            token_secrets.iter().collect(),
+            output_values.clone(),
+            aggregate_outputs,
+        )
+    };
+
+    let separate_tx = deposit(false);
+    let aggregated_tx = deposit(true);
+
+    let separate_size = serialize(&separate_tx).len();
+    let aggregated_size = serialize(&aggregated_tx).len();
+    println!(
+        "4 outputs: {} bytes with separate range proofs, {} bytes aggregated",
+        separate_size, aggregated_size
+    );
+    assert!(aggregated_size < separate_size);
+
+    for tx in &[separate_tx, aggregated_tx] {
+        let mut signatures = Vec::new();
+        for service in services.iter_mut().take(threshold_service as usize) {
+            signatures.push(service.process(tx).unwrap());
+        }
+        let (tokens, _) = tx
+            .unblind(
+                &coconut,
+                &service_verify_keys,
+                &token_secrets.iter().collect(),
+                signatures,
+            )
+            .unwrap();
+        assert_eq!(tokens.len(), 4);
+    }
+
+    // Outputs relying on the aggregated proof can't drop it
+    let mut tx = deposit(true);
+    tx.outputs_rangeproof = None;
+    match services[0].process(&tx) {
+        Err(Error::MissingProofs) => {}
+        _ => panic!("outputs without range proofs were accepted"),
+    }
+
+    // Nor can the outputs be reordered under it
+    let mut tx = deposit(true);
+    tx.outputs.swap(0, 1);
+    match services[0].process(&tx) {
+        Err(Error::RangeproofPedersenMatchFailed) => {}
+        _ => panic!("reordered outputs were accepted"),
+    }
+}
+
+#[test]
+fn test_schema_rangeproof_bits() {
+    use crate::serial::serialize;
+
+    let number_attributes = 3;
+    let threshold_service = 1;
+    let total_services = 1;
+
+    let (mut secret_keys, verify_key) =
+        generate_keys(number_attributes, threshold_service, total_services).unwrap();
+    let secret = secret_keys.pop().unwrap();
+
+    // Wallet and mint agree on 16 bit range proofs
+    let coconut16 =
+        Coconut::<OsRngInstance>::new(number_attributes, threshold_service, total_services)
+            .unwrap()
+            .with_rangeproof_bits(16)
+            .unwrap();
+    let coconut64 =
+        Coconut::<OsRngInstance>::new(number_attributes, threshold_service, total_services)
+            .unwrap();
+
+    let token_secret = TokenSecret::generate(1000, &coconut16);
+    let deposit = |coconut| {
+        make_transaction(
+            coconut,
+            &verify_key,
+            vec![token_secret.asset_value()],
+            vec![],
+            vec![],
+            vec![],
+            vec![&token_secret],
+            vec![token_secret.asset_value()],
+        )
+    };
+
+    let tx16 = deposit(&coconut16);
+    let tx64 = deposit(&coconut64);
+    assert!(serialize(&tx16).len() < serialize(&tx64).len());
+
+    let mut service16 = SigningService::from_secret(&coconut16, secret, verify_key.clone(), 1);
+    service16.process(&tx16).unwrap();
+
+    // Proofs of another width are rejected
+    match service16.process(&tx64) {
+        Err(Error::ProofsFailed) => {}
+        _ => panic!("range proof of the wrong width was accepted"),
+    }
+
+    // A value too wide for the proofs is an error for the wallet
+    let wide_secret = TokenSecret::generate(1 << 16, &coconut16);
+    let (_, mut output_secret) = Output::new(&coconut16, &wide_secret).unwrap();
+    assert!(matches!(
+        output_secret.setup(coconut16.params.random_scalar()),
+        Err(Error::ValueOutOfRange)
+    ));
+}
+
+#[test]
+fn test_schema_hostile_input() {
+    use crate::serial::{deserialize, serialize};
+
+    let number_attributes = 3;
+    let threshold_service = 1;
+    let total_services = 1;
+
+    let (mut secret_keys, verify_key) =
+        generate_keys(number_attributes, threshold_service, total_services).unwrap();
+    let coconut =
+        Coconut::<OsRngInstance>::new(number_attributes, threshold_service, total_services)
+            .unwrap();
+    let mut service =
+        SigningService::from_secret(&coconut, secret_keys.pop().unwrap(), verify_key.clone(), 1);
+
+    // Wallet side misuse
+    let token_secret = TokenSecret::generate(110, &coconut);
+    let unsigned = Token {
+        signature: None,
+        epoch: 0,
+    };
+    assert!(matches!(
+        Input::new(&coconut, &verify_key, &unsigned, &token_secret),
+        Err(Error::MissingSignature)
+    ));
+    assert!(matches!(
+        compute_pedersen_blinds(&coconut, 0, 0, &vec![], &vec![]),
+        Err(Error::EmptyTransaction)
+    ));
+    assert!(matches!(
+        compute_pedersen_blinds(&coconut, 0, 0, &vec![110], &vec![]),
+        Err(Error::EmptyTransaction)
+    ));
+
+    // Bad values are errors and leave the transaction untouched
+    let unregistered = AssetValue::new(u64::MAX, 110);
+    let mut tx = Transaction::new();
+    tx.add_deposit(110);
+    let (output, _) = Output::new(&coconut, &token_secret).unwrap();
+    let pedersen = output.pedersen;
+    tx.add_output(output);
+    assert!(matches!(
+        tx.compute_pedersens(&coconut, &vec![], &vec![]),
+        Err(Error::InvalidAttributeCount)
+    ));
+    assert!(matches!(
+        tx.compute_pedersens(
+            &coconut,
+            &vec![],
+            &vec![AssetValue::new(DEFAULT_ASSET, 111)]
+        ),
+        Err(Error::TransactionPedersenCheckFailed)
+    ));
+    assert!(matches!(
+        tx.compute_pedersens(&coconut, &vec![], &vec![unregistered]),
+        Err(Error::TransactionPedersenCheckFailed)
+    ));
+    assert!(matches!(
+        tx.set_blinds(
+            &coconut,
+            bls::Scalar::one(),
+            bls::Scalar::zero(),
+            &vec![],
+            &vec![],
+            &vec![bls::Scalar::one()],
+            &vec![unregistered],
+        ),
+        Err(Error::UnknownAsset)
+    ));
+    assert!(tx.secret().is_none());
+    assert_eq!(tx.outputs[0].pedersen, pedersen);
+    assert!(tx.secret().is_none());
+    tx.add_deposit(u64::MAX);
+    tx.add_output(Output::new(&coconut, &token_secret).unwrap().0);
+    assert!(matches!(
+        tx.compute_pedersens(
+            &coconut,
+            &vec![],
+            &vec![
+                token_secret.asset_value(),
+                AssetValue::new(DEFAULT_ASSET, u64::MAX)
+            ]
+        ),
+        Err(Error::ValueOverflow)
+    ));
+    assert!(tx.secret().is_none());
+    assert_eq!(tx.outputs[0].pedersen, pedersen);
+
+    let mut tx = Transaction::new();
+    tx.add_asset_deposit(u64::MAX, 110);
+    tx.add_output(Output::new(&coconut, &token_secret).unwrap().0);
+    assert!(matches!(
+        tx.compute_pedersens(&coconut, &vec![], &vec![unregistered]),
+        Err(Error::UnknownAsset)
+    ));
+    assert!(tx.secret().is_none());
+
+    // Huge length prefixes fail instead of allocating
+    let mut data = vec![0xff];
+    data.extend_from_slice(&u64::MAX.to_le_bytes());
+    assert!(deserialize::<Vec<u8>>(&data).is_err());
+    assert!(deserialize::<Vec<bls::Scalar>>(&data).is_err());
+
+    let deposit = || {
+        make_transaction(
+            &coconut,
+            &verify_key,
+            vec![token_secret.asset_value()],
+            vec![],
+            vec![],
+            vec![],
+            vec![&token_secret],
+            vec![token_secret.asset_value()],
+        )
+    };
+
+    // A sign request pointing past the signer's keys
+    let mut tx = deposit();
+    tx.outputs[0].request.sign_request.encrypted_attributes[0].index = 99;
+    assert!(service.process(&tx).is_err());
+
+    // Corrupt a valid transaction all over, whatever still decodes mustn't panic
+    let data = serialize(&deposit());
+    for position in (0..data.len()).step_by(data.len() / 32 + 1) {
+        let mut corrupted = data.clone();
+        corrupted[position] ^= 0x5a;
+        if let Ok(tx) = deserialize::<Transaction>(&corrupted) {
+            let _ = service.process(&tx);
+        }
+    }
+
+    service.process(&deposit()).unwrap();
+}
+
+#[test]
+fn test_schema_transaction_digest() {
+    use crate::serial::{deserialize, serialize};
+
+    let number_attributes = 3;
+    let threshold_service = 1;
+    let total_services = 1;
+
+    let (mut secret_keys, verify_key) =
+        generate_keys(number_attributes, threshold_service, total_services).unwrap();
+    let coconut =
+        Coconut::<OsRngInstance>::new(number_attributes, threshold_service, total_services)
+            .unwrap();
+    let mut service =
+        SigningService::from_secret(&coconut, secret_keys.pop().unwrap(), verify_key.clone(), 1);
+
+    let token_secret = TokenSecret::generate(100, &coconut);
+    let tx = make_transaction(
+        &coconut,
+        &verify_key,
+        vec![token_secret.asset_value()],
+        vec![],
+        vec![],
+        vec![],
+        vec![&token_secret],
+        vec![token_secret.asset_value()],
+    );
+    let signatures = vec![service.process(&tx).unwrap()];
+    let (tokens, _) = tx
+        .unblind(
+            &coconut,
+            &vec![service.share_verify_key()],
+            &vec![&token_secret],
+            signatures,
+        )
+        .unwrap();
+
+    // 10 + 100 == 20 + 50 + 40
+    let output1_secret = TokenSecret::generate(50, &coconut);
+    let output2_secret = TokenSecret::generate(40, &coconut);
+    let data = serialize(&make_transaction(
+        &coconut,
+        &verify_key,
+        vec![AssetValue::new(DEFAULT_ASSET, 10)],
+        vec![AssetValue::new(DEFAULT_ASSET, 20)],
+        vec![(&tokens[0], &token_secret)],
+        vec![token_secret.asset_value()],
+        vec![&output1_secret, &output2_secret],
+        vec![output1_secret.asset_value(), output2_secret.asset_value()],
+    ));
+
+    // The responses and the transaction challenge aren't part of the digest
+    let tx: Transaction = deserialize(&data).unwrap();
+    let mut stripped: Transaction = deserialize(&data).unwrap();
+    stripped.challenge = bls::Scalar::zero();
+    for input in &mut stripped.inputs {
+        input.proofs = None;
+    }
+    for output in &mut stripped.outputs {
+        output.proofs = None;
+    }
+    stripped.outputs_rangeproof = None;
+    assert_eq!(tx.digest(), stripped.digest());
+
+    // Changes a relay could make that keep every proof and the balance valid
+    let tampers: Vec<fn(&mut Transaction)> = vec![
+        |tx| tx.outputs[0].challenge = Some(bls::Scalar::one()),
+        |tx| {
+            let attribute = &mut tx.outputs[1].request.sign_request.encrypted_attributes[0];
+            attribute.value.1 += bls::G1Projective::generator();
+        },
+        |tx| tx.outputs[0].request.gamma.public_key += bls::G1Projective::generator(),
+        |tx| tx.outputs.swap(0, 1),
+    ];
+    for tamper in tampers {
+        let mut tx: Transaction = deserialize(&data).unwrap();
+        tamper(&mut tx);
+        assert_ne!(tx.digest(), stripped.digest());
+        assert!(matches!(service.process(&tx), Err(Error::ProofsFailed)));
+    }
+
+    // The amounts are bound to the deposits and withdraws commits
+    let mut tampered: Transaction = deserialize(&data).unwrap();
+    tampered.deposits[0].value += 1;
+    tampered.withdraws[0].value += 1;
+    assert!(matches!(
+        service.process(&tampered),
+        Err(Error::TransactionPedersenCheckFailed)
+    ));
+
+    service.process(&tx).unwrap();
+}
+
+#[test]
+fn test_schema_transaction_wire_format() {
+    use crate::serial::{deserialize, serialize};
+
+    let number_attributes = 3;
+    let threshold_service = 1;
+    let total_services = 1;
+
+    let (mut secret_keys, verify_key) =
+        generate_keys(number_attributes, threshold_service, total_services).unwrap();
+    let coconut =
+        Coconut::<OsRngInstance>::new(number_attributes, threshold_service, total_services)
+            .unwrap();
+    let mut service =
+        SigningService::from_secret(&coconut, secret_keys.pop().unwrap(), verify_key.clone(), 1);
+
+    let token_secret = TokenSecret::generate(100, &coconut);
+    let tx = make_transaction(
+        &coconut,
+        &verify_key,
+        vec![token_secret.asset_value()],
+        vec![],
+        vec![],
+        vec![],
+        vec![&token_secret],
+        vec![token_secret.asset_value()],
+    );
+
+    // The blinds stay with the wallet
+    let secret = tx.secret().unwrap();
+    let data = serialize(&tx);
+    assert_eq!(data[0], TRANSACTION_VERSION);
+    let blind = serialize(&secret.deposits_blind);
+    assert!(!data.windows(blind.len()).any(|window| window == &blind[..]));
+
+    let decoded_secret: TransactionSecret = deserialize(&serialize(secret)).unwrap();
+    assert_eq!(&decoded_secret, secret);
+
+    let decoded: Transaction = deserialize(&data).unwrap();
+    assert!(decoded.secret().is_none());
+    assert_eq!(decoded.digest(), tx.digest());
+
+    let mut wrong_version = data.clone();
+    wrong_version[0] = TRANSACTION_VERSION + 1;
+    assert!(matches!(
+        deserialize::<Transaction>(&wrong_version),
+        Err(Error::ParseFailed(_))
+    ));
+
+    // Moving value into the deposits commit keeps the sums balanced,
+    // but the commit no longer opens to the public deposits.
+    let extra = bls::G1Projective::generator();
+    let mut forged: Transaction = deserialize(&data).unwrap();
+    forged.deposits_commit += extra;
+    forged.outputs[0].pedersen += extra;
+    assert!(!forged.check(&coconut));
+    assert!(matches!(
+        service.process(&forged),
+        Err(Error::TransactionPedersenCheckFailed)
+    ));
+
+    let mut forged: Transaction = deserialize(&data).unwrap();
+    forged.deposits_proof = None;
+    assert!(!forged.check(&coconut));
+
+    service.process(&decoded).unwrap();
+}
+
+#[test]
+fn test_schema_transaction_verify() {
+    use crate::schema::verify::{Component, TransactionPart};
+    use crate::serial::{deserialize, serialize};
+
+    let number_attributes = 3;
+    let threshold_service = 1;
+    let total_services = 1;
+
+    let (mut secret_keys, verify_key) =
+        generate_keys(number_attributes, threshold_service, total_services).unwrap();
+    let coconut =
+        Coconut::<OsRngInstance>::new(number_attributes, threshold_service, total_services)
+            .unwrap();
+    let mut service =
+        SigningService::from_secret(&coconut, secret_keys.pop().unwrap(), verify_key.clone(), 1);
+    let g1 = bls::G1Projective::from(coconut.params.g1);
+
+    let token_secret = TokenSecret::generate(100, &coconut);
+    let tx = make_transaction(
+        &coconut,
+        &verify_key,
+        vec![token_secret.asset_value()],
+        vec![],
+        vec![],
+        vec![],
+        vec![&token_secret],
+        vec![token_secret.asset_value()],
+    );
+    assert!(tx.verify(&coconut, service.keyset()).is_valid());
+    let signatures = vec![service.process(&tx).unwrap()];
+    let (tokens, _) = tx
+        .unblind(
+            &coconut,
+            &vec![service.share_verify_key()],
+            &vec![&token_secret],
+            signatures,
+        )
+        .unwrap();
+
+    let output1_secret = TokenSecret::generate(60, &coconut);
+    let output2_secret = TokenSecret::generate(40, &coconut);
+    let data = serialize(&make_transaction(
+        &coconut,
+        &verify_key,
+        vec![],
+        vec![],
+        vec![(&tokens[0], &token_secret)],
+        vec![token_secret.asset_value()],
+        vec![&output1_secret, &output2_secret],
+        vec![output1_secret.asset_value(), output2_secret.asset_value()],
+    ));
+    let tampered = |tamper: &dyn Fn(&mut Transaction)| {
+        let mut tx: Transaction = deserialize(&data).unwrap();
+        tamper(&mut tx);
+        tx
+    };
+    let failures = |tx: &Transaction| {
+        tx.verify(&coconut, service.keyset())
+            .failures
+            .iter()
+            .map(|failure| (failure.part, failure.component))
+            .collect::<Vec<_>>()
+    };
+
+    let tx = tampered(&|tx| tx.outputs[1].challenge = None);
+    assert_eq!(
+        failures(&tx),
+        vec![(TransactionPart::Output(1), Component::Proofs)]
+    );
+
+    // Output proofs answering a challenge of the prover's choosing, with
+    // the transaction challenge computed over it as usual
+    let deposit_secret = TokenSecret::generate(100, &coconut);
+    let mut tx = Transaction::new();
+    tx.add_deposit(deposit_secret.value);
+    let (output, mut output_secret) = Output::new(&coconut, &deposit_secret).unwrap();
+    tx.add_output(output);
+    let (_, output_blinds) = tx
+        .compute_pedersens(&coconut, &vec![], &vec![deposit_secret.asset_value()])
+        .unwrap();
+    output_secret.setup(output_blinds[0]).unwrap();
+    let output_hash = output_secret.proof_commits().hash();
+    let challenge = coconut.params.random_scalar();
+    assert!(challenge != Transaction::output_challenge(&tx.outputs[0], &output_hash));
+    tx.outputs[0].set_proof(output_secret.finish(&challenge));
+    tx.outputs[0].challenge = Some(challenge);
+    let mut transcript = tx.transcript();
+    transcript.append_scalar(b"output", &output_hash);
+    tx.challenge = transcript.challenge_scalar(b"challenge");
+    assert_eq!(
+        failures(&tx),
+        vec![(TransactionPart::Output(0), Component::Proofs)]
+    );
+    assert!(matches!(
+        tx.verify(&coconut, service.keyset()).into_result(),
+        Err(Error::ProofsFailed)
+    ));
+
+    let tx = tampered(&|tx| tx.outputs[1].range_commit += g1);
+    assert_eq!(
+        failures(&tx),
+        vec![
+            (TransactionPart::Output(1), Component::Rangeproof),
+            (TransactionPart::Output(1), Component::Proofs),
+            (TransactionPart::Transaction, Component::Challenge)
+        ]
+    );
+
+    let tx = tampered(&|tx| {
+        tx.inputs[0].request.credential.v += g1;
+        tx.outputs[0].request.epoch += 1;
+    });
+    assert_eq!(
+        failures(&tx),
+        vec![
+            (TransactionPart::Input(0), Component::Credential),
+            (TransactionPart::Output(0), Component::Epoch),
+            (TransactionPart::Output(0), Component::Proofs),
+            (TransactionPart::Transaction, Component::Challenge)
+        ]
+    );
+
+    // The first failure is what the mint rejects with
+    assert!(matches!(
+        tx.verify(&coconut, service.keyset()).into_result(),
+        Err(Error::InputTokenVerifyFailed)
+    ));
+    assert!(matches!(
+        service.process(&tx),
+        Err(Error::InputTokenVerifyFailed)
+    ));
+
+    // Verifying doesn't burn anything
+    let tx: Transaction = deserialize(&data).unwrap();
+    assert!(tx.verify(&coconut, service.keyset()).is_valid());
+    service.process(&tx).unwrap();
+    assert!(tx.verify(&coconut, service.keyset()).is_valid());
+    assert!(matches!(
+        service.process(&tx),
+        Err(Error::TokenAlreadySpent)
+    ));
+}
+
+#[test]
+fn test_schema_bad_signature_shares() {
+    let number_attributes = 3;
+    let threshold_service = 2;
+    let total_services = 3;
+
+    let (secret_keys, verify_key) =
+        generate_keys(number_attributes, threshold_service, total_services).unwrap();
+    let coconut =
+        Coconut::<OsRngInstance>::new(number_attributes, threshold_service, total_services)
+            .unwrap();
+
+    let mut services: Vec<_> = secret_keys
+        .into_iter()
+        .enumerate()
+        .map(|(index, secret)| {
+            SigningService::from_secret(&coconut, secret, verify_key.clone(), (index + 1) as u64)
+        })
+        .collect();
+    let service_verify_keys: Vec<_> = services
+        .iter()
+        .map(|service| service.share_verify_key())
+        .collect();
+
+    // Service 2 signs with a key nobody agreed on
+    let (mut rogue_keys, _) =
+        generate_keys(number_attributes, threshold_service, total_services).unwrap();
+    let mut rogue =
+        SigningService::from_secret(&coconut, rogue_keys.remove(1), verify_key.clone(), 2);
+
+    let token_secret = TokenSecret::generate(110, &coconut);
+    let tx = make_transaction(
+        &coconut,
+        &verify_key,
+        vec![token_secret.asset_value()],
+        vec![],
+        vec![],
+        vec![],
+        vec![&token_secret],
+        vec![token_secret.asset_value()],
+    );
+    let sign = |service: &mut SigningService<OsRngInstance>| service.process(&tx).unwrap();
+    let (good1, bad2, good3) = (
+        sign(&mut services[0]),
+        sign(&mut rogue),
+        sign(&mut services[2]),
+    );
+
+    // The bad share is dropped and reported, the rest still make a token
+    let (tokens, rejected) = tx
+        .unblind(
+            &coconut,
+            &service_verify_keys,
+            &vec![&token_secret],
+            vec![good1, bad2, good3],
+        )
+        .unwrap();
+    assert_eq!(rejected, vec![2]);
+    Input::new(&coconut, &verify_key, &tokens[0], &token_secret).unwrap();
+
+    // Below threshold once the bad share is gone
+    let bad2 = sign(&mut rogue);
+    match tx.unblind(
+        &coconut,
+        &service_verify_keys,
+        &vec![&token_secret],
+        vec![sign(&mut services[0]), bad2],
+    ) {
+        Err(Error::NotEnoughSignatureShares(rejected)) => assert_eq!(rejected, vec![2]),
+        _ => panic!("bad share was aggregated"),
+    }
+
+    // Repeating a good share doesn't count twice
+    let repeated = vec![sign(&mut services[0]), sign(&mut services[0])];
+    match tx.unblind(
+        &coconut,
+        &service_verify_keys,
+        &vec![&token_secret],
+        repeated,
+    ) {
+        Err(Error::NotEnoughSignatureShares(rejected)) => assert_eq!(rejected, vec![1]),
+        _ => panic!("repeated share was counted twice"),
+    }
+
+    // A reply that doesn't cover every output is dropped
+    let output1_secret = TokenSecret::generate(100, &coconut);
+    let output2_secret = TokenSecret::generate(10, &coconut);
+    let tx = make_transaction(
+        &coconut,
+        &verify_key,
+        vec![token_secret.asset_value()],
+        vec![],
+        vec![],
+        vec![],
+        vec![&output1_secret, &output2_secret],
+        vec![output1_secret.asset_value(), output2_secret.asset_value()],
+    );
+    let token_secrets = vec![&output1_secret, &output2_secret];
+    let sign = |service: &mut SigningService<OsRngInstance>| service.process(&tx).unwrap();
+    let mut truncated = sign(&mut services[1]);
+    truncated.pop();
+    let replies = vec![sign(&mut services[0]), truncated, sign(&mut services[2])];
+    let (tokens, rejected) = tx
+        .unblind(&coconut, &service_verify_keys, &token_secrets, replies)
+        .unwrap();
+    assert_eq!(tokens.len(), 2);
+    assert_eq!(rejected, vec![2]);
+
+    let mut truncated = sign(&mut services[1]);
+    truncated.pop();
+    let replies = vec![sign(&mut services[0]), truncated, vec![]];
+    match tx.unblind(&coconut, &service_verify_keys, &token_secrets, replies) {
+        Err(Error::NotEnoughSignatureShares(rejected)) => assert_eq!(rejected, vec![2]),
+        _ => panic!("truncated reply was aggregated"),
+    }
+    assert!(matches!(
+        tx.unblind(
+            &coconut,
+            &service_verify_keys,
+            &vec![&output1_secret],
+            vec![sign(&mut services[0]), sign(&mut services[2])],
+        ),
+        Err(Error::InvalidAttributeCount)
+    ));
+}
+
+#[test]
+fn test_schema_token_presentation() {
+    let number_attributes = 4;
+    let threshold_service = 1;
+    let total_services = 1;
+
+    let (mut secret_keys, verify_key) =
+        generate_keys(number_attributes, threshold_service, total_services).unwrap();
+    let coconut =
+        Coconut::<OsRngInstance>::new(number_attributes, threshold_service, total_services)
+            .unwrap();
+    let mut service =
+        SigningService::from_secret(&coconut, secret_keys.pop().unwrap(), verify_key.clone(), 1);
+    let service_verify_keys = vec![service.share_verify_key()];
+
+    // A voter token whose public attribute is the poll it's valid for
+    let poll = bls::Scalar::from(7);
+    let token_secret = TokenSecret::generate_with_public(DEFAULT_ASSET, 1, vec![poll], &coconut);
+    let deposit = make_transaction(
+        &coconut,
+        &verify_key,
+        vec![token_secret.asset_value()],
+        vec![],
+        vec![],
+        vec![],
+        vec![&token_secret],
+        vec![token_secret.asset_value()],
+    );
+    let signatures = vec![service.process(&deposit).unwrap()];
+    let (tokens, _) = deposit
+        .unblind(
+            &coconut,
+            &service_verify_keys,
+            &vec![&token_secret],
+            signatures,
+        )
+        .unwrap();
+    let token = &tokens[0];
+
+    let presentation = token
+        .present(&coconut, &verify_key, &token_secret, b"ballot 1")
+        .unwrap();
+    assert!(coconut.verify_presentation(&verify_key, &presentation, b"ballot 1"));
+    assert!(presentation.disclosed_attributes[0].value == poll);
+
+    // Presenting doesn't spend the token
+    let output_secret = TokenSecret::generate_with_public(DEFAULT_ASSET, 1, vec![poll], &coconut);
+    let spend = make_transaction(
+        &coconut,
+        &verify_key,
+        vec![],
+        vec![],
+        vec![(token, &token_secret)],
+        vec![token_secret.asset_value()],
+        vec![&output_secret],
+        vec![output_secret.asset_value()],
+    );
+    service.process(&spend).unwrap();
+    assert_eq!(service.spent_len(), 1);
+
+    let unsigned = Token {
+        signature: None,
+        epoch: 0,
+    };
+    assert!(matches!(
+        unsigned.present(&coconut, &verify_key, &token_secret, b"ballot 1"),
+        Err(Error::MissingSignature)
+    ));
+}
+
+#[test]
+fn test_schema_key_epochs() {
+    use crate::schema::epoch::*;
+    use crate::serial::{deserialize, serialize};
+
+    let number_attributes = 3;
+    let threshold_service = 1;
+    let total_services = 1;
+
+    let mut epoch_keys: Vec<_> = (0..3)
+        .map(|_| {
+            let (mut secret_keys, verify_key) =
+                generate_keys(number_attributes, threshold_service, total_services).unwrap();
+            (secret_keys.pop().unwrap(), verify_key)
+        })
+        .collect();
+    let verify_keys: Vec<_> = epoch_keys.iter().map(|(_, key)| key.clone()).collect();
+    let coconut =
+        Coconut::<OsRngInstance>::new(number_attributes, threshold_service, total_services)
+            .unwrap();
+
+    let (secret, verify_key) = epoch_keys.remove(0);
+    let mut service = SigningService::from_secret(&coconut, secret, verify_key, 1);
+
+    let transact = |service: &mut SigningService<OsRngInstance>,
+                    kind: TransactionKind,
+                    withdraws: Vec<AssetValue>,
+                    inputs: Vec<(&Token, &TokenSecret)>,
+                    output_value: u64|
+     -> Result<(Token, TokenSecret), Error> {
+        let input_values: Vec<_> = inputs
+            .iter()
+            .map(|(_, secret)| secret.asset_value())
+            .collect();
+        let deposits = if inputs.is_empty() {
+            vec![AssetValue::new(DEFAULT_ASSET, output_value)]
+        } else {
+            vec![]
+        };
+        let output_secret = TokenSecret::generate(output_value, &coconut);
+        let verify_key = match inputs.first() {
+            Some((token, _)) => &verify_keys[token.epoch as usize],
+            None => &verify_keys[0],
+        };
+        let epoch = service.epoch();
+        let tx = make_transaction_with(
+            &coconut,
+            verify_key,
+            deposits,
+            withdraws,
+            inputs,
+            input_values,
+            vec![&output_secret],
+            vec![output_secret.asset_value()],
+            false,
+            |tx| {
+                tx.kind = kind;
+                tx.outputs[0].request.epoch = epoch;
+            },
+        );
+
+        let signatures = vec![service.process(&tx)?];
+        let (mut tokens, _) = tx.unblind(
+            &coconut,
+            &vec![service.share_verify_key()],
+            &vec![&output_secret],
+            signatures,
+        )?;
+        Ok((tokens.pop().unwrap(), output_secret))
+    };
+    let payment = TransactionKind::Payment;
+
+    let (token_a, secret_a) = transact(&mut service, payment, vec![], vec![], 100).unwrap();
+    let (token_b, secret_b) = transact(&mut service, payment, vec![], vec![], 50).unwrap();
+    assert_eq!(token_a.epoch, 0);
+    let token_a: Token = deserialize(&serialize(&token_a)).unwrap();
+    assert_eq!(token_a.epoch, 0);
+
+    let (secret, verify_key) = epoch_keys.remove(0);
+    service.rotate(1, secret, verify_key).unwrap();
+
+    // Outputs made for the old key are refused
+    let tx = make_transaction(
+        &coconut,
+        &verify_keys[0],
+        vec![secret_a.asset_value()],
+        vec![],
+        vec![],
+        vec![],
+        vec![&secret_a],
+        vec![secret_a.asset_value()],
+    );
+    assert_eq!(tx.outputs[0].request.epoch, 0);
+    assert!(matches!(service.process(&tx), Err(Error::WrongEpoch)));
+
+    // Tokens from the previous epoch are still spendable
+    let inputs = vec![(&token_a, &secret_a)];
+    let (token_c, _) = transact(&mut service, payment, vec![], inputs, 100).unwrap();
+    assert_eq!(token_c.epoch, 1);
+
+    let (secret, verify_key) = epoch_keys.remove(0);
+    service.rotate(2, secret, verify_key).unwrap();
+
+    // Older ones have to be migrated, which can't take value out
+    let inputs = vec![(&token_b, &secret_b)];
+    assert!(matches!(
+        transact(&mut service, payment, vec![], inputs.clone(), 50),
+        Err(Error::ExpiredEpoch)
+    ));
+    let migration = TransactionKind::Migration;
+    let withdraw = vec![AssetValue::new(DEFAULT_ASSET, 10)];
+    assert!(matches!(
+        transact(&mut service, migration, withdraw, inputs.clone(), 40),
+        Err(Error::InvalidMigration)
+    ));
+    let (token_d, _) = transact(&mut service, migration, vec![], inputs, 50).unwrap();
+    assert_eq!(token_d.epoch, 2);
+    assert_eq!(service.spent_len(), 2);
+
+    let mut keyset: KeySet = deserialize(&serialize(service.keyset())).unwrap();
+    assert_eq!(keyset.epochs(), vec![0, 1, 2]);
+    assert_eq!(keyset.previous_epoch(), Some(1));
+    assert!(matches!(
+        keyset.rotate(1, verify_keys[1].clone()),
+        Err(Error::InvalidEpoch)
+    ));
+    keyset.prune_before(1);
+    assert!(keyset.get(0).is_none());
+    assert!(keyset.is_spendable(1));
+}
+
+#[test]
+fn test_schema_token_secret() {
+    use crate::serial::{deserialize, serialize};
+
+    let coconut = Coconut::<OsRngInstance>::new(3, 1, 1).unwrap();
+    let token_secret = TokenSecret::generate(110, &coconut);
+
+    let decoded: TokenSecret = deserialize(&serialize(&token_secret)).unwrap();
+    assert_eq!(decoded, token_secret);
+    assert_ne!(TokenSecret::generate(110, &coconut), token_secret);
+
+    let debug = format!("{:?}", token_secret);
+    assert!(debug.contains("<redacted>"));
+    assert!(!debug.contains(&format!("{:?}", token_secret.serial)));
+    assert!(!debug.contains(&format!("{:?}", token_secret.private_key.private_key)));
+}
+
+#[test]
+fn test_schema_wallet() {
+    use crate::schema::wallet::*;
+    use crate::serial::serialize;
+
+    let number_attributes = 3;
+    let threshold_service = 1;
+    let total_services = 1;
+
+    let (mut secret_keys, verify_key) =
+        generate_keys(number_attributes, threshold_service, total_services).unwrap();
+    let coconut =
+        Coconut::<OsRngInstance>::new(number_attributes, threshold_service, total_services)
+            .unwrap();
+    let mut service =
+        SigningService::from_secret(&coconut, secret_keys.pop().unwrap(), verify_key.clone(), 1);
+    let service_verify_keys = vec![service.share_verify_key()];
+
+    let token1_secret = TokenSecret::generate(100, &coconut);
+    let token2_secret = TokenSecret::generate(40, &coconut);
+    let mut tx = make_transaction(
+        &coconut,
+        &verify_key,
+        vec![AssetValue::new(DEFAULT_ASSET, 140)],
+        vec![],
+        vec![],
+        vec![],
+        vec![&token1_secret, &token2_secret],
+        vec![token1_secret.asset_value(), token2_secret.asset_value()],
+    );
+    let signatures = service.process(&tx).unwrap();
+
+    let mut wallet = Wallet::new();
+    wallet.verify_key = Some(verify_key.clone());
+    for (output, secret) in tx.outputs.drain(..).zip(vec![token1_secret, token2_secret]) {
+        wallet.add_pending(output, secret);
+    }
+    assert!(matches!(
+        wallet.confirm_pending(&coconut, &service_verify_keys, 2, vec![]),
+        Err(Error::InvalidWalletIndex)
+    ));
+    for signature in signatures {
+        let rejected = wallet
+            .confirm_pending(&coconut, &service_verify_keys, 0, vec![signature])
+            .unwrap();
+        assert!(rejected.is_empty());
+    }
+    assert!(wallet.pending.is_empty());
+
+    assert_eq!(wallet.balance(DEFAULT_ASSET).unwrap(), 140);
+    let balances = wallet.balances().unwrap();
+    assert_eq!(balances.len(), 1);
+    assert_eq!(balances[0].value, 140);
+
+    // Largest tokens first
+    assert_eq!(wallet.select_coins(DEFAULT_ASSET, 30).unwrap(), vec![0]);
+    assert_eq!(wallet.select_coins(DEFAULT_ASSET, 120).unwrap(), vec![0, 1]);
+    assert!(matches!(
+        wallet.select_coins(DEFAULT_ASSET, 141),
+        Err(Error::InsufficientFunds)
+    ));
+
+    let data = wallet.encrypt("passphrase").unwrap();
+    let token_secret = serialize(&wallet.tokens[0].secret);
+    assert!(!data
+        .windows(token_secret.len())
+        .any(|window| window == &token_secret[..]));
+
+    assert!(matches!(
+        Wallet::decrypt(&data, "wrong"),
+        Err(Error::WalletDecryptFailed)
+    ));
+    // Changing the salt changes the key
+    let mut tampered = data.clone();
+    tampered[20] ^= 1;
+    assert!(matches!(
+        Wallet::decrypt(&tampered, "passphrase"),
+        Err(Error::WalletDecryptFailed)
+    ));
+    let mut tampered = data.clone();
+    tampered[8] = WALLET_VERSION + 1;
+    assert!(matches!(
+        Wallet::decrypt(&tampered, "passphrase"),
+        Err(Error::ParseFailed(_))
+    ));
+    // The iterations can't be lowered, or raised enough to stall the wallet
+    for iterations in &[1, WALLET_KDF_ITERATIONS - 1, 10 * WALLET_KDF_ITERATIONS + 1] {
+        let mut tampered = data.clone();
+        tampered[9..13].copy_from_slice(&iterations.to_le_bytes());
+        assert!(matches!(
+            Wallet::decrypt(&tampered, "passphrase"),
+            Err(Error::ParseFailed(_))
+        ));
+    }
+
+    let path = std::env::temp_dir().join(format!("df-wallet-{}.dat", rand::random::<u64>()));
+    Wallet::decrypt(&data, "passphrase")
+        .unwrap()
+        .save(&path, "passphrase")
+        .unwrap();
+    let mut wallet = Wallet::load(&path, "passphrase").unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(wallet.balance(DEFAULT_ASSET).unwrap(), 140);
+
+    // Bad indexes leave the tokens alone
+    for indexes in &[vec![2], vec![0, 0]] {
+        assert!(matches!(
+            wallet.take_tokens(indexes),
+            Err(Error::InvalidWalletIndex)
+        ));
+    }
+    assert_eq!(wallet.balance(DEFAULT_ASSET).unwrap(), 140);
+
+    // Tokens read back from the wallet can still be spent
+    let spent = wallet
+        .take_tokens(&wallet.select_coins(DEFAULT_ASSET, 100).unwrap())
+        .unwrap();
+    assert_eq!(wallet.balance(DEFAULT_ASSET).unwrap(), 40);
+    let tx = make_transaction(
+        &coconut,
+        wallet.verify_key.as_ref().unwrap(),
+        vec![],
+        vec![spent[0].secret.asset_value()],
+        vec![(&spent[0].token, &spent[0].secret)],
+        vec![spent[0].secret.asset_value()],
+        vec![],
+        vec![],
+    );
+    service.process(&tx).unwrap();
+
+    // Balances that don't fit in a u64 are errors
+    let unsigned = Token {
+        signature: None,
+        epoch: 0,
+    };
+    wallet.add_token(unsigned, TokenSecret::generate(u64::MAX, &coconut));
+    assert!(matches!(
+        wallet.balance(DEFAULT_ASSET),
+        Err(Error::ValueOverflow)
+    ));
+    assert!(matches!(wallet.balances(), Err(Error::ValueOverflow)));
+}
+
+#[test]
+fn test_schema_transaction_builder() {
+    use crate::proofs::bulletproof::MAX_AGGREGATED_VALUES;
+    use crate::schema::builder::*;
+    use crate::schema::epoch::*;
+    use crate::schema::wallet::*;
+
+    let number_attributes = 3;
+    let threshold_service = 1;
+    let total_services = 1;
+
+    let (mut secret_keys, verify_key) =
+        generate_keys(number_attributes, threshold_service, total_services).unwrap();
+    let coconut =
+        Coconut::<OsRngInstance>::new(number_attributes, threshold_service, total_services)
+            .unwrap();
+    let mut service =
+        SigningService::from_secret(&coconut, secret_keys.pop().unwrap(), verify_key.clone(), 1);
+    let service_verify_keys = vec![service.share_verify_key()];
+
+    let token1_secret = TokenSecret::generate(100, &coconut);
+    let token2_secret = TokenSecret::generate(40, &coconut);
+    let mut tx = make_transaction(
+        &coconut,
+        &verify_key,
+        vec![AssetValue::new(DEFAULT_ASSET, 140)],
+        vec![],
+        vec![],
+        vec![],
+        vec![&token1_secret, &token2_secret],
+        vec![token1_secret.asset_value(), token2_secret.asset_value()],
+    );
+    let signatures = service.process(&tx).unwrap();
+
+    let mut wallet = Wallet::new();
+    for (output, secret) in tx.outputs.drain(..).zip(vec![token1_secret, token2_secret]) {
+        wallet.add_pending(output, secret);
+    }
+    for signature in signatures {
+        wallet
+            .confirm_pending(&coconut, &service_verify_keys, 0, vec![signature])
+            .unwrap();
+    }
+
+    let keys = KeySet::new(DEFAULT_EPOCH, verify_key.clone());
+    assert!(matches!(
+        TransactionBuilder::new(&coconut, &keys).build(&wallet.tokens),
+        Err(Error::EmptyTransaction)
+    ));
+    assert!(matches!(
+        TransactionBuilder::new(&coconut, &keys)
+            .with_payments(vec![u64::MAX, 1])
+            .build(&wallet.tokens),
+        Err(Error::ValueOverflow)
+    ));
+    assert!(matches!(
+        TransactionBuilder::new(&coconut, &keys)
+            .with_payments(vec![100, 41])
+            .build(&wallet.tokens),
+        Err(Error::InsufficientFunds)
+    ));
+
+    // Pays 30 and 20 and withdraws 15 from the 100 token
+    let built = TransactionBuilder::new(&coconut, &keys)
+        .with_payments(vec![30, 20])
+        .with_withdraw(15)
+        .build(&wallet.tokens)
+        .unwrap();
+    assert_eq!(built.inputs, vec![0]);
+    assert_eq!(built.change, Some(2));
+    let values: Vec<_> = built
+        .output_secrets
+        .iter()
+        .map(|secret| secret.value)
+        .collect();
+    assert_eq!(values, vec![30, 20, 35]);
+    assert!(built.transaction.check(&coconut));
+
+    let signatures = service.process(&built.transaction).unwrap();
+    assert_eq!(signatures.len(), 3);
+    for ((output, secret), signature) in built
+        .transaction
+        .outputs
+        .iter()
+        .zip(&built.output_secrets)
+        .zip(signatures)
+    {
+        output
+            .unblind(&coconut, &service_verify_keys, secret, vec![signature])
+            .unwrap();
+    }
+    wallet.take_tokens(&built.inputs).unwrap();
+
+    // The exact amount leaves no change
+    let built = TransactionBuilder::new(&coconut, &keys)
+        .with_payments(vec![25, 15])
+        .with_aggregated_rangeproof()
+        .build(&wallet.tokens)
+        .unwrap();
+    assert_eq!(built.inputs, vec![0]);
+    assert_eq!(built.change, None);
+    assert!(built.transaction.outputs_rangeproof.is_some());
+    assert!(built.transaction.verify(&coconut, &keys).is_valid());
+
+    // One aggregated proof can't cover every output
+    let mut payments = vec![0; MAX_AGGREGATED_VALUES];
+    payments.push(1);
+    assert!(matches!(
+        TransactionBuilder::new(&coconut, &keys)
+            .with_payments(payments)
+            .with_aggregated_rangeproof()
+            .build(&wallet.tokens),
+        Err(Error::InvalidAggregationSize)
+    ));
+
+    // After a rotation the token is spent with the previous key and
+    // the outputs are for the new epoch
+    let rotate = |service: &mut SigningService<OsRngInstance>, epoch| {
+        let (mut secret_keys, verify_key) =
+            generate_keys(number_attributes, threshold_service, total_services).unwrap();
+        service
+            .rotate(epoch, secret_keys.pop().unwrap(), verify_key)
+            .unwrap();
+    };
+    rotate(&mut service, 1);
+    let built = TransactionBuilder::new(&coconut, service.keyset())
+        .with_payments(vec![40])
+        .build(&wallet.tokens)
+        .unwrap();
+    assert_eq!(built.transaction.outputs[0].request.epoch, 1);
+    assert!(built
+        .transaction
+        .verify(&coconut, service.keyset())
+        .is_valid());
+
+    // Older tokens have to be migrated, so they aren't picked
+    rotate(&mut service, 2);
+    assert!(matches!(
+        TransactionBuilder::new(&coconut, service.keyset())
+            .with_payments(vec![40])
+            .build(&wallet.tokens),
+        Err(Error::InsufficientFunds)
+    ));
+}
+
+#[test]
+fn test_schema_known_answers() {
+    use crate::serial::serialize;
+
+    // A deposit and then a split of the deposited token
+    let run = || {
+        SeededRngInstance::seed(b"transaction known answers");
+
+        let coconut = Coconut::<SeededRngInstance>::new(3, 2, 3).unwrap();
+        let (secret_keys, service_verify_keys) = coconut.multiparty_keygen();
+        let verify_key = coconut.aggregate_keys(&service_verify_keys).unwrap();
+
+        let mut services: Vec<_> = secret_keys
+            .into_iter()
+            .enumerate()
+            .map(|(index, secret)| {
+                SigningService::from_secret(
+                    &coconut,
+                    secret,
+                    verify_key.clone(),
+                    (index + 1) as u64,
+                )
+            })
+            .collect();
+
+        let token_secret = TokenSecret::generate(110, &coconut);
+        let deposit = make_transaction(
+            &coconut,
+            &verify_key,
+            vec![token_secret.asset_value()],
+            vec![],
+            vec![],
+            vec![],
+            vec![&token_secret],
+            vec![token_secret.asset_value()],
+        );
+        let signatures: Vec<_> = services
+            .iter_mut()
+            .map(|service| service.process(&deposit).unwrap())
+            .collect();
+        let (tokens, _) = deposit
+            .unblind(
+                &coconut,
+                &service_verify_keys,
+                &vec![&token_secret],
+                signatures,
+            )
+            .unwrap();
+
+        let secret1 = TokenSecret::generate(100, &coconut);
+        let secret2 = TokenSecret::generate(10, &coconut);
+        let split = make_transaction(
+            &coconut,
+            &verify_key,
+            vec![],
+            vec![],
+            vec![(&tokens[0], &token_secret)],
+            vec![token_secret.asset_value()],
+            vec![&secret1, &secret2],
+            vec![secret1.asset_value(), secret2.asset_value()],
+        );
+        for service in &mut services {
+            service.process(&split).unwrap();
+        }
+
+        (
+            serialize(&deposit),
+            serialize(&tokens[0]),
+            serialize(&split),
+        )
+    };
+
+    let (deposit, token, split) = run();
+    assert_eq!(run(), (deposit.clone(), token.clone(), split.clone()));
+
+    crate::utility::check_known_answer("transaction_deposit", &deposit);
+    crate::utility::check_known_answer("token", &token);
+    crate::utility::check_known_answer("transaction_split", &split);
+}
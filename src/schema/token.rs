@@ -1,29 +1,70 @@
 use bls12_381 as bls;
+use std::fmt;
 use std::io;
+use subtle::{Choice, ConstantTimeEq};
 
 use crate::bls_extensions::*;
 use crate::coconut::coconut::*;
+use crate::coconut::presentation::Presentation;
 use crate::elgamal::*;
 use crate::error::{Error, Result};
+use crate::parameters::{Parameters, DEFAULT_ASSET};
+use crate::schema::epoch::Epoch;
 use crate::serial::{Decodable, Encodable};
 
 pub struct Token {
     pub signature: Option<Signature>,
+    // Key epoch the token was signed in
+    pub epoch: Epoch,
+}
+
+// Number of private attributes in a token: serial, value and asset
+pub const TOKEN_PRIVATE_ATTRIBUTES: u64 = 3;
+
+#[derive(Clone, Copy)]
+pub struct AssetValue {
+    pub asset: u64,
+    pub value: u64,
 }
 
 pub struct TokenSecret {
     pub value: u64,
+    pub asset: u64,
     pub serial: bls::Scalar,
     pub private_key: ElGamalPrivateKey,
+    // Signed in the clear after the private attributes. e.g. asset type, expiry epoch
+    pub public_attributes: Vec<bls::Scalar>,
     //token: Token,
 }
 
+impl Token {
+    // Shows the token without spending it. Serial, value and asset stay
+    // hidden while the public attributes are disclosed.
+    pub fn present<R: RngInstance>(
+        &self,
+        coconut: &Coconut<R>,
+        verify_key: &VerifyKey,
+        token_secret: &TokenSecret,
+        context: &[u8],
+    ) -> Result<Presentation> {
+        let signature = self.signature.as_ref().ok_or(Error::MissingSignature)?;
+        coconut.present(
+            verify_key,
+            signature,
+            &token_secret.private_attributes(),
+            &token_secret.public_attributes(),
+            context,
+        )
+    }
+}
+
 impl Encodable for Token {
     fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
-        match &self.signature {
-            None => 0u8.encode(s),
-            Some(signature) => Ok(1u8.encode(&mut s)? + signature.encode(s)?),
-        }
+        let len = match &self.signature {
+            None => 0u8.encode(&mut s)?,
+            Some(signature) => 1u8.encode(&mut s)? + signature.encode(&mut s)?,
+        };
+        Ok(len + self.epoch.encode(s)?)
     }
 }
 
@@ -32,29 +73,140 @@ impl Decodable for Token {
         Ok(Self {
             signature: match Decodable::decode(&mut d)? {
                 0u8 => None,
-                1u8 => Some(Decodable::decode(d)?),
+                1u8 => Some(Decodable::decode(&mut d)?),
                 _ => return Err(Error::ParseFailed("wrong option byte for input")),
             },
+            epoch: Decodable::decode(d)?,
+        })
+    }
+}
+
+impl AssetValue {
+    pub fn new(asset: u64, value: u64) -> Self {
+        Self { asset, value }
+    }
+}
+
+impl Encodable for AssetValue {
+    fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
+        let len = self.asset.encode(&mut s)?;
+        Ok(len + self.value.encode(s)?)
+    }
+}
+
+impl Decodable for AssetValue {
+    fn decode<D: io::Read>(mut d: D) -> Result<Self> {
+        Ok(Self {
+            asset: Decodable::decode(&mut d)?,
+            value: Decodable::decode(d)?,
         })
     }
 }
 
 impl TokenSecret {
     pub fn generate<R: RngInstance>(value: u64, coconut: &Coconut<R>) -> Self {
+        Self::generate_with_public(DEFAULT_ASSET, value, Vec::new(), coconut)
+    }
+
+    pub fn generate_asset<R: RngInstance>(asset: u64, value: u64, coconut: &Coconut<R>) -> Self {
+        Self::generate_with_public(asset, value, Vec::new(), coconut)
+    }
+
+    pub fn generate_with_public<R: RngInstance>(
+        asset: u64,
+        value: u64,
+        public_attributes: Vec<bls::Scalar>,
+        coconut: &Coconut<R>,
+    ) -> Self {
         Self {
             value,
+            asset,
             serial: coconut.params.random_scalar(),
             private_key: ElGamalPrivateKey::new(&coconut.params),
+            public_attributes,
+        }
+    }
+
+    pub fn private_attributes(&self) -> Vec<Attribute> {
+        vec![
+            Attribute::new(self.serial, 0),
+            Attribute::new(bls::Scalar::from(self.value), 1),
+            Attribute::new(bls::Scalar::from(self.asset), 2),
+        ]
+    }
+
+    // The token must fill the attribute slots and hold a registered asset
+    pub fn check<R: RngInstance>(&self, params: &Parameters<R>) -> Result<()> {
+        if params.hs.len() as u64 != TOKEN_PRIVATE_ATTRIBUTES + self.public_attributes.len() as u64
+        {
+            return Err(Error::InvalidAttributeCount);
         }
+        if params.asset_generator(self.asset).is_none() {
+            return Err(Error::UnknownAsset);
+        }
+        Ok(())
+    }
+
+    pub fn asset_value(&self) -> AssetValue {
+        AssetValue::new(self.asset, self.value)
+    }
+
+    pub fn public_attributes(&self) -> Vec<Attribute> {
+        self.public_attributes
+            .iter()
+            .enumerate()
+            .map(|(i, value)| Attribute::new(*value, TOKEN_PRIVATE_ATTRIBUTES + i as u64))
+            .collect()
+    }
+}
+
+// The private attributes are wiped. Public attributes aren't secret.
+impl Drop for TokenSecret {
+    fn drop(&mut self) {
+        self.value.wipe();
+        self.asset.wipe();
+        self.serial.wipe();
+    }
+}
+
+impl fmt::Debug for TokenSecret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TokenSecret")
+            .field("value", &Redacted)
+            .field("asset", &Redacted)
+            .field("serial", &Redacted)
+            .field("private_key", &self.private_key)
+            .field("public_attributes", &self.public_attributes)
+            .finish()
+    }
+}
+
+impl ConstantTimeEq for TokenSecret {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.value.ct_eq(&other.value)
+            & self.asset.ct_eq(&other.asset)
+            & self.serial.ct_eq(&other.serial)
+            & self.private_key.ct_eq(&other.private_key)
+            & self.public_attributes.ct_eq(&other.public_attributes)
+    }
+}
+
+impl PartialEq for TokenSecret {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
     }
 }
 
+impl Eq for TokenSecret {}
+
 impl Encodable for TokenSecret {
     fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
         let mut len = 0;
         len += self.value.encode(&mut s)?;
+        len += self.asset.encode(&mut s)?;
         len += self.serial.encode(&mut s)?;
         len += self.private_key.encode(&mut s)?;
+        len += self.public_attributes.encode(&mut s)?;
         Ok(len)
     }
 }
@@ -63,8 +215,10 @@ impl Decodable for TokenSecret {
     fn decode<D: io::Read>(mut d: D) -> Result<Self> {
         Ok(Self {
             value: Decodable::decode(&mut d)?,
+            asset: Decodable::decode(&mut d)?,
             serial: Decodable::decode(&mut d)?,
-            private_key: Decodable::decode(d)?,
+            private_key: Decodable::decode(&mut d)?,
+            public_attributes: Decodable::decode(d)?,
         })
     }
 }
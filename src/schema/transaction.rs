@@ -1,51 +1,218 @@
 use bls12_381 as bls;
 use itertools::izip;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
 use std::io;
+use std::rc::Rc;
+use subtle::{Choice, ConstantTimeEq};
 
 use crate::bls_extensions::*;
 use crate::coconut::coconut::*;
-use crate::error::Result;
+use crate::error::{Error, Result};
+use crate::parameters::{Parameters, DEFAULT_ASSET};
 use crate::pedersen::*;
+use crate::proofs::bulletproof;
+use crate::proofs::ownership_proof;
+use crate::proofs::proof::*;
 use crate::schema::input::*;
 use crate::schema::output::*;
 use crate::schema::token::*;
-use crate::serial::{Decodable, Encodable};
-use crate::utility::*;
+use crate::serial::{Decodable, Encodable, VarInt};
+
+// Wire format version. The unversioned format started with the
+// transaction kind, so 0 and 1 are never used.
+pub const TRANSACTION_VERSION: u8 = 3;
+pub const TRANSACTION_SECRET_VERSION: u8 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TransactionKind {
+    Payment,
+    // Re-issues tokens from old key epochs under the current key.
+    // Value can't enter or leave the system.
+    Migration,
+}
 
 // deposits + inputs == withdraws + outputs
+// This holds separately for every asset.
 pub struct Transaction {
+    pub kind: TransactionKind,
+
     // deposits - withdraws
-    pub deposits: u64,
-    deposits_blind: bls::Scalar,
-    pub withdraws: u64,
-    withdraws_blind: bls::Scalar,
+    pub deposits: Vec<AssetValue>,
+    // g1 * blind + the deposits under their asset generators
+    pub deposits_commit: bls::G1Projective,
+    pub deposits_proof: Option<BalanceProof>,
+    pub withdraws: Vec<AssetValue>,
+    pub withdraws_commit: bls::G1Projective,
+    pub withdraws_proof: Option<BalanceProof>,
 
     // burns
     pub inputs: Vec<Input>,
     // mints
     pub outputs: Vec<Output>,
+    // One range proof for every output without its own
+    pub outputs_rangeproof: Option<bulletproof::Proof>,
 
     pub challenge: bls::Scalar,
+
+    // Only kept by the wallet that built the transaction. Never encoded.
+    secret: Option<TransactionSecret>,
+}
+
+// The blinds of the deposits and withdraws commits
+pub struct TransactionSecret {
+    pub deposits_blind: bls::Scalar,
+    pub withdraws_blind: bls::Scalar,
+}
+
+impl Drop for TransactionSecret {
+    fn drop(&mut self) {
+        self.deposits_blind.wipe();
+        self.withdraws_blind.wipe();
+    }
+}
+
+impl fmt::Debug for TransactionSecret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TransactionSecret")
+            .field("deposits_blind", &Redacted)
+            .field("withdraws_blind", &Redacted)
+            .finish()
+    }
+}
+
+impl ConstantTimeEq for TransactionSecret {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.deposits_blind.ct_eq(&other.deposits_blind)
+            & self.withdraws_blind.ct_eq(&other.withdraws_blind)
+    }
+}
+
+impl PartialEq for TransactionSecret {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl Eq for TransactionSecret {}
+
+// Shows a deposits or withdraws commit hides nothing except the blind,
+// so it can't bring in value that isn't in the public amounts.
+pub struct BalanceProof {
+    challenge: bls::Scalar,
+    proof: ownership_proof::Proof,
+}
+
+impl BalanceProof {
+    fn transcript(label: &[u8], public: &bls::G1Projective) -> Transcript {
+        let mut transcript = Transcript::new(b"balance");
+        transcript.append_message(b"label", label);
+        transcript.append_g1(b"public", public);
+        transcript
+    }
+
+    fn new<R: RngInstance>(
+        params: &Parameters<R>,
+        label: &[u8],
+        blind: &bls::Scalar,
+        public: &bls::G1Projective,
+    ) -> Self {
+        let witness = Rc::new(Witness::new(params, *blind));
+        let mut transcript = Self::transcript(label, public);
+        ownership_proof::Builder::new(params, witness.clone())
+            .commitments()
+            .commit(&mut transcript);
+        let challenge = transcript.challenge_scalar(b"challenge");
+        Self {
+            challenge,
+            proof: ownership_proof::Proof {
+                response: witness.derive(&challenge),
+            },
+        }
+    }
+
+    fn verify<R: RngInstance>(
+        &self,
+        params: &Parameters<R>,
+        label: &[u8],
+        public: &bls::G1Projective,
+    ) -> bool {
+        let mut transcript = Self::transcript(label, public);
+        self.proof
+            .commitments(params, &self.challenge, public)
+            .commit(&mut transcript);
+        transcript.challenge_scalar(b"challenge") == self.challenge
+    }
+}
+
+// Sum of the values under their asset generators
+fn values_commit<R: RngInstance>(
+    params: &Parameters<R>,
+    values: &[AssetValue],
+) -> Option<bls::G1Projective> {
+    let mut commit = bls::G1Projective::identity();
+    for value in values {
+        commit += params.asset_generator(value.asset)? * bls::Scalar::from(value.value);
+    }
+    Some(commit)
+}
+
+fn check_balance_proof<R: RngInstance>(
+    params: &Parameters<R>,
+    label: &[u8],
+    commit: &bls::G1Projective,
+    values: &[AssetValue],
+    proof: &Option<BalanceProof>,
+) -> bool {
+    match (values_commit(params, values), proof) {
+        (Some(values), Some(proof)) => proof.verify(params, label, &(*commit - values)),
+        _ => false,
+    }
 }
 
 impl Transaction {
     pub fn new() -> Self {
+        Self::with_kind(TransactionKind::Payment)
+    }
+
+    pub fn new_migration() -> Self {
+        Self::with_kind(TransactionKind::Migration)
+    }
+
+    fn with_kind(kind: TransactionKind) -> Self {
         Self {
-            deposits: 0,
-            deposits_blind: bls::Scalar::zero(),
-            withdraws: 0,
-            withdraws_blind: bls::Scalar::zero(),
+            kind,
+            deposits: Vec::new(),
+            deposits_commit: bls::G1Projective::identity(),
+            deposits_proof: None,
+            withdraws: Vec::new(),
+            withdraws_commit: bls::G1Projective::identity(),
+            withdraws_proof: None,
             inputs: Vec::new(),
             outputs: Vec::new(),
+            outputs_rangeproof: None,
             challenge: bls::Scalar::zero(),
+            secret: None,
         }
     }
 
+    pub fn secret(&self) -> Option<&TransactionSecret> {
+        self.secret.as_ref()
+    }
+
     pub fn add_deposit(&mut self, value: u64) {
-        self.deposits += value;
+        self.add_asset_deposit(DEFAULT_ASSET, value);
     }
     pub fn add_withdraw(&mut self, value: u64) {
-        self.withdraws += value;
+        self.add_asset_withdraw(DEFAULT_ASSET, value);
+    }
+
+    pub fn add_asset_deposit(&mut self, asset: u64, value: u64) {
+        self.deposits.push(AssetValue::new(asset, value));
+    }
+    pub fn add_asset_withdraw(&mut self, asset: u64, value: u64) {
+        self.withdraws.push(AssetValue::new(asset, value));
     }
 
     // Burn input
@@ -62,21 +229,22 @@ impl Transaction {
     pub fn compute_pedersens<R: RngInstance>(
         &mut self,
         coconut: &Coconut<R>,
-        input_values: &Vec<u64>,
-        output_values: &Vec<u64>,
-    ) -> (Vec<bls::Scalar>, Vec<bls::Scalar>) {
-        assert_eq!(input_values.len(), self.inputs.len());
-        assert_eq!(output_values.len(), self.outputs.len());
+        input_values: &Vec<AssetValue>,
+        output_values: &Vec<AssetValue>,
+    ) -> Result<(Vec<bls::Scalar>, Vec<bls::Scalar>)> {
+        self.check_value_counts(input_values, output_values)?;
+        self.check_values_balance(input_values, output_values)?;
         let params = &coconut.params;
-        assert!(params.hs.len() > 0);
 
         // deposits + sum(inputs) == withdraws + sum(outputs)
 
-        if self.deposits > 0 {
-            self.deposits_blind = params.random_scalar();
+        let mut deposits_blind = bls::Scalar::zero();
+        let mut withdraws_blind = bls::Scalar::zero();
+        if !self.deposits.is_empty() {
+            deposits_blind = params.random_scalar();
         }
-        if self.withdraws > 0 {
-            self.withdraws_blind = params.random_scalar();
+        if !self.withdraws.is_empty() {
+            withdraws_blind = params.random_scalar();
         }
 
         let mut input_blinds = Vec::with_capacity(self.inputs.len());
@@ -91,51 +259,41 @@ impl Transaction {
 
         if !input_values.is_empty() {
             // rhs = withdraws + sum(outputs)
-            let rhs = self.withdraws_blind + sum_scalar(output_blinds.iter());
+            let rhs = withdraws_blind + sum_scalar(output_blinds.iter());
             // lhs = deposits + sum(inputs[1:])
-            let lhs = self.deposits_blind + sum_scalar(input_blinds.iter().skip(1));
+            let lhs = deposits_blind + sum_scalar(input_blinds.iter().skip(1));
 
             // inputs[0] = rhs - lhs
             input_blinds[0] = rhs - lhs;
         } else if !output_values.is_empty() {
-            assert!(input_values.is_empty());
-
             // rhs = withdraws + sum(outputs[1:]
-            let rhs = self.withdraws_blind + sum_scalar(output_blinds.iter().skip(1));
+            let rhs = withdraws_blind + sum_scalar(output_blinds.iter().skip(1));
 
             // outputs[0] = deposits - rhs
-            output_blinds[0] = self.deposits_blind - rhs;
+            output_blinds[0] = deposits_blind - rhs;
         } else {
-            assert!(input_values.is_empty());
-            assert!(output_values.is_empty());
-
             // A nonsensical transaction
             // Maybe this should be disallowed.
-            assert!(self.deposits == self.withdraws);
-            self.deposits_blind = self.withdraws_blind;
+            deposits_blind = withdraws_blind;
         }
 
-        // Test both sides are equal
-        assert_eq!(
-            self.deposits_blind + sum_scalar(input_blinds.iter()),
-            self.withdraws_blind + sum_scalar(output_blinds.iter())
-        );
-
         // Now set the pedersen commits
 
-        for (input, value, blind) in izip!(&mut self.inputs, input_values, &input_blinds) {
-            input.pedersen = compute_pedersen_with_u64(params, blind, *value);
+        self.set_all_commits(
+            coconut,
+            deposits_blind,
+            withdraws_blind,
+            &input_blinds,
+            input_values,
+            &output_blinds,
+            output_values,
+        )?;
+
+        if !self.check(coconut) {
+            return Err(Error::TransactionPedersenCheckFailed);
         }
-        for (output, value, blind) in izip!(&mut self.outputs, output_values, &output_blinds) {
-            output.pedersen = compute_pedersen_with_u64(params, blind, *value);
-        }
-
-        // TODO: DOESNT WORK!!!! WTF
-        //let lhs: bls::G1Projective = compute_pedersen(params, self.deposits, &self.deposits_blind)
-        //    + self.inputs.iter().map(|input| input.pedersen).sum();
-        assert!(self.check(coconut));
 
-        (input_blinds, output_blinds)
+        Ok((input_blinds, output_blinds))
     }
 
     pub fn set_blinds<R: RngInstance>(
@@ -144,36 +302,179 @@ impl Transaction {
         deposits_blind: bls::Scalar,
         withdraws_blind: bls::Scalar,
         input_blinds: &Vec<bls::Scalar>,
-        input_values: &Vec<u64>,
+        input_values: &Vec<AssetValue>,
         output_blinds: &Vec<bls::Scalar>,
-        output_values: &Vec<u64>,
-    ) {
-        assert_eq!(input_values.len(), self.inputs.len());
-        assert_eq!(output_values.len(), self.outputs.len());
-        let params = &coconut.params;
-        assert!(params.hs.len() > 0);
+        output_values: &Vec<AssetValue>,
+    ) -> Result<()> {
+        self.check_value_counts(input_values, output_values)?;
+        if input_blinds.len() != self.inputs.len() || output_blinds.len() != self.outputs.len() {
+            return Err(Error::InvalidAttributeCount);
+        }
+
+        self.set_all_commits(
+            coconut,
+            deposits_blind,
+            withdraws_blind,
+            input_blinds,
+            input_values,
+            output_blinds,
+            output_values,
+        )
+    }
 
-        self.deposits_blind = deposits_blind;
-        self.withdraws_blind = withdraws_blind;
+    fn check_value_counts(
+        &self,
+        input_values: &[AssetValue],
+        output_values: &[AssetValue],
+    ) -> Result<()> {
+        if input_values.len() != self.inputs.len() || output_values.len() != self.outputs.len() {
+            return Err(Error::InvalidAttributeCount);
+        }
+        Ok(())
+    }
+
+    // Each asset must balance by itself, summed without overflowing
+    fn check_values_balance(
+        &self,
+        input_values: &[AssetValue],
+        output_values: &[AssetValue],
+    ) -> Result<()> {
+        let mut totals: HashMap<u64, (u64, u64)> = HashMap::new();
+        for value in self.deposits.iter().chain(input_values) {
+            let total = &mut totals.entry(value.asset).or_default().0;
+            *total = total.checked_add(value.value).ok_or(Error::ValueOverflow)?;
+        }
+        for value in self.withdraws.iter().chain(output_values) {
+            let total = &mut totals.entry(value.asset).or_default().1;
+            *total = total.checked_add(value.value).ok_or(Error::ValueOverflow)?;
+        }
+        if totals.values().any(|(lhs, rhs)| lhs != rhs) {
+            return Err(Error::TransactionPedersenCheckFailed);
+        }
+        Ok(())
+    }
 
-        for (input, value, blind) in izip!(&mut self.inputs, input_values, input_blinds) {
-            input.pedersen = compute_pedersen_with_u64(params, blind, *value);
+    // Every commit is computed before any is set, so an unknown asset
+    // leaves the transaction untouched
+    fn set_all_commits<R: RngInstance>(
+        &mut self,
+        coconut: &Coconut<R>,
+        deposits_blind: bls::Scalar,
+        withdraws_blind: bls::Scalar,
+        input_blinds: &[bls::Scalar],
+        input_values: &[AssetValue],
+        output_blinds: &[bls::Scalar],
+        output_values: &[AssetValue],
+    ) -> Result<()> {
+        let params = &coconut.params;
+        let asset_pedersens = |values: &[AssetValue], blinds: &[bls::Scalar]| {
+            values
+                .iter()
+                .zip(blinds)
+                .map(|(value, blind)| {
+                    compute_asset_pedersen(params, value.asset, blind, value.value)
+                        .ok_or(Error::UnknownAsset)
+                })
+                .collect::<Result<Vec<_>>>()
+        };
+        let input_pedersens = asset_pedersens(input_values, input_blinds)?;
+        let output_pedersens = asset_pedersens(output_values, output_blinds)?;
+
+        self.set_balance_commits(coconut, deposits_blind, withdraws_blind)?;
+
+        for (input, pedersen) in self.inputs.iter_mut().zip(input_pedersens) {
+            input.pedersen = pedersen;
         }
-        for (output, value, blind) in izip!(&mut self.outputs, output_values, output_blinds) {
-            output.pedersen = compute_pedersen_with_u64(params, blind, *value);
+        for (output, pedersen) in self.outputs.iter_mut().zip(output_pedersens) {
+            output.pedersen = pedersen;
         }
+        Ok(())
+    }
+
+    // Deposits and withdraws must be final before this is called
+    fn set_balance_commits<R: RngInstance>(
+        &mut self,
+        coconut: &Coconut<R>,
+        deposits_blind: bls::Scalar,
+        withdraws_blind: bls::Scalar,
+    ) -> Result<()> {
+        let params = &coconut.params;
+        let deposits_values = values_commit(params, &self.deposits).ok_or(Error::UnknownAsset)?;
+        let withdraws_values = values_commit(params, &self.withdraws).ok_or(Error::UnknownAsset)?;
+
+        let deposits_public = params.g1 * deposits_blind;
+        self.deposits_commit = deposits_public + deposits_values;
+        self.deposits_proof = Some(BalanceProof::new(
+            params,
+            b"deposits",
+            &deposits_blind,
+            &deposits_public,
+        ));
+
+        let withdraws_public = params.g1 * withdraws_blind;
+        self.withdraws_commit = withdraws_public + withdraws_values;
+        self.withdraws_proof = Some(BalanceProof::new(
+            params,
+            b"withdraws",
+            &withdraws_blind,
+            &withdraws_public,
+        ));
+
+        self.secret = Some(TransactionSecret {
+            deposits_blind,
+            withdraws_blind,
+        });
+        Ok(())
     }
 
+    // Range proof for the outputs set up with setup_for_aggregation(),
+    // in the same order as they were added. Its commitments go into the
+    // transaction challenge after the output proofs.
+    pub fn outputs_rangeproof_builder<'a, R: RngInstance>(
+        coconut: &'a Coconut<R>,
+        output_secrets: &[&OutputSecret<'a, R>],
+    ) -> Result<bulletproof::Builder<'a, R>> {
+        let params = &coconut.params;
+        let blinds: Vec<_> = output_secrets
+            .iter()
+            .map(|output_secret| output_secret.range_blind())
+            .collect();
+        let values: Vec<_> = output_secrets
+            .iter()
+            .map(|output_secret| output_secret.value)
+            .collect();
+        let range_base = bls::G1Projective::from(params.range_base);
+        bulletproof::Builder::new_aggregated(params, &range_base, &blinds, &values)
+    }
+
+    // Every asset has its own value generator so the sums only match
+    // when each asset balances by itself.
     pub fn check<R: RngInstance>(&self, coconut: &Coconut<R>) -> bool {
         let params = &coconut.params;
-        assert!(params.hs.len() > 0);
 
-        let mut lhs = compute_pedersen_with_u64(params, &self.deposits_blind, self.deposits);
+        // The commits must open to the public amounts
+        if !check_balance_proof(
+            params,
+            b"deposits",
+            &self.deposits_commit,
+            &self.deposits,
+            &self.deposits_proof,
+        ) || !check_balance_proof(
+            params,
+            b"withdraws",
+            &self.withdraws_commit,
+            &self.withdraws,
+            &self.withdraws_proof,
+        ) {
+            return false;
+        }
+
+        let mut lhs = self.deposits_commit;
         for input in &self.inputs {
             lhs += input.pedersen;
         }
 
-        let mut rhs = compute_pedersen_with_u64(params, &self.withdraws_blind, self.withdraws);
+        let mut rhs = self.withdraws_commit;
         for output in &self.outputs {
             rhs += output.pedersen;
         }
@@ -181,53 +482,247 @@ impl Transaction {
         lhs == rhs
     }
 
+    pub fn total_deposits(&self) -> u64 {
+        self.deposits.iter().map(|deposit| deposit.value).sum()
+    }
+    pub fn total_withdraws(&self) -> u64 {
+        self.withdraws.iter().map(|withdraw| withdraw.value).sum()
+    }
+
+    // Canonical encoding of everything the proofs are bound to. Only the
+    // proof responses and the transaction challenge they answer are left out.
+    // The balance proofs are self-contained and also left out.
+    fn encode_body<S: io::Write>(&self, mut s: S) -> Result<usize> {
+        let mut len = 0;
+        len += TRANSACTION_VERSION.encode(&mut s)?;
+        len += self.kind.encode(&mut s)?;
+        len += self.deposits.encode(&mut s)?;
+        len += self.deposits_commit.encode(&mut s)?;
+        len += self.withdraws.encode(&mut s)?;
+        len += self.withdraws_commit.encode(&mut s)?;
+        len += VarInt(self.inputs.len() as u64).encode(&mut s)?;
+        for input in &self.inputs {
+            len += input.encode_body(&mut s)?;
+        }
+        len += VarInt(self.outputs.len() as u64).encode(&mut s)?;
+        for output in &self.outputs {
+            len += output.encode_body(&mut s)?;
+        }
+        Ok(len)
+    }
+
+    pub fn digest(&self) -> [u8; 32] {
+        let mut data = Vec::new();
+        self.encode_body(&mut data).unwrap();
+        let mut digest = [0u8; 32];
+        digest.copy_from_slice(Sha256::digest(&data).as_slice());
+        digest
+    }
+
+    // The transaction challenge is computed on this. Every field is bound
+    // through the digest, and the proof commitments are appended after it
+    // in order: inputs, outputs then the outputs range proof.
+    // Output challenges must be set before the transcript is started.
+    pub fn transcript(&self) -> Transcript {
+        let mut transcript = Transcript::new(b"transaction");
+        transcript.append_message(b"digest", &self.digest());
+        transcript
+    }
+
+    // Output proofs answer their own challenge so each output can be
+    // proved apart from the rest of the transaction. Provers and the
+    // verifier both derive it from the output and its proof commitments.
+    pub fn output_challenge(output: &Output, output_hash: &ProofHash) -> bls::Scalar {
+        let mut data = Vec::new();
+        output.encode_statement(&mut data).unwrap();
+
+        let mut transcript = Transcript::new(b"output-challenge");
+        transcript.append_message(b"statement", &data);
+        transcript.append_scalar(b"output", output_hash);
+        transcript.challenge_scalar(b"challenge")
+    }
+
+    // Returns the tokens and the indexes of services that sent a bad share
+    // for any of the outputs. There is one reply per service, each with a
+    // share for every output. A reply of the wrong size is dropped and
+    // the services it names are reported.
     pub fn unblind<R: RngInstance>(
         &self,
         coconut: &Coconut<R>,
+        verify_keys: &Vec<VerifyKey>,
         token_secrets: &Vec<&TokenSecret>,
         output_signatures: Vec<Vec<OutputSignature>>,
-    ) -> Vec<Token> {
-        // output_signatures.len() == number of authorities
-        // output_signatures[i].len() == self.outputs.len()
-        let output_signatures = transpose(output_signatures);
-        assert_eq!(output_signatures.len(), self.outputs.len());
-        assert_eq!(token_secrets.len(), self.outputs.len());
+    ) -> Result<(Vec<Token>, Vec<u64>)> {
+        if token_secrets.len() != self.outputs.len() {
+            return Err(Error::InvalidAttributeCount);
+        }
+
+        let mut shares: Vec<Vec<_>> = self.outputs.iter().map(|_| Vec::new()).collect();
+        let mut malformed = Vec::new();
+        for reply in output_signatures {
+            if reply.len() != self.outputs.len() {
+                malformed.extend(reply.iter().map(|signature| signature.index));
+                continue;
+            }
+            for (output_shares, signature) in shares.iter_mut().zip(reply) {
+                output_shares.push(signature);
+            }
+        }
 
         let mut tokens = Vec::with_capacity(self.outputs.len());
+        let mut rejected = malformed.clone();
 
         for (output, token_secret, partial_signatures) in
-            izip!(&self.outputs, token_secrets, output_signatures)
+            izip!(&self.outputs, token_secrets, shares)
         {
-            tokens.push(output.unblind(coconut, token_secret, partial_signatures));
+            let (token, output_rejected) = output
+                .unblind(coconut, verify_keys, token_secret, partial_signatures)
+                .map_err(|err| match err {
+                    Error::NotEnoughSignatureShares(mut output_rejected) => {
+                        output_rejected.extend(&malformed);
+                        output_rejected.sort();
+                        output_rejected.dedup();
+                        Error::NotEnoughSignatureShares(output_rejected)
+                    }
+                    err => err,
+                })?;
+            tokens.push(token);
+            rejected.extend(output_rejected);
+        }
+        rejected.sort();
+        rejected.dedup();
+
+        Ok((tokens, rejected))
+    }
+}
+
+impl Encodable for TransactionKind {
+    fn encode<S: io::Write>(&self, s: S) -> Result<usize> {
+        match self {
+            TransactionKind::Payment => 0u8.encode(s),
+            TransactionKind::Migration => 1u8.encode(s),
+        }
+    }
+}
+
+impl Decodable for TransactionKind {
+    fn decode<D: io::Read>(d: D) -> Result<Self> {
+        match Decodable::decode(d)? {
+            0u8 => Ok(TransactionKind::Payment),
+            1u8 => Ok(TransactionKind::Migration),
+            _ => Err(Error::ParseFailed("unknown transaction kind")),
+        }
+    }
+}
+
+impl Encodable for BalanceProof {
+    fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
+        let len = self.challenge.encode(&mut s)?;
+        Ok(len + self.proof.response.encode(s)?)
+    }
+}
+
+impl Decodable for BalanceProof {
+    fn decode<D: io::Read>(mut d: D) -> Result<Self> {
+        Ok(Self {
+            challenge: Decodable::decode(&mut d)?,
+            proof: ownership_proof::Proof {
+                response: Decodable::decode(d)?,
+            },
+        })
+    }
+}
+
+fn encode_balance_proof<S: io::Write>(proof: &Option<BalanceProof>, mut s: S) -> Result<usize> {
+    match proof {
+        None => 0u8.encode(s),
+        Some(proof) => {
+            let len = 1u8.encode(&mut s)?;
+            Ok(len + proof.encode(s)?)
         }
+    }
+}
 
-        tokens
+fn decode_balance_proof<D: io::Read>(mut d: D) -> Result<Option<BalanceProof>> {
+    match Decodable::decode(&mut d)? {
+        0u8 => Ok(None),
+        1u8 => Ok(Some(Decodable::decode(d)?)),
+        _ => Err(Error::ParseFailed("wrong option byte for balance proof")),
     }
 }
 
+// The public transaction sent to mints and relays. The builder state
+// in TransactionSecret is never part of it.
 impl Encodable for Transaction {
     fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
         let mut len = 0;
+        len += TRANSACTION_VERSION.encode(&mut s)?;
+        len += self.kind.encode(&mut s)?;
         len += self.deposits.encode(&mut s)?;
-        len += self.deposits_blind.encode(&mut s)?;
+        len += self.deposits_commit.encode(&mut s)?;
+        len += encode_balance_proof(&self.deposits_proof, &mut s)?;
         len += self.withdraws.encode(&mut s)?;
-        len += self.withdraws_blind.encode(&mut s)?;
+        len += self.withdraws_commit.encode(&mut s)?;
+        len += encode_balance_proof(&self.withdraws_proof, &mut s)?;
         len += self.inputs.encode(&mut s)?;
         len += self.outputs.encode(&mut s)?;
+        match &self.outputs_rangeproof {
+            None => len += 0u8.encode(&mut s)?,
+            Some(rangeproof) => {
+                len += 1u8.encode(&mut s)?;
+                len += rangeproof.encode(&mut s)?;
+            }
+        }
         Ok(len + self.challenge.encode(s)?)
     }
 }
 
 impl Decodable for Transaction {
     fn decode<D: io::Read>(mut d: D) -> Result<Self> {
+        let version: u8 = Decodable::decode(&mut d)?;
+        if version != TRANSACTION_VERSION {
+            return Err(Error::ParseFailed("unknown transaction version"));
+        }
         Ok(Self {
+            kind: Decodable::decode(&mut d)?,
             deposits: Decodable::decode(&mut d)?,
-            deposits_blind: Decodable::decode(&mut d)?,
+            deposits_commit: Decodable::decode(&mut d)?,
+            deposits_proof: decode_balance_proof(&mut d)?,
             withdraws: Decodable::decode(&mut d)?,
-            withdraws_blind: Decodable::decode(&mut d)?,
+            withdraws_commit: Decodable::decode(&mut d)?,
+            withdraws_proof: decode_balance_proof(&mut d)?,
             inputs: Decodable::decode(&mut d)?,
             outputs: Decodable::decode(&mut d)?,
+            outputs_rangeproof: match Decodable::decode(&mut d)? {
+                0u8 => None,
+                1u8 => Some(Decodable::decode(&mut d)?),
+                _ => return Err(Error::ParseFailed("wrong option byte for transaction")),
+            },
             challenge: Decodable::decode(d)?,
+            secret: None,
+        })
+    }
+}
+
+// Wallet-side builder state. Keep it out of anything sent to a mint.
+impl Encodable for TransactionSecret {
+    fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
+        let mut len = 0;
+        len += TRANSACTION_SECRET_VERSION.encode(&mut s)?;
+        len += self.deposits_blind.encode(&mut s)?;
+        Ok(len + self.withdraws_blind.encode(s)?)
+    }
+}
+
+impl Decodable for TransactionSecret {
+    fn decode<D: io::Read>(mut d: D) -> Result<Self> {
+        let version: u8 = Decodable::decode(&mut d)?;
+        if version != TRANSACTION_SECRET_VERSION {
+            return Err(Error::ParseFailed("unknown transaction secret version"));
+        }
+        Ok(Self {
+            deposits_blind: Decodable::decode(&mut d)?,
+            withdraws_blind: Decodable::decode(d)?,
         })
     }
 }
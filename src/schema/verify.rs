@@ -0,0 +1,416 @@
+use bls12_381 as bls;
+use std::collections::HashSet;
+
+use crate::bls_extensions::*;
+use crate::coconut::coconut::*;
+use crate::error::{Error, Result};
+use crate::schema::epoch::KeySet;
+use crate::schema::input::*;
+use crate::schema::spent::spent_key;
+use crate::schema::token::TOKEN_PRIVATE_ATTRIBUTES;
+use crate::schema::transaction::*;
+
+// Checks a transaction the same way the mints do, except for double spends
+// and signing. Wallets use this to validate a transaction assembled from
+// parts made by other parties before submitting it.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TransactionPart {
+    Transaction,
+    Input(usize),
+    Output(usize),
+    OutputsRangeproof,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Component {
+    // deposits + inputs == withdraws + outputs, or the deposits and
+    // withdraws commits don't open to their amounts
+    Balance,
+    Kind,
+    // The same token burned twice in the transaction
+    Burn,
+    Epoch,
+    PublicAttributes,
+    SignRequest,
+    Credential,
+    Rangeproof,
+    // Missing proofs, or an output challenge that wasn't derived from
+    // the output and its proofs
+    Proofs,
+    // The proof commitments don't hash to the transaction challenge.
+    // This can't be narrowed down any further.
+    Challenge,
+}
+
+#[derive(Debug)]
+pub struct VerifyFailure {
+    pub part: TransactionPart,
+    pub component: Component,
+    // What the mint would reject the transaction with
+    pub error: Error,
+}
+
+// Failures are in the order the mint runs its checks
+#[derive(Debug)]
+pub struct VerifyReport {
+    pub failures: Vec<VerifyFailure>,
+}
+
+impl VerifyReport {
+    fn new() -> Self {
+        Self {
+            failures: Vec::new(),
+        }
+    }
+
+    fn fail(&mut self, part: TransactionPart, component: Component, error: Error) {
+        self.failures.push(VerifyFailure {
+            part,
+            component,
+            error,
+        });
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    pub fn failures_for(&self, part: TransactionPart) -> Vec<&VerifyFailure> {
+        self.failures
+            .iter()
+            .filter(|failure| failure.part == part)
+            .collect()
+    }
+
+    // The first failure is the error the mint returns
+    pub fn into_result(self) -> Result<()> {
+        match self.failures.into_iter().next() {
+            Some(failure) => Err(failure.error),
+            None => Ok(()),
+        }
+    }
+}
+
+// Public attributes always follow the private ones and must fill
+// the remaining attribute slots.
+fn check_public_attributes<R: RngInstance>(
+    coconut: &Coconut<R>,
+    public_attributes: &Vec<Attribute>,
+) -> bool {
+    let attributes_size = coconut.params.hs.len() as u64;
+    public_attributes.len() as u64 + TOKEN_PRIVATE_ATTRIBUTES == attributes_size
+        && public_attributes
+            .iter()
+            .enumerate()
+            .all(|(i, attribute)| attribute.index == TOKEN_PRIVATE_ATTRIBUTES + i as u64)
+}
+
+// Only migrations can spend tokens older than the previous epoch
+fn input_verify_key<'a>(
+    keys: &'a KeySet,
+    input: &Input,
+    kind: TransactionKind,
+) -> Result<&'a VerifyKey> {
+    let epoch = input.request.epoch;
+    let verify_key = keys.get(epoch).ok_or(Error::UnknownEpoch)?;
+    if kind == TransactionKind::Payment && !keys.is_spendable(epoch) {
+        return Err(Error::ExpiredEpoch);
+    }
+    Ok(verify_key)
+}
+
+// Many inputs are cheaper to check together in a single multi-pairing.
+// Only when that fails is every credential checked by itself to find the
+// bad ones.
+fn failing_credentials<R: RngInstance>(
+    coconut: &Coconut<R>,
+    credentials: &[(usize, &Credential, &VerifyKey, &Vec<Attribute>)],
+) -> Vec<usize> {
+    if credentials.len() > 1 {
+        let batch: Vec<_> = credentials
+            .iter()
+            .map(|(_, credential, verify_key, public_attributes)| {
+                (*credential, *verify_key, *public_attributes)
+            })
+            .collect();
+        if Credential::batch_verify(&coconut.params, &batch) {
+            return Vec::new();
+        }
+    }
+
+    credentials
+        .iter()
+        .filter(|(_, credential, verify_key, public_attributes)| {
+            !credential.verify(&coconut.params, verify_key, public_attributes)
+        })
+        .map(|(index, _, _, _)| *index)
+        .collect()
+}
+
+impl Transaction {
+    // Runs every check it can rather than stopping at the first failure
+    pub fn verify<R: RngInstance>(&self, coconut: &Coconut<R>, keys: &KeySet) -> VerifyReport {
+        let params = &coconut.params;
+        let mut report = VerifyReport::new();
+
+        if !self.check(coconut) {
+            report.fail(
+                TransactionPart::Transaction,
+                Component::Balance,
+                Error::TransactionPedersenCheckFailed,
+            );
+        }
+
+        if self.kind == TransactionKind::Migration
+            && !(self.deposits.is_empty() && self.withdraws.is_empty())
+        {
+            report.fail(
+                TransactionPart::Transaction,
+                Component::Kind,
+                Error::InvalidMigration,
+            );
+        }
+
+        // The challenge can only be recomputed when every proof could be
+        // checked. Otherwise there is already a failure to report.
+        let mut transcript = self.transcript();
+        let mut is_complete = true;
+
+        let mut burns = HashSet::with_capacity(self.inputs.len());
+        let mut credentials = Vec::with_capacity(self.inputs.len());
+        for (i, input) in self.inputs.iter().enumerate() {
+            let part = TransactionPart::Input(i);
+
+            if !burns.insert(spent_key(&input.request.burn_value)) {
+                report.fail(part, Component::Burn, Error::TokenAlreadySpent);
+            }
+
+            let verify_key = match input_verify_key(keys, input, self.kind) {
+                Ok(verify_key) => verify_key,
+                Err(err) => {
+                    report.fail(part, Component::Epoch, err);
+                    is_complete = false;
+                    continue;
+                }
+            };
+
+            if !check_public_attributes(coconut, &input.request.public_attributes) {
+                report.fail(
+                    part,
+                    Component::PublicAttributes,
+                    Error::InvalidPublicAttributes,
+                );
+                is_complete = false;
+                continue;
+            }
+            credentials.push((
+                i,
+                &input.request.credential,
+                verify_key,
+                &input.request.public_attributes,
+            ));
+
+            let proofs = match &input.proofs {
+                Some(proofs) => proofs,
+                None => {
+                    report.fail(part, Component::Proofs, Error::MissingProofs);
+                    is_complete = false;
+                    continue;
+                }
+            };
+
+            if proofs.rangeproof.value_commit() != input.pedersen {
+                report.fail(
+                    part,
+                    Component::Rangeproof,
+                    Error::RangeproofPedersenMatchFailed,
+                );
+            }
+
+            let commits = proofs.commits(
+                params,
+                &self.challenge,
+                verify_key,
+                &input.request.credential,
+                &input.request.burn_value,
+                &input.asset_tag,
+                &input.asset_commit,
+                &input.pedersen,
+            );
+            transcript.append_scalar(b"input", &commits.hash());
+        }
+
+        for i in failing_credentials(coconut, &credentials) {
+            report.fail(
+                TransactionPart::Input(i),
+                Component::Credential,
+                Error::InputTokenVerifyFailed,
+            );
+        }
+
+        let epoch = keys.current_epoch();
+        for (i, output) in self.outputs.iter().enumerate() {
+            let part = TransactionPart::Output(i);
+
+            let challenge = match &output.challenge {
+                Some(challenge) => challenge,
+                None => {
+                    report.fail(part, Component::Proofs, Error::InvalidCredential);
+                    is_complete = false;
+                    continue;
+                }
+            };
+
+            // Mints only sign in their current epoch
+            if output.request.epoch != epoch {
+                report.fail(part, Component::Epoch, Error::WrongEpoch);
+            }
+
+            if !check_public_attributes(coconut, &output.request.public_attributes) {
+                report.fail(
+                    part,
+                    Component::PublicAttributes,
+                    Error::InvalidPublicAttributes,
+                );
+                is_complete = false;
+                continue;
+            }
+
+            if let Err(err) = output
+                .request
+                .sign_request
+                .check(params, &output.request.public_attributes)
+            {
+                report.fail(part, Component::SignRequest, err);
+                is_complete = false;
+                continue;
+            }
+
+            let proofs = match &output.proofs {
+                Some(proofs) => proofs,
+                None => {
+                    report.fail(part, Component::Proofs, Error::MissingProofs);
+                    is_complete = false;
+                    continue;
+                }
+            };
+
+            if let Some(rangeproof) = &proofs.rangeproof {
+                if rangeproof.value_commit() != output.range_commit {
+                    report.fail(
+                        part,
+                        Component::Rangeproof,
+                        Error::RangeproofPedersenMatchFailed,
+                    );
+                }
+            }
+
+            let commitish = output.request.sign_request.compute_commitish();
+            let commits = proofs.commits(
+                params,
+                challenge,
+                &output.request.gamma,
+                &commitish,
+                &output.request.sign_request.attribute_commit,
+                &output.request.sign_request.encrypted_attributes,
+                &output.request.public_attributes,
+                &output.asset_tag,
+                &output.asset_commit,
+                &output.pedersen,
+                &output.range_commit,
+            );
+            // The challenge must be derived from the output, otherwise the
+            // proofs could answer one picked to fit them
+            let output_hash = commits.hash();
+            if Transaction::output_challenge(output, &output_hash) != *challenge {
+                report.fail(part, Component::Proofs, Error::ProofsFailed);
+            }
+            transcript.append_scalar(b"output", &output_hash);
+        }
+
+        // Outputs without their own range proof are covered by the transaction one
+        let range_commits: Vec<_> = self
+            .outputs
+            .iter()
+            .filter(|output| match &output.proofs {
+                Some(proofs) => proofs.rangeproof.is_none(),
+                None => false,
+            })
+            .map(|output| output.range_commit)
+            .collect();
+
+        let part = TransactionPart::OutputsRangeproof;
+        let range_base = bls::G1Projective::from(params.range_base);
+        match &self.outputs_rangeproof {
+            Some(rangeproof) => {
+                if *rangeproof.value_commits() != range_commits {
+                    report.fail(
+                        part,
+                        Component::Rangeproof,
+                        Error::RangeproofPedersenMatchFailed,
+                    );
+                }
+
+                rangeproof
+                    .commitments(params, &self.challenge, &range_base)
+                    .commit(&mut transcript);
+            }
+            None => {
+                if !range_commits.is_empty() {
+                    report.fail(part, Component::Proofs, Error::MissingProofs);
+                    is_complete = false;
+                }
+            }
+        }
+
+        if is_complete && transcript.challenge_scalar(b"challenge") != self.challenge {
+            // Range proofs can be checked by themselves to narrow it down
+            for part in self.failing_rangeproofs(coconut) {
+                report.fail(part, Component::Rangeproof, Error::ProofsFailed);
+            }
+            report.fail(
+                TransactionPart::Transaction,
+                Component::Challenge,
+                Error::ProofsFailed,
+            );
+        }
+
+        report
+    }
+
+    fn failing_rangeproofs<R: RngInstance>(&self, coconut: &Coconut<R>) -> Vec<TransactionPart> {
+        let params = &coconut.params;
+        let range_base = bls::G1Projective::from(params.range_base);
+        let mut failing = Vec::new();
+
+        for (i, input) in self.inputs.iter().enumerate() {
+            if let Some(proofs) = &input.proofs {
+                if !proofs
+                    .rangeproof
+                    .verify(params, &self.challenge, &input.asset_tag)
+                {
+                    failing.push(TransactionPart::Input(i));
+                }
+            }
+        }
+
+        for (i, output) in self.outputs.iter().enumerate() {
+            if let (Some(proofs), Some(challenge)) = (&output.proofs, &output.challenge) {
+                if let Some(rangeproof) = &proofs.rangeproof {
+                    if !rangeproof.verify(params, challenge, &range_base) {
+                        failing.push(TransactionPart::Output(i));
+                    }
+                }
+            }
+        }
+
+        if let Some(rangeproof) = &self.outputs_rangeproof {
+            if !rangeproof.verify(params, &self.challenge, &range_base) {
+                failing.push(TransactionPart::OutputsRangeproof);
+            }
+        }
+
+        failing
+    }
+}
@@ -0,0 +1,312 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::aes::*;
+use crate::bls_extensions::*;
+use crate::coconut::coconut::*;
+use crate::error::{Error, Result};
+use crate::schema::output::{Output, OutputSignature};
+use crate::schema::token::*;
+use crate::serial::{deserialize, serialize, Decodable, Encodable};
+
+// On disk a wallet is the header followed by the encrypted contents.
+// The header is authenticated along with the contents.
+//
+//   magic | version | kdf iterations | salt | nonce | ciphertext
+const WALLET_MAGIC: [u8; 8] = *b"dfwallet";
+pub const WALLET_VERSION: u8 = 1;
+pub const WALLET_KDF_ITERATIONS: u32 = 100_000;
+
+// A signed token and the secret to spend it
+pub struct OwnedToken {
+    pub token: Token,
+    pub secret: TokenSecret,
+}
+
+// An output we asked the mints to sign. It becomes an owned token
+// once their signature shares are unblinded.
+pub struct PendingOutput {
+    pub output: Output,
+    pub secret: TokenSecret,
+}
+
+// Picks tokens of the asset worth at least value, largest first so
+// spends use as few inputs as possible. Returns their indexes.
+pub fn select_coins(tokens: &[OwnedToken], asset: u64, value: u64) -> Result<Vec<usize>> {
+    select_coins_where(tokens, asset, value, |_| true)
+}
+
+// Same as select_coins() but skips the tokens the filter rejects
+pub fn select_coins_where<F: Fn(&OwnedToken) -> bool>(
+    tokens: &[OwnedToken],
+    asset: u64,
+    value: u64,
+    filter: F,
+) -> Result<Vec<usize>> {
+    let mut candidates: Vec<_> = tokens
+        .iter()
+        .enumerate()
+        .filter(|(_, owned)| owned.secret.asset == asset && filter(owned))
+        .map(|(index, owned)| (index, owned.secret.value))
+        .collect();
+    candidates.sort_by(|a, b| b.1.cmp(&a.1));
+
+    let mut selected = Vec::new();
+    let mut total: u64 = 0;
+    for (index, token_value) in candidates {
+        if total >= value {
+            break;
+        }
+        selected.push(index);
+        total = total.checked_add(token_value).ok_or(Error::ValueOverflow)?;
+    }
+
+    if total < value {
+        return Err(Error::InsufficientFunds);
+    }
+    Ok(selected)
+}
+
+#[derive(Default)]
+pub struct Wallet {
+    // Unspent tokens
+    pub tokens: Vec<OwnedToken>,
+    pub pending: Vec<PendingOutput>,
+    // Keys of the mint services run from this wallet
+    pub secret_keys: Vec<SecretKey>,
+    // Aggregated key of the mints the tokens are spent with
+    pub verify_key: Option<VerifyKey>,
+}
+
+impl Wallet {
+    pub fn new() -> Self {
+        Self {
+            tokens: Vec::new(),
+            pending: Vec::new(),
+            secret_keys: Vec::new(),
+            verify_key: None,
+        }
+    }
+
+    pub fn add_token(&mut self, token: Token, secret: TokenSecret) -> usize {
+        self.tokens.push(OwnedToken { token, secret });
+        self.tokens.len() - 1
+    }
+
+    pub fn add_pending(&mut self, output: Output, secret: TokenSecret) -> usize {
+        self.pending.push(PendingOutput { output, secret });
+        self.pending.len() - 1
+    }
+
+    // Moves a pending output to the tokens once the mints signed it.
+    // Returns the indexes of services that sent a bad share.
+    pub fn confirm_pending<R: RngInstance>(
+        &mut self,
+        coconut: &Coconut<R>,
+        verify_keys: &Vec<VerifyKey>,
+        index: usize,
+        signatures: Vec<OutputSignature>,
+    ) -> Result<Vec<u64>> {
+        let PendingOutput { output, secret } =
+            self.pending.get(index).ok_or(Error::InvalidWalletIndex)?;
+        let (token, rejected) = output.unblind(coconut, verify_keys, secret, signatures)?;
+
+        let pending = self.pending.remove(index);
+        self.add_token(token, pending.secret);
+        Ok(rejected)
+    }
+
+    pub fn balance(&self, asset: u64) -> Result<u64> {
+        self.tokens
+            .iter()
+            .filter(|owned| owned.secret.asset == asset)
+            .try_fold(0u64, |total, owned| total.checked_add(owned.secret.value))
+            .ok_or(Error::ValueOverflow)
+    }
+
+    // Total of every asset held, ordered by asset
+    pub fn balances(&self) -> Result<Vec<AssetValue>> {
+        let mut balances = BTreeMap::new();
+        for owned in &self.tokens {
+            let balance = balances.entry(owned.secret.asset).or_insert(0u64);
+            *balance = balance
+                .checked_add(owned.secret.value)
+                .ok_or(Error::ValueOverflow)?;
+        }
+        Ok(balances
+            .into_iter()
+            .map(|(asset, value)| AssetValue::new(asset, value))
+            .collect())
+    }
+
+    pub fn select_coins(&self, asset: u64, value: u64) -> Result<Vec<usize>> {
+        select_coins(&self.tokens, asset, value)
+    }
+
+    // Removes the tokens about to be spent, returned in the same order
+    // as the indexes. Nothing is removed if an index is out of range or
+    // repeated.
+    pub fn take_tokens(&mut self, indexes: &[usize]) -> Result<Vec<OwnedToken>> {
+        let mut seen = vec![false; self.tokens.len()];
+        for index in indexes {
+            match seen.get_mut(*index) {
+                Some(seen) if !*seen => *seen = true,
+                _ => return Err(Error::InvalidWalletIndex),
+            }
+        }
+
+        let mut slots: Vec<_> = self.tokens.drain(..).map(Some).collect();
+        let taken = indexes
+            .iter()
+            .filter_map(|index| slots[*index].take())
+            .collect();
+        self.tokens = slots.into_iter().flatten().collect();
+        Ok(taken)
+    }
+
+    pub fn encrypt(&self, passphrase: &str) -> Result<Vec<u8>> {
+        let iterations = WALLET_KDF_ITERATIONS;
+        let mut salt = [0u8; 32];
+        let mut nonce = [0u8; 12];
+        OsRngInstance::fill_bytes(&mut salt);
+        OsRngInstance::fill_bytes(&mut nonce);
+
+        let mut data = Vec::new();
+        WALLET_MAGIC.encode(&mut data)?;
+        WALLET_VERSION.encode(&mut data)?;
+        iterations.encode(&mut data)?;
+        salt.encode(&mut data)?;
+        nonce.encode(&mut data)?;
+
+        let mut key = derive_passphrase_key(passphrase.as_bytes(), &salt, iterations);
+        let mut plaintext = serialize(self);
+        let ciphertext = aes_encrypt_with_nonce(&key, &nonce, &data, &plaintext);
+        key.wipe();
+        plaintext.wipe();
+
+        ciphertext
+            .ok_or(Error::WalletEncryptFailed)?
+            .encode(&mut data)?;
+        Ok(data)
+    }
+
+    pub fn decrypt(data: &[u8], passphrase: &str) -> Result<Self> {
+        let mut d = io::Cursor::new(data);
+        let magic: [u8; 8] = Decodable::decode(&mut d)?;
+        if magic != WALLET_MAGIC {
+            return Err(Error::ParseFailed("not a wallet file"));
+        }
+        let version: u8 = Decodable::decode(&mut d)?;
+        if version != WALLET_VERSION {
+            return Err(Error::ParseFailed("unknown wallet version"));
+        }
+        // Bounded so a tampered file can't weaken the key or stall the
+        // derivation
+        let iterations: u32 = Decodable::decode(&mut d)?;
+        if !(WALLET_KDF_ITERATIONS..=10 * WALLET_KDF_ITERATIONS).contains(&iterations) {
+            return Err(Error::ParseFailed(
+                "wallet key derivation iterations are out of range",
+            ));
+        }
+        let salt: [u8; 32] = Decodable::decode(&mut d)?;
+        let nonce: [u8; 12] = Decodable::decode(&mut d)?;
+        let header_len = d.position() as usize;
+        let ciphertext: Vec<u8> = Decodable::decode(&mut d)?;
+        if d.position() as usize != data.len() {
+            return Err(Error::ParseFailed("data not consumed entirely"));
+        }
+
+        let mut key = derive_passphrase_key(passphrase.as_bytes(), &salt, iterations);
+        let plaintext = aes_decrypt_with_nonce(&key, &nonce, &data[..header_len], &ciphertext);
+        key.wipe();
+
+        let mut plaintext = plaintext.ok_or(Error::WalletDecryptFailed)?;
+        let wallet = deserialize(&plaintext);
+        plaintext.wipe();
+        wallet
+    }
+
+    // The file is replaced in one rename so a crash leaves either
+    // the old wallet or the new one.
+    pub fn save<P: AsRef<Path>>(&self, path: P, passphrase: &str) -> Result<()> {
+        let path = path.as_ref();
+        let data = self.encrypt(passphrase)?;
+
+        let tmp_path = path.with_extension("tmp");
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&data)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P, passphrase: &str) -> Result<Self> {
+        Self::decrypt(&fs::read(path)?, passphrase)
+    }
+}
+
+impl Encodable for OwnedToken {
+    fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
+        let len = self.token.encode(&mut s)?;
+        Ok(len + self.secret.encode(s)?)
+    }
+}
+
+impl Decodable for OwnedToken {
+    fn decode<D: io::Read>(mut d: D) -> Result<Self> {
+        Ok(Self {
+            token: Decodable::decode(&mut d)?,
+            secret: Decodable::decode(d)?,
+        })
+    }
+}
+
+impl Encodable for PendingOutput {
+    fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
+        let len = self.output.encode(&mut s)?;
+        Ok(len + self.secret.encode(s)?)
+    }
+}
+
+impl Decodable for PendingOutput {
+    fn decode<D: io::Read>(mut d: D) -> Result<Self> {
+        Ok(Self {
+            output: Decodable::decode(&mut d)?,
+            secret: Decodable::decode(d)?,
+        })
+    }
+}
+
+impl Encodable for Wallet {
+    fn encode<S: io::Write>(&self, mut s: S) -> Result<usize> {
+        let mut len = 0;
+        len += self.tokens.encode(&mut s)?;
+        len += self.pending.encode(&mut s)?;
+        len += self.secret_keys.encode(&mut s)?;
+        match &self.verify_key {
+            None => len += 0u8.encode(&mut s)?,
+            Some(verify_key) => {
+                len += 1u8.encode(&mut s)?;
+                len += verify_key.encode(&mut s)?;
+            }
+        }
+        Ok(len)
+    }
+}
+
+impl Decodable for Wallet {
+    fn decode<D: io::Read>(mut d: D) -> Result<Self> {
+        Ok(Self {
+            tokens: Decodable::decode(&mut d)?,
+            pending: Decodable::decode(&mut d)?,
+            secret_keys: Decodable::decode(&mut d)?,
+            verify_key: match Decodable::decode(&mut d)? {
+                0u8 => None,
+                1u8 => Some(Decodable::decode(d)?),
+                _ => return Err(Error::ParseFailed("wrong option byte for wallet")),
+            },
+        })
+    }
+}
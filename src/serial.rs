@@ -2,17 +2,23 @@ use bls12_381 as bls;
 use std::borrow::Cow;
 use std::io::{Cursor, Read, Write};
 use std::rc::Rc;
-use std::{io, mem};
+use std::{cmp, io, mem};
 
 use crate::bls_extensions::*;
 use crate::coconut::coconut;
+use crate::coconut::presentation;
 use crate::endian;
 use crate::error::{Error, Result};
 use crate::parameters::*;
 use crate::proofs::proof::Witness;
+use crate::proofs::range;
 use crate::proofs::simple_or;
 use crate::schema;
 
+/// Most items a length prefix can make us allocate up front. Hostile
+/// lengths then run out of data instead of memory.
+pub const MAX_PREALLOCATION: usize = 4096;
+
 /// Encode an object into a vector
 pub fn serialize<T: Encodable + ?Sized>(data: &T) -> Vec<u8> {
     let mut encoder = Vec::new();
@@ -411,7 +417,7 @@ macro_rules! impl_vec {
             #[inline]
             fn decode<D: io::Read>(mut d: D) -> Result<Self> {
                 let len = VarInt::decode(&mut d)?.0;
-                let mut ret = Vec::with_capacity(len as usize);
+                let mut ret = Vec::with_capacity(cmp::min(len as usize, MAX_PREALLOCATION));
                 for _ in 0..len {
                     ret.push(Decodable::decode(&mut d)?);
                 }
@@ -426,11 +432,20 @@ impl_vec!(bls::G1Affine);
 impl_vec!(bls::G1Projective);
 impl_vec!(bls::G2Affine);
 impl_vec!(bls::G2Projective);
+impl_vec!(Vec<bls::G2Projective>);
 impl_vec!(simple_or::Proof);
+impl_vec!(coconut::Attribute);
 impl_vec!(coconut::EncryptedAttribute);
+impl_vec!(coconut::SecretKey);
+impl_vec!(presentation::Predicate);
+impl_vec!(presentation::AttributeCommit);
+impl_vec!(range::Proof);
 impl_vec!(schema::Input);
 impl_vec!(schema::Output);
 impl_vec!(schema::OutputSignature);
+impl_vec!(schema::token::AssetValue);
+impl_vec!(schema::wallet::OwnedToken);
+impl_vec!(schema::wallet::PendingOutput);
 impl_vec!(Rc<Witness>);
 
 pub fn encode_with_size<S: io::Write>(data: &[u8], mut s: S) -> Result<usize> {
@@ -450,8 +465,11 @@ impl Decodable for Vec<u8> {
     #[inline]
     fn decode<D: io::Read>(mut d: D) -> Result<Self> {
         let len = VarInt::decode(&mut d)?.0 as usize;
-        let mut ret = vec![0u8; len];
-        d.read_slice(&mut ret)?;
+        let mut ret = Vec::with_capacity(cmp::min(len, MAX_PREALLOCATION));
+        (&mut d).take(len as u64).read_to_end(&mut ret)?;
+        if ret.len() != len {
+            return Err(Error::Io(io::ErrorKind::UnexpectedEof.into()));
+        }
         Ok(ret)
     }
 }
@@ -91,6 +91,26 @@ pub fn get_current_time() -> u64 {
     return in_ms;
 }
 
+// Known answer vectors are stored as hex in vectors/ at the top of the repo.
+// Vectors are only written when DF_UPDATE_VECTORS is set, after an
+// intentional change to the output. Otherwise a missing one fails.
+#[cfg(test)]
+pub fn check_known_answer(name: &str, data: &[u8]) {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("vectors")
+        .join(format!("{}.hex", name));
+    let encoded = hex::encode(data);
+
+    if std::env::var_os("DF_UPDATE_VECTORS").is_some() {
+        std::fs::write(&path, encoded + "\n").unwrap();
+        return;
+    }
+    match std::fs::read_to_string(&path) {
+        Ok(expected) => assert_eq!(expected.trim(), encoded, "known answer {} changed", name),
+        Err(err) => panic!("known answer {} is missing: {}", name, err),
+    }
+}
+
 #[test]
 fn test_transpose() {
     let x = vec![vec![1, 2, 3], vec![4, 5, 6]];